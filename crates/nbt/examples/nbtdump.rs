@@ -12,6 +12,9 @@ use clap::Parser;
 #[derive(Debug, Parser)]
 #[command(version)]
 struct Args {
+	/// Print the data in SNBT syntax
+	#[arg(long)]
+	snbt: bool,
 	/// Filename to dump
 	file: PathBuf,
 }
@@ -20,7 +23,11 @@ fn main() -> Result<()> {
 	let args = Args::parse();
 
 	let value: fastnbt::Value = minedmap_nbt::data::from_file(args.file.as_path())?;
-	println!("{:#x?}", value);
+	if args.snbt {
+		println!("{}", minedmap_nbt::snbt::Snbt(&value));
+	} else {
+		println!("{:#x?}", value);
+	}
 
 	Ok(())
 }
@@ -12,6 +12,9 @@ use clap::Parser;
 #[derive(Debug, Parser)]
 #[command(version)]
 struct Args {
+	/// Print the data in SNBT syntax
+	#[arg(long)]
+	snbt: bool,
 	/// Filename to dump
 	file: PathBuf,
 }
@@ -20,8 +23,12 @@ fn main() -> Result<()> {
 	let args = Args::parse();
 
 	minedmap_nbt::region::from_file(args.file.as_path())?.foreach_chunk(
-		|coords, value: fastnbt::Value| {
-			println!("Chunk {:?}: {:#x?}", coords, value);
+		|coords, value: fastnbt::Value| -> Result<()> {
+			if args.snbt {
+				println!("Chunk {:?}: {}", coords, minedmap_nbt::snbt::Snbt(&value));
+			} else {
+				println!("Chunk {:?}: {:#x?}", coords, value);
+			}
 			Ok(())
 		},
 	)
@@ -1,24 +1,194 @@
-//! Functions for reading and deserializing compressed NBT data
+//! Functions for reading and deserializing compressed NBT data, and for
+//! serializing and writing NBT data
 
 use std::{fs::File, io::prelude::*, path::Path};
 
-use anyhow::{Context, Result};
-use flate2::read::GzDecoder;
-use serde::de::DeserializeOwned;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub use crate::error::NbtError;
+use crate::region::{decompressed_limit, read_to_limit, Compression};
+
+/// Result type of NBT operations
+pub type Result<T, E = NbtError> = std::result::Result<T, E>;
+
+/// Limits enforced when decoding NBT data
+///
+/// Corrupt or hostile data can contain deeply nested or absurdly large
+/// structures. Such data is rejected with [NbtError::TooDeep] or
+/// [NbtError::TooManyElements] before it is deserialized, instead of
+/// overflowing the stack or exhausting memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+	/// Maximum nesting depth of compounds and lists
+	pub max_depth: usize,
+	/// Maximum total number of compound entries and list elements
+	///
+	/// The elements of typed arrays are not counted, as their size is
+	/// bounded by the length of the data.
+	pub max_elements: usize,
+}
+
+impl Default for Limits {
+	fn default() -> Self {
+		Limits {
+			max_depth: 512,
+			max_elements: 1 << 24,
+		}
+	}
+}
+
+/// Helper for checking big-endian NBT data against [Limits]
+struct Validator<'a> {
+	/// Remaining input data
+	input: &'a [u8],
+	/// Limits to enforce
+	limits: Limits,
+	/// Number of compound entries and list elements seen so far
+	elements: usize,
+}
+
+impl Validator<'_> {
+	/// Skips a number of bytes
+	fn skip(&mut self, len: usize) -> Result<()> {
+		self.input = self.input.get(len..).ok_or(NbtError::UnexpectedEof)?;
+		Ok(())
+	}
+
+	/// Reads a single byte
+	fn byte(&mut self) -> Result<u8> {
+		let value = *self.input.first().ok_or(NbtError::UnexpectedEof)?;
+		self.skip(1)?;
+		Ok(value)
+	}
+
+	/// Reads the length of an array or list
+	fn length(&mut self) -> Result<usize> {
+		let data = self.input.get(..4).ok_or(NbtError::UnexpectedEof)?;
+		let len = i32::from_be_bytes(data.try_into().unwrap());
+		self.skip(4)?;
+		usize::try_from(len).map_err(|_| NbtError::InvalidLength)
+	}
+
+	/// Skips a string
+	fn string(&mut self) -> Result<()> {
+		let data = self.input.get(..2).ok_or(NbtError::UnexpectedEof)?;
+		let len = u16::from_be_bytes(data.try_into().unwrap());
+		self.skip(2 + usize::from(len))
+	}
+
+	/// Accounts for a number of compound entries or list elements
+	fn elements(&mut self, count: usize) -> Result<()> {
+		self.elements = self.elements.saturating_add(count);
+		if self.elements > self.limits.max_elements {
+			return Err(NbtError::TooManyElements);
+		}
+		Ok(())
+	}
+
+	/// Checks the payload of a tag of the given type
+	fn payload(&mut self, tag: u8, depth: usize) -> Result<()> {
+		if depth > self.limits.max_depth {
+			return Err(NbtError::TooDeep);
+		}
+
+		match tag {
+			1 => self.skip(1),
+			2 => self.skip(2),
+			3 | 5 => self.skip(4),
+			4 | 6 => self.skip(8),
+			7 => {
+				let len = self.length()?;
+				self.skip(len)
+			}
+			8 => self.string(),
+			9 => {
+				let elem = self.byte()?;
+				let len = self.length()?;
+				self.elements(len)?;
+				for _ in 0..len {
+					self.payload(elem, depth + 1)?;
+				}
+				Ok(())
+			}
+			10 => loop {
+				let elem = self.byte()?;
+				if elem == 0 {
+					return Ok(());
+				}
+				self.elements(1)?;
+				self.string()?;
+				self.payload(elem, depth + 1)?;
+			},
+			11 => {
+				let len = self.length()?;
+				self.skip(len.checked_mul(4).ok_or(NbtError::InvalidLength)?)
+			}
+			12 => {
+				let len = self.length()?;
+				self.skip(len.checked_mul(8).ok_or(NbtError::InvalidLength)?)
+			}
+			_ => Err(NbtError::UnexpectedTag(tag)),
+		}
+	}
+}
+
+/// Checks uncompressed NBT data against the given [Limits]
+///
+/// Only the structure of the toplevel tag is checked; the data is not
+/// deserialized.
+pub fn validate(data: &[u8], limits: &Limits) -> Result<()> {
+	let mut validator = Validator {
+		input: data,
+		limits: *limits,
+		elements: 0,
+	};
+
+	let tag = validator.byte()?;
+	validator.string()?;
+	validator.payload(tag, 0)
+}
+
+/// Deserializes uncompressed NBT data to a given data structure
+///
+/// Unlike the other functions of this module, the data structure may borrow
+/// strings and arrays from the passed buffer, like the chunk data structures
+/// of the [de](crate::de) module. The data is checked against the default
+/// [Limits] first.
+pub fn from_bytes<'a, T>(data: &'a [u8]) -> Result<T>
+where
+	T: Deserialize<'a>,
+{
+	from_bytes_with_limits(data, &Limits::default())
+}
+
+/// Deserializes uncompressed NBT data to a given data structure, after
+/// checking it against the given [Limits]
+pub fn from_bytes_with_limits<'a, T>(data: &'a [u8], limits: &Limits) -> Result<T>
+where
+	T: Deserialize<'a>,
+{
+	validate(data, limits)?;
+	Ok(fastnbt::from_bytes(data)?)
+}
 
 /// Reads compressed NBT data from a reader and deserializes to a given data structure
-pub fn from_reader<R, T>(reader: R) -> Result<T>
+///
+/// Fails with [NbtError::DecompressedTooLarge] if the decompressed size
+/// exceeds the limit returned by [decompressed_limit].
+pub fn from_reader<R, T>(mut reader: R) -> Result<T>
 where
 	R: Read,
 	T: DeserializeOwned,
 {
-	let mut decoder = GzDecoder::new(reader);
-	let mut buf = vec![];
-	decoder
-		.read_to_end(&mut buf)
-		.context("Failed to read file")?;
+	let mut compressed = vec![];
+	reader.read_to_end(&mut compressed)?;
 
-	fastnbt::from_bytes(&buf).context("Failed to decode NBT data")
+	let limit = decompressed_limit(compressed.len());
+	let buf = read_to_limit(GzDecoder::new(compressed.as_slice()), limit)?
+		.ok_or(NbtError::DecompressedTooLarge(limit))?;
+
+	from_bytes(&buf)
 }
 
 /// Reads compressed NBT data from a file and deserializes to a given data structure
@@ -27,6 +197,167 @@ where
 	P: AsRef<Path>,
 	T: DeserializeOwned,
 {
-	let file = File::open(path).context("Failed to open file")?;
+	let file = File::open(path)?;
 	from_reader(file)
 }
+
+/// Detects the [Compression] of NBT data from its first bytes
+///
+/// GZip and Zlib streams are recognized by their headers; all other data is
+/// assumed to be uncompressed.
+pub fn detect_compression(data: &[u8]) -> Compression {
+	match data {
+		[0x1f, 0x8b, ..] => Compression::Gzip,
+		[cmf, flg, ..] if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 => {
+			Compression::Zlib
+		}
+		_ => Compression::Uncompressed,
+	}
+}
+
+/// Decompresses NBT data, detecting its [Compression]
+///
+/// Fails with [NbtError::DecompressedTooLarge] if the decompressed size
+/// exceeds the limit returned by [decompressed_limit].
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+	let limit = decompressed_limit(data.len());
+	let buf = match detect_compression(data) {
+		Compression::Gzip => read_to_limit(GzDecoder::new(data), limit)?,
+		Compression::Zlib => read_to_limit(ZlibDecoder::new(data), limit)?,
+		Compression::Uncompressed => Some(data.to_vec()),
+	};
+	buf.ok_or(NbtError::DecompressedTooLarge(limit))
+}
+
+/// Serializes a data structure to uncompressed NBT data
+pub fn to_bytes<T>(value: &T) -> Result<Vec<u8>>
+where
+	T: Serialize,
+{
+	fastnbt::to_bytes(value).map_err(NbtError::Encode)
+}
+
+/// Serializes a data structure and writes it as NBT data using the given
+/// [Compression]
+pub fn to_writer<W, T>(mut writer: W, value: &T, compression: Compression) -> Result<()>
+where
+	W: Write,
+	T: Serialize,
+{
+	let data = compression.compress(&to_bytes(value)?)?;
+	writer.write_all(&data)?;
+	Ok(())
+}
+
+/// Serializes a data structure and writes it to a file as compressed NBT data
+///
+/// The data is GZip-compressed like Minecraft's `level.dat`.
+pub fn to_file<P, T>(path: P, value: &T) -> Result<()>
+where
+	P: AsRef<Path>,
+	T: Serialize,
+{
+	let file = File::create(path)?;
+	to_writer(file, value, Compression::Gzip)
+}
+
+#[cfg(test)]
+mod test {
+	use std::collections::HashMap;
+
+	use fastnbt::Value;
+
+	use super::*;
+
+	/// Returns a value with lists nested *depth* levels deep
+	fn nested(depth: usize) -> Value {
+		let mut value = Value::List(vec![]);
+		for _ in 0..depth {
+			value = Value::List(vec![value]);
+		}
+		Value::Compound(HashMap::from([("value".to_string(), value)]))
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let value = Value::Compound(HashMap::from([
+			("int".to_string(), Value::Int(1)),
+			(
+				"list".to_string(),
+				Value::List(vec![Value::String("a".to_string())]),
+			),
+			(
+				"longs".to_string(),
+				Value::LongArray(fastnbt::LongArray::new(vec![1, 2])),
+			),
+		]));
+
+		for compression in [
+			Compression::Gzip,
+			Compression::Zlib,
+			Compression::Uncompressed,
+		] {
+			let mut data = vec![];
+			to_writer(&mut data, &value, compression).unwrap();
+			assert_eq!(detect_compression(&data), compression);
+			let decoded: Value = from_bytes(&decompress(&data).unwrap()).unwrap();
+			assert_eq!(decoded, value);
+		}
+	}
+
+	#[test]
+	fn test_limits_depth() {
+		let limits = Limits {
+			max_depth: 10,
+			..Limits::default()
+		};
+
+		// The toplevel compound is at depth 0, the outermost list at depth 1
+		let data = to_bytes(&nested(9)).unwrap();
+		assert!(validate(&data, &limits).is_ok());
+		let data = to_bytes(&nested(10)).unwrap();
+		assert!(matches!(
+			from_bytes_with_limits::<Value>(&data, &limits),
+			Err(NbtError::TooDeep)
+		));
+	}
+
+	#[test]
+	fn test_limits_elements() {
+		let limits = Limits {
+			max_elements: 10,
+			..Limits::default()
+		};
+
+		let value = |len| {
+			Value::Compound(HashMap::from([(
+				"list".to_string(),
+				Value::List(vec![Value::Int(0); len]),
+			)]))
+		};
+
+		// One compound entry plus the list elements
+		let data = to_bytes(&value(9)).unwrap();
+		assert!(validate(&data, &limits).is_ok());
+		let data = to_bytes(&value(10)).unwrap();
+		assert!(matches!(
+			validate(&data, &limits),
+			Err(NbtError::TooManyElements)
+		));
+	}
+
+	#[test]
+	fn test_limits_invalid() {
+		let data = to_bytes(&nested(3)).unwrap();
+		for len in 0..data.len() {
+			assert!(validate(&data[..len], &Limits::default()).is_err());
+		}
+
+		// List of ints claiming a negative length
+		let data = [10, 0, 0, 9, 0, 1, b'l', 3, 0xff, 0xff, 0xff, 0xff, 0];
+		assert!(matches!(
+			validate(&data, &Limits::default()),
+			Err(NbtError::InvalidLength)
+		));
+	}
+}
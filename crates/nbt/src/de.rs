@@ -0,0 +1,581 @@
+//! Data structures used to deserialize Minecraft save data
+//!
+//! Only the parts of the data needed for rendering are deserialized. The
+//! version-specific variants are determined after reading the fields of a
+//! compound, rather than by `#[serde(flatten)]` and untagged enums, as the
+//! latter buffer all unknown fields (like entities and block ticks) in memory
+//! instead of skipping them.
+//!
+//! The chunk data structures borrow strings and arrays from the NBT data they
+//! are deserialized from where possible, so
+//! [data::from_bytes](crate::data::from_bytes) should be used to decode them.
+
+use std::{borrow::Cow, collections::HashMap, fmt, marker::PhantomData};
+
+use serde::{de, Deserialize, Deserializer};
+
+/// Element type of an [Array]
+pub trait ArrayElement: Copy + fmt::Debug {
+	/// Decodes an element from its big-endian representation
+	fn from_be_slice(bytes: &[u8]) -> Self;
+}
+
+/// Implements [ArrayElement] for primitive integer types
+macro_rules! array_element {
+	($($t:ty),*) => {
+		$(impl ArrayElement for $t {
+			fn from_be_slice(bytes: &[u8]) -> Self {
+				Self::from_be_bytes(bytes.try_into().unwrap())
+			}
+		})*
+	};
+}
+
+array_element!(i8, i32, i64);
+
+/// NBT array, borrowing the big-endian data from the deserialized NBT data
+/// if possible
+#[derive(Debug, Clone)]
+pub enum Array<'a, T> {
+	/// Elements in big-endian byte order, borrowed from the NBT data
+	Borrowed(&'a [u8]),
+	/// Owned elements
+	Owned(Vec<T>),
+}
+
+/// NBT ByteArray that may borrow from the deserialized NBT data
+pub type ByteArray<'a> = Array<'a, i8>;
+
+/// NBT LongArray that may borrow from the deserialized NBT data
+pub type LongArray<'a> = Array<'a, i64>;
+
+impl<T: ArrayElement> Array<'_, T> {
+	/// Size of an element in bytes
+	const ELEMENT_SIZE: usize = std::mem::size_of::<T>();
+
+	/// Returns the number of elements
+	pub fn len(&self) -> usize {
+		match self {
+			Array::Borrowed(data) => data.len() / Self::ELEMENT_SIZE,
+			Array::Owned(values) => values.len(),
+		}
+	}
+
+	/// Returns true if the array does not have any elements
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Returns the element at the given index
+	///
+	/// # Panics
+	///
+	/// Panics if the index is out of bounds, like slice indexing.
+	pub fn value(&self, index: usize) -> T {
+		match self {
+			Array::Borrowed(data) => {
+				let start = index * Self::ELEMENT_SIZE;
+				T::from_be_slice(&data[start..start + Self::ELEMENT_SIZE])
+			}
+			Array::Owned(values) => values[index],
+		}
+	}
+
+	/// Returns an iterator over the elements
+	pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+		(0..self.len()).map(|index| self.value(index))
+	}
+}
+
+impl ByteArray<'_> {
+	/// Returns the elements as a slice
+	pub fn as_slice(&self) -> &[i8] {
+		match self {
+			Array::Borrowed(data) => bytemuck::cast_slice(data),
+			Array::Owned(values) => values,
+		}
+	}
+}
+
+impl<T> From<Vec<T>> for Array<'_, T> {
+	fn from(values: Vec<T>) -> Self {
+		Array::Owned(values)
+	}
+}
+
+impl<'de: 'a, 'a, T: ArrayElement> Deserialize<'de> for Array<'a, T> {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		/// Visitor for [Array]
+		struct ArrayVisitor<'a, T>(PhantomData<Array<'a, T>>);
+
+		impl<'a, T: ArrayElement> ArrayVisitor<'a, T> {
+			/// Checks that the data consists of whole elements
+			fn check<E: de::Error>(data: &[u8]) -> Result<(), E> {
+				if !data.len().is_multiple_of(Array::<T>::ELEMENT_SIZE) {
+					return Err(E::invalid_length(data.len(), &"whole array elements"));
+				}
+				Ok(())
+			}
+		}
+
+		impl<'de: 'a, 'a, T: ArrayElement> de::Visitor<'de> for ArrayVisitor<'a, T> {
+			type Value = Array<'a, T>;
+
+			fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+				formatter.write_str("an NBT array")
+			}
+
+			fn visit_borrowed_bytes<E: de::Error>(self, data: &'de [u8]) -> Result<Self::Value, E> {
+				Self::check(data)?;
+				Ok(Array::Borrowed(data))
+			}
+
+			fn visit_bytes<E: de::Error>(self, data: &[u8]) -> Result<Self::Value, E> {
+				Self::check(data)?;
+				Ok(Array::Owned(
+					data.chunks_exact(Array::<T>::ELEMENT_SIZE)
+						.map(T::from_be_slice)
+						.collect(),
+				))
+			}
+		}
+
+		deserializer.deserialize_bytes(ArrayVisitor(PhantomData))
+	}
+}
+
+/// Element of the `palette` list of 1.18+ [block states](BlockStatesV1_18)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BlockStatePaletteEntry<'a> {
+	/// Block type ID
+	#[serde(borrow)]
+	pub name: Cow<'a, str>,
+}
+
+/// 1.18+ `block_states` element found in a [section](SectionV1_18)
+#[derive(Debug, Deserialize)]
+pub struct BlockStatesV1_18<'a> {
+	/// Palette of block types, indexed by block data
+	#[serde(borrow)]
+	pub palette: Vec<BlockStatePaletteEntry<'a>>,
+	/// Block data
+	#[serde(borrow)]
+	pub data: Option<LongArray<'a>>,
+}
+
+/// 1.18+ `biomes` element found in a [section](SectionV1_18)
+#[derive(Debug, Deserialize)]
+pub struct BiomesV1_18<'a> {
+	/// Palette of biome types, indexed by biome data
+	#[serde(borrow)]
+	pub palette: Vec<Cow<'a, str>>,
+	/// Biome data
+	#[serde(borrow)]
+	pub data: Option<LongArray<'a>>,
+}
+
+/// Variable part of a [SectionV1_18]
+#[derive(Debug)]
+pub enum SectionV1_18Variants<'a> {
+	/// Populated 1.18+ section
+	V1_18 {
+		/// Block type data
+		block_states: BlockStatesV1_18<'a>,
+		/// Biome data
+		biomes: BiomesV1_18<'a>,
+		/// Block light data
+		block_light: Option<ByteArray<'a>>,
+	},
+	/// Empty section
+	Empty {},
+}
+
+/// Fields of a [SectionV1_18] compound
+#[derive(Debug, Deserialize)]
+struct SectionV1_18Fields<'a> {
+	/// Y coordinate
+	#[serde(rename = "Y")]
+	y: i32,
+	/// Block type data
+	#[serde(borrow)]
+	block_states: Option<BlockStatesV1_18<'a>>,
+	/// Biome data
+	#[serde(borrow)]
+	biomes: Option<BiomesV1_18<'a>>,
+	/// Block light data
+	#[serde(rename = "BlockLight", borrow)]
+	block_light: Option<ByteArray<'a>>,
+}
+
+impl<'a> From<SectionV1_18Fields<'a>> for SectionV1_18<'a> {
+	fn from(fields: SectionV1_18Fields<'a>) -> Self {
+		let SectionV1_18Fields {
+			y,
+			block_states,
+			biomes,
+			block_light,
+		} = fields;
+
+		let section = match (block_states, biomes) {
+			(Some(block_states), Some(biomes)) => SectionV1_18Variants::V1_18 {
+				block_states,
+				biomes,
+				block_light,
+			},
+			_ => SectionV1_18Variants::Empty {},
+		};
+		SectionV1_18 { y, section }
+	}
+}
+
+/// Element of the 1.18+ `sections` list found in a [Chunk]
+#[derive(Debug, Deserialize)]
+#[serde(from = "SectionV1_18Fields<'a>", bound(deserialize = "'de: 'a"))]
+pub struct SectionV1_18<'a> {
+	/// Y coordinate
+	pub y: i32,
+	/// Variable part of section
+	pub section: SectionV1_18Variants<'a>,
+}
+
+/// Version-specific part of a pre-1.18 [Section](SectionV0)
+#[derive(Debug)]
+pub enum SectionV0Variants<'a> {
+	/// v1.13+ data
+	V1_13 {
+		/// Block data
+		block_states: LongArray<'a>,
+		/// Block type palette, indexed by block data
+		palette: Vec<BlockStatePaletteEntry<'a>>,
+	},
+	/// Pre-1.13 data
+	V0 {
+		/// Block type data
+		blocks: ByteArray<'a>,
+		/// Block damage / subtype data
+		data: ByteArray<'a>,
+	},
+	/// Empty section
+	Empty {},
+}
+
+/// Fields of a [SectionV0] compound
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SectionV0Fields<'a> {
+	/// Y coordinate
+	y: i32,
+	/// Block light data
+	#[serde(borrow)]
+	block_light: Option<ByteArray<'a>>,
+	/// v1.13+ block data
+	#[serde(borrow)]
+	block_states: Option<LongArray<'a>>,
+	/// v1.13+ block type palette
+	#[serde(borrow)]
+	palette: Option<Vec<BlockStatePaletteEntry<'a>>>,
+	/// Pre-1.13 block type data
+	#[serde(borrow)]
+	blocks: Option<ByteArray<'a>>,
+	/// Pre-1.13 block damage / subtype data
+	#[serde(borrow)]
+	data: Option<ByteArray<'a>>,
+}
+
+impl<'a> From<SectionV0Fields<'a>> for SectionV0<'a> {
+	fn from(fields: SectionV0Fields<'a>) -> Self {
+		let SectionV0Fields {
+			y,
+			block_light,
+			block_states,
+			palette,
+			blocks,
+			data,
+		} = fields;
+
+		let section = match (block_states, palette, blocks, data) {
+			(Some(block_states), Some(palette), _, _) => SectionV0Variants::V1_13 {
+				block_states,
+				palette,
+			},
+			(_, _, Some(blocks), Some(data)) => SectionV0Variants::V0 { blocks, data },
+			_ => SectionV0Variants::Empty {},
+		};
+		SectionV0 {
+			y,
+			block_light,
+			section,
+		}
+	}
+}
+
+/// Pre-1.18 section element found in the [Level](LevelV0) compound
+#[derive(Debug, Deserialize)]
+#[serde(from = "SectionV0Fields<'a>", bound(deserialize = "'de: 'a"))]
+pub struct SectionV0<'a> {
+	/// Y coordinate
+	///
+	/// Stored as a byte by Minecraft, but may be larger in worlds of the
+	/// Cubic Chunks mod.
+	pub y: i32,
+	/// Block light data
+	pub block_light: Option<ByteArray<'a>>,
+	/// Version-specific data
+	pub section: SectionV0Variants<'a>,
+}
+
+/// Pre-1.18 biome fields found in the [Level](LevelV0) compound
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum BiomesV0 {
+	/// Data for Minecraft versions storing biome data as an IntArray
+	IntArray(fastnbt::IntArray),
+	/// Data for Minecraft versions storing biome data as an ByteArray
+	ByteArray(fastnbt::ByteArray),
+}
+
+/// `Level` compound element found in pre-1.18 [chunks](Chunk)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LevelV0<'a> {
+	/// Section data
+	#[serde(default, borrow)]
+	pub sections: Vec<SectionV0<'a>>,
+	/// Biome data
+	pub biomes: Option<BiomesV0>,
+	/// Total time players have spent in the chunk, in ticks
+	pub inhabited_time: Option<i64>,
+}
+
+/// Version-specific part of a [Chunk] compound
+#[derive(Debug)]
+pub enum ChunkVariants<'a> {
+	/// 1.18+ chunk data
+	V1_18 {
+		/// List of chunk sections
+		sections: Vec<SectionV1_18<'a>>,
+	},
+	/// Pre-1.18 chunk data
+	V0 {
+		/// `Level` field of the chunk
+		level: LevelV0<'a>,
+	},
+}
+
+/// Fields of a [Chunk] compound
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ChunkFields<'a> {
+	/// The data version of the chunk
+	data_version: Option<u32>,
+	/// 1.18+ list of chunk sections
+	#[serde(rename = "sections", borrow)]
+	sections: Option<Vec<SectionV1_18<'a>>>,
+	/// Pre-1.18 `Level` field
+	#[serde(borrow)]
+	level: Option<LevelV0<'a>>,
+	/// 1.18+ total time players have spent in the chunk
+	inhabited_time: Option<i64>,
+}
+
+impl<'a> TryFrom<ChunkFields<'a>> for Chunk<'a> {
+	type Error = &'static str;
+
+	fn try_from(fields: ChunkFields<'a>) -> Result<Self, Self::Error> {
+		let ChunkFields {
+			data_version,
+			sections,
+			level,
+			inhabited_time,
+		} = fields;
+
+		let inhabited_time =
+			inhabited_time.or_else(|| level.as_ref().and_then(|level| level.inhabited_time));
+		let chunk = match (sections, level) {
+			(Some(sections), _) => ChunkVariants::V1_18 { sections },
+			(None, Some(level)) => ChunkVariants::V0 { level },
+			(None, None) => return Err("missing field `sections` or `Level`"),
+		};
+		Ok(Chunk {
+			data_version,
+			chunk,
+			inhabited_time,
+		})
+	}
+}
+
+/// Toplevel compound element of a Minecraft chunk
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "ChunkFields<'a>", bound(deserialize = "'de: 'a"))]
+pub struct Chunk<'a> {
+	/// The data version of the chunk
+	pub data_version: Option<u32>,
+	/// Version-specific chunk data
+	pub chunk: ChunkVariants<'a>,
+	/// Total time players have spent in the chunk, in ticks
+	///
+	/// Taken from the `Level` compound for chunks saved before Minecraft 1.18.
+	pub inhabited_time: Option<i64>,
+}
+
+/// Height range and ceiling of a dimension, as specified by its dimension type
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct DimensionType {
+	/// Lowest Y coordinate of the dimension
+	pub min_y: i32,
+	/// Number of block layers of the dimension
+	pub height: i32,
+	/// Set if the dimension has a solid ceiling, like the Nether
+	#[serde(default)]
+	pub has_ceiling: bool,
+}
+
+/// Color of a [WorldgenBiomeEffects] entry
+///
+/// Colors are stored as integers, or as `#rrggbb` strings by newer versions.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum WorldgenColor {
+	/// Color as an integer RGB value
+	Int(u32),
+	/// Color in `#rrggbb` notation
+	Hex(String),
+}
+
+impl WorldgenColor {
+	/// Returns the red, green and blue components of the color
+	///
+	/// Returns [None] for malformed color strings.
+	pub fn rgb(&self) -> Option<[u8; 3]> {
+		let value = match self {
+			WorldgenColor::Int(value) => *value,
+			WorldgenColor::Hex(value) => {
+				let hex = value.strip_prefix('#')?;
+				if hex.len() != 6 {
+					return None;
+				}
+				u32::from_str_radix(hex, 16).ok()?
+			}
+		};
+		let [_, r, g, b] = value.to_be_bytes();
+		Some([r, g, b])
+	}
+}
+
+/// `effects` element of a [WorldgenBiome]
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct WorldgenBiomeEffects {
+	/// Water color
+	pub water_color: Option<WorldgenColor>,
+	/// Foliage color override
+	pub foliage_color: Option<WorldgenColor>,
+	/// Grass color override
+	pub grass_color: Option<WorldgenColor>,
+	/// Grass color modifier (`none`, `dark_forest` or `swamp`)
+	pub grass_color_modifier: Option<String>,
+}
+
+/// Biome definition found in `worldgen/biome` of the game data or a datapack
+#[derive(Debug, Clone, Deserialize)]
+pub struct WorldgenBiome {
+	/// Temperature value
+	pub temperature: f32,
+	/// Downfall value
+	pub downfall: f32,
+	/// Biome colors
+	#[serde(default)]
+	pub effects: WorldgenBiomeEffects,
+}
+
+/// Dimension type of a [LevelDimension]
+///
+/// Dimension types can either be referenced by their ID or specified inline.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum DimensionTypeRef {
+	/// Reference to a built-in or datapack dimension type
+	Id(String),
+	/// Inline dimension type
+	Inline(DimensionType),
+}
+
+/// Element of the `dimensions` compound of [WorldGenSettings]
+#[derive(Debug, Deserialize)]
+pub struct LevelDimension {
+	/// The dimension type
+	#[serde(rename = "type")]
+	pub dimension_type: DimensionTypeRef,
+}
+
+/// `WorldGenSettings` compound element of level.dat
+#[derive(Debug, Deserialize)]
+pub struct WorldGenSettings {
+	/// World seed
+	pub seed: Option<i64>,
+	/// Dimensions of the world, indexed by their IDs
+	#[serde(default)]
+	pub dimensions: HashMap<String, LevelDimension>,
+}
+
+/// `DataPacks` compound element of level.dat
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DataPacks {
+	/// IDs of the enabled datapacks, in order of increasing priority
+	#[serde(default)]
+	pub enabled: Vec<String>,
+	/// IDs of the disabled datapacks
+	#[serde(default)]
+	pub disabled: Vec<String>,
+}
+
+/// `Data` compound element of level.dat
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LevelDatData {
+	/// The data version of the level.dat
+	pub data_version: Option<u32>,
+	/// X coordinate of spawn point for new players
+	pub spawn_x: i32,
+	/// Z coordinate of spawn point for new players
+	pub spawn_z: i32,
+	/// World seed (before Minecraft 1.16)
+	pub random_seed: Option<i64>,
+	/// World generation settings (Minecraft 1.16+)
+	pub world_gen_settings: Option<WorldGenSettings>,
+	/// Enabled and disabled datapacks (Minecraft 1.13+)
+	pub data_packs: Option<DataPacks>,
+	/// X coordinate of the center of the world border
+	pub border_center_x: Option<f64>,
+	/// Z coordinate of the center of the world border
+	pub border_center_z: Option<f64>,
+	/// Side length of the world border
+	pub border_size: Option<f64>,
+	/// Game rules of the world
+	///
+	/// The values are stored as strings by Java Edition.
+	#[serde(default)]
+	pub game_rules: HashMap<String, fastnbt::Value>,
+}
+
+impl LevelDatData {
+	/// Returns the world seed
+	pub fn seed(&self) -> Option<i64> {
+		self.world_gen_settings
+			.as_ref()
+			.and_then(|settings| settings.seed)
+			.or(self.random_seed)
+	}
+}
+
+/// Toplevel compound element of level.dat
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LevelDat {
+	/// The `Data` field
+	pub data: LevelDatData,
+}
@@ -0,0 +1,139 @@
+//! Error types of the NBT and region file handling
+
+use std::io;
+
+use thiserror::Error;
+
+use minedmap_types::*;
+
+/// Error reading or decoding NBT data
+#[derive(Debug, Error)]
+pub enum NbtError {
+	/// Failed to read or decompress the data
+	#[error("Failed to read NBT data")]
+	Io(#[from] io::Error),
+	/// The data is not valid NBT or does not match the expected structure
+	#[error("Failed to decode NBT data")]
+	Decode(#[from] fastnbt::error::Error),
+	/// Failed to serialize a value
+	#[error("Failed to encode NBT data")]
+	Encode(#[source] fastnbt::error::Error),
+	/// The decompressed data exceeds the size limit
+	#[error("Decompressed NBT data exceeds limit of {0} bytes")]
+	DecompressedTooLarge(usize),
+	/// The data ended inside of a tag
+	#[error("Unexpected end of NBT data")]
+	UnexpectedEof,
+	/// An unknown tag type was found
+	#[error("Invalid NBT tag type {0}")]
+	UnexpectedTag(u8),
+	/// A negative array or list length was found
+	#[error("Invalid NBT length")]
+	InvalidLength,
+	/// Compounds and lists are nested too deeply
+	#[error("NBT data nested too deeply")]
+	TooDeep,
+	/// Compounds and lists contain too many elements in total
+	#[error("Too many NBT elements")]
+	TooManyElements,
+	/// Failed to parse SNBT
+	#[error("Invalid SNBT at position {position}: {message}")]
+	InvalidSnbt {
+		/// Byte offset of the error in the input
+		position: usize,
+		/// Description of the error
+		message: &'static str,
+	},
+}
+
+/// Error reading or writing the data of a single chunk
+#[derive(Debug, Error)]
+pub enum ChunkError {
+	/// The length stored in front of the chunk data is invalid
+	#[error("Invalid chunk length")]
+	InvalidLength,
+	/// Failed to read the chunk data
+	#[error("Failed to read chunk data (truncated region file?)")]
+	Read(#[source] io::Error),
+	/// The chunk is stored in an external file, but no
+	/// [ExternalChunkReader](crate::region::ExternalChunkReader) was set
+	#[error("Unsupported external chunk")]
+	UnsupportedExternal,
+	/// Failed to read the external file of an oversized chunk
+	#[error("Failed to read external chunk")]
+	External(#[source] io::Error),
+	/// The compression format byte is unknown
+	#[error("Unknown chunk format {0}")]
+	UnknownFormat(u8),
+	/// Failed to decompress the chunk data
+	#[error("Failed to decompress chunk data")]
+	Decompress(#[source] io::Error),
+	/// The decompressed chunk data exceeds the size limit
+	#[error("Decompressed chunk data exceeds limit of {0} bytes")]
+	DecompressedTooLarge(usize),
+	/// Failed to compress the chunk data
+	#[error("Failed to compress chunk data")]
+	Compress(#[source] io::Error),
+	/// The chunk data does not fit into a region file
+	#[error("Chunk too large")]
+	TooLarge,
+	/// Failed to decode or encode the NBT data of the chunk
+	#[error(transparent)]
+	Nbt(#[from] NbtError),
+}
+
+/// Error reading or writing a region file
+#[derive(Debug, Error)]
+pub enum RegionError {
+	/// Failed to open the region file
+	#[error("Failed to open file")]
+	Open(#[source] io::Error),
+	/// Failed to read the header of the region
+	#[error("Failed to read region header")]
+	CorruptHeader(#[source] io::Error),
+	/// The header contains an invalid offset for a chunk
+	#[error("Invalid offset for chunk {0:?}")]
+	InvalidOffset(ChunkCoords),
+	/// The data of a chunk overlaps with another chunk
+	#[error("Overlapping data for chunk {0:?}")]
+	OverlappingChunk(ChunkCoords),
+	/// The signature of a linear region file is invalid
+	#[error("Invalid linear region signature")]
+	InvalidSignature,
+	/// The version of a linear region file is not supported
+	#[error("Unsupported linear region version {0}")]
+	UnsupportedVersion(u8),
+	/// Failed to decompress a linear region file
+	#[error("Failed to decompress region")]
+	Decompress(#[source] io::Error),
+	/// The decompressed data of a linear region file exceeds the size limit
+	#[error("Decompressed region data exceeds limit of {0} bytes")]
+	DecompressedTooLarge(usize),
+	/// The decompressed data of a linear region file is invalid
+	#[error("Invalid region data")]
+	InvalidData,
+	/// Failed to read, decode or write a chunk
+	#[error("Failed to process chunk {coords:?}")]
+	Chunk {
+		/// Coordinates of the chunk
+		coords: ChunkCoords,
+		/// Error of the chunk
+		#[source]
+		source: ChunkError,
+	},
+	/// Failed to write the region file
+	#[error("Failed to write region file")]
+	Write(#[source] io::Error),
+}
+
+impl RegionError {
+	/// Returns the coordinates of the chunk the error refers to, if any
+	pub fn chunk_coords(&self) -> Option<ChunkCoords> {
+		match self {
+			RegionError::InvalidOffset(coords)
+			| RegionError::OverlappingChunk(coords)
+			| RegionError::Chunk { coords, .. } => Some(*coords),
+			_ => None,
+		}
+	}
+}
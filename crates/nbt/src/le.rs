@@ -0,0 +1,169 @@
+//! Functions for decoding little-endian NBT data
+//!
+//! Minecraft Bedrock Edition stores NBT data with little-endian numbers.
+//! The data is converted to the big-endian format used by the Java Edition,
+//! so it can be deserialized using [fastnbt].
+
+use serde::de::DeserializeOwned;
+
+use crate::data::{Limits, NbtError, Result};
+
+/// Helper for converting little-endian to big-endian NBT data
+struct Converter<'a> {
+	/// Remaining input data
+	input: &'a [u8],
+	/// Converted data
+	output: Vec<u8>,
+	/// Limits to enforce
+	limits: Limits,
+	/// Number of compound entries and list elements seen so far
+	elements: usize,
+}
+
+impl<'a> Converter<'a> {
+	/// Consumes a number of bytes from the input
+	fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+		if self.input.len() < len {
+			return Err(NbtError::UnexpectedEof);
+		}
+		let (head, tail) = self.input.split_at(len);
+		self.input = tail;
+		Ok(head)
+	}
+
+	/// Copies a single byte
+	fn byte(&mut self) -> Result<u8> {
+		let value = self.take(1)?[0];
+		self.output.push(value);
+		Ok(value)
+	}
+
+	/// Copies a number of *len* bytes, reversing its byte order
+	fn number(&mut self, len: usize) -> Result<()> {
+		let data = self.take(len)?;
+		self.output.extend(data.iter().rev());
+		Ok(())
+	}
+
+	/// Copies the length of an array or list
+	fn length(&mut self) -> Result<usize> {
+		let data = self.take(4)?;
+		let len = i32::from_le_bytes(data.try_into().unwrap());
+		self.output.extend(len.to_be_bytes());
+		usize::try_from(len).map_err(|_| NbtError::InvalidLength)
+	}
+
+	/// Copies a string
+	fn string(&mut self) -> Result<()> {
+		let data = self.take(2)?;
+		let len = u16::from_le_bytes(data.try_into().unwrap());
+		self.output.extend(len.to_be_bytes());
+		let data = self.take(len.into())?;
+		self.output.extend_from_slice(data);
+		Ok(())
+	}
+
+	/// Accounts for a number of compound entries or list elements
+	fn elements(&mut self, count: usize) -> Result<()> {
+		self.elements = self.elements.saturating_add(count);
+		if self.elements > self.limits.max_elements {
+			return Err(NbtError::TooManyElements);
+		}
+		Ok(())
+	}
+
+	/// Copies the payload of a tag of the given type
+	fn payload(&mut self, tag: u8, depth: usize) -> Result<()> {
+		if depth > self.limits.max_depth {
+			return Err(NbtError::TooDeep);
+		}
+
+		match tag {
+			1 => self.number(1),
+			2 => self.number(2),
+			3 | 5 => self.number(4),
+			4 | 6 => self.number(8),
+			7 => {
+				let len = self.length()?;
+				let data = self.take(len)?;
+				self.output.extend_from_slice(data);
+				Ok(())
+			}
+			8 => self.string(),
+			9 => {
+				let elem = self.byte()?;
+				let len = self.length()?;
+				self.elements(len)?;
+				for _ in 0..len {
+					self.payload(elem, depth + 1)?;
+				}
+				Ok(())
+			}
+			10 => loop {
+				let elem = self.byte()?;
+				if elem == 0 {
+					return Ok(());
+				}
+				self.elements(1)?;
+				self.string()?;
+				self.payload(elem, depth + 1)?;
+			},
+			11 => {
+				let len = self.length()?;
+				for _ in 0..len {
+					self.number(4)?;
+				}
+				Ok(())
+			}
+			12 => {
+				let len = self.length()?;
+				for _ in 0..len {
+					self.number(8)?;
+				}
+				Ok(())
+			}
+			_ => Err(NbtError::UnexpectedTag(tag)),
+		}
+	}
+}
+
+/// Converts the little-endian NBT tag at the start of a buffer to big-endian format
+///
+/// Returns the converted data and the number of bytes consumed. The data is
+/// checked against the default [Limits].
+pub fn to_big_endian(input: &[u8]) -> Result<(Vec<u8>, usize)> {
+	to_big_endian_with_limits(input, &Limits::default())
+}
+
+/// Converts the little-endian NBT tag at the start of a buffer to big-endian
+/// format, checking it against the given [Limits]
+///
+/// Returns the converted data and the number of bytes consumed.
+pub fn to_big_endian_with_limits(input: &[u8], limits: &Limits) -> Result<(Vec<u8>, usize)> {
+	let mut converter = Converter {
+		input,
+		output: Vec::new(),
+		limits: *limits,
+		elements: 0,
+	};
+
+	let tag = converter.byte()?;
+	converter.string()?;
+	converter.payload(tag, 0)?;
+
+	let consumed = input.len() - converter.input.len();
+	Ok((converter.output, consumed))
+}
+
+/// Deserializes the little-endian NBT tag at the start of a buffer
+///
+/// Returns the deserialized data and the number of bytes consumed, allowing
+/// to decode multiple consecutive tags.
+pub fn from_bytes<T>(input: &[u8]) -> Result<(T, usize)>
+where
+	T: DeserializeOwned,
+{
+	let (data, consumed) = to_big_endian(input)?;
+	let value = fastnbt::from_bytes(&data)?;
+	Ok((value, consumed))
+}
@@ -1,6 +1,34 @@
 #![doc = env!("CARGO_PKG_DESCRIPTION")]
+//!
+//! The crate provides the save data reading of MinedMap for use by other
+//! tools:
+//!
+//! - [region] reads (and writes) Anvil region files, either by iterating
+//!   over all chunks or by accessing single chunks using the region header
+//! - [data] and [le] decode standalone NBT data like `level.dat`
+//! - [de] contains [serde] data structures for the chunk and level data of
+//!   the different Minecraft versions
+//! - [snbt] formats and parses NBT data in the text syntax of Minecraft
+//!   commands
+//!
+//! ```no_run
+//! use minedmap_nbt::{data, de, region};
+//!
+//! for (coords, chunk) in region::from_file("r.0.0.mca")?.chunks()? {
+//!     let buffer = chunk?.decompress()?;
+//!     let chunk: de::Chunk = data::from_bytes(&buffer)?;
+//!     println!("{:?}: DataVersion {:?}", coords, chunk.data_version);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
 #![warn(missing_docs)]
 #![warn(clippy::missing_docs_in_private_items)]
 
 pub mod data;
+pub mod de;
+mod error;
+pub mod le;
+#[cfg(feature = "zstd")]
+pub mod linear;
 pub mod region;
+pub mod snbt;
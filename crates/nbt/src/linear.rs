@@ -0,0 +1,295 @@
+//! Functions for reading and deserializing Paper "linear" region files
+//!
+//! The LinearRegionFormat used by some Paper forks stores a whole region as
+//! a single zstd-compressed stream. After decompression, a table of chunk
+//! sizes and timestamps is followed by the uncompressed NBT data of all
+//! chunks in order.
+
+use std::io::{self, prelude::*};
+
+use serde::de::DeserializeOwned;
+
+use minedmap_types::*;
+
+use crate::{
+	data,
+	region::{decompressed_limit, read_to_limit, ChunkError, RegionError, Result},
+};
+
+/// Signature at the start and the end of linear region files
+const SIGNATURE: u64 = 0xc3ff13183cca9d9a;
+
+/// Size of the header of linear region files
+///
+/// The header consists of the signature, the format version, the newest
+/// chunk timestamp, the compression level, the number of chunks, the length
+/// of the compressed data and a reserved field.
+const HEADER_SIZE: usize = 32;
+
+/// Size of the chunk table at the start of the decompressed data
+const TABLE_SIZE: usize = 8 * CHUNKS_PER_REGION * CHUNKS_PER_REGION;
+
+/// Wraps a reader used to read a linear region file
+#[derive(Debug)]
+pub struct Linear<R: Read> {
+	/// The wrapped reader
+	reader: R,
+}
+
+impl<R: Read> Linear<R> {
+	/// Iterates over the chunks of the region data
+	///
+	/// Chunks are iterated in ZX order. Errors returned by *f* end the
+	/// iteration and are passed through.
+	pub fn foreach_chunk<T, E, F>(self, mut f: F) -> Result<(), E>
+	where
+		T: DeserializeOwned,
+		E: From<RegionError>,
+		F: FnMut(ChunkCoords, T) -> Result<(), E>,
+	{
+		self.foreach_chunk_data(|coords, buffer| {
+			let chunk = data::from_bytes(buffer).map_err(|err| RegionError::Chunk {
+				coords,
+				source: err.into(),
+			})?;
+			f(coords, chunk)
+		})
+	}
+
+	/// Iterates over the uncompressed NBT data of the chunks of the region
+	///
+	/// This allows to deserialize data structures borrowing from the data
+	/// using [data::from_bytes](crate::data::from_bytes). Chunks are iterated
+	/// in ZX order. Errors returned by *f* end the iteration and are passed
+	/// through.
+	pub fn foreach_chunk_data<E, F>(self, mut f: F) -> Result<(), E>
+	where
+		E: From<RegionError>,
+		F: FnMut(ChunkCoords, &[u8]) -> Result<(), E>,
+	{
+		self.foreach_chunk_entry(|coords, _, buffer| f(coords, buffer))
+	}
+
+	/// Iterates over the uncompressed NBT data and the modification timestamps
+	/// of the chunks of the region
+	///
+	/// Like [Linear::foreach_chunk_data], but the timestamp of each chunk (in
+	/// seconds since the Unix epoch) is passed to *f* as well.
+	pub fn foreach_chunk_entry<E, F>(self, mut f: F) -> Result<(), E>
+	where
+		E: From<RegionError>,
+		F: FnMut(ChunkCoords, u32, &[u8]) -> Result<(), E>,
+	{
+		let Linear { mut reader } = self;
+
+		let mut header = [0u8; HEADER_SIZE];
+		reader
+			.read_exact(&mut header)
+			.map_err(RegionError::CorruptHeader)?;
+
+		let signature = u64::from_be_bytes(header[0..8].try_into().unwrap());
+		if signature != SIGNATURE {
+			return Err(RegionError::InvalidSignature.into());
+		}
+		let version = header[8];
+		if version != 1 {
+			return Err(RegionError::UnsupportedVersion(version).into());
+		}
+		let len = u32::from_be_bytes(header[20..24].try_into().unwrap());
+
+		// The length is not trusted for allocating the buffer, so a corrupt
+		// header can't make us reserve more memory than the file contains
+		let mut compressed = vec![];
+		reader
+			.take(len.into())
+			.read_to_end(&mut compressed)
+			.map_err(RegionError::Decompress)?;
+		if compressed.len() != len as usize {
+			return Err(RegionError::Decompress(io::ErrorKind::UnexpectedEof.into()).into());
+		}
+
+		let limit = decompressed_limit(compressed.len());
+		let decoder = zstd::stream::read::Decoder::new(compressed.as_slice())
+			.map_err(RegionError::Decompress)?;
+		let data = read_to_limit(decoder, limit)
+			.map_err(RegionError::Decompress)?
+			.ok_or(RegionError::DecompressedTooLarge(limit))?;
+		if data.len() < TABLE_SIZE {
+			return Err(RegionError::InvalidData.into());
+		}
+		let (table, mut chunk_data) = data.split_at(TABLE_SIZE);
+
+		for (coords, entry) in ChunkArray::<()>::keys().zip(table.chunks_exact(8)) {
+			let size = u32::from_be_bytes(entry[0..4].try_into().unwrap()) as usize;
+			let timestamp = u32::from_be_bytes(entry[4..8].try_into().unwrap());
+			if size == 0 {
+				continue;
+			}
+			if chunk_data.len() < size {
+				return Err(RegionError::Chunk {
+					coords,
+					source: ChunkError::InvalidLength,
+				}
+				.into());
+			}
+			let (buffer, rest) = chunk_data.split_at(size);
+			chunk_data = rest;
+
+			f(coords, timestamp, buffer)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Creates a new [Linear] from a reader
+pub fn from_reader<R>(reader: R) -> Linear<R>
+where
+	R: Read,
+{
+	Linear { reader }
+}
+
+/// Writer for linear region files
+///
+/// As the whole region is compressed as a single stream, the chunk data is
+/// collected in memory and only written when [Writer::finish] is called.
+#[derive(Debug, Default)]
+pub struct Writer {
+	/// Timestamp and uncompressed NBT data of each chunk
+	chunks: ChunkArray<Option<(u32, Vec<u8>)>>,
+	/// zstd compression level
+	level: i8,
+}
+
+impl Writer {
+	/// Creates an empty linear region
+	pub fn new() -> Self {
+		Writer::default()
+	}
+
+	/// Sets the zstd compression level
+	///
+	/// The default level of 0 selects the default of the zstd library.
+	pub fn with_compression_level(mut self, level: i8) -> Self {
+		self.level = level;
+		self
+	}
+
+	/// Adds the uncompressed NBT data of a chunk with the given modification
+	/// timestamp, replacing any previous data of the chunk
+	pub fn write_chunk_data(&mut self, coords: ChunkCoords, data: &[u8], timestamp: u32) {
+		self.chunks[coords] = Some((timestamp, data.to_vec()));
+	}
+
+	/// Removes a chunk from the region
+	pub fn remove_chunk(&mut self, coords: ChunkCoords) {
+		self.chunks[coords] = None;
+	}
+
+	/// Compresses the region and writes it to a writer
+	pub fn finish<W: Write>(self, mut writer: W) -> Result<W> {
+		let mut data = Vec::with_capacity(TABLE_SIZE);
+		for chunk in self.chunks.values() {
+			let (size, timestamp) = chunk
+				.as_ref()
+				.map_or((0, 0), |(timestamp, data)| (data.len() as u32, *timestamp));
+			data.extend_from_slice(&size.to_be_bytes());
+			data.extend_from_slice(&timestamp.to_be_bytes());
+		}
+		for (_, chunk) in self.chunks.values().flatten() {
+			data.extend_from_slice(chunk);
+		}
+
+		let compressed = zstd::stream::encode_all(data.as_slice(), self.level.into())
+			.map_err(RegionError::Write)?;
+		let len = u32::try_from(compressed.len())
+			.map_err(|_| RegionError::Write(std::io::ErrorKind::FileTooLarge.into()))?;
+		let newest = self
+			.chunks
+			.values()
+			.flatten()
+			.map(|(timestamp, _)| *timestamp)
+			.max()
+			.unwrap_or_default();
+		let count = self.chunks.values().flatten().count() as u16;
+
+		let mut header = Vec::with_capacity(HEADER_SIZE);
+		header.extend_from_slice(&SIGNATURE.to_be_bytes());
+		header.push(1);
+		header.extend_from_slice(&u64::from(newest).to_be_bytes());
+		header.push(self.level as u8);
+		header.extend_from_slice(&count.to_be_bytes());
+		header.extend_from_slice(&len.to_be_bytes());
+		header.extend_from_slice(&[0; 8]);
+
+		(|| -> std::io::Result<()> {
+			writer.write_all(&header)?;
+			writer.write_all(&compressed)?;
+			writer.write_all(&SIGNATURE.to_be_bytes())?;
+			writer.flush()
+		})()
+		.map_err(RegionError::Write)?;
+
+		Ok(writer)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// Returns the coordinates of a chunk inside a region
+	fn coords(x: u8, z: u8) -> ChunkCoords {
+		ChunkCoords {
+			x: ChunkX::new(x),
+			z: ChunkZ::new(z),
+		}
+	}
+
+	/// Reads the entries of all chunks of a linear region
+	fn read_entries(data: &[u8]) -> Result<Vec<(ChunkCoords, u32, Vec<u8>)>> {
+		let mut entries = Vec::new();
+		from_reader(data).foreach_chunk_entry(|coords, timestamp, data| {
+			entries.push((coords, timestamp, data.to_vec()));
+			Ok::<_, RegionError>(())
+		})?;
+		Ok(entries)
+	}
+
+	#[test]
+	fn test_writer_round_trip() {
+		let mut writer = Writer::new().with_compression_level(3);
+		writer.write_chunk_data(coords(0, 0), b"first", 1000);
+		writer.write_chunk_data(coords(31, 0), b"second", 2000);
+		writer.write_chunk_data(coords(5, 31), b"third", 3000);
+		writer.write_chunk_data(coords(6, 31), b"removed", 4000);
+		writer.remove_chunk(coords(6, 31));
+		let data = writer.finish(Vec::new()).unwrap();
+
+		assert_eq!(
+			read_entries(&data).unwrap(),
+			[
+				(coords(0, 0), 1000, b"first".to_vec()),
+				(coords(31, 0), 2000, b"second".to_vec()),
+				(coords(5, 31), 3000, b"third".to_vec()),
+			]
+		);
+	}
+
+	#[test]
+	fn test_truncated() {
+		let mut writer = Writer::new();
+		writer.write_chunk_data(coords(0, 0), b"data", 1000);
+		let data = writer.finish(Vec::new()).unwrap();
+
+		assert!(matches!(
+			read_entries(&data[..HEADER_SIZE + 4]),
+			Err(RegionError::Decompress(_))
+		));
+		assert!(matches!(
+			read_entries(&data[..8]),
+			Err(RegionError::CorruptHeader(_))
+		));
+	}
+}
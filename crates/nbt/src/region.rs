@@ -1,17 +1,27 @@
-//! Functions for reading and deserializing region data
+//! Functions for reading and deserializing region data, and for writing
+//! region files
 
 use std::{
-	fs::File,
-	io::{prelude::*, SeekFrom},
+	fs::{File, OpenOptions},
+	io::{self, prelude::*, SeekFrom},
 	path::Path,
+	time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::{bail, Context, Result};
-use flate2::read::ZlibDecoder;
-use serde::de::DeserializeOwned;
+use flate2::{
+	read::{GzDecoder, ZlibDecoder},
+	write::{GzEncoder, ZlibEncoder},
+};
+use serde::{de::DeserializeOwned, Serialize};
 
 use minedmap_types::*;
 
+use crate::data;
+pub use crate::error::{ChunkError, RegionError};
+
+/// Result type of region operations
+pub type Result<T, E = RegionError> = std::result::Result<T, E>;
+
 /// Data block size of region data files
 ///
 /// After one header block, the region file consists of one or more consecutive blocks
@@ -19,7 +29,7 @@ use minedmap_types::*;
 const BLOCKSIZE: usize = 4096;
 
 /// Chunk descriptor extracted from region file header
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 struct ChunkDesc {
 	/// Offset of data block where the chunk starts
 	offset: u32,
@@ -29,134 +39,1187 @@ struct ChunkDesc {
 	coords: ChunkCoords,
 }
 
+impl ChunkDesc {
+	/// Creates a [ChunkDesc] from a big-endian header entry
+	///
+	/// Returns [None] for entries of chunks that don't exist.
+	fn new(coords: ChunkCoords, entry: u32) -> Option<Self> {
+		let offset_len = u32::from_be(entry);
+
+		let offset = offset_len >> 8;
+		let len = offset_len as u8;
+
+		if offset == 0 || len == 0 {
+			return None;
+		}
+
+		Some(ChunkDesc {
+			offset,
+			len,
+			coords,
+		})
+	}
+
+	/// Checks that the chunk data is located after the header and starts
+	/// inside of a file of *file_len* bytes
+	///
+	/// The last data block of a file may be incomplete.
+	fn in_bounds(&self, file_len: u64) -> bool {
+		let end = u64::from(self.offset) + u64::from(self.len);
+		self.offset >= 2 && (end - 1) * (BLOCKSIZE as u64) < file_len
+	}
+}
+
+/// Strategy for resolving header entries of chunks with overlapping data
+///
+/// In corrupt region files, the data of multiple chunks may be located in the
+/// same data blocks, e.g. when a stale header entry points to blocks that
+/// were reused for a different chunk. Only one of these chunks is kept; the
+/// others are reported by [Region::invalid_chunks].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverlapResolution {
+	/// Keep the chunk stored at the lowest offset
+	#[default]
+	LowestOffset,
+	/// Keep the chunk with the newest timestamp
+	///
+	/// Chunks with equal timestamps are resolved by keeping the chunk stored
+	/// at the highest offset, which was usually written last.
+	NewestTimestamp,
+}
+
 /// Parses the header of a region data file
-fn parse_header(header: &ChunkArray<u32>) -> Vec<ChunkDesc> {
+///
+/// Returns the descriptors of all valid chunks, sorted by offset, as well as
+/// errors for the entries pointing into the header or beyond the end of the
+/// file or overlapping with a chunk that is kept according to the given
+/// [OverlapResolution].
+fn parse_header(
+	header: &Header,
+	resolution: OverlapResolution,
+) -> (Vec<ChunkDesc>, Vec<RegionError>) {
+	let mut invalid = Vec::new();
+
 	let mut chunks: Vec<_> = header
+		.chunks
 		.iter()
-		.filter_map(|(coords, &chunk)| {
-			let offset_len = u32::from_be(chunk);
-
-			let offset = offset_len >> 8;
-			let len = offset_len as u8;
-
-			if offset == 0 || len == 0 {
-				return None;
+		.filter_map(|(coords, &entry)| ChunkDesc::new(coords, entry))
+		.filter(|chunk| {
+			let in_bounds = chunk.in_bounds(header.file_len);
+			if !in_bounds {
+				invalid.push(RegionError::InvalidOffset(chunk.coords));
 			}
-
-			Some(ChunkDesc {
-				offset,
-				len,
-				coords,
-			})
+			in_bounds
 		})
 		.collect();
 
+	// Sort by priority, so overlapping chunks lose against the chunk that
+	// is checked first
+	match resolution {
+		OverlapResolution::LowestOffset => chunks.sort_by_key(|chunk| chunk.offset),
+		OverlapResolution::NewestTimestamp => chunks.sort_by_key(|chunk| {
+			let timestamp = u32::from_be(header.timestamps[chunk.coords]);
+			std::cmp::Reverse((timestamp, chunk.offset))
+		}),
+	}
+
+	let mut used = Vec::new();
+	chunks.retain(|chunk| {
+		let start = chunk.offset as usize;
+		let end = start + chunk.len as usize;
+		if used.len() < end {
+			used.resize(end, false);
+		}
+		if used[start..end].iter().any(|&used| used) {
+			invalid.push(RegionError::OverlappingChunk(chunk.coords));
+			return false;
+		}
+		used[start..end].fill(true);
+		true
+	});
+
 	chunks.sort_by_key(|chunk| chunk.offset);
 
-	chunks
+	(chunks, invalid)
 }
 
-/// Decompresses chunk data and deserializes to a given data structure
-fn decode_chunk<T>(buf: &[u8]) -> Result<T>
-where
-	T: DeserializeOwned,
-{
-	let (format, buf) = buf.split_at(1);
-	if format[0] != 2 {
-		bail!("Unknown chunk format");
-	}
+/// Flag in the compression format byte marking chunks stored in an external file
+const EXTERNAL_CHUNK: u8 = 0x80;
+
+/// Function reading the data of an oversized chunk from its external file
+///
+/// Chunks that don't fit into 255 data blocks are stored in a separate
+/// `c.<x>.<z>.mcc` file next to the region file, using absolute chunk
+/// coordinates. The function is passed the coordinates of the chunk inside the
+/// region and returns the (still compressed) contents of the external file.
+pub type ExternalChunkReader<'a> = Box<dyn FnMut(ChunkCoords) -> io::Result<Vec<u8>> + 'a>;
+
+/// Lower bound of the size limit for decompressed chunk data
+const MIN_DECOMPRESSED_LIMIT: usize = 16 * 1024 * 1024;
+
+/// Maximum ratio of the decompressed size to the size of the data blocks of a chunk
+const MAX_COMPRESSION_RATIO: usize = 64;
 
-	let mut decoder = ZlibDecoder::new(buf);
+/// Returns the size limit for the decompressed data of a chunk
+///
+/// The limit is derived from the number of data blocks occupied by the
+/// compressed data, so a small chunk in a corrupt or malicious region file
+/// can't make us allocate gigabytes of memory. It is also used for other
+/// compressed data of a world, like linear region files and NBT files.
+pub fn decompressed_limit(compressed_len: usize) -> usize {
+	compressed_len
+		.div_ceil(BLOCKSIZE)
+		.saturating_mul(BLOCKSIZE * MAX_COMPRESSION_RATIO)
+		.max(MIN_DECOMPRESSED_LIMIT)
+}
+
+/// Reads all data from a decoder, stopping after *limit* bytes
+///
+/// Returns [None] if the decoder would yield more than *limit* bytes.
+pub fn read_to_limit<R: Read>(decoder: R, limit: usize) -> io::Result<Option<Vec<u8>>> {
 	let mut decode_buffer = vec![];
 	decoder
-		.read_to_end(&mut decode_buffer)
-		.context("Failed to decompress chunk data")?;
+		.take(limit as u64 + 1)
+		.read_to_end(&mut decode_buffer)?;
+	if decode_buffer.len() > limit {
+		return Ok(None);
+	}
+	Ok(Some(decode_buffer))
+}
 
-	fastnbt::from_bytes(&decode_buffer).context("Failed to decode NBT data")
+/// Reads all data from a decoder, failing if it exceeds *limit* bytes
+fn read_limited<R: Read>(decoder: R, limit: usize) -> Result<Vec<u8>, ChunkError> {
+	read_to_limit(decoder, limit)
+		.map_err(ChunkError::Decompress)?
+		.ok_or(ChunkError::DecompressedTooLarge(limit))
+}
+
+/// Magic number at the start of each block of LZ4-compressed chunk data
+const LZ4_MAGIC: &[u8] = b"LZ4Block";
+
+/// Decompresses LZ4-compressed chunk data
+///
+/// Minecraft uses the block stream format of the lz4-java library, which
+/// splits the data into blocks with individual headers. Checksums are not
+/// verified.
+fn decompress_lz4(mut buf: &[u8], limit: usize) -> Result<Vec<u8>, ChunkError> {
+	/// Size of the header of each block
+	const HEADER_SIZE: usize = LZ4_MAGIC.len() + 13;
+	/// Compression method of uncompressed blocks
+	const METHOD_RAW: u8 = 0x10;
+	/// Compression method of LZ4-compressed blocks
+	const METHOD_LZ4: u8 = 0x20;
+
+	/// Returns an error for invalid LZ4 data
+	fn invalid(msg: &str) -> ChunkError {
+		ChunkError::Decompress(io::Error::new(io::ErrorKind::InvalidData, msg))
+	}
+
+	let mut output = Vec::new();
+
+	loop {
+		if buf.len() < HEADER_SIZE || !buf.starts_with(LZ4_MAGIC) {
+			return Err(invalid("Invalid LZ4 block header"));
+		}
+		let header = &buf[LZ4_MAGIC.len()..HEADER_SIZE];
+		let method = header[0] & 0xf0;
+		let compressed_len = u32::from_le_bytes(header[1..5].try_into().unwrap()) as usize;
+		let decompressed_len = u32::from_le_bytes(header[5..9].try_into().unwrap()) as usize;
+		buf = &buf[HEADER_SIZE..];
+
+		if decompressed_len == 0 {
+			// End of stream
+			return Ok(output);
+		}
+
+		let data = buf
+			.get(..compressed_len)
+			.ok_or_else(|| invalid("Truncated LZ4 block"))?;
+		buf = &buf[compressed_len..];
+
+		if output.len().saturating_add(decompressed_len) > limit {
+			return Err(ChunkError::DecompressedTooLarge(limit));
+		}
+
+		match method {
+			METHOD_RAW => output.extend_from_slice(data),
+			METHOD_LZ4 => {
+				let start = output.len();
+				output.resize(start + decompressed_len, 0);
+				let len = lz4_flex::block::decompress_into(data, &mut output[start..]).map_err(
+					|err| ChunkError::Decompress(io::Error::new(io::ErrorKind::InvalidData, err)),
+				)?;
+				if len != decompressed_len {
+					return Err(invalid("Invalid LZ4 block length"));
+				}
+			}
+			_ => return Err(invalid("Unknown LZ4 block compression method")),
+		}
+	}
+}
+
+/// Magic number at the start of a zstd frame
+#[cfg(feature = "zstd")]
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Decompresses chunk data
+///
+/// Fails with [ChunkError::DecompressedTooLarge] if the decompressed size
+/// exceeds the limit returned by [decompressed_limit].
+fn decompress_chunk(format: u8, buf: &[u8]) -> Result<Vec<u8>, ChunkError> {
+	let limit = decompressed_limit(buf.len());
+
+	Ok(match format {
+		// GZip
+		1 => read_limited(GzDecoder::new(buf), limit)?,
+		// Zlib
+		2 => read_limited(ZlibDecoder::new(buf), limit)?,
+		// Uncompressed
+		3 => buf.to_vec(),
+		// LZ4
+		4 => decompress_lz4(buf, limit)?,
+		// There is no agreed-upon format ID for zstd-compressed chunks written
+		// by modified servers, so the data is recognized by its frame header
+		#[cfg(feature = "zstd")]
+		_ if buf.starts_with(ZSTD_MAGIC) => read_limited(
+			zstd::stream::read::Decoder::new(buf).map_err(ChunkError::Decompress)?,
+			limit,
+		)?,
+		_ => return Err(ChunkError::UnknownFormat(format)),
+	})
+}
+
+/// Compressed chunk data as stored in a region file
+///
+/// The data of oversized chunks is read from their external files, so
+/// [RawChunk::data] always contains the full payload of the chunk.
+#[derive(Debug, Clone)]
+pub struct RawChunk {
+	/// Compression format byte, without the external chunk flag
+	pub format: u8,
+	/// Compressed chunk data
+	pub data: Vec<u8>,
+}
+
+impl RawChunk {
+	/// Decompresses the chunk data, returning its binary NBT data
+	pub fn decompress(&self) -> Result<Vec<u8>, ChunkError> {
+		decompress_chunk(self.format, &self.data)
+	}
+
+	/// Decompresses the chunk data and deserializes it to a given data structure
+	pub fn decode<T>(&self) -> Result<T, ChunkError>
+	where
+		T: DeserializeOwned,
+	{
+		let decode_buffer = self.decompress()?;
+		Ok(data::from_bytes(&decode_buffer)?)
+	}
+}
+
+/// Information about the storage of a chunk in a region file
+///
+/// Returned by [Region::chunk_info].
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkInfo {
+	/// Index of the first data block of the chunk
+	pub offset: u32,
+	/// Number of data blocks allocated for the chunk
+	pub len: u8,
+	/// Time of the last modification in seconds since the Unix epoch, or 0
+	/// if no timestamp was recorded
+	pub timestamp: u32,
+	/// Length of the stored chunk data in bytes, including the format byte
+	///
+	/// [None] if the data is located outside of the file or could not be read.
+	pub byte_len: Option<u32>,
+	/// Compression format byte, including the external chunk flag
+	///
+	/// [None] if the data is located outside of the file or could not be read.
+	pub format: Option<u8>,
+}
+
+impl ChunkInfo {
+	/// Returns true if the chunk data is stored in an external file
+	pub fn is_external(&self) -> bool {
+		self.format
+			.is_some_and(|format| format & EXTERNAL_CHUNK != 0)
+	}
+}
+
+/// Header of a region data file
+///
+/// All values are stored in big-endian byte order, as found in the file.
+#[derive(Debug, Default)]
+struct Header {
+	/// Offset and length of each chunk
+	chunks: ChunkArray<u32>,
+	/// Time of the last modification of each chunk, in seconds since the
+	/// Unix epoch
+	timestamps: ChunkArray<u32>,
+	/// Size of the region file in bytes
+	file_len: u64,
+}
+
+/// Reads the header of a region data file
+fn read_header<R: Read + Seek>(reader: &mut R) -> Result<Header> {
+	let mut header = Header {
+		file_len: reader
+			.seek(SeekFrom::End(0))
+			.map_err(RegionError::CorruptHeader)?,
+		..Default::default()
+	};
+	reader
+		.seek(SeekFrom::Start(0))
+		.map_err(RegionError::CorruptHeader)?;
+	for table in [&mut header.chunks, &mut header.timestamps] {
+		reader
+			.read_exact(bytemuck::cast_mut::<_, [u8; BLOCKSIZE]>(&mut table.0))
+			.map_err(RegionError::CorruptHeader)?;
+	}
+	Ok(header)
 }
 
 /// Wraps a reader used to read a region data file
-#[derive(Debug)]
-pub struct Region<R: Read + Seek> {
+pub struct Region<'a, R: Read + Seek> {
 	/// The wrapper reader
 	reader: R,
+	/// Reader for oversized chunks stored in external files
+	external: Option<ExternalChunkReader<'a>>,
+	/// Header of the region, read on first random access
+	header: Option<Header>,
+	/// Strategy for resolving chunks with overlapping data
+	overlap_resolution: OverlapResolution,
 }
 
-impl<R: Read + Seek> Region<R> {
+impl<'a, R: Read + Seek> Region<'a, R> {
+	/// Sets the function used to read oversized chunks from external files
+	///
+	/// Without an [ExternalChunkReader], reading a region containing oversized
+	/// chunks fails.
+	pub fn with_external_chunks<E>(mut self, external: E) -> Self
+	where
+		E: FnMut(ChunkCoords) -> io::Result<Vec<u8>> + 'a,
+	{
+		self.external = Some(Box::new(external));
+		self
+	}
+
+	/// Sets the strategy for resolving chunks with overlapping data
+	///
+	/// Defaults to [OverlapResolution::LowestOffset].
+	pub fn with_overlap_resolution(mut self, resolution: OverlapResolution) -> Self {
+		self.overlap_resolution = resolution;
+		self
+	}
+
+	/// Reads the data of a chunk described by a [ChunkDesc]
+	fn read_chunk(&mut self, desc: &ChunkDesc) -> Result<RawChunk, ChunkError> {
+		let ChunkDesc {
+			offset,
+			len,
+			coords,
+		} = *desc;
+
+		self.reader
+			.seek(SeekFrom::Start(offset as u64 * BLOCKSIZE as u64))
+			.map_err(ChunkError::Read)?;
+
+		let mut len_buf = [0u8; 4];
+		self.reader
+			.read_exact(&mut len_buf)
+			.map_err(ChunkError::Read)?;
+		let byte_len = u32::from_be_bytes(len_buf) as usize;
+		if byte_len < 1 || byte_len > (len as usize) * BLOCKSIZE - 4 {
+			return Err(ChunkError::InvalidLength);
+		}
+
+		let mut buffer = vec![0; byte_len];
+		self.reader
+			.read_exact(&mut buffer)
+			.map_err(ChunkError::Read)?;
+
+		let format = buffer[0];
+		if format & EXTERNAL_CHUNK != 0 {
+			let external = self
+				.external
+				.as_mut()
+				.ok_or(ChunkError::UnsupportedExternal)?;
+			let data = external(coords).map_err(ChunkError::External)?;
+			Ok(RawChunk {
+				format: format & !EXTERNAL_CHUNK,
+				data,
+			})
+		} else {
+			buffer.remove(0);
+			Ok(RawChunk {
+				format,
+				data: buffer,
+			})
+		}
+	}
+
+	/// Returns the header of the region, reading it if necessary
+	fn header(&mut self) -> Result<&Header> {
+		Ok(match &mut self.header {
+			Some(header) => header,
+			header @ None => header.insert(read_header(&mut self.reader)?),
+		})
+	}
+
+	/// Returns the header entry of a chunk, reading the header if necessary
+	///
+	/// Fails with [RegionError::InvalidOffset] if the entry points into the
+	/// header or beyond the end of the file.
+	fn chunk_desc(&mut self, coords: ChunkCoords) -> Result<Option<ChunkDesc>> {
+		let header = self.header()?;
+		let Some(desc) = ChunkDesc::new(coords, header.chunks[coords]) else {
+			return Ok(None);
+		};
+		if !desc.in_bounds(header.file_len) {
+			return Err(RegionError::InvalidOffset(coords));
+		}
+		Ok(Some(desc))
+	}
+
+	/// Returns information about the storage of a chunk
+	///
+	/// Only the region header and the first bytes of the chunk data are read.
+	/// Unlike other functions, this does not fail for invalid header entries.
+	/// Returns [None] if the region does not contain the chunk.
+	pub fn chunk_info(&mut self, coords: ChunkCoords) -> Result<Option<ChunkInfo>> {
+		let header = self.header()?;
+		let Some(desc) = ChunkDesc::new(coords, header.chunks[coords]) else {
+			return Ok(None);
+		};
+		let timestamp = u32::from_be(header.timestamps[coords]);
+		let in_bounds = desc.in_bounds(header.file_len);
+
+		let mut prefix = [0u8; 5];
+		let prefix = (in_bounds
+			&& self
+				.reader
+				.seek(SeekFrom::Start(desc.offset as u64 * BLOCKSIZE as u64))
+				.and_then(|_| self.reader.read_exact(&mut prefix))
+				.is_ok())
+		.then_some(prefix);
+
+		Ok(Some(ChunkInfo {
+			offset: desc.offset,
+			len: desc.len,
+			timestamp,
+			byte_len: prefix.map(|prefix| u32::from_be_bytes(prefix[..4].try_into().unwrap())),
+			format: prefix.map(|prefix| prefix[4]),
+		}))
+	}
+
+	/// Returns errors for all invalid entries of the region header
+	///
+	/// Entries pointing into the header or beyond the end of the file, and
+	/// entries whose data overlaps with another chunk that is kept according
+	/// to the [OverlapResolution] are skipped when iterating over the chunks
+	/// of the region. This function allows to
+	/// find out about these chunks, e.g. to print a warning.
+	pub fn invalid_chunks(&mut self) -> Result<Vec<RegionError>> {
+		let overlap_resolution = self.overlap_resolution;
+		Ok(parse_header(self.header()?, overlap_resolution).1)
+	}
+
+	/// Checks whether the region contains data for a chunk
+	///
+	/// Only the region header is read.
+	pub fn contains_chunk(&mut self, coords: ChunkCoords) -> Result<bool> {
+		Ok(self.chunk_desc(coords)?.is_some())
+	}
+
+	/// Returns the modification times of all chunks from the region header
+	///
+	/// The times are given in seconds since the Unix epoch, with 0 for chunks
+	/// that don't exist or were never written with a timestamp.
+	pub fn timestamps(&mut self) -> Result<ChunkArray<u32>> {
+		let mut timestamps = self.header()?.timestamps;
+		for timestamp in timestamps.0.iter_mut().flatten() {
+			*timestamp = u32::from_be(*timestamp);
+		}
+		Ok(timestamps)
+	}
+
+	/// Returns the modification time of a chunk from the region header
+	///
+	/// Returns [None] if the region does not contain the chunk or no timestamp
+	/// was recorded for it.
+	pub fn chunk_modified(&mut self, coords: ChunkCoords) -> Result<Option<SystemTime>> {
+		if !self.contains_chunk(coords)? {
+			return Ok(None);
+		}
+		let timestamp = u32::from_be(self.header()?.timestamps[coords]);
+		if timestamp == 0 {
+			return Ok(None);
+		}
+		Ok(Some(UNIX_EPOCH + Duration::from_secs(timestamp.into())))
+	}
+
+	/// Reads and decodes a single chunk
+	///
+	/// The chunk data is located using the region header, without reading any
+	/// other chunks. Returns [None] if the region does not contain the chunk.
+	pub fn get_chunk<T>(&mut self, coords: ChunkCoords) -> Result<Option<T>>
+	where
+		T: DeserializeOwned,
+	{
+		let Some(raw) = self.get_chunk_raw(coords)? else {
+			return Ok(None);
+		};
+		raw.decode()
+			.map_err(|source| RegionError::Chunk { coords, source })
+			.map(Some)
+	}
+
+	/// Reads the compressed data of a single chunk
+	///
+	/// Like [Region::get_chunk], but without decompressing and decoding the data.
+	pub fn get_chunk_raw(&mut self, coords: ChunkCoords) -> Result<Option<RawChunk>> {
+		let Some(desc) = self.chunk_desc(coords)? else {
+			return Ok(None);
+		};
+		self.read_chunk(&desc)
+			.map_err(|source| RegionError::Chunk { coords, source })
+			.map(Some)
+	}
+
 	/// Iterates over the chunks of the region data
 	///
 	/// The order of iteration is based on the order the chunks appear in the
-	/// data file.
-	pub fn foreach_chunk<T, F>(self, mut f: F) -> Result<()>
+	/// data file. Chunks with invalid header entries are skipped (see
+	/// [Region::invalid_chunks]). Iteration stops at the first chunk that fails
+	/// to be read or decoded; use [Region::foreach_chunk_result] to continue
+	/// after errors.
+	///
+	/// Errors returned by *f* end the iteration and are passed through.
+	pub fn foreach_chunk<T, E, F>(self, mut f: F) -> Result<(), E>
 	where
 		R: Read + Seek,
 		T: DeserializeOwned,
-		F: FnMut(ChunkCoords, T) -> Result<()>,
+		E: From<RegionError>,
+		F: FnMut(ChunkCoords, T) -> Result<(), E>,
 	{
-		let Region { mut reader } = self;
-
-		let chunks = {
-			let mut header = ChunkArray::<u32>::default();
-			reader
-				.read_exact(bytemuck::cast_mut::<_, [u8; BLOCKSIZE]>(&mut header.0))
-				.context("Failed to read region header")?;
-
-			parse_header(&header)
-		};
+		self.foreach_chunk_result(|coords, chunk| {
+			let chunk = chunk.map_err(|source| RegionError::Chunk { coords, source })?;
+			f(coords, chunk)
+		})
+	}
 
-		let mut seen = ChunkArray::<bool>::default();
+	/// Iterates over the chunks of the region data, passing per-chunk errors
+	/// to the callback
+	///
+	/// Errors reading or decoding a single chunk don't end the iteration;
+	/// instead, the error is passed to *f*, which can decide whether to skip
+	/// the chunk (by returning `Ok`) or to abort (by returning an error).
+	/// Only errors reading the region header end the iteration immediately.
+	pub fn foreach_chunk_result<T, E, F>(self, mut f: F) -> Result<(), E>
+	where
+		R: Read + Seek,
+		T: DeserializeOwned,
+		E: From<RegionError>,
+		F: FnMut(ChunkCoords, Result<T, ChunkError>) -> Result<(), E>,
+	{
+		self.foreach_chunk_raw_result(|coords, raw| f(coords, raw.and_then(|raw| raw.decode())))
+	}
 
-		for ChunkDesc {
-			offset,
-			len,
-			coords,
-		} in chunks
-		{
-			if seen[coords] {
-				bail!("Duplicate chunk {:?}", coords);
-			}
-			seen[coords] = true;
-
-			reader
-				.seek(SeekFrom::Start(offset as u64 * BLOCKSIZE as u64))
-				.context("Failed to seek chunk data")?;
-
-			let mut len_buf = [0u8; 4];
-			reader
-				.read_exact(&mut len_buf)
-				.with_context(|| format!("Failed to read length for chunk {:?}", coords))?;
-			let byte_len = u32::from_be_bytes(len_buf) as usize;
-			if byte_len < 1 || byte_len > (len as usize) * BLOCKSIZE - 4 {
-				bail!("Invalid length for chunk {:?}", coords);
-			}
+	/// Iterates over the compressed data of the chunks of the region
+	///
+	/// Like [Region::foreach_chunk], but without decompressing and decoding
+	/// the data.
+	pub fn foreach_chunk_raw<E, F>(self, mut f: F) -> Result<(), E>
+	where
+		R: Read + Seek,
+		E: From<RegionError>,
+		F: FnMut(ChunkCoords, RawChunk) -> Result<(), E>,
+	{
+		self.foreach_chunk_raw_result(|coords, raw| {
+			let raw = raw.map_err(|source| RegionError::Chunk { coords, source })?;
+			f(coords, raw)
+		})
+	}
 
-			let mut buffer = vec![0; byte_len];
-			reader
-				.read_exact(&mut buffer)
-				.with_context(|| format!("Failed to read data for chunk {:?}", coords))?;
-			let chunk = decode_chunk(&buffer)
-				.with_context(|| format!("Failed to decode data for chunk {:?}", coords))?;
+	/// Iterates over the uncompressed NBT data of the chunks of the region
+	///
+	/// Like [Region::foreach_chunk], but without decoding the data. This
+	/// allows to deserialize data structures borrowing from the data using
+	/// [data::from_bytes](crate::data::from_bytes).
+	pub fn foreach_chunk_data<E, F>(self, mut f: F) -> Result<(), E>
+	where
+		R: Read + Seek,
+		E: From<RegionError>,
+		F: FnMut(ChunkCoords, &[u8]) -> Result<(), E>,
+	{
+		self.foreach_chunk_raw(|coords, raw| {
+			let data = raw
+				.decompress()
+				.map_err(|source| RegionError::Chunk { coords, source })?;
+			f(coords, &data)
+		})
+	}
 
+	/// Iterates over the compressed data of the chunks of the region, passing
+	/// per-chunk errors to the callback
+	///
+	/// Like [Region::foreach_chunk_result], but without decompressing and
+	/// decoding the data.
+	pub fn foreach_chunk_raw_result<E, F>(self, mut f: F) -> Result<(), E>
+	where
+		R: Read + Seek,
+		E: From<RegionError>,
+		F: FnMut(ChunkCoords, Result<RawChunk, ChunkError>) -> Result<(), E>,
+	{
+		for (coords, chunk) in self.chunks()? {
 			f(coords, chunk)?;
 		}
 
 		Ok(())
 	}
+
+	/// Returns an iterator over the compressed data of the chunks of the region
+	///
+	/// The region header is read right away; errors reading single chunks are
+	/// returned as items of the iterator. The order of iteration is based on
+	/// the order the chunks appear in the data file. Chunks with invalid header
+	/// entries are skipped (see [Region::invalid_chunks]).
+	pub fn chunks(mut self) -> Result<Chunks<'a, R>> {
+		let header = match self.header.take() {
+			Some(header) => header,
+			None => read_header(&mut self.reader)?,
+		};
+		let (chunks, _) = parse_header(&header, self.overlap_resolution);
+		let chunks = chunks.into_iter();
+
+		Ok(Chunks {
+			region: self,
+			chunks,
+		})
+	}
+}
+
+/// Iterator over the chunks of a [Region]
+///
+/// Returned by [Region::chunks].
+pub struct Chunks<'a, R: Read + Seek> {
+	/// The region the chunks are read from
+	region: Region<'a, R>,
+	/// Remaining chunks
+	chunks: std::vec::IntoIter<ChunkDesc>,
+}
+
+impl<R: Read + Seek> Iterator for Chunks<'_, R> {
+	type Item = (ChunkCoords, Result<RawChunk, ChunkError>);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let desc = self.chunks.next()?;
+		Some((desc.coords, self.region.read_chunk(&desc)))
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.chunks.size_hint()
+	}
 }
 
+impl<R: Read + Seek> ExactSizeIterator for Chunks<'_, R> {}
+
 /// Creates a new [Region] from a reader
-pub fn from_reader<R>(reader: R) -> Region<R>
+pub fn from_reader<'a, R>(reader: R) -> Region<'a, R>
 where
 	R: Read + Seek,
 {
-	Region { reader }
+	Region {
+		reader,
+		external: None,
+		header: None,
+		overlap_resolution: OverlapResolution::default(),
+	}
 }
 
 /// Creates a new [Region] for a file
-pub fn from_file<P>(path: P) -> Result<Region<File>>
+///
+/// When the file name has the usual `r.<x>.<z>.mca` format, oversized chunks
+/// are read from the external files in the same directory.
+pub fn from_file<'a, P>(path: P) -> Result<Region<'a, File>>
+where
+	P: AsRef<Path>,
+{
+	let path = path.as_ref();
+	let file = File::open(path).map_err(RegionError::Open)?;
+	let region = from_reader(file);
+
+	let region_coords = (|| -> Option<(i32, i32)> {
+		let name = path.file_name()?.to_str()?;
+		let mut parts = name.strip_prefix("r.")?.split('.');
+		let x = parts.next()?.parse().ok()?;
+		let z = parts.next()?.parse().ok()?;
+		Some((x, z))
+	})();
+	let Some((x, z)) = region_coords else {
+		return Ok(region);
+	};
+
+	let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+	Ok(region.with_external_chunks(move |coords| {
+		std::fs::read(dir.join(external_chunk_filename(x, z, coords)))
+	}))
+}
+
+/// Returns the file name of the external file of an oversized chunk
+///
+/// *x* and *z* are the coordinates of the region.
+pub fn external_chunk_filename(x: i32, z: i32, coords: ChunkCoords) -> String {
+	format!(
+		"c.{}.{}.mcc",
+		x * CHUNKS_PER_REGION as i32 + i32::from(coords.x.0),
+		z * CHUNKS_PER_REGION as i32 + i32::from(coords.z.0),
+	)
+}
+
+/// Compression format used for chunks written by a [Writer]
+///
+/// Also used for standalone NBT data written by [to_writer](crate::data::to_writer).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+	/// GZip compression
+	Gzip,
+	/// Zlib compression (used by Minecraft)
+	#[default]
+	Zlib,
+	/// No compression
+	Uncompressed,
+}
+
+impl Compression {
+	/// Returns the format byte stored in front of the chunk data
+	pub fn format(self) -> u8 {
+		match self {
+			Compression::Gzip => 1,
+			Compression::Zlib => 2,
+			Compression::Uncompressed => 3,
+		}
+	}
+
+	/// Compresses NBT data
+	pub(crate) fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+		Ok(match self {
+			Compression::Gzip => {
+				let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+				encoder.write_all(data)?;
+				encoder.finish()?
+			}
+			Compression::Zlib => {
+				let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+				encoder.write_all(data)?;
+				encoder.finish()?
+			}
+			Compression::Uncompressed => data.to_vec(),
+		})
+	}
+}
+
+/// Returns the offset of the header entry of a chunk
+fn header_offset(coords: ChunkCoords) -> u64 {
+	4 * (usize::from(coords.z.0) * CHUNKS_PER_REGION + usize::from(coords.x.0)) as u64
+}
+
+/// Creates or updates a region data file
+///
+/// Chunks are written to the first free range of data blocks large enough to
+/// hold them, and the file header is updated right away, so the file remains
+/// valid after each operation.
+pub struct Writer<F: Read + Write + Seek> {
+	/// The wrapped file
+	file: F,
+	/// Offset and length of each chunk, as stored in the header
+	chunks: ChunkArray<u32>,
+	/// Allocation state of the data blocks of the file
+	used: Vec<bool>,
+	/// Compression format for written chunks
+	compression: Compression,
+}
+
+impl<F: Read + Write + Seek> Writer<F> {
+	/// Creates an empty region in a file
+	///
+	/// Existing data in the file is overwritten, but the file is not truncated.
+	pub fn create(mut file: F) -> Result<Self> {
+		file.seek(SeekFrom::Start(0))
+			.and_then(|_| file.write_all(&[0; 2 * BLOCKSIZE]))
+			.map_err(RegionError::Write)?;
+
+		Ok(Writer {
+			file,
+			chunks: ChunkArray::default(),
+			used: vec![true; 2],
+			compression: Compression::default(),
+		})
+	}
+
+	/// Opens an existing region file for modification
+	///
+	/// Fails if the header contains invalid entries, as chunks could not be
+	/// written without risking to overwrite the data of other chunks.
+	pub fn open(mut file: F) -> Result<Self> {
+		let header = read_header(&mut file)?;
+		let (descs, invalid) = parse_header(&header, OverlapResolution::default());
+		if let Some(err) = invalid.into_iter().next() {
+			return Err(err);
+		}
+
+		let mut used = vec![true; 2];
+		for chunk in descs {
+			let start = chunk.offset as usize;
+			let end = start + chunk.len as usize;
+			if used.len() < end {
+				used.resize(end, false);
+			}
+			used[start..end].fill(true);
+		}
+
+		let Header { mut chunks, .. } = header;
+		for chunk in chunks.0.iter_mut().flatten() {
+			*chunk = u32::from_be(*chunk);
+		}
+
+		Ok(Writer {
+			file,
+			chunks,
+			used,
+			compression: Compression::default(),
+		})
+	}
+
+	/// Sets the compression format used for chunks written from now on
+	pub fn with_compression(mut self, compression: Compression) -> Self {
+		self.compression = compression;
+		self
+	}
+
+	/// Serializes and writes a chunk, replacing any previous data of the chunk
+	pub fn write_chunk<T>(&mut self, coords: ChunkCoords, value: &T) -> Result<()>
+	where
+		T: Serialize,
+	{
+		let data = crate::data::to_bytes(value).map_err(|err| RegionError::Chunk {
+			coords,
+			source: err.into(),
+		})?;
+		self.write_chunk_data(coords, &data)
+	}
+
+	/// Writes the uncompressed NBT data of a chunk, replacing any previous data
+	/// of the chunk
+	pub fn write_chunk_data(&mut self, coords: ChunkCoords, data: &[u8]) -> Result<()> {
+		let data = self
+			.compression
+			.compress(data)
+			.map_err(|err| RegionError::Chunk {
+				coords,
+				source: ChunkError::Compress(err),
+			})?;
+
+		self.write_chunk_raw(
+			coords,
+			&RawChunk {
+				format: self.compression.format(),
+				data,
+			},
+		)
+	}
+
+	/// Writes compressed chunk data, replacing any previous data of the chunk
+	///
+	/// Allows to copy chunks between regions without decompressing them.
+	pub fn write_chunk_raw(&mut self, coords: ChunkCoords, chunk: &RawChunk) -> Result<()> {
+		let byte_len = chunk.data.len() + 1;
+		let len = (byte_len + 4).div_ceil(BLOCKSIZE);
+		if len > u8::MAX as usize {
+			return Err(RegionError::Chunk {
+				coords,
+				source: ChunkError::TooLarge,
+			});
+		}
+
+		self.free(coords);
+		let offset = self.allocate(len);
+
+		let mut buffer = Vec::with_capacity(len * BLOCKSIZE);
+		buffer.extend_from_slice(&(byte_len as u32).to_be_bytes());
+		buffer.push(chunk.format);
+		buffer.extend_from_slice(&chunk.data);
+		buffer.resize(len * BLOCKSIZE, 0);
+
+		self.file
+			.seek(SeekFrom::Start((offset * BLOCKSIZE) as u64))
+			.and_then(|_| self.file.write_all(&buffer))
+			.map_err(RegionError::Write)?;
+
+		let timestamp = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map_or(0, |time| time.as_secs() as u32);
+		self.write_header(coords, ((offset as u32) << 8) | len as u32, timestamp)
+	}
+
+	/// Sets the modification timestamp of a chunk
+	///
+	/// Writing a chunk sets its timestamp to the current time; this allows
+	/// to preserve the original timestamp when copying chunks.
+	pub fn set_timestamp(&mut self, coords: ChunkCoords, timestamp: u32) -> Result<()> {
+		self.write_header(coords, self.chunks[coords], timestamp)
+	}
+
+	/// Removes a chunk from the region
+	pub fn remove_chunk(&mut self, coords: ChunkCoords) -> Result<()> {
+		self.free(coords);
+		self.write_header(coords, 0, 0)
+	}
+
+	/// Flushes the wrapped file and returns it
+	pub fn finish(mut self) -> Result<F> {
+		self.file.flush().map_err(RegionError::Write)?;
+		Ok(self.file)
+	}
+
+	/// Marks the data blocks of a chunk as unused
+	fn free(&mut self, coords: ChunkCoords) {
+		let offset_len = self.chunks[coords];
+		let start = (offset_len >> 8) as usize;
+		let end = start + (offset_len & 0xff) as usize;
+		if start >= 2 && end > start {
+			self.used[start..end].fill(false);
+		}
+		self.chunks[coords] = 0;
+	}
+
+	/// Finds and marks a range of unused data blocks
+	///
+	/// Returns the offset of the first block.
+	fn allocate(&mut self, len: usize) -> usize {
+		let mut start = 2;
+		let mut free = 0;
+		for (offset, &used) in self.used.iter().enumerate().skip(2) {
+			if used {
+				start = offset + 1;
+				free = 0;
+				continue;
+			}
+			free += 1;
+			if free == len {
+				break;
+			}
+		}
+
+		let end = start + len;
+		if self.used.len() < end {
+			self.used.resize(end, false);
+		}
+		self.used[start..end].fill(true);
+		start
+	}
+
+	/// Updates the header entries of a chunk
+	fn write_header(&mut self, coords: ChunkCoords, offset_len: u32, timestamp: u32) -> Result<()> {
+		self.chunks[coords] = offset_len;
+
+		let offset = header_offset(coords);
+		(|| -> io::Result<()> {
+			self.file.seek(SeekFrom::Start(offset))?;
+			self.file.write_all(&offset_len.to_be_bytes())?;
+			self.file.seek(SeekFrom::Start(BLOCKSIZE as u64 + offset))?;
+			self.file.write_all(&timestamp.to_be_bytes())?;
+			Ok(())
+		})()
+		.map_err(RegionError::Write)
+	}
+}
+
+/// Creates a [Writer] for a region file
+///
+/// A new empty region is created if the file does not exist; otherwise, the
+/// existing region is opened for modification.
+pub fn writer_for_file<P>(path: P) -> Result<Writer<File>>
 where
 	P: AsRef<Path>,
 {
-	let file = File::open(path).context("Failed to open file")?;
-	Ok(from_reader(file))
+	let file = OpenOptions::new()
+		.read(true)
+		.write(true)
+		.create(true)
+		.truncate(false)
+		.open(path)
+		.map_err(RegionError::Open)?;
+	if file.metadata().map_err(RegionError::Open)?.len() == 0 {
+		Writer::create(file)
+	} else {
+		Writer::open(file)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::{collections::HashMap, io::Cursor};
+
+	use super::*;
+
+	/// Returns the coordinates of a chunk inside a region
+	fn coords(x: u8, z: u8) -> ChunkCoords {
+		ChunkCoords {
+			x: ChunkX::new(x),
+			z: ChunkZ::new(z),
+		}
+	}
+
+	/// Returns a chunk with a single value of the given size
+	fn chunk(value: i32, size: usize) -> HashMap<String, fastnbt::Value> {
+		HashMap::from([
+			("Value".to_string(), fastnbt::Value::Int(value)),
+			(
+				"Data".to_string(),
+				fastnbt::Value::ByteArray(fastnbt::ByteArray::new(vec![value as i8; size])),
+			),
+		])
+	}
+
+	/// Reads all chunks of a region
+	fn read_chunks(data: &[u8]) -> Vec<(ChunkCoords, HashMap<String, fastnbt::Value>)> {
+		let mut chunks = Vec::new();
+		from_reader(Cursor::new(data))
+			.foreach_chunk(|coords, chunk| {
+				chunks.push((coords, chunk));
+				Ok::<_, RegionError>(())
+			})
+			.unwrap();
+		chunks.sort_by_key(|&(coords, _)| (coords.z.0, coords.x.0));
+		chunks
+	}
+
+	#[test]
+	fn test_writer_round_trip() {
+		for compression in [
+			Compression::Gzip,
+			Compression::Zlib,
+			Compression::Uncompressed,
+		] {
+			let mut writer = Writer::create(Cursor::new(Vec::new()))
+				.unwrap()
+				.with_compression(compression);
+			writer.write_chunk(coords(0, 0), &chunk(1, 10)).unwrap();
+			writer.write_chunk(coords(31, 0), &chunk(2, 10000)).unwrap();
+			writer.write_chunk(coords(5, 31), &chunk(3, 10)).unwrap();
+			writer.set_timestamp(coords(5, 31), 1234).unwrap();
+			let data = writer.finish().unwrap().into_inner();
+
+			assert_eq!(
+				read_chunks(&data),
+				[
+					(coords(0, 0), chunk(1, 10)),
+					(coords(31, 0), chunk(2, 10000)),
+					(coords(5, 31), chunk(3, 10)),
+				]
+			);
+
+			let mut region = from_reader(Cursor::new(&data));
+			assert!(region.invalid_chunks().unwrap().is_empty());
+			assert_eq!(region.timestamps().unwrap()[coords(5, 31)], 1234);
+			let info = region.chunk_info(coords(0, 0)).unwrap().unwrap();
+			assert_eq!(info.format, Some(compression.format()));
+		}
+	}
+
+	#[test]
+	fn test_writer_reuses_space() {
+		let mut writer = Writer::create(Cursor::new(Vec::new())).unwrap();
+		writer.write_chunk(coords(0, 0), &chunk(1, 10)).unwrap();
+		writer.write_chunk(coords(1, 0), &chunk(2, 10)).unwrap();
+		writer.remove_chunk(coords(0, 0)).unwrap();
+		// Incompressible data, so the chunk needs more than one block
+		let mut state = 1u32;
+		let large: Vec<_> = (0..20000)
+			.map(|_| {
+				state ^= state << 13;
+				state ^= state >> 17;
+				state ^= state << 5;
+				state as i8
+			})
+			.collect();
+		let large = HashMap::from([(
+			"Data".to_string(),
+			fastnbt::Value::ByteArray(fastnbt::ByteArray::new(large)),
+		)]);
+		writer.write_chunk(coords(2, 0), &large).unwrap();
+		writer.write_chunk(coords(3, 0), &chunk(3, 10)).unwrap();
+		let data = writer.finish().unwrap().into_inner();
+
+		let mut region = from_reader(Cursor::new(&data));
+		// The small chunk fills the block freed by the removed chunk
+		assert_eq!(region.chunk_info(coords(3, 0)).unwrap().unwrap().offset, 2);
+		assert!(region.chunk_info(coords(0, 0)).unwrap().is_none());
+
+		let chunks = read_chunks(&data);
+		assert_eq!(chunks.len(), 3);
+		assert_eq!(chunks[1], (coords(2, 0), large));
+	}
+
+	#[test]
+	fn test_writer_open() {
+		let mut writer = Writer::create(Cursor::new(Vec::new())).unwrap();
+		writer.write_chunk(coords(0, 0), &chunk(1, 10)).unwrap();
+		writer.write_chunk(coords(1, 0), &chunk(2, 10)).unwrap();
+		let file = writer.finish().unwrap();
+
+		let mut writer = Writer::open(file).unwrap();
+		writer.write_chunk(coords(0, 0), &chunk(3, 10000)).unwrap();
+		writer.write_chunk(coords(2, 0), &chunk(4, 10)).unwrap();
+		let data = writer.finish().unwrap().into_inner();
+
+		assert_eq!(
+			read_chunks(&data),
+			[
+				(coords(0, 0), chunk(3, 10000)),
+				(coords(1, 0), chunk(2, 10)),
+				(coords(2, 0), chunk(4, 10)),
+			]
+		);
+	}
+
+	#[test]
+	fn test_overlap_resolution() {
+		let mut writer = Writer::create(Cursor::new(Vec::new())).unwrap();
+		writer.write_chunk(coords(0, 0), &chunk(1, 10)).unwrap();
+		writer.set_timestamp(coords(0, 0), 1000).unwrap();
+		writer.write_chunk(coords(1, 0), &chunk(2, 10)).unwrap();
+		// Point the newer chunk to the data blocks of the older one
+		writer
+			.write_header(coords(1, 0), (2 << 8) | 1, 2000)
+			.unwrap();
+		let data = writer.finish().unwrap().into_inner();
+
+		for (resolution, kept, dropped) in [
+			(OverlapResolution::LowestOffset, coords(0, 0), coords(1, 0)),
+			(
+				OverlapResolution::NewestTimestamp,
+				coords(1, 0),
+				coords(0, 0),
+			),
+		] {
+			let mut region = from_reader(Cursor::new(&data)).with_overlap_resolution(resolution);
+			let invalid = region.invalid_chunks().unwrap();
+			assert!(matches!(
+				invalid.as_slice(),
+				[RegionError::OverlappingChunk(coords)] if *coords == dropped
+			));
+
+			let mut chunks = Vec::new();
+			region
+				.foreach_chunk(|coords, chunk: HashMap<String, fastnbt::Value>| {
+					chunks.push((coords, chunk));
+					Ok::<_, RegionError>(())
+				})
+				.unwrap();
+			assert_eq!(chunks, [(kept, chunk(1, 10))]);
+		}
+	}
 }
@@ -0,0 +1,417 @@
+//! Formatting and parsing of stringified NBT (SNBT)
+//!
+//! SNBT is the text representation of NBT data used by Minecraft commands
+//! like `/data`, e.g. `{Name: "minecraft:stone", Count: 1b}`.
+//!
+//! ```
+//! use minedmap_nbt::snbt;
+//!
+//! let value = snbt::from_str("{Count: 1b, Pos: [I; 1, 2, 3]}")?;
+//! assert_eq!(snbt::to_string(&value), "{Count: 1b, Pos: [I; 1, 2, 3]}");
+//! # Ok::<(), minedmap_nbt::data::NbtError>(())
+//! ```
+
+use std::{collections::HashMap, fmt};
+
+use fastnbt::{ByteArray, IntArray, LongArray, Value};
+
+use crate::data::{NbtError, Result};
+
+/// Maximum nesting depth of compounds and lists
+const MAX_DEPTH: usize = 512;
+
+/// Returns true for characters allowed in unquoted strings
+fn is_unquoted_char(c: char) -> bool {
+	c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+}
+
+/// Writes a quoted and escaped string
+fn write_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+	f.write_str("\"")?;
+	for c in value.chars() {
+		if matches!(c, '"' | '\\') {
+			f.write_str("\\")?;
+		}
+		write!(f, "{}", c)?;
+	}
+	f.write_str("\"")
+}
+
+/// Writes the elements of a typed array
+fn write_array<T: fmt::Display>(
+	f: &mut fmt::Formatter<'_>,
+	prefix: &str,
+	suffix: &str,
+	values: &[T],
+) -> fmt::Result {
+	write!(f, "[{};", prefix)?;
+	for (i, value) in values.iter().enumerate() {
+		let sep = if i == 0 { " " } else { ", " };
+		write!(f, "{}{}{}", sep, value, suffix)?;
+	}
+	f.write_str("]")
+}
+
+/// Wrapper implementing [Display](fmt::Display) for an NBT [Value] using
+/// SNBT syntax
+///
+/// Compound keys are sorted to make the output deterministic.
+#[derive(Debug, Clone, Copy)]
+pub struct Snbt<'a>(pub &'a Value);
+
+impl fmt::Display for Snbt<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.0 {
+			Value::Byte(v) => write!(f, "{}b", v),
+			Value::Short(v) => write!(f, "{}s", v),
+			Value::Int(v) => write!(f, "{}", v),
+			Value::Long(v) => write!(f, "{}L", v),
+			Value::Float(v) => write!(f, "{:?}f", v),
+			Value::Double(v) => write!(f, "{:?}d", v),
+			Value::String(v) => write_string(f, v),
+			Value::ByteArray(v) => write_array(f, "B", "b", v),
+			Value::IntArray(v) => write_array(f, "I", "", v),
+			Value::LongArray(v) => write_array(f, "L", "L", v),
+			Value::List(v) => {
+				f.write_str("[")?;
+				for (i, value) in v.iter().enumerate() {
+					if i > 0 {
+						f.write_str(", ")?;
+					}
+					write!(f, "{}", Snbt(value))?;
+				}
+				f.write_str("]")
+			}
+			Value::Compound(v) => {
+				let mut entries: Vec<_> = v.iter().collect();
+				entries.sort_by_key(|&(key, _)| key);
+
+				f.write_str("{")?;
+				for (i, (key, value)) in entries.into_iter().enumerate() {
+					if i > 0 {
+						f.write_str(", ")?;
+					}
+					if !key.is_empty() && key.chars().all(is_unquoted_char) {
+						f.write_str(key)?;
+					} else {
+						write_string(f, key)?;
+					}
+					write!(f, ": {}", Snbt(value))?;
+				}
+				f.write_str("}")
+			}
+		}
+	}
+}
+
+/// Formats an NBT [Value] as SNBT
+pub fn to_string(value: &Value) -> String {
+	Snbt(value).to_string()
+}
+
+/// Recursive-descent SNBT parser
+struct Parser<'a> {
+	/// Full input
+	input: &'a str,
+	/// Byte offset of the next character to parse
+	pos: usize,
+}
+
+impl<'a> Parser<'a> {
+	/// Returns an error at the current position
+	fn error<T>(&self, message: &'static str) -> Result<T> {
+		Err(NbtError::InvalidSnbt {
+			position: self.pos,
+			message,
+		})
+	}
+
+	/// Returns the remaining input
+	fn rest(&self) -> &'a str {
+		&self.input[self.pos..]
+	}
+
+	/// Skips whitespace and returns the next character without consuming it
+	fn peek(&mut self) -> Option<char> {
+		let rest = self.rest();
+		self.pos += rest.len() - rest.trim_start().len();
+		self.rest().chars().next()
+	}
+
+	/// Consumes the next character if it matches
+	fn eat(&mut self, c: char) -> bool {
+		if self.peek() == Some(c) {
+			self.pos += c.len_utf8();
+			true
+		} else {
+			false
+		}
+	}
+
+	/// Consumes the next character, which must match
+	fn expect(&mut self, c: char, message: &'static str) -> Result<()> {
+		if !self.eat(c) {
+			return self.error(message);
+		}
+		Ok(())
+	}
+
+	/// Parses a quoted or unquoted string
+	fn string(&mut self) -> Result<String> {
+		let Some(quote @ ('"' | '\'')) = self.peek() else {
+			let rest = self.rest();
+			let len = rest.find(|c| !is_unquoted_char(c)).unwrap_or(rest.len());
+			if len == 0 {
+				return self.error("Expected string");
+			}
+			self.pos += len;
+			return Ok(rest[..len].to_string());
+		};
+		self.pos += 1;
+
+		let mut value = String::new();
+		let mut chars = self.rest().char_indices();
+		while let Some((i, c)) = chars.next() {
+			match c {
+				'\\' => match chars.next() {
+					Some((_, c)) => value.push(c),
+					None => break,
+				},
+				_ if c == quote => {
+					self.pos += i + 1;
+					return Ok(value);
+				}
+				_ => value.push(c),
+			}
+		}
+
+		self.pos = self.input.len();
+		self.error("Unterminated string")
+	}
+
+	/// Parses an unquoted string as a number or boolean if possible
+	fn scalar(word: &str) -> Option<Value> {
+		match word {
+			"true" => return Some(Value::Byte(1)),
+			"false" => return Some(Value::Byte(0)),
+			_ => {}
+		}
+
+		let (number, suffix) = match word.char_indices().last()? {
+			(i, c) if c.is_ascii_alphabetic() => (&word[..i], Some(c.to_ascii_lowercase())),
+			_ => (word, None),
+		};
+		Some(match suffix {
+			Some('b') => Value::Byte(number.parse().ok()?),
+			Some('s') => Value::Short(number.parse().ok()?),
+			Some('l') => Value::Long(number.parse().ok()?),
+			Some('f') => Value::Float(number.parse().ok()?),
+			Some('d') => Value::Double(number.parse().ok()?),
+			Some(_) => return None,
+			None => match number.parse() {
+				Ok(v) => Value::Int(v),
+				Err(_) if number.contains(['.', 'e', 'E']) => Value::Double(number.parse().ok()?),
+				Err(_) => return None,
+			},
+		})
+	}
+
+	/// Parses the elements of a typed array
+	fn array<T, F>(&mut self, f: F) -> Result<Vec<T>>
+	where
+		F: Fn(Value) -> Option<T>,
+	{
+		let mut values = Vec::new();
+		if self.eat(']') {
+			return Ok(values);
+		}
+		loop {
+			let start = self.pos;
+			let value = self.value(MAX_DEPTH)?;
+			let Some(value) = f(value) else {
+				self.pos = start;
+				return self.error("Invalid array element");
+			};
+			values.push(value);
+			if self.eat(']') {
+				return Ok(values);
+			}
+			self.expect(',', "Expected ',' or ']'")?;
+		}
+	}
+
+	/// Parses a list or typed array, after the opening bracket
+	fn list(&mut self, depth: usize) -> Result<Value> {
+		let rest = self.rest().trim_start();
+		if let Some(kind @ ('B' | 'I' | 'L')) = rest.chars().next() {
+			if rest[1..].trim_start().starts_with(';') {
+				self.peek();
+				self.pos += 1;
+				self.expect(';', "Expected ';'")?;
+				return Ok(match kind {
+					'B' => Value::ByteArray(ByteArray::new(self.array(|value| match value {
+						Value::Byte(v) => Some(v),
+						_ => None,
+					})?)),
+					'I' => Value::IntArray(IntArray::new(self.array(|value| match value {
+						Value::Int(v) => Some(v),
+						_ => None,
+					})?)),
+					_ => Value::LongArray(LongArray::new(self.array(|value| match value {
+						Value::Long(v) => Some(v),
+						_ => None,
+					})?)),
+				});
+			}
+		}
+
+		let mut values = Vec::new();
+		if self.eat(']') {
+			return Ok(Value::List(values));
+		}
+		loop {
+			let start = self.pos;
+			let value = self.value(depth + 1)?;
+			if values.first().is_some_and(|first| {
+				std::mem::discriminant(first) != std::mem::discriminant(&value)
+			}) {
+				self.pos = start;
+				return self.error("Mixed list element types");
+			}
+			values.push(value);
+			if self.eat(']') {
+				return Ok(Value::List(values));
+			}
+			self.expect(',', "Expected ',' or ']'")?;
+		}
+	}
+
+	/// Parses a compound, after the opening brace
+	fn compound(&mut self, depth: usize) -> Result<Value> {
+		let mut values = HashMap::new();
+		if self.eat('}') {
+			return Ok(Value::Compound(values));
+		}
+		loop {
+			let key = self.string()?;
+			self.expect(':', "Expected ':'")?;
+			let value = self.value(depth + 1)?;
+			values.insert(key, value);
+			if self.eat('}') {
+				return Ok(Value::Compound(values));
+			}
+			self.expect(',', "Expected ',' or '}'")?;
+		}
+	}
+
+	/// Parses a value
+	fn value(&mut self, depth: usize) -> Result<Value> {
+		if depth > MAX_DEPTH {
+			return self.error("SNBT data nested too deeply");
+		}
+
+		match self.peek() {
+			Some('{') => {
+				self.pos += 1;
+				self.compound(depth)
+			}
+			Some('[') => {
+				self.pos += 1;
+				self.list(depth)
+			}
+			Some('"' | '\'') => Ok(Value::String(self.string()?)),
+			Some(_) => {
+				let word = self.string()?;
+				Ok(Self::scalar(&word).unwrap_or(Value::String(word)))
+			}
+			None => self.error("Unexpected end of SNBT data"),
+		}
+	}
+}
+
+/// Parses SNBT into an NBT [Value]
+///
+/// Numbers without a type suffix are parsed as ints, or as doubles if they
+/// contain a decimal point or exponent. `true` and `false` are parsed as
+/// bytes.
+pub fn from_str(input: &str) -> Result<Value> {
+	let mut parser = Parser { input, pos: 0 };
+	let value = parser.value(0)?;
+	if parser.peek().is_some() {
+		return parser.error("Trailing characters after SNBT data");
+	}
+	Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_round_trip() {
+		let value = Value::Compound(HashMap::from([
+			("byte".to_string(), Value::Byte(-1)),
+			("short".to_string(), Value::Short(300)),
+			("int".to_string(), Value::Int(i32::MIN)),
+			("long".to_string(), Value::Long(i64::MAX)),
+			("float".to_string(), Value::Float(0.5)),
+			("double".to_string(), Value::Double(-1e100)),
+			(
+				"string".to_string(),
+				Value::String("quote \" and \\ backslash".to_string()),
+			),
+			("with space".to_string(), Value::String(String::new())),
+			(
+				"list".to_string(),
+				Value::List(vec![
+					Value::Compound(HashMap::new()),
+					Value::Compound(HashMap::from([("a".to_string(), Value::Int(1))])),
+				]),
+			),
+			("empty".to_string(), Value::List(vec![])),
+			(
+				"bytes".to_string(),
+				Value::ByteArray(ByteArray::new(vec![1, -2])),
+			),
+			("ints".to_string(), Value::IntArray(IntArray::new(vec![]))),
+			(
+				"longs".to_string(),
+				Value::LongArray(LongArray::new(vec![i64::MIN, 0])),
+			),
+		]));
+
+		let snbt = to_string(&value);
+		assert_eq!(from_str(&snbt).unwrap(), value);
+		assert_eq!(to_string(&from_str(&snbt).unwrap()), snbt);
+	}
+
+	#[test]
+	fn test_parse() {
+		assert_eq!(from_str("true").unwrap(), Value::Byte(1));
+		assert_eq!(from_str("1.5").unwrap(), Value::Double(1.5));
+		assert_eq!(
+			from_str("'single'").unwrap(),
+			Value::String("single".to_string())
+		);
+		assert_eq!(
+			from_str("{ key : [ 1 , 2 ] }").unwrap(),
+			Value::Compound(HashMap::from([(
+				"key".to_string(),
+				Value::List(vec![Value::Int(1), Value::Int(2)])
+			)]))
+		);
+	}
+
+	#[test]
+	fn test_invalid() {
+		for input in ["", "{", "{a: 1", "[1, 2b]", "[B; 1, 2]x", "\"open", "1 2"] {
+			assert!(
+				matches!(from_str(input), Err(NbtError::InvalidSnbt { .. })),
+				"{input}"
+			);
+		}
+
+		let deep = "[".repeat(MAX_DEPTH + 2) + &"]".repeat(MAX_DEPTH + 2);
+		assert!(from_str(&deep).is_err());
+	}
+}
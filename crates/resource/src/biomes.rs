@@ -106,6 +106,20 @@ impl Biome {
 	pub fn downfall(&self) -> f32 {
 		Self::decode(self.downfall)
 	}
+
+	/// Builder function to set the temperature and downfall from their
+	/// original float values
+	pub fn with_climate(self, temp: f32, downfall: f32) -> Biome {
+		/// Helper to encode float temperature and downfall values
+		fn encode(v: f32) -> i8 {
+			(v * 20.0).round().clamp(i8::MIN.into(), i8::MAX.into()) as i8
+		}
+		Biome {
+			temp: encode(temp),
+			downfall: encode(downfall),
+			..self
+		}
+	}
 }
 
 /// Standard biome specifications
@@ -235,6 +249,154 @@ pub const BIOMES: &[(&str, Biome)] = {
 	]
 };
 
+/// Default colors of the biome map
+///
+/// Visually distinct colors for each of the standard biomes, loosely
+/// following the colors used by common seed map tools.
+pub const BIOME_COLORS: &[(&str, Color)] = &[
+	// Overworld
+	("badlands", Color([217, 69, 21])),
+	("bamboo_jungle", Color([118, 142, 20])),
+	("beach", Color([250, 222, 85])),
+	("birch_forest", Color([48, 116, 68])),
+	("cherry_grove", Color([255, 145, 200])),
+	("cold_ocean", Color([32, 32, 112])),
+	("dark_forest", Color([64, 81, 26])),
+	("deep_cold_ocean", Color([32, 32, 56])),
+	("deep_dark", Color([3, 31, 41])),
+	("deep_frozen_ocean", Color([64, 64, 144])),
+	("deep_lukewarm_ocean", Color([0, 0, 64])),
+	("deep_ocean", Color([0, 0, 48])),
+	("desert", Color([250, 148, 24])),
+	("dripstone_caves", Color([78, 48, 18])),
+	("eroded_badlands", Color([255, 109, 61])),
+	("flower_forest", Color([45, 142, 73])),
+	("forest", Color([5, 102, 33])),
+	("frozen_ocean", Color([112, 112, 214])),
+	("frozen_peaks", Color([176, 179, 206])),
+	("frozen_river", Color([160, 160, 255])),
+	("grove", Color([71, 114, 108])),
+	("ice_spikes", Color([180, 220, 220])),
+	("jagged_peaks", Color([220, 220, 200])),
+	("jungle", Color([83, 123, 9])),
+	("lukewarm_ocean", Color([0, 0, 144])),
+	("lush_caves", Color([40, 60, 0])),
+	("mangrove_swamp", Color([44, 204, 142])),
+	("meadow", Color([96, 164, 69])),
+	("mushroom_fields", Color([255, 0, 255])),
+	("ocean", Color([0, 0, 112])),
+	("old_growth_birch_forest", Color([88, 156, 108])),
+	("old_growth_pine_taiga", Color([89, 102, 81])),
+	("old_growth_spruce_taiga", Color([129, 142, 121])),
+	("plains", Color([141, 179, 96])),
+	("river", Color([0, 0, 255])),
+	("savanna", Color([189, 178, 95])),
+	("savanna_plateau", Color([167, 157, 100])),
+	("snowy_beach", Color([250, 240, 192])),
+	("snowy_plains", Color([255, 255, 255])),
+	("snowy_slopes", Color([196, 196, 196])),
+	("snowy_taiga", Color([49, 85, 74])),
+	("sparse_jungle", Color([98, 139, 23])),
+	("stony_peaks", Color([123, 143, 116])),
+	("stony_shore", Color([162, 162, 132])),
+	("sunflower_plains", Color([181, 219, 136])),
+	("swamp", Color([7, 249, 178])),
+	("taiga", Color([11, 102, 89])),
+	("the_void", Color([0, 0, 0])),
+	("warm_ocean", Color([0, 0, 172])),
+	("windswept_forest", Color([80, 112, 80])),
+	("windswept_gravelly_hills", Color([136, 136, 136])),
+	("windswept_hills", Color([96, 96, 96])),
+	("windswept_savanna", Color([229, 218, 135])),
+	("wooded_badlands", Color([176, 151, 101])),
+	// Nether
+	("basalt_deltas", Color([64, 54, 54])),
+	("crimson_forest", Color([221, 8, 8])),
+	("nether_wastes", Color([191, 59, 59])),
+	("soul_sand_valley", Color([94, 56, 48])),
+	("warped_forest", Color([73, 144, 123])),
+	// End
+	("end_barrens", Color([112, 112, 204])),
+	("end_highlands", Color([181, 181, 54])),
+	("end_midlands", Color([201, 201, 89])),
+	("small_end_islands", Color([75, 75, 171])),
+	("the_end", Color([128, 128, 255])),
+];
+
+/// Colorblind-friendly colors of the biome map
+///
+/// Based on the Okabe-Ito palette, distinguishing groups of related biomes
+/// by hue and the biomes within each group by lightness.
+pub const COLORBLIND_BIOME_COLORS: &[(&str, Color)] = &[
+	// Overworld
+	("badlands", Color([213, 94, 0])),
+	("bamboo_jungle", Color([0, 110, 70])),
+	("beach", Color([240, 228, 66])),
+	("birch_forest", Color([90, 195, 155])),
+	("cherry_grove", Color([204, 121, 167])),
+	("cold_ocean", Color([0, 90, 150])),
+	("dark_forest", Color([0, 90, 65])),
+	("deep_cold_ocean", Color([0, 55, 100])),
+	("deep_dark", Color([20, 20, 30])),
+	("deep_frozen_ocean", Color([110, 160, 205])),
+	("deep_lukewarm_ocean", Color([15, 95, 150])),
+	("deep_ocean", Color([0, 65, 115])),
+	("desert", Color([250, 205, 60])),
+	("dripstone_caves", Color([115, 70, 35])),
+	("eroded_badlands", Color([245, 125, 45])),
+	("flower_forest", Color([55, 185, 145])),
+	("forest", Color([0, 158, 115])),
+	("frozen_ocean", Color([165, 210, 240])),
+	("frozen_peaks", Color([200, 220, 235])),
+	("frozen_river", Color([190, 225, 245])),
+	("grove", Color([150, 200, 200])),
+	("ice_spikes", Color([215, 235, 250])),
+	("jagged_peaks", Color([220, 220, 220])),
+	("jungle", Color([0, 130, 90])),
+	("lukewarm_ocean", Color([35, 145, 205])),
+	("lush_caves", Color([120, 160, 40])),
+	("mangrove_swamp", Color([60, 85, 60])),
+	("meadow", Color([185, 230, 200])),
+	("mushroom_fields", Color([165, 85, 135])),
+	("ocean", Color([0, 114, 178])),
+	("old_growth_birch_forest", Color([75, 175, 140])),
+	("old_growth_pine_taiga", Color([0, 70, 75])),
+	("old_growth_spruce_taiga", Color([0, 80, 85])),
+	("plains", Color([165, 215, 165])),
+	("river", Color([86, 180, 233])),
+	("savanna", Color([230, 159, 0])),
+	("savanna_plateau", Color([195, 135, 0])),
+	("snowy_beach", Color([245, 240, 175])),
+	("snowy_plains", Color([250, 250, 250])),
+	("snowy_slopes", Color([235, 235, 235])),
+	("snowy_taiga", Color([120, 170, 170])),
+	("sparse_jungle", Color([60, 160, 110])),
+	("stony_peaks", Color([110, 110, 110])),
+	("stony_shore", Color([140, 140, 140])),
+	("sunflower_plains", Color([200, 230, 150])),
+	("swamp", Color([90, 110, 80])),
+	("taiga", Color([0, 100, 100])),
+	("the_void", Color([0, 0, 0])),
+	("warm_ocean", Color([86, 180, 233])),
+	("windswept_forest", Color([70, 120, 110])),
+	("windswept_gravelly_hills", Color([160, 160, 160])),
+	("windswept_hills", Color([125, 125, 125])),
+	("windswept_savanna", Color([250, 185, 60])),
+	("wooded_badlands", Color([165, 70, 0])),
+	// Nether
+	("basalt_deltas", Color([70, 70, 70])),
+	("crimson_forest", Color([204, 121, 167])),
+	("nether_wastes", Color([213, 94, 0])),
+	("soul_sand_valley", Color([115, 85, 60])),
+	("warped_forest", Color([0, 158, 115])),
+	// End
+	("end_barrens", Color([150, 140, 100])),
+	("end_highlands", Color([225, 215, 160])),
+	("end_midlands", Color([185, 175, 125])),
+	("small_end_islands", Color([115, 105, 75])),
+	("the_end", Color([240, 228, 66])),
+];
+
 /// Biome ID aliases
 ///
 /// Some biomes have been renamed or merged in recent Minecraft versions.
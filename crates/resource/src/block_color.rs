@@ -105,6 +105,14 @@ pub fn needs_biome(block: BlockType) -> bool {
 /// [needs_biome] must be used to determine whether passing a [Biome] is necessary.
 /// Will panic if a [Biome] is necessary, but none is passed.
 pub fn block_color(block: BlockType, biome: Option<&Biome>, depth: f32) -> Vec3 {
+	base_block_color(block, biome, depth) * (0.5 + 0.005 * depth)
+}
+
+/// Determines the color of a [BlockType] without darkening it by its depth
+///
+/// Like [block_color], but without the depth-based brightness, for use with
+/// other kinds of shading.
+pub fn base_block_color(block: BlockType, biome: Option<&Biome>, depth: f32) -> Vec3 {
 	use super::BlockFlag::*;
 
 	let get_biome = || biome.expect("needs biome to determine block color");
@@ -127,5 +135,5 @@ pub fn block_color(block: BlockType, biome: Option<&Biome>, depth: f32) -> Vec3
 		color *= get_biome().water_color();
 	}
 
-	color * (0.5 + 0.005 * depth)
+	color
 }
@@ -54,6 +54,35 @@ impl BlockType {
 	}
 }
 
+/// Name suffixes of unknown block IDs and the standard block types used for them
+const BLOCK_FALLBACKS: &[(&str, &str)] = &[
+	("_planks", "oak_planks"),
+	("_log", "oak_log"),
+	("_wood", "oak_wood"),
+	("_leaves", "oak_leaves"),
+	("_ore", "stone"),
+	("_stone", "stone"),
+	("_cobblestone", "cobblestone"),
+	("_bricks", "stone_bricks"),
+	("_brick", "stone_bricks"),
+	("_tiles", "stone_bricks"),
+	("_deepslate", "deepslate"),
+	("_sand", "sand"),
+	("_sandstone", "sandstone"),
+	("_gravel", "gravel"),
+	("_dirt", "dirt"),
+	("_grass_block", "grass_block"),
+	("_glass", "glass"),
+	("_wool", "white_wool"),
+	("_concrete", "light_gray_concrete"),
+	("_terracotta", "terracotta"),
+	("_ice", "ice"),
+];
+
+/// Name suffixes of unknown block IDs whose color is taken from the block
+/// they are made of
+const SHAPE_SUFFIXES: &[&str] = &["_stairs", "_slab", "_wall", "_fence", "_fence_gate"];
+
 /// Used to look up standard Minecraft block types
 #[derive(Debug)]
 pub struct BlockTypes {
@@ -82,10 +111,73 @@ impl Default for BlockTypes {
 
 impl BlockTypes {
 	/// Resolves a Minecraft 1.13+ string block type ID
+	///
+	/// IDs outside of the `minecraft:` namespace only resolve to block types
+	/// added using [BlockTypes::insert].
 	#[inline]
 	pub fn get(&self, id: &str) -> Option<BlockType> {
-		let suffix = id.strip_prefix("minecraft:")?;
-		self.block_type_map.get(suffix).copied()
+		let key = id.strip_prefix("minecraft:").unwrap_or(id);
+		self.block_type_map.get(key).copied()
+	}
+
+	/// Adds a block type or replaces an existing one
+	///
+	/// When a standard block type is replaced, the pre-1.13 numeric IDs
+	/// corresponding to it resolve to the new block type as well. Block types
+	/// outside of the `minecraft:` namespace must be passed with their
+	/// namespace.
+	pub fn insert(&mut self, id: &str, block_type: BlockType) {
+		let id = id.strip_prefix("minecraft:").unwrap_or(id);
+		self.block_type_map.insert(String::from(id), block_type);
+
+		// Numeric IDs only exist for standard block types
+		if id.contains(':') {
+			return;
+		}
+
+		for (legacy_ids, entries) in legacy_block_types::LEGACY_BLOCK_TYPES
+			.iter()
+			.zip(self.legacy_block_types.iter_mut())
+		{
+			for (&legacy_id, entry) in legacy_ids.iter().zip(entries.iter_mut()) {
+				if legacy_id == id {
+					*entry = block_type;
+				}
+			}
+		}
+	}
+
+	/// Resolves a Minecraft 1.13+ string block type ID, guessing the block
+	/// types of unknown IDs from their names
+	pub fn resolve(&self, id: &str) -> Option<BlockType> {
+		self.get(id).or_else(|| self.guess(id))
+	}
+
+	/// Guesses the block type of an unknown block ID from its name
+	///
+	/// Blocks named like standard block types, for example `*_planks` or
+	/// `*_ore`, use the color of a matching standard block. Stairs, slabs,
+	/// walls and fences use the block type of the block they are made of.
+	fn guess(&self, id: &str) -> Option<BlockType> {
+		let (namespace, name) = id.rsplit_once(':').unwrap_or(("minecraft", id));
+
+		for suffix in SHAPE_SUFFIXES {
+			let Some(base) = name.strip_suffix(suffix) else {
+				continue;
+			};
+			let block_type = [base, &format!("{}_planks", base)]
+				.into_iter()
+				.find_map(|base| self.get(&format!("{}:{}", namespace, base)));
+			if block_type.is_some() {
+				return block_type;
+			}
+			return self.guess(&format!("{}:{}", namespace, base));
+		}
+
+		BLOCK_FALLBACKS
+			.iter()
+			.find(|(suffix, _)| name.ends_with(suffix))
+			.and_then(|(_, fallback)| self.block_type_map.get(*fallback).copied())
 	}
 
 	/// Resolves a Minecraft pre-1.13 numeric block type ID
@@ -95,23 +187,37 @@ impl BlockTypes {
 	}
 }
 
-pub use biomes::{Biome, BiomeGrassColorModifier};
-pub use block_color::{block_color, needs_biome};
+/// Returns the string ID corresponding to a Minecraft pre-1.13 numeric
+/// block type ID
+///
+/// The returned ID does not include the `minecraft:` namespace.
+pub fn legacy_block_id(id: u8, data: u8) -> &'static str {
+	legacy_block_types::LEGACY_BLOCK_TYPES[id as usize][data as usize]
+}
+
+pub use biomes::{Biome, BiomeGrassColorModifier, BIOME_COLORS, COLORBLIND_BIOME_COLORS};
+pub use block_color::{base_block_color, block_color, needs_biome};
 
 /// Used to look up standard Minecraft biome types
 #[derive(Debug)]
 pub struct BiomeTypes {
 	/// Map of string IDs to biome types
 	biome_map: HashMap<String, &'static Biome>,
+	/// List of biome types with their (current) string IDs
+	///
+	/// Used for reverse lookups; the biome references are identical to the
+	/// values of *biome_map*.
+	biome_ids: Vec<(&'static Biome, &'static str)>,
 	/// Array used to look up old numeric biome IDs
 	legacy_biomes: Box<[&'static Biome; 256]>,
 }
 
 impl Default for BiomeTypes {
 	fn default() -> Self {
-		let mut biome_map: HashMap<_, _> = biomes::BIOMES
+		let biome_ids: Vec<_> = biomes::BIOMES.iter().map(|(k, v)| (v, *k)).collect();
+		let mut biome_map: HashMap<_, _> = biome_ids
 			.iter()
-			.map(|(k, v)| (String::from(*k), v))
+			.map(|&(v, k)| (String::from(k), v))
 			.collect();
 
 		for &(old, new) in biomes::BIOME_ALIASES.iter().rev() {
@@ -133,6 +239,7 @@ impl Default for BiomeTypes {
 
 		Self {
 			biome_map,
+			biome_ids,
 			legacy_biomes,
 		}
 	}
@@ -140,10 +247,45 @@ impl Default for BiomeTypes {
 
 impl BiomeTypes {
 	/// Resolves a Minecraft 1.18+ string biome type ID
+	///
+	/// IDs outside of the `minecraft:` namespace only resolve to biomes
+	/// added using [BiomeTypes::insert].
 	#[inline]
 	pub fn get(&self, id: &str) -> Option<&Biome> {
-		let suffix = id.strip_prefix("minecraft:")?;
-		self.biome_map.get(suffix).copied()
+		let key = id.strip_prefix("minecraft:").unwrap_or(id);
+		self.biome_map.get(key).copied()
+	}
+
+	/// Adds a biome type or replaces an existing one
+	///
+	/// When a standard biome is replaced, its aliases and numeric IDs resolve
+	/// to the new biome as well. Biomes outside of the `minecraft:` namespace
+	/// must be passed with their namespace.
+	///
+	/// The biome and its ID are leaked, as biome references must remain valid
+	/// for the whole runtime of the program; this is meant for a small number
+	/// of custom biomes loaded at startup.
+	pub fn insert(&mut self, id: &str, biome: Biome) {
+		let id = id.strip_prefix("minecraft:").unwrap_or(id);
+		let new: &'static Biome = Box::leak(Box::new(biome));
+
+		let Some(old) = self.biome_map.get(id).copied() else {
+			let id: &'static str = Box::leak(id.into());
+			self.biome_map.insert(String::from(id), new);
+			self.biome_ids.push((new, id));
+			return;
+		};
+
+		for entry in self
+			.biome_map
+			.values_mut()
+			.chain(self.legacy_biomes.iter_mut())
+			.chain(self.biome_ids.iter_mut().map(|(entry, _)| entry))
+		{
+			if std::ptr::eq(*entry, old) {
+				*entry = new;
+			}
+		}
 	}
 
 	/// Resolves a Minecraft pre-1.18 numeric biome type ID
@@ -151,4 +293,17 @@ impl BiomeTypes {
 	pub fn get_legacy(&self, id: u8) -> Option<&Biome> {
 		Some(self.legacy_biomes[id as usize])
 	}
+
+	/// Returns the string ID of a biome type returned by this [BiomeTypes]
+	///
+	/// Biomes are identified by reference, as different biomes can have
+	/// identical properties. Old biome IDs are resolved to the current
+	/// name of the biome. The returned ID does not include the `minecraft:`
+	/// namespace.
+	pub fn id(&self, biome: &Biome) -> Option<&'static str> {
+		self.biome_ids
+			.iter()
+			.find(|(entry, _)| std::ptr::eq(*entry, biome))
+			.map(|&(_, id)| id)
+	}
 }
@@ -1,20 +1,144 @@
 //! Common data types and functions used by multiple generation steps
 
 use std::{
-	collections::{BTreeMap, BTreeSet},
+	collections::{BTreeMap, BTreeSet, HashMap},
+	ffi::OsStr,
 	fmt::Debug,
+	hash::{Hash, Hasher},
+	io::{Read, Write},
 	path::{Path, PathBuf},
+	sync::{Arc, Mutex},
+	time::{Duration, SystemTime},
 };
 
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use glam::Vec3;
 use indexmap::IndexSet;
 use serde::{Deserialize, Serialize};
 
-use crate::{io::fs::FileMetaVersion, resource::Biome, types::*, world::layer};
+use super::layout::{self, DimensionLayout, WorldLayout};
+use crate::{
+	io::{
+		fs::{self, FileMetaVersion, FileStatus, SettingsHash},
+		input::{self, InputSource},
+		target::OutputTarget,
+	},
+	nbt::region::OverlapResolution,
+	resource::{
+		Biome, BiomeGrassColorModifier, BiomeTypes, BlockFlag, BlockType, BlockTypes, Color,
+		BIOME_COLORS, COLORBLIND_BIOME_COLORS,
+	},
+	types::*,
+	world::{bedrock, cubic, de, layer, section::BlockFilter},
+};
 
 /// MinedMap data version number
 ///
 /// Increase to force regeneration of all output files
-pub const FILE_META_VERSION: FileMetaVersion = FileMetaVersion(0);
+pub const FILE_META_VERSION: FileMetaVersion = FileMetaVersion(4);
+
+/// Block types hidden by `--hide-vegetation` by default
+///
+/// [LEAF_BLOCKS] are hidden as well.
+pub const VEGETATION_BLOCKS: &[&str] = &[
+	"short_grass",
+	"grass",
+	"tall_grass",
+	"fern",
+	"large_fern",
+	"dead_bush",
+	"dandelion",
+	"poppy",
+	"blue_orchid",
+	"allium",
+	"azure_bluet",
+	"red_tulip",
+	"orange_tulip",
+	"white_tulip",
+	"pink_tulip",
+	"oxeye_daisy",
+	"cornflower",
+	"lily_of_the_valley",
+	"wither_rose",
+	"torchflower",
+	"sunflower",
+	"lilac",
+	"rose_bush",
+	"peony",
+	"pink_petals",
+	"sweet_berry_bush",
+	"wheat",
+	"carrots",
+	"potatoes",
+	"beetroots",
+	"melon_stem",
+	"pumpkin_stem",
+	"attached_melon_stem",
+	"attached_pumpkin_stem",
+	"torchflower_crop",
+	"pitcher_crop",
+	"pitcher_plant",
+	"sugar_cane",
+	"vine",
+];
+
+/// Leaf block types
+///
+/// Used for `--hide-vegetation` and `--canopy-opacity`.
+pub const LEAF_BLOCKS: &[&str] = &[
+	"oak_leaves",
+	"spruce_leaves",
+	"birch_leaves",
+	"jungle_leaves",
+	"acacia_leaves",
+	"dark_oak_leaves",
+	"mangrove_leaves",
+	"cherry_leaves",
+	"pale_oak_leaves",
+	"azalea_leaves",
+	"flowering_azalea_leaves",
+];
+
+/// Block types treated as translucent by `--translucent` by default
+pub const TRANSLUCENT_BLOCKS: &[&str] = &[
+	"glass",
+	"glass_pane",
+	"white_stained_glass",
+	"orange_stained_glass",
+	"magenta_stained_glass",
+	"light_blue_stained_glass",
+	"yellow_stained_glass",
+	"lime_stained_glass",
+	"pink_stained_glass",
+	"gray_stained_glass",
+	"light_gray_stained_glass",
+	"cyan_stained_glass",
+	"purple_stained_glass",
+	"blue_stained_glass",
+	"brown_stained_glass",
+	"green_stained_glass",
+	"red_stained_glass",
+	"black_stained_glass",
+	"white_stained_glass_pane",
+	"orange_stained_glass_pane",
+	"magenta_stained_glass_pane",
+	"light_blue_stained_glass_pane",
+	"yellow_stained_glass_pane",
+	"lime_stained_glass_pane",
+	"pink_stained_glass_pane",
+	"gray_stained_glass_pane",
+	"light_gray_stained_glass_pane",
+	"cyan_stained_glass_pane",
+	"purple_stained_glass_pane",
+	"blue_stained_glass_pane",
+	"brown_stained_glass_pane",
+	"green_stained_glass_pane",
+	"red_stained_glass_pane",
+	"black_stained_glass_pane",
+	"ice",
+	"frosted_ice",
+];
 
 /// Coordinate pair of a generated tile
 ///
@@ -61,6 +185,24 @@ pub struct ProcessedChunk {
 	pub biomes: Box<layer::BiomeArray>,
 	/// Block height/depth data
 	pub depths: Box<layer::DepthArray>,
+	/// Block light data
+	pub block_light: Box<layer::BlockLightArray>,
+	/// Covered column data
+	pub covered: Box<layer::CoveredArray>,
+	/// Water depth data
+	pub water_depths: Box<layer::WaterDepthArray>,
+	/// Block type data of the ground below water or see-through blocks
+	pub floors: Box<layer::BlockArray>,
+	/// Block type data of translucent blocks above the top block
+	pub tints: Box<layer::BlockArray>,
+}
+
+impl ProcessedChunk {
+	/// Returns the height of the surface of a block column, including water
+	pub fn surface_height(&self, block: LayerBlockCoords) -> Option<i32> {
+		let depth = self.depths[block]?;
+		Some(depth.0 + i32::from(self.water_depths[block]))
+	}
 }
 
 /// Data structure for storing region data between processing and rendering steps
@@ -72,6 +214,9 @@ pub struct ProcessedRegion {
 	pub biome_list: IndexSet<Biome>,
 	/// Processed chunk data
 	pub chunks: ChunkArray<Option<Box<ProcessedChunk>>>,
+	/// Unknown block IDs found in the region with the number of chunk
+	/// sections containing each ID
+	pub unknown_blocks: BTreeMap<String, u64>,
 }
 
 /// Derives a filename from region coordinates and a file extension
@@ -81,59 +226,1904 @@ fn coord_filename(coords: TileCoords, ext: &str) -> String {
 	format!("r.{}.{}.{}", coords.x, coords.z, ext)
 }
 
+/// Parses a filename in the format r.X.Z.ext into the contained X and Z values
+///
+/// Inverse of [coord_filename].
+pub fn parse_coord_filename(file_name: &OsStr, ext: &str) -> Option<TileCoords> {
+	let parts: Vec<_> = file_name.to_str()?.split('.').collect();
+	let &["r", x, z, file_ext] = parts.as_slice() else {
+		return None;
+	};
+	if file_ext != ext {
+		return None;
+	}
+
+	Some(TileCoords {
+		x: x.parse().ok()?,
+		z: z.parse().ok()?,
+	})
+}
+
+/// Kind of modification recorded in the [ChangeList]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+	/// A new file was created
+	Added,
+	/// An existing file was updated
+	Modified,
+	/// A file was removed
+	Deleted,
+}
+
+/// List of viewer files modified during a run
+///
+/// Written in the same format as `git diff --name-status`, so the list of
+/// changed files can easily be passed to `rsync --files-from` or similar tools.
+#[derive(Debug, Default)]
+pub struct ChangeList(Mutex<BTreeMap<String, FileChange>>);
+
+impl ChangeList {
+	/// Records a modification of the file with a given output-relative path
+	pub fn record(&self, key: String, change: FileChange) {
+		self.0.lock().unwrap().insert(key, change);
+	}
+
+	/// Returns the recorded changes, ordered by path
+	pub fn entries(&self) -> Vec<(String, FileChange)> {
+		let changes = self.0.lock().unwrap();
+		changes
+			.iter()
+			.map(|(key, change)| (key.clone(), *change))
+			.collect()
+	}
+
+	/// Writes the list of changes to a file
+	pub fn write(&self, path: &Path) -> Result<()> {
+		let changes = self.0.lock().unwrap();
+		fs::create_with_tmpfile(path, |file| {
+			for (key, change) in changes.iter() {
+				let status = match change {
+					FileChange::Added => 'A',
+					FileChange::Modified => 'M',
+					FileChange::Deleted => 'D',
+				};
+				writeln!(file, "{}\t{}", status, key)?;
+			}
+			Ok(())
+		})?;
+		Ok(())
+	}
+}
+
+/// Append-only list of the string values referenced by a data tile kind
+///
+/// Data tiles store indices into the palette. As the tiles of unchanged
+/// regions are kept between runs, the palette of the previous run is loaded
+/// from the tile directory and new values are only ever appended, so
+/// existing indices stay valid. The palette must be [saved](Self::save)
+/// before the tiles referencing its new values are written.
+#[derive(Debug, Default)]
+pub struct DataPalette(Mutex<DataPaletteState>);
+
+/// Values and save state of a [DataPalette]
+#[derive(Debug, Default)]
+struct DataPaletteState {
+	/// Values of the palette in index order
+	names: IndexSet<String>,
+	/// Number of values stored in the palette file, if it has been loaded
+	/// or saved
+	saved: Option<usize>,
+}
+
+impl DataPalette {
+	/// Loads a palette file written by a previous run
+	///
+	/// Returns an empty palette if the file does not exist. A palette file
+	/// that can't be read is an error, as the existing data tiles can't be
+	/// interpreted without it.
+	pub fn load(path: &Path) -> Result<Self> {
+		let data = match std::fs::read(path) {
+			Ok(data) => data,
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+				return Ok(DataPalette::default());
+			}
+			Err(err) => {
+				return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+			}
+		};
+		let names: IndexSet<String> = serde_json::from_slice(&data).with_context(|| {
+			format!(
+				"Failed to parse {}, remove the data tiles to generate them again",
+				path.display()
+			)
+		})?;
+		let saved = Some(names.len());
+		Ok(DataPalette(Mutex::new(DataPaletteState { names, saved })))
+	}
+
+	/// Returns the index of a value, appending it to the palette if necessary
+	///
+	/// Returns [None] if the palette is full.
+	pub fn index(&self, name: &str) -> Option<u16> {
+		let mut state = self.0.lock().unwrap();
+		if let Some(index) = state.names.get_index_of(name) {
+			return index.try_into().ok();
+		}
+		let index = state.names.len().try_into().ok()?;
+		state.names.insert(name.to_string());
+		Some(index)
+	}
+
+	/// Returns the values of the palette in index order
+	pub fn names(&self) -> Vec<String> {
+		self.0.lock().unwrap().names.iter().cloned().collect()
+	}
+
+	/// Writes the palette file if values have been appended since it was
+	/// loaded or last saved
+	///
+	/// The file is replaced atomically and passed on by calling *store*. The
+	/// palette stays locked until then, so the stored file always contains
+	/// all indices returned before.
+	pub fn save<S>(&self, path: &Path, store: S) -> Result<()>
+	where
+		S: FnOnce(FileStatus) -> Result<()>,
+	{
+		let mut state = self.0.lock().unwrap();
+		if state.saved == Some(state.names.len()) {
+			return Ok(());
+		}
+
+		let ((), status) = fs::create_with_tmpfile(path, |file| {
+			serde_json::to_writer(file, &state.names).context("Failed to write palette")
+		})?;
+		store(status)?;
+		state.saved = Some(state.names.len());
+
+		Ok(())
+	}
+}
+
+/// List of chunks skipped because they could not be loaded
+///
+/// Only used with the `--skip-corrupt` option. The list is sorted by region
+/// path and chunk coordinates, so the report is stable between runs.
+#[derive(Debug, Default)]
+pub struct CorruptChunkList(Mutex<BTreeMap<(PathBuf, u8, u8), String>>);
+
+impl CorruptChunkList {
+	/// Records a skipped chunk of the region file at *region_path*
+	pub fn record(&self, region_path: PathBuf, chunk_coords: ChunkCoords, reason: String) {
+		self.0
+			.lock()
+			.unwrap()
+			.insert((region_path, chunk_coords.x.0, chunk_coords.z.0), reason);
+	}
+
+	/// Writes the list of skipped chunks to a file
+	pub fn write(&self, path: &Path) -> Result<()> {
+		let chunks = self.0.lock().unwrap();
+		fs::create_with_tmpfile(path, |file| {
+			for ((region_path, x, z), reason) in chunks.iter() {
+				writeln!(file, "{}	{}	{}	{}", region_path.display(), x, z, reason)?;
+			}
+			Ok(())
+		})?;
+		Ok(())
+	}
+}
+
+/// Unknown block IDs found in the processed regions
+///
+/// Only used with the `--unknown-blocks` option. Maps each block ID to the
+/// number of chunk sections it was found in.
+#[derive(Debug, Default)]
+pub struct UnknownBlockList(Mutex<BTreeMap<String, u64>>);
+
+impl UnknownBlockList {
+	/// Adds the unknown block counts of a processed region
+	pub fn merge(&self, counts: &BTreeMap<String, u64>) {
+		let mut blocks = self.0.lock().unwrap();
+		for (id, count) in counts {
+			*blocks.entry(id.clone()).or_default() += count;
+		}
+	}
+
+	/// Writes the unknown block IDs to a file
+	///
+	/// Each line of the report contains a block ID and the number of chunk
+	/// sections it was found in, separated by a tab. The IDs are sorted by
+	/// descending count.
+	pub fn write(&self, path: &Path) -> Result<()> {
+		let mut blocks: Vec<_> = self.0.lock().unwrap().clone().into_iter().collect();
+		blocks
+			.sort_by(|(id_a, count_a), (id_b, count_b)| count_b.cmp(count_a).then(id_a.cmp(id_b)));
+
+		fs::create_with_tmpfile(path, |file| {
+			for (id, count) in &blocks {
+				writeln!(file, "{}	{}", id, count)?;
+			}
+			Ok(())
+		})?;
+		Ok(())
+	}
+}
+
 /// Tile kind corresponding to a map layer
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum TileKind {
 	/// Regular map tile contains block colors
 	Map,
 	/// Lightmap tile for illumination layer
 	Lightmap,
+	/// Ore density overlay tile
+	Ores,
+	/// Player activity overlay tile
+	Activity,
+	/// Mob spawning overlay tile
+	Spawnable,
+	/// Chunk grid overlay tile
+	ChunkGrid,
+	/// Region grid overlay tile
+	RegionGrid,
+	/// Slime chunk overlay tile
+	SlimeChunks,
+	/// Spawn chunk overlay tile
+	SpawnChunks,
+	/// World border overlay tile
+	WorldBorder,
+	/// Biome map tile
+	Biomes,
+	/// Heightmap tile
+	Heightmap,
+	/// Contour line overlay tile
+	Contours,
+	/// Elevation-tinted terrain map tile
+	Terrain,
+	/// Cave map tile
+	Caves,
+	/// Night map tile
+	Night,
+	/// Ocean floor map tile
+	OceanFloor,
+	/// Isometric map tile
+	Isometric,
+	/// Surface height data tile
+	HeightData,
+	/// Biome data tile
+	BiomeData,
+	/// Top block data tile
+	BlockData,
+}
+
+impl TileKind {
+	/// All tile kinds
+	pub const ALL: [TileKind; 21] = [
+		TileKind::Map,
+		TileKind::Lightmap,
+		TileKind::Ores,
+		TileKind::Activity,
+		TileKind::Spawnable,
+		TileKind::ChunkGrid,
+		TileKind::RegionGrid,
+		TileKind::SlimeChunks,
+		TileKind::SpawnChunks,
+		TileKind::WorldBorder,
+		TileKind::Biomes,
+		TileKind::Heightmap,
+		TileKind::Contours,
+		TileKind::Terrain,
+		TileKind::Caves,
+		TileKind::Night,
+		TileKind::OceanFloor,
+		TileKind::Isometric,
+		TileKind::HeightData,
+		TileKind::BiomeData,
+		TileKind::BlockData,
+	];
+
+	/// Returns the name of the tile kind, used as its output directory name
+	pub fn name(self) -> &'static str {
+		match self {
+			TileKind::Map => "map",
+			TileKind::Lightmap => "light",
+			TileKind::Ores => "ores",
+			TileKind::Activity => "activity",
+			TileKind::Spawnable => "spawnable",
+			TileKind::ChunkGrid => "chunks",
+			TileKind::RegionGrid => "regions",
+			TileKind::SlimeChunks => "slime",
+			TileKind::SpawnChunks => "spawn",
+			TileKind::WorldBorder => "border",
+			TileKind::Biomes => "biomes",
+			TileKind::Heightmap => "height",
+			TileKind::Contours => "contours",
+			TileKind::Terrain => "terrain",
+			TileKind::Caves => "caves",
+			TileKind::Night => "night",
+			TileKind::OceanFloor => "floor",
+			TileKind::Isometric => "iso",
+			TileKind::HeightData => "heightdata",
+			TileKind::BiomeData => "biomedata",
+			TileKind::BlockData => "blockdata",
+		}
+	}
+
+	/// Returns true for alternative maps and overlays rendered together with
+	/// the regular map tiles
+	///
+	/// Like the regular map, these tiles are rendered from the processed data
+	/// of a region and its neighbors, so computations depending on neighboring
+	/// block columns are seamless across region boundaries.
+	pub fn is_rendered_with_map(self) -> bool {
+		matches!(
+			self,
+			TileKind::Contours
+				| TileKind::Terrain
+				| TileKind::Night
+				| TileKind::OceanFloor
+				| TileKind::Isometric
+		)
+	}
+
+	/// Returns true for overlays that are rendered separately for each mipmap level
+	///
+	/// Other overlays are generated from the region data and scaled down for
+	/// higher mipmap levels.
+	pub fn is_per_level(self) -> bool {
+		matches!(
+			self,
+			TileKind::ChunkGrid
+				| TileKind::RegionGrid
+				| TileKind::SlimeChunks
+				| TileKind::SpawnChunks
+				| TileKind::WorldBorder
+		)
+	}
+
+	/// Returns true for data tiles, which encode values for each block column
+	/// rather than colors
+	///
+	/// Data tiles are only generated for the full-resolution mipmap level, as
+	/// their values can't be blended.
+	pub fn is_data(self) -> bool {
+		matches!(
+			self,
+			TileKind::HeightData | TileKind::BiomeData | TileKind::BlockData
+		)
+	}
+
+	/// Returns true for overlays that are only generated for the overworld
+	pub fn is_overworld_only(self) -> bool {
+		matches!(self, TileKind::SlimeChunks | TileKind::SpawnChunks)
+	}
+}
+
+/// Inhabited time in ticks at the lower bound of the activity overlay scale
+///
+/// Chunks with a lower inhabited time are not shown on the overlay.
+pub const ACTIVITY_MIN_TICKS: i64 = 20 * 60;
+
+/// Inhabited time in ticks at the upper bound of the activity overlay scale
+pub const ACTIVITY_MAX_TICKS: i64 = 20 * 60 * 60 * 100;
+
+/// Minecraft dimension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Dimension {
+	/// The overworld
+	Overworld,
+	/// The Nether
+	Nether,
+	/// The End
+	End,
+}
+
+impl Dimension {
+	/// Returns the name of the dimension
+	pub fn name(self) -> &'static str {
+		match self {
+			Dimension::Overworld => "overworld",
+			Dimension::Nether => "nether",
+			Dimension::End => "end",
+		}
+	}
+
+	/// Returns the name of the output subdirectory of the dimension
+	///
+	/// The overworld is stored in the output directory of the world itself.
+	pub fn subdir(self) -> Option<&'static str> {
+		match self {
+			Dimension::Overworld => None,
+			other => Some(other.name()),
+		}
+	}
+
+	/// Returns the namespaced ID of the dimension in Java Edition worlds
+	pub fn id(self) -> &'static str {
+		match self {
+			Dimension::Overworld => "minecraft:overworld",
+			Dimension::Nether => "minecraft:the_nether",
+			Dimension::End => "minecraft:the_end",
+		}
+	}
+
+	/// Returns the numeric ID of the dimension in Bedrock Edition worlds
+	pub fn bedrock_id(self) -> i32 {
+		match self {
+			Dimension::Overworld => 0,
+			Dimension::Nether => 1,
+			Dimension::End => 2,
+		}
+	}
+}
+
+/// Playable area of a dimension, limited by the world border
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorldBorder {
+	/// X coordinate of the center of the world border
+	pub center_x: f64,
+	/// Z coordinate of the center of the world border
+	pub center_z: f64,
+	/// Side length of the world border
+	pub size: f64,
+}
+
+impl WorldBorder {
+	/// Side length of the world border of newly created worlds
+	const DEFAULT_SIZE: f64 = 59_999_968.0;
+
+	/// Determines the world border of a dimension from the `level.dat`
+	///
+	/// The border of the Nether is scaled down according to its coordinate
+	/// scale. Returns [None] if no border is set, or if the border still
+	/// has its default size.
+	pub fn new(level_dat: &de::LevelDatData, dimension: Dimension) -> Option<Self> {
+		let size = level_dat.border_size?;
+		if size >= Self::DEFAULT_SIZE {
+			return None;
+		}
+
+		let scale = match dimension {
+			Dimension::Nether => 8.0,
+			_ => 1.0,
+		};
+
+		Some(WorldBorder {
+			center_x: level_dat.border_center_x.unwrap_or_default() / scale,
+			center_z: level_dat.border_center_z.unwrap_or_default() / scale,
+			size: size / scale,
+		})
+	}
+
+	/// Returns the minimum and maximum X and Z block coordinates of the
+	/// playable area
+	pub fn bounds(&self) -> ((f64, f64), (f64, f64)) {
+		let radius = self.size / 2.0;
+		(
+			(self.center_x - radius, self.center_x + radius),
+			(self.center_z - radius, self.center_z + radius),
+		)
+	}
+}
+
+/// Spawn point published in the viewer metadata
+#[derive(Debug, Clone, Copy, Default)]
+pub enum SpawnPoint {
+	/// The actual spawn point of the world
+	#[default]
+	World,
+	/// No spawn point
+	Hidden,
+	/// Fixed overworld block coordinates
+	Fixed(i32, i32),
+}
+
+/// Translated viewer labels of a locale
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Labels {
+	/// Titles of the map layers, by tile kind name (`map` for the regular
+	/// map and `light` for the illumination layer)
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub layers: BTreeMap<String, String>,
+	/// Titles of the dimensions, by dimension name
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub dimensions: BTreeMap<String, String>,
+	/// Other texts of the viewer interface
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub ui: BTreeMap<String, String>,
+}
+
+/// Descriptive information about the map, shown by the viewer
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MapInfo {
+	/// Title of the map
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub title: Option<String>,
+	/// Description of the map
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub description: Option<String>,
+	/// Attribution of the map, may contain HTML
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub attribution: Option<String>,
+	/// Additional entries for custom viewers
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")]
+	pub extra: BTreeMap<String, String>,
+	/// Translated viewer labels, by locale
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")]
+	pub labels: BTreeMap<String, Labels>,
+}
+
+/// Retention policy of the snapshot archive
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SnapshotPolicy {
+	/// Maximum number of kept snapshots
+	pub keep: Option<usize>,
+	/// Maximum age of kept snapshots
+	pub max_age: Option<Duration>,
+}
+
+/// Layer of markers in the format of the squaremap and Pl3xMap plugins
+///
+/// The markers themselves are passed on to the viewer unchanged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarkerLayer {
+	/// Identifier of the layer
+	pub id: String,
+	/// Name of the layer, shown in the layer control
+	pub name: String,
+	/// Position of the layer in the layer control
+	#[serde(default)]
+	pub order: i32,
+	/// Stacking order of the layer on the map
+	#[serde(default)]
+	pub z_index: i32,
+	/// Hide the layer by default
+	#[serde(default)]
+	pub hide: bool,
+	/// Markers of the layer, like icons, polygons or circles
+	pub markers: Vec<serde_json::Value>,
+}
+
+/// Initial view of the viewer and the range of zoom levels it is limited to
+///
+/// Unset fields are left to the viewer, which centers on the world spawn by
+/// default.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewConfig {
+	/// X coordinate of the initial center
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub x: Option<i32>,
+	/// Z coordinate of the initial center
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub z: Option<i32>,
+	/// Initial zoom level
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub zoom: Option<i32>,
+	/// Lowest zoom level
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub min_zoom: Option<i32>,
+	/// Highest zoom level
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_zoom: Option<i32>,
+}
+
+impl ViewConfig {
+	/// Creates a [ViewConfig] from [command line arguments](super::Args)
+	fn new(args: &super::Args) -> Result<Self> {
+		let min_zoom = args.min_zoom.unwrap_or(i32::MIN);
+		let max_zoom = args.max_zoom.unwrap_or(i32::MAX);
+		if min_zoom > max_zoom {
+			bail!("Minimum zoom level must not be higher than maximum zoom level");
+		}
+		if args
+			.view_zoom
+			.is_some_and(|zoom| !(min_zoom..=max_zoom).contains(&zoom))
+		{
+			bail!("Initial zoom level must be between minimum and maximum zoom level");
+		}
+
+		Ok(ViewConfig {
+			x: args.view_center.map(|(x, _)| x),
+			z: args.view_center.map(|(_, z)| z),
+			zoom: args.view_zoom,
+			min_zoom: args.min_zoom,
+			max_zoom: args.max_zoom,
+		})
+	}
+
+	/// Derives the view of a dimension
+	///
+	/// The center is given in overworld coordinates; it is scaled down for
+	/// the Nether like the world border and not used for the End.
+	fn for_dimension(self, dimension: Dimension) -> Self {
+		let scale = |coord: Option<i32>| match dimension {
+			Dimension::Overworld => coord,
+			Dimension::Nether => coord.map(|coord| coord.div_euclid(8)),
+			Dimension::End => None,
+		};
+
+		ViewConfig {
+			x: scale(self.x),
+			z: scale(self.z),
+			..self
+		}
+	}
+
+	/// Returns true if no field is set
+	pub fn is_empty(&self) -> bool {
+		self.x.is_none()
+			&& self.z.is_none()
+			&& self.zoom.is_none()
+			&& self.min_zoom.is_none()
+			&& self.max_zoom.is_none()
+	}
+}
+
+/// Parses a color in `#rrggbb` notation
+fn parse_color(color: &str) -> Option<[u8; 3]> {
+	let hex = color.strip_prefix('#')?;
+	if hex.len() != 6 || !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+		return None;
+	}
+	let value = u32::from_str_radix(hex, 16).ok()?;
+	Some([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+/// Colors of the biome map
+#[derive(Debug)]
+pub struct BiomePalette(HashMap<String, [u8; 3]>);
+
+impl BiomePalette {
+	/// Loads the biome palette
+	///
+	/// The [default colors](Theme::biome_colors) of the theme can be
+	/// overridden by a JSON file mapping biome IDs to colors in `#rrggbb`
+	/// notation.
+	pub fn new(path: Option<&Path>, theme: Theme) -> Result<Self> {
+		let mut colors: HashMap<_, _> = theme
+			.biome_colors()
+			.iter()
+			.map(|&(id, Color(color))| (id.to_string(), color))
+			.collect();
+
+		let Some(path) = path else {
+			return Ok(BiomePalette(colors));
+		};
+
+		let data = std::fs::read_to_string(path)
+			.with_context(|| format!("Failed to read {}", path.display()))?;
+		let overrides: HashMap<String, String> = serde_json::from_str(&data)
+			.with_context(|| format!("Failed to parse {}", path.display()))?;
+
+		for (id, color) in overrides {
+			let color = parse_color(&color)
+				.with_context(|| format!("Invalid color '{}' for biome '{}'", color, id))?;
+			let id = id.strip_prefix("minecraft:").unwrap_or(&id).to_string();
+			colors.insert(id, color);
+		}
+
+		Ok(BiomePalette(colors))
+	}
+
+	/// Returns the color of a biome
+	///
+	/// The ID must not include the `minecraft:` namespace.
+	pub fn color(&self, id: &str) -> Option<[u8; 3]> {
+		self.0.get(id).copied()
+	}
+}
+
+/// Entry of a biome override file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BiomeOverride {
+	/// Grass color in `#rrggbb` notation
+	grass: Option<String>,
+	/// Foliage color in `#rrggbb` notation
+	foliage: Option<String>,
+	/// Water color in `#rrggbb` notation
+	water: Option<String>,
+	/// Temperature of the biome
+	temperature: Option<f32>,
+	/// Downfall of the biome
+	downfall: Option<f32>,
+}
+
+/// Converts a biome definition of a datapack into a [Biome]
+fn worldgen_biome(biome: &de::WorldgenBiome, plains: Biome) -> Biome {
+	let effects = &biome.effects;
+	let color = |color: &Option<de::WorldgenColor>| color.as_ref()?.rgb().map(Color);
+
+	Biome {
+		water_color: color(&effects.water_color),
+		foliage_color: color(&effects.foliage_color),
+		grass_color: color(&effects.grass_color),
+		grass_color_modifier: match effects.grass_color_modifier.as_deref() {
+			Some("dark_forest") => Some(BiomeGrassColorModifier::DarkForest),
+			Some("swamp") => Some(BiomeGrassColorModifier::Swamp),
+			_ => None,
+		},
+		..plains.with_climate(biome.temperature, biome.downfall)
+	}
+}
+
+/// Loads the biome types, adding the biomes of the datapacks of a world and
+/// applying the overrides of an optional JSON file
+///
+/// The file maps biome IDs to objects with optional `grass`, `foliage` and
+/// `water` colors in `#rrggbb` notation and `temperature` and `downfall`
+/// values. Unknown biomes, for example from mods, are added based on the
+/// plains biome.
+pub fn load_biome_types(
+	datapack_biomes: &[(String, de::WorldgenBiome)],
+	path: Option<&Path>,
+) -> Result<BiomeTypes> {
+	let mut biome_types = BiomeTypes::default();
+
+	let plains = *biome_types
+		.get("minecraft:plains")
+		.expect("plains biome must exist");
+
+	for (id, biome) in datapack_biomes {
+		biome_types.insert(id, worldgen_biome(biome, plains));
+	}
+
+	let Some(path) = path else {
+		return Ok(biome_types);
+	};
+
+	let overrides: BTreeMap<String, BiomeOverride> = read_json(path)?;
+
+	for (id, entry) in overrides {
+		let parse = |color: &Option<String>| {
+			color
+				.as_deref()
+				.map(|color| {
+					parse_color(color)
+						.map(Color)
+						.with_context(|| format!("Invalid color '{}' for biome '{}'", color, id))
+				})
+				.transpose()
+		};
+
+		let mut biome = biome_types.get(&id).copied().unwrap_or(plains);
+		if entry.temperature.is_some() || entry.downfall.is_some() {
+			biome = biome.with_climate(
+				entry.temperature.unwrap_or(biome.temp()),
+				entry.downfall.unwrap_or(biome.downfall()),
+			);
+		}
+		if let Some(grass) = parse(&entry.grass)? {
+			biome.grass_color = Some(grass);
+			biome.grass_color_modifier = None;
+		}
+		if let Some(foliage) = parse(&entry.foliage)? {
+			biome.foliage_color = Some(foliage);
+		}
+		if let Some(water) = parse(&entry.water)? {
+			biome.water_color = Some(water);
+		}
+
+		biome_types.insert(&id, biome);
+	}
+
+	Ok(biome_types)
+}
+
+/// Entry of a block override file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockOverride {
+	/// Block color in `#rrggbb` notation
+	color: Option<String>,
+	/// Whether the block is visible on the map
+	opaque: Option<bool>,
+	/// Whether the block is colored using biome grass colors
+	grass: Option<bool>,
+	/// Whether the block is colored using biome foliage colors
+	foliage: Option<bool>,
+	/// Whether the block is colored as birch foliage
+	birch: Option<bool>,
+	/// Whether the block is colored as spruce foliage
+	spruce: Option<bool>,
+	/// Whether the block is colored using biome water colors
+	water: Option<bool>,
+}
+
+/// Block definition pack, usually describing the blocks of a mod
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockPack {
+	/// Namespace of block IDs given without a namespace
+	namespace: String,
+	/// Block definitions in the format of a block override file
+	blocks: BTreeMap<String, BlockOverride>,
+}
+
+/// Computes the [SettingsHash] of the settings of a generation step
+///
+/// The settings are hashed in their debug representation, so they must not
+/// contain hash maps or sets, whose iteration order is random.
+fn settings_hash(settings: &impl Debug) -> SettingsHash {
+	let mut hasher = rustc_hash::FxHasher::default();
+	format!("{:?}", settings).hash(&mut hasher);
+	SettingsHash(hasher.finish())
+}
+
+/// Reads and parses a JSON file
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+	let data = std::fs::read_to_string(path)
+		.with_context(|| format!("Failed to read {}", path.display()))?;
+	serde_json::from_str(&data).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Returns the files of a block pack path
+///
+/// For directories, all JSON files in the directory are returned in
+/// alphabetical order.
+fn block_pack_files(path: &Path) -> Result<Vec<PathBuf>> {
+	if !path.is_dir() {
+		return Ok(vec![path.to_path_buf()]);
+	}
+
+	let mut files = Vec::new();
+	for entry in std::fs::read_dir(path)
+		.with_context(|| format!("Failed to read directory {}", path.display()))?
+	{
+		let file = entry?.path();
+		if file.extension() == Some(OsStr::new("json")) {
+			files.push(file);
+		}
+	}
+	files.sort();
+
+	Ok(files)
+}
+
+/// Loads the block types, applying block packs and the overrides of an
+/// optional JSON file
+///
+/// The override file maps block IDs to objects with an optional `color` in
+/// `#rrggbb` notation and optional `opaque`, `grass`, `foliage`, `birch`,
+/// `spruce` and `water` flags. Unknown blocks, for example from new Minecraft
+/// versions or mods, are added as opaque blocks and must specify a color.
+///
+/// Block packs contain a `namespace` and `blocks` in the same format, with
+/// the namespace added to all block IDs given without one. Packs are applied
+/// in order before the override file, so later definitions take precedence.
+///
+/// Finally, the alias file maps block IDs to the IDs of the block types to
+/// use for them, which may be defined by the packs or the override file.
+pub fn load_block_types(
+	packs: &[PathBuf],
+	path: Option<&Path>,
+	aliases: Option<&Path>,
+) -> Result<BlockTypes> {
+	let mut block_types = BlockTypes::default();
+
+	for pack in packs {
+		for file in block_pack_files(pack)? {
+			let BlockPack { namespace, blocks } = read_json(&file)?;
+			let blocks = blocks.into_iter().map(|(id, entry)| {
+				if id.contains(':') {
+					(id, entry)
+				} else {
+					(format!("{}:{}", namespace, id), entry)
+				}
+			});
+			apply_block_overrides(&mut block_types, blocks)
+				.with_context(|| format!("Failed to load block pack {}", file.display()))?;
+		}
+	}
+
+	if let Some(path) = path {
+		let overrides: BTreeMap<String, BlockOverride> = read_json(path)?;
+		apply_block_overrides(&mut block_types, overrides)?;
+	}
+
+	if let Some(aliases) = aliases {
+		let aliases: BTreeMap<String, String> = read_json(aliases)?;
+		for (id, target) in aliases {
+			let block_type = block_types.get(&target).with_context(|| {
+				format!("Unknown block '{}' used as alias for '{}'", target, id)
+			})?;
+			block_types.insert(&id, block_type);
+		}
+	}
+
+	Ok(block_types)
+}
+
+/// Adds or replaces block types with the entries of a block override file
+fn apply_block_overrides(
+	block_types: &mut BlockTypes,
+	overrides: impl IntoIterator<Item = (String, BlockOverride)>,
+) -> Result<()> {
+	for (id, entry) in overrides {
+		let color = entry
+			.color
+			.as_deref()
+			.map(|color| {
+				parse_color(color)
+					.map(Color)
+					.with_context(|| format!("Invalid color '{}' for block '{}'", color, id))
+			})
+			.transpose()?;
+
+		let mut block_type = match (block_types.get(&id), color) {
+			(Some(block_type), _) => block_type,
+			(None, Some(color)) => BlockType {
+				flags: BlockFlag::Opaque.into(),
+				color,
+			},
+			(None, None) => bail!("Missing color for unknown block '{}'", id),
+		};
+		if let Some(color) = color {
+			block_type.color = color;
+		}
+
+		for (flag, value) in [
+			(BlockFlag::Opaque, entry.opaque),
+			(BlockFlag::Grass, entry.grass),
+			(BlockFlag::Foliage, entry.foliage),
+			(BlockFlag::Birch, entry.birch),
+			(BlockFlag::Spruce, entry.spruce),
+			(BlockFlag::Water, entry.water),
+		] {
+			if let Some(value) = value {
+				block_type.flags.set(flag, value);
+			}
+		}
+
+		block_types.insert(&id, block_type);
+	}
+
+	Ok(())
+}
+
+/// Interpolates a color gradient at a given position
+///
+/// The stops must be sorted by their position. Positions outside of the
+/// range of the stops are clamped.
+pub fn gradient(stops: &[(f32, Vec3)], pos: f32) -> Vec3 {
+	let Some(index) = stops.iter().position(|&(stop, _)| pos < stop) else {
+		return stops[stops.len() - 1].1;
+	};
+	if index == 0 {
+		return stops[0].1;
+	}
+
+	let (pos0, color0) = stops[index - 1];
+	let (pos1, color1) = stops[index];
+	color0.lerp(color1, (pos - pos0) / (pos1 - pos0))
+}
+
+/// Coloring of water by the depth of the water column
+#[derive(Debug, Clone, Copy)]
+pub struct WaterShading {
+	/// Color of shallow water
+	pub shallow: Vec3,
+	/// Color of deep water
+	pub deep: Vec3,
+	/// Water depth in blocks at which the deep color is reached
+	pub depth: f32,
+	/// Water depth in blocks up to which the ground below is visible
+	pub floor_cutoff: Option<f32>,
+}
+
+impl WaterShading {
+	/// Constructs a new [WaterShading] from command line arguments
+	///
+	/// The colors are passed in `#rrggbb` notation.
+	pub fn new(colors: &[String], depth: i32, floor_cutoff: Option<i32>) -> Result<Self> {
+		let [shallow, deep] = colors else {
+			bail!("Expected two water colors, got {}", colors.len());
+		};
+		let parse = |color: &str| {
+			parse_color(color)
+				.map(|[r, g, b]| Vec3::new(r.into(), g.into(), b.into()))
+				.with_context(|| format!("Invalid water color '{}'", color))
+		};
+
+		Ok(WaterShading {
+			shallow: parse(shallow)?,
+			deep: parse(deep)?,
+			depth: depth as f32,
+			floor_cutoff: floor_cutoff.map(|cutoff| cutoff as f32),
+		})
+	}
+
+	/// Returns the color of a water column of the given depth
+	pub fn color(&self, depth: f32) -> Vec3 {
+		self.shallow
+			.lerp(self.deep, (depth / self.depth).clamp(0.0, 1.0))
+	}
+}
+
+/// Adjustment of the final colors of the map
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorAdjustment {
+	/// Gamma correction exponent
+	pub gamma: f32,
+	/// Brightness factor
+	pub brightness: f32,
+	/// Contrast factor
+	pub contrast: f32,
+	/// Saturation factor
+	pub saturation: f32,
+}
+
+impl ColorAdjustment {
+	/// Constructs a new [ColorAdjustment] from command line arguments
+	///
+	/// Brightness, contrast and saturation are passed as percentages.
+	pub fn new(gamma: f32, brightness: i32, contrast: i32, saturation: i32) -> Result<Self> {
+		if !(gamma.is_finite() && gamma > 0.0) {
+			bail!("Invalid gamma {}, expected a positive number", gamma);
+		}
+
+		Ok(ColorAdjustment {
+			gamma,
+			brightness: brightness as f32 / 100.0,
+			contrast: contrast as f32 / 100.0,
+			saturation: saturation as f32 / 100.0,
+		})
+	}
+
+	/// Combines two adjustments by multiplying their factors
+	pub fn combine(self, other: ColorAdjustment) -> Self {
+		ColorAdjustment {
+			gamma: self.gamma * other.gamma,
+			brightness: self.brightness * other.brightness,
+			contrast: self.contrast * other.contrast,
+			saturation: self.saturation * other.saturation,
+		}
+	}
+
+	/// Returns true if the adjustment leaves all colors unchanged
+	pub fn is_identity(&self) -> bool {
+		*self
+			== ColorAdjustment {
+				gamma: 1.0,
+				brightness: 1.0,
+				contrast: 1.0,
+				saturation: 1.0,
+			}
+	}
+
+	/// Applies the adjustment to a color with components in the range 0..255
+	///
+	/// Saturation, contrast and brightness are applied in this order,
+	/// followed by the gamma correction.
+	pub fn apply(&self, color: Vec3) -> Vec3 {
+		/// Relative luminance of the RGB components
+		const LUMA: Vec3 = Vec3::new(0.2126, 0.7152, 0.0722);
+
+		let color = color / 255.0;
+		let luma = Vec3::splat(color.dot(LUMA));
+		let color = luma.lerp(color, self.saturation);
+		let color = (color - 0.5) * self.contrast + 0.5;
+		let color = (color * self.brightness).clamp(Vec3::ZERO, Vec3::ONE);
+
+		255.0 * color.powf(1.0 / self.gamma)
+	}
+}
+
+/// Built-in color theme of the map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Theme {
+	/// The default colors
+	Default,
+	/// Desaturated, softer colors
+	Muted,
+	/// Shades of gray
+	Grayscale,
+	/// Stronger contrast and saturation
+	HighContrast,
+	/// Colorblind-friendly biome map palette
+	Colorblind,
+}
+
+impl Theme {
+	/// Returns the name of the theme
+	pub fn name(self) -> &'static str {
+		match self {
+			Theme::Default => "default",
+			Theme::Muted => "muted",
+			Theme::Grayscale => "grayscale",
+			Theme::HighContrast => "high-contrast",
+			Theme::Colorblind => "colorblind",
+		}
+	}
+
+	/// Returns the adjustment of the map colors of the theme
+	pub fn adjustment(self) -> ColorAdjustment {
+		let (contrast, saturation) = match self {
+			Theme::Default | Theme::Colorblind => (1.0, 1.0),
+			Theme::Muted => (0.85, 0.6),
+			Theme::Grayscale => (1.0, 0.0),
+			Theme::HighContrast => (1.35, 1.25),
+		};
+
+		ColorAdjustment {
+			gamma: 1.0,
+			brightness: 1.0,
+			contrast,
+			saturation,
+		}
+	}
+
+	/// Returns the default colors of the biome map of the theme
+	pub fn biome_colors(self) -> &'static [(&'static str, Color)] {
+		match self {
+			Theme::Colorblind => COLORBLIND_BIOME_COLORS,
+			_ => BIOME_COLORS,
+		}
+	}
+}
+
+/// Directional lighting of the ground, computed from its slope
+#[derive(Debug, Clone, Copy)]
+pub struct Hillshade {
+	/// Unit vector pointing towards the sun
+	///
+	/// The X and Y components point east and south, the Z component points up.
+	light: Vec3,
+}
+
+impl Hillshade {
+	/// Constructs a new [Hillshade] from the position of the sun
+	///
+	/// The azimuth is given in degrees clockwise from north, the elevation
+	/// in degrees above the horizon, which must be greater than zero.
+	pub fn new(azimuth: f32, elevation: f32) -> Self {
+		let (azimuth, elevation) = (azimuth.to_radians(), elevation.to_radians());
+		Hillshade {
+			light: Vec3::new(
+				elevation.cos() * azimuth.sin(),
+				-elevation.cos() * azimuth.cos(),
+				elevation.sin(),
+			),
+		}
+	}
+
+	/// Returns the unit vector pointing towards the sun
+	///
+	/// The X and Y components point east and south, the Z component points up.
+	pub fn light(&self) -> Vec3 {
+		self.light
+	}
+
+	/// Returns the brightness factor of ground with the given height gradient
+	///
+	/// *dx* and *dz* are the height differences per block towards east and
+	/// south. The hillshading is blended with the unshaded color, so flat
+	/// ground keeps its brightness of 1.
+	pub fn shade(&self, dx: f32, dz: f32) -> f32 {
+		let normal = Vec3::new(-dx, -dz, 1.0).normalize();
+		0.4 + 0.6 * normal.dot(self.light).max(0.0) / self.light.z
+	}
+}
+
+/// Storage format of the regions of a [RegionSource]
+#[derive(Clone)]
+pub enum RegionFormat {
+	/// Java Edition region files
+	Anvil,
+	/// Pre-Anvil (Minecraft Beta 1.3 to 1.1) region files
+	McRegion,
+	/// LinearRegionFormat files of some Paper forks
+	Linear,
+	/// Bedrock Edition world database and dimension ID
+	Bedrock(Arc<bedrock::World>, i32),
+	/// Cube index of a world saved by the Cubic Chunks mod
+	Cubic(Arc<cubic::World>),
+}
+
+/// Source of region files for a dimension
+#[derive(Clone)]
+pub struct RegionSource {
+	/// Source of the Minecraft save data
+	pub input: Arc<dyn InputSource>,
+	/// Path of input region directory, relative to the [InputSource]
+	pub region_dir: PathBuf,
+	/// Offset added to the region coordinates of the source
+	pub offset: TileCoords,
+	/// Storage format of the regions
+	pub format: RegionFormat,
+}
+
+impl RegionFormat {
+	/// Determines the region format of a dimension of a [WorldLayout]
+	fn new(layout: &WorldLayout, dimension: &DimensionLayout) -> Self {
+		if let Some(world) = &dimension.cubic {
+			return RegionFormat::Cubic(world.clone());
+		}
+		match &layout.bedrock {
+			Some(world) => RegionFormat::Bedrock(world.clone(), dimension.dimension.bedrock_id()),
+			None => match dimension.extension {
+				"mcr" => RegionFormat::McRegion,
+				"linear" => RegionFormat::Linear,
+				_ => RegionFormat::Anvil,
+			},
+		}
+	}
+
+	/// Returns the file extension of region files
+	fn extension(&self) -> &'static str {
+		match self {
+			RegionFormat::McRegion => "mcr",
+			RegionFormat::Linear => "linear",
+			_ => "mca",
+		}
+	}
+}
+
+impl RegionSource {
+	/// Creates a [RegionSource] for a dimension of a [WorldLayout]
+	pub fn new(
+		input: Arc<dyn InputSource>,
+		layout: &WorldLayout,
+		dimension: &DimensionLayout,
+		offset: TileCoords,
+	) -> Self {
+		RegionSource {
+			input,
+			region_dir: dimension.region_dir.clone(),
+			offset,
+			format: RegionFormat::new(layout, dimension),
+		}
+	}
+
+	/// Constructs the path to an input region file, relative to the [InputSource]
+	///
+	/// *coords* are the region coordinates in the source, without offset.
+	pub fn region_path(&self, coords: TileCoords) -> PathBuf {
+		let filename = coord_filename(coords, self.format.extension());
+		[&self.region_dir, Path::new(&filename)].iter().collect()
+	}
+
+	/// Returns the coordinates of all regions of the source, without offset
+	pub fn list_regions(&self) -> Result<Vec<TileCoords>> {
+		Ok(match &self.format {
+			RegionFormat::Anvil | RegionFormat::McRegion | RegionFormat::Linear => self
+				.input
+				.list_files(&self.region_dir)?
+				.iter()
+				.filter_map(|file_name| {
+					parse_coord_filename(file_name.as_ref(), self.format.extension())
+				})
+				.collect(),
+			RegionFormat::Bedrock(world, dimension) => world
+				.regions(*dimension)
+				.map(|(x, z)| TileCoords { x, z })
+				.collect(),
+			RegionFormat::Cubic(world) => {
+				world.regions().map(|(x, z)| TileCoords { x, z }).collect()
+			}
+		})
+	}
+
+	/// Returns the time of the last modification of a region
+	pub fn modified(&self, coords: TileCoords) -> Result<SystemTime> {
+		match &self.format {
+			RegionFormat::Anvil | RegionFormat::McRegion | RegionFormat::Linear => {
+				self.input.modified(&self.region_path(coords))
+			}
+			RegionFormat::Bedrock(world, dimension) => world
+				.region_modified(*dimension, coords.x, coords.z)
+				.with_context(|| format!("Region {:?} not found", coords)),
+			RegionFormat::Cubic(world) => world.region_modified(coords.x, coords.z),
+		}
+	}
+}
+
+/// Additional world merged into the map
+#[derive(Clone)]
+pub struct MergeInput {
+	/// Source of the Minecraft save data
+	pub input: Arc<dyn InputSource>,
+	/// Dimensions of the world
+	pub layout: WorldLayout,
+	/// Offset added to the region coordinates of the world
+	pub offset: TileCoords,
+}
+
+impl MergeInput {
+	/// Opens a world given as `PATH` or `PATH@X,Z`
+	///
+	/// *X* and *Z* are block coordinates, which must be multiples of the
+	/// region size, as worlds are merged region by region. Of multiple
+	/// worlds in the input, the first is used.
+	fn new(spec: &str, num_threads: usize) -> Result<Self> {
+		/// Width/height of a region in blocks
+		const REGION_BLOCKS: i32 = (BLOCKS_PER_CHUNK * CHUNKS_PER_REGION) as i32;
+
+		let parse_offset = |offset: &str| -> Option<(i32, i32)> {
+			let (x, z) = offset.split_once(',')?;
+			Some((x.trim().parse().ok()?, z.trim().parse().ok()?))
+		};
+		let (path, (x, z)) = match spec.rsplit_once('@') {
+			Some((path, offset)) if parse_offset(offset).is_some() => {
+				(path, parse_offset(offset).unwrap())
+			}
+			_ => (spec, (0, 0)),
+		};
+		if x % REGION_BLOCKS != 0 || z % REGION_BLOCKS != 0 {
+			bail!(
+				"Offset of merged world {} must be a multiple of {} blocks",
+				path,
+				REGION_BLOCKS
+			);
+		}
+
+		let input: Arc<dyn InputSource> = input::open(Path::new(path), num_threads)?.into();
+		let layout = layout::discover(&input)?
+			.into_iter()
+			.next()
+			.expect("discover must return at least one world");
+
+		Ok(MergeInput {
+			input,
+			layout,
+			offset: TileCoords {
+				x: x / REGION_BLOCKS,
+				z: z / REGION_BLOCKS,
+			},
+		})
+	}
 }
 
 /// Common configuration based on command line arguments
+///
+/// A separate [Config] is derived for each rendered dimension using
+/// [Config::for_dimension].
+#[derive(Clone)]
 pub struct Config {
 	/// Number of threads for parallel processing
 	pub num_threads: usize,
-	/// Path of input region directory
-	pub region_dir: PathBuf,
-	/// Path of input `level.dat` file
+	/// Source of the Minecraft save data
+	pub input: Arc<dyn InputSource>,
+	/// Additional worlds merged into the map
+	pub merge_inputs: Vec<MergeInput>,
+	/// Sources of region files of the current dimension, in order of priority
+	pub region_sources: Vec<RegionSource>,
+	/// Path of input `level.dat` file, relative to the [InputSource]
 	pub level_dat_path: PathBuf,
-	/// Base path for storage of rendered tile data
+	/// Height range of the current dimension, if known
+	pub height: Option<de::DimensionType>,
+	/// MinedMap data directory
+	pub data_dir: PathBuf,
+	/// Base path for storage of rendered tile data of the current dimension
 	pub output_dir: PathBuf,
 	/// Path for storage of intermediate processed data files
 	pub processed_dir: PathBuf,
 	/// Path of viewer metadata file
 	pub metadata_path: PathBuf,
+	/// Destination for generated viewer files
+	pub output_target: Arc<dyn OutputTarget>,
+	/// Path to write the list of modified viewer files to
+	pub change_list_path: Option<PathBuf>,
+	/// Modified viewer files of the current run
+	pub changes: Arc<ChangeList>,
+	/// Path to write the report of skipped corrupt chunks to
+	///
+	/// Chunks that fail to load are only skipped when this is set.
+	pub skip_corrupt_path: Option<PathBuf>,
+	/// Chunks skipped in the current run
+	pub corrupt_chunks: Arc<CorruptChunkList>,
+	/// Path of the report of unknown block types, if requested
+	pub unknown_blocks_path: Option<PathBuf>,
+	/// Unknown block IDs found in all processed regions
+	pub unknown_blocks: Arc<UnknownBlockList>,
+	/// Strategy for resolving chunks with overlapping data in region files
+	pub overlap_resolution: OverlapResolution,
+	/// Dimension to render
+	pub dimension: Dimension,
+	/// All rendered dimensions of the current world
+	pub dimensions: Vec<Dimension>,
+	/// Overlay tile kinds generated in addition to the map and lightmap
+	pub overlays: Vec<TileKind>,
+	/// Block types counted for the ore density overlay
+	pub ore_filter: Arc<BlockFilter>,
+	/// Colors of the biome map
+	pub biome_palette: Arc<BiomePalette>,
+	/// Biome types used to process the world, including overrides
+	pub biome_types: Arc<BiomeTypes>,
+	/// Path of the biome override file
+	pub biome_overrides_path: Option<PathBuf>,
+	/// Biome IDs referenced by the biome data tiles
+	pub biome_data_palette: Arc<DataPalette>,
+	/// Block IDs referenced by the block data tiles
+	pub block_data_palette: Arc<DataPalette>,
+	/// Block types used to process the world, including overrides
+	pub block_types: Arc<BlockTypes>,
+	/// Color theme of the map
+	pub theme: Theme,
+	/// Vertical distance of contour lines in blocks
+	pub contour_interval: i32,
+	/// Highest Y coordinate considered for the map
+	pub max_y: Option<i32>,
+	/// Hillshading of the terrain map, and of the regular map if enabled
+	pub sun: Hillshade,
+	/// Radius of the biome color blending
+	pub biome_blend: u8,
+	/// Shade the regular map by slope instead of height
+	pub hillshade: bool,
+	/// Strength of the ambient occlusion of the regular map, if enabled
+	pub ambient_occlusion: Option<f32>,
+	/// Cast shadows on the regular map
+	pub shadows: bool,
+	/// Depth-based coloring of water, if enabled
+	pub water_shading: Option<WaterShading>,
+	/// Adjustment of the final map colors, if any
+	pub color_adjustment: Option<ColorAdjustment>,
+	/// Block types treated like air for the map
+	pub hide_filter: Option<Arc<BlockFilter>>,
+	/// Opacity of leaves blended with the ground below them, if enabled
+	pub canopy_opacity: Option<f32>,
+	/// Block types blended with the ground below them
+	pub canopy_filter: Option<Arc<BlockFilter>>,
+	/// Block types tinting the blocks below them
+	pub translucent_filter: Option<Arc<BlockFilter>>,
+	/// Initial view and zoom limits of the viewer
+	pub view: ViewConfig,
+	/// Spawn point published in the viewer metadata
+	pub spawn: SpawnPoint,
+	/// Include the world seed in the viewer metadata
+	pub publish_seed: bool,
+	/// Additionally store the tiles in the layout of Minecraft Overviewer
+	pub overviewer_layout: bool,
+	/// Title, description and other information about the map
+	pub info: Arc<MapInfo>,
+	/// Marker layers to show in the viewer, with their dimensions
+	pub marker_layers: Arc<Vec<(Dimension, MarkerLayer)>>,
+	/// Retention policy of the snapshot archive, if enabled
+	pub snapshots: Option<SnapshotPolicy>,
+	/// Hash of the settings affecting the processed region data and the
+	/// tiles generated from it by the [RegionProcessor](super::region_processor::RegionProcessor)
+	pub processing_settings: SettingsHash,
+	/// Hash of the settings affecting the tiles rendered by the
+	/// [TileRenderer](super::tile_renderer::TileRenderer)
+	pub render_settings: SettingsHash,
 }
 
 impl Config {
 	/// Crates a new [Config] from [command line arguments](super::Args)
-	pub fn new(args: &super::Args) -> Self {
+	pub fn new(args: &super::Args) -> Result<Self> {
 		let num_threads = match args.jobs {
 			Some(0) => num_cpus::get(),
 			Some(threads) => threads,
 			None => 1,
 		};
 
-		let region_dir = [&args.input_dir, Path::new("region")].iter().collect();
-		let level_dat_path = [&args.input_dir, Path::new("level.dat")].iter().collect();
-		let processed_dir = [&args.output_dir, Path::new("processed")].iter().collect();
-		let metadata_path = [&args.output_dir, Path::new("info.json")].iter().collect();
+		let (Some(input_dir), Some(output_dir)) = (&args.input_dir, &args.output_dir) else {
+			bail!("Input and output directories are required");
+		};
+
+		let input = input::open(input_dir, num_threads)?.into();
+		let merge_inputs = args
+			.merge
+			.iter()
+			.map(|spec| MergeInput::new(spec, num_threads))
+			.collect::<Result<_>>()?;
+		let level_dat_path = PathBuf::from("level.dat");
+		let output_dir = Self::data_dir(args, output_dir);
+		let processed_dir = output_dir.join("processed");
+		let metadata_path = output_dir.join("info.json");
+		let biome_palette = BiomePalette::new(args.biome_colors.as_deref(), args.theme)?;
+		let processing_settings = Self::processing_settings(args, &biome_palette)?;
 
-		Config {
+		let mut config = Config {
 			num_threads,
-			region_dir,
+			input,
+			merge_inputs,
+			region_sources: Vec::new(),
 			level_dat_path,
-			output_dir: args.output_dir.clone(),
+			height: None,
+			data_dir: output_dir.clone(),
+			output_dir: output_dir.clone(),
 			processed_dir,
 			metadata_path,
+			output_target: Self::output_target(args)?.into(),
+			change_list_path: args.change_list.clone(),
+			changes: Default::default(),
+			skip_corrupt_path: args.skip_corrupt.clone(),
+			corrupt_chunks: Default::default(),
+			unknown_blocks_path: args.unknown_blocks.clone(),
+			unknown_blocks: Default::default(),
+			overlap_resolution: if args.prefer_newer_chunks {
+				OverlapResolution::NewestTimestamp
+			} else {
+				OverlapResolution::LowestOffset
+			},
+			dimension: Dimension::Overworld,
+			dimensions: vec![Dimension::Overworld],
+			overlays: [
+				(args.ore_overlay, TileKind::Ores),
+				(args.activity_overlay, TileKind::Activity),
+				(args.spawnable_overlay, TileKind::Spawnable),
+				(args.chunk_grid, TileKind::ChunkGrid),
+				(args.region_grid, TileKind::RegionGrid),
+				(args.slime_chunks, TileKind::SlimeChunks),
+				(args.spawn_chunks, TileKind::SpawnChunks),
+				(args.world_border, TileKind::WorldBorder),
+				(args.biome_map, TileKind::Biomes),
+				(args.heightmap, TileKind::Heightmap),
+				(args.contours, TileKind::Contours),
+				(args.terrain_map, TileKind::Terrain),
+				(args.cave_map, TileKind::Caves),
+				(args.night_map, TileKind::Night),
+				(args.ocean_floor_map, TileKind::OceanFloor),
+				(args.isometric_map, TileKind::Isometric),
+				(args.height_data, TileKind::HeightData),
+				(args.biome_data, TileKind::BiomeData),
+				(args.block_data, TileKind::BlockData),
+			]
+			.into_iter()
+			.filter_map(|(enabled, kind)| enabled.then_some(kind))
+			.collect(),
+			ore_filter: Arc::new(BlockFilter::new(&args.ore_blocks)),
+			biome_palette: Arc::new(biome_palette),
+			biome_types: Arc::new(load_biome_types(&[], args.biome_overrides.as_deref())?),
+			biome_overrides_path: args.biome_overrides.clone(),
+			biome_data_palette: Default::default(),
+			block_data_palette: Default::default(),
+			block_types: Arc::new(load_block_types(
+				&args.block_packs,
+				args.block_overrides.as_deref(),
+				args.block_aliases.as_deref(),
+			)?),
+			theme: args.theme,
+			contour_interval: args.contour_interval,
+			max_y: args.max_y,
+			sun: Hillshade::new(args.sun_azimuth as f32, args.sun_elevation as f32),
+			biome_blend: args.biome_blend,
+			hillshade: args.hillshade,
+			ambient_occlusion: args.ambient_occlusion.map(|percent| percent as f32 / 100.0),
+			shadows: args.shadows,
+			water_shading: args
+				.water_shading
+				.then(|| {
+					WaterShading::new(
+						&args.water_colors,
+						args.water_depth,
+						args.water_floor_cutoff,
+					)
+				})
+				.transpose()?,
+			color_adjustment: Some(ColorAdjustment::new(
+				args.gamma,
+				args.brightness,
+				args.contrast,
+				args.saturation,
+			)?)
+			.map(|adjustment| args.theme.adjustment().combine(adjustment))
+			.filter(|adjustment| !adjustment.is_identity()),
+			hide_filter: Self::hide_filter(args),
+			canopy_opacity: args.canopy_opacity.map(|percent| percent as f32 / 100.0),
+			canopy_filter: args
+				.canopy_opacity
+				.map(|_| Arc::new(BlockFilter::new(LEAF_BLOCKS))),
+			translucent_filter: args
+				.translucent
+				.then(|| Arc::new(BlockFilter::new(Self::translucent_blocks(args)))),
+			view: ViewConfig::new(args)?,
+			spawn: match args.spawn {
+				_ if args.hide_spawn => SpawnPoint::Hidden,
+				Some((x, z)) => SpawnPoint::Fixed(x, z),
+				None => SpawnPoint::World,
+			},
+			publish_seed: args.publish_seed,
+			overviewer_layout: args.overviewer_layout,
+			info: Arc::new(MapInfo {
+				title: args.title.clone(),
+				description: args.description.clone(),
+				attribution: args.attribution.clone(),
+				extra: args.extra.iter().cloned().collect(),
+				labels: args
+					.labels
+					.as_deref()
+					.map(read_json)
+					.transpose()?
+					.unwrap_or_default(),
+			}),
+			marker_layers: Arc::new(Self::marker_layers(args)?),
+			snapshots: args.snapshots.then_some(SnapshotPolicy {
+				keep: args.keep_snapshots.map(|keep| keep as usize),
+				max_age: args
+					.snapshot_max_age
+					.map(|days| Duration::from_secs(days.saturating_mul(24 * 60 * 60))),
+			}),
+			processing_settings,
+			render_settings: SettingsHash::default(),
+		};
+		config.render_settings = config.render_settings();
+
+		Ok(config)
+	}
+
+	/// Computes the hash of the settings affecting the processed region data
+	///
+	/// Besides the command line arguments, the contents of the block and
+	/// biome definition files are included, so editing these files causes
+	/// all regions to be processed again.
+	fn processing_settings(
+		args: &super::Args,
+		biome_palette: &BiomePalette,
+	) -> Result<SettingsHash> {
+		let mut definition_files = Vec::new();
+		for pack in &args.block_packs {
+			definition_files.extend(block_pack_files(pack)?);
 		}
+		definition_files.extend(
+			[
+				&args.block_overrides,
+				&args.block_aliases,
+				&args.biome_overrides,
+			]
+			.into_iter()
+			.flatten()
+			.cloned(),
+		);
+		let definitions: Vec<_> = definition_files
+			.iter()
+			.map(|path| {
+				std::fs::read_to_string(path)
+					.with_context(|| format!("Failed to read {}", path.display()))
+			})
+			.collect::<Result<_>>()?;
+
+		let hidden_blocks: BTreeSet<_> = Self::hidden_blocks(args).into_iter().collect();
+		let translucent_blocks: Option<BTreeSet<_>> = args
+			.translucent
+			.then(|| Self::translucent_blocks(args).into_iter().collect());
+		let ore_blocks: BTreeSet<_> = args.ore_blocks.iter().collect();
+		// The biome map is the only region tile depending on the biome colors
+		let biome_colors: Option<BTreeMap<_, _>> =
+			args.biome_map.then(|| biome_palette.0.iter().collect());
+
+		Ok(settings_hash(&(
+			args.max_y,
+			hidden_blocks,
+			args.canopy_opacity.is_some(),
+			translucent_blocks,
+			ore_blocks,
+			args.prefer_newer_chunks,
+			biome_colors,
+			definitions,
+			// The order of the merged worlds determines their precedence
+			&args.merge,
+		)))
 	}
 
-	/// Constructs the path to an input region file
-	pub fn region_path(&self, coords: TileCoords) -> PathBuf {
-		let filename = coord_filename(coords, "mca");
-		[&self.region_dir, Path::new(&filename)].iter().collect()
+	/// Computes the hash of the settings affecting the rendered map tiles
+	///
+	/// The theme is covered by the color adjustment.
+	fn render_settings(&self) -> SettingsHash {
+		settings_hash(&(
+			self.color_adjustment,
+			self.sun,
+			self.biome_blend,
+			self.hillshade,
+			self.ambient_occlusion,
+			self.shadows,
+			self.water_shading,
+			self.canopy_opacity,
+			self.contour_interval,
+		))
+	}
+
+	/// Builds the filter of block types hidden from the map
+	fn hide_filter(args: &super::Args) -> Option<Arc<BlockFilter>> {
+		let blocks = Self::hidden_blocks(args);
+		(!blocks.is_empty()).then(|| Arc::new(BlockFilter::new(blocks)))
+	}
+
+	/// Returns the block types hidden from the map
+	///
+	/// Combines the `--hide-blocks` list with the vegetation blocks if
+	/// `--hide-vegetation` is set.
+	fn hidden_blocks(args: &super::Args) -> Vec<&str> {
+		let mut blocks: Vec<&str> = args.hide_blocks.iter().map(String::as_str).collect();
+		if args.hide_vegetation {
+			if args.vegetation_blocks.is_empty() {
+				blocks.extend_from_slice(VEGETATION_BLOCKS);
+				blocks.extend_from_slice(LEAF_BLOCKS);
+			} else {
+				blocks.extend(args.vegetation_blocks.iter().map(String::as_str));
+			}
+		}
+		blocks
+	}
+
+	/// Returns the block types treated as translucent by `--translucent`
+	fn translucent_blocks(args: &super::Args) -> Vec<&str> {
+		if args.translucent_blocks.is_empty() {
+			TRANSLUCENT_BLOCKS.to_vec()
+		} else {
+			args.translucent_blocks.iter().map(String::as_str).collect()
+		}
+	}
+
+	/// Derives the [Config] for a world of a [WorldLayout]
+	///
+	/// Custom biomes defined by the datapacks of the world are added to the
+	/// biome types. The biome override file still takes precedence.
+	pub fn for_world(&self, layout: &WorldLayout) -> Result<Self> {
+		let biomes = layout::datapack_biomes(&*self.input, Path::new(&layout.name));
+		if biomes.is_empty() {
+			return Ok(self.clone());
+		}
+
+		println!("Found {} datapack biomes", biomes.len());
+
+		Ok(Config {
+			biome_types: Arc::new(load_biome_types(
+				&biomes,
+				self.biome_overrides_path.as_deref(),
+			)?),
+			processing_settings: settings_hash(&(self.processing_settings, &biomes)),
+			..self.clone()
+		})
+	}
+
+	/// Derives the [Config] for a dimension of a [WorldLayout]
+	///
+	/// *world_dir* is the output directory of the world. The overworld is
+	/// stored in this directory itself, other dimensions use a subdirectory
+	/// named after the dimension. The same dimension of all [MergeInput]s
+	/// is added to the region sources.
+	pub fn for_dimension(
+		&self,
+		world_dir: &Path,
+		layout: &WorldLayout,
+		dimension: &DimensionLayout,
+	) -> Result<Self> {
+		let output_dir = match dimension.dimension.subdir() {
+			None => world_dir.to_path_buf(),
+			Some(subdir) => world_dir.join(subdir),
+		};
+
+		let primary = RegionSource::new(
+			self.input.clone(),
+			layout,
+			dimension,
+			TileCoords { x: 0, z: 0 },
+		);
+		let merged = self.merge_inputs.iter().filter_map(|merge| {
+			let merge_dimension = merge
+				.layout
+				.dimensions
+				.iter()
+				.find(|merge_dimension| merge_dimension.dimension == dimension.dimension)?;
+			Some(RegionSource::new(
+				merge.input.clone(),
+				&merge.layout,
+				merge_dimension,
+				merge.offset,
+			))
+		});
+
+		Ok(Config {
+			region_sources: std::iter::once(primary).chain(merged).collect(),
+			level_dat_path: layout.level_dat_path.clone(),
+			height: dimension.height,
+			dimension: dimension.dimension,
+			dimensions: layout
+				.dimensions
+				.iter()
+				.map(|dimension| dimension.dimension)
+				.collect(),
+			overlays: self
+				.overlays
+				.iter()
+				.copied()
+				.filter(|kind| {
+					dimension.dimension == Dimension::Overworld || !kind.is_overworld_only()
+				})
+				.collect(),
+			processed_dir: output_dir.join("processed"),
+			metadata_path: output_dir.join("info.json"),
+			view: self.view.for_dimension(dimension.dimension),
+			biome_data_palette: self.load_palette(&output_dir, TileKind::BiomeData)?,
+			block_data_palette: self.load_palette(&output_dir, TileKind::BlockData)?,
+			output_dir,
+			..self.clone()
+		})
+	}
+
+	/// Returns the range of block heights considered for the map
+	///
+	/// The ceiling of roofed dimensions is skipped unless the maximum
+	/// height has been limited explicitly.
+	pub fn height_limits(&self) -> layer::HeightLimits {
+		layer::HeightLimits {
+			dimension: self.height,
+			max_y: self.max_y,
+			skip_ceiling: self.max_y.is_none()
+				&& self
+					.height
+					.map_or(self.dimension == Dimension::Nether, |dimension_type| {
+						dimension_type.has_ceiling
+					}),
+		}
+	}
+
+	/// Returns true if the primary input is a Bedrock Edition world
+	pub fn is_bedrock(&self) -> bool {
+		matches!(
+			self.region_sources.first().map(|source| &source.format),
+			Some(RegionFormat::Bedrock(..))
+		)
+	}
+
+	/// Reads and deserializes the `level.dat` of the Minecraft save data
+	///
+	/// The `level.dat` of Bedrock Edition worlds is uncompressed little-endian
+	/// NBT data following an 8-byte header, without a `Data` compound.
+	pub fn read_level_dat(&self) -> Result<de::LevelDatData> {
+		(|| -> Result<de::LevelDatData> {
+			let mut file = self.input.open(&self.level_dat_path)?;
+			if !self.is_bedrock() {
+				let level_dat: de::LevelDat = crate::nbt::data::from_reader(file)?;
+				return Ok(level_dat.data);
+			}
+
+			let mut data = Vec::new();
+			file.read_to_end(&mut data)?;
+			let data = data.get(8..).context("Missing level.dat header")?;
+			let (level_dat, _) = crate::nbt::le::from_bytes(data)?;
+			Ok(level_dat)
+		})()
+		.context("Failed to read level.dat")
+	}
+
+	/// Reads the marker layers of all marker files
+	fn marker_layers(args: &super::Args) -> Result<Vec<(Dimension, MarkerLayer)>> {
+		let mut layers = Vec::new();
+		for (dimension, path) in &args.markers {
+			let file_layers: Vec<MarkerLayer> = read_json(path)?;
+			layers.extend(file_layers.into_iter().map(|layer| (*dimension, layer)));
+		}
+		Ok(layers)
+	}
+
+	/// Returns the MinedMap data directory for the given output directory
+	///
+	/// When the viewer is written into the output directory as well, the
+	/// data is stored in its `data` subdirectory, where the viewer expects it.
+	fn data_dir(args: &super::Args, output_dir: &Path) -> PathBuf {
+		#[cfg(feature = "viewer")]
+		if args.with_viewer {
+			return output_dir.join("data");
+		}
+
+		#[cfg(not(feature = "viewer"))]
+		let _ = args;
+
+		output_dir.to_path_buf()
+	}
+
+	/// Creates the [OutputTarget] selected by the command line arguments
+	fn output_target(args: &super::Args) -> Result<Box<dyn OutputTarget>> {
+		#[cfg(feature = "s3")]
+		if let Some(bucket) = &args.s3_bucket {
+			return Ok(Box::new(crate::io::target::S3Target::new(
+				bucket,
+				&args.s3_region,
+				args.s3_endpoint.as_deref(),
+				&args.s3_prefix,
+			)?));
+		}
+
+		#[cfg(not(feature = "s3"))]
+		let _ = args;
+
+		Ok(Box::new(crate::io::target::LocalTarget))
 	}
 
 	/// Constructs the path of an intermediate processed region file
@@ -144,14 +2134,130 @@ impl Config {
 
 	/// Constructs the base output path for a [TileKind] and mipmap level
 	pub fn tile_dir(&self, kind: TileKind, level: usize) -> PathBuf {
-		let prefix = match kind {
-			TileKind::Map => "map",
-			TileKind::Lightmap => "light",
-		};
-		let dir = format!("{}/{}", prefix, level);
+		let dir = format!("{}/{}", kind.name(), level);
 		[&self.output_dir, Path::new(&dir)].iter().collect()
 	}
 
+	/// Returns the path of a file in the data directory relative to the data
+	/// directory, using `/` as a separator
+	fn output_key(&self, path: &Path) -> String {
+		path.strip_prefix(&self.data_dir)
+			.expect("output file must be in output directory")
+			.components()
+			.map(|component| component.as_os_str().to_string_lossy())
+			.collect::<Vec<_>>()
+			.join("/")
+	}
+
+	/// Passes a generated viewer file in the output directory on to the [OutputTarget]
+	///
+	/// The file is also recorded in the [ChangeList]. Nothing happens for unchanged files.
+	pub fn store_output(&self, path: &Path, status: FileStatus) -> Result<()> {
+		let change = match status {
+			FileStatus::Created => FileChange::Added,
+			FileStatus::Updated => FileChange::Modified,
+			FileStatus::Unchanged => return Ok(()),
+		};
+		let key = self.output_key(path);
+		self.output_target.store(&key, path)?;
+		self.changes.record(key, change);
+		Ok(())
+	}
+
+	/// Removes a generated viewer file from the output directory and the [OutputTarget]
+	pub fn remove_output(&self, path: &Path) -> Result<()> {
+		let key = self.output_key(path);
+		fs::remove_with_timestamp(path)?;
+		self.output_target.remove(&key)?;
+		self.changes.record(key, FileChange::Deleted);
+		Ok(())
+	}
+
+	/// Returns all tile kinds of the map, including the enabled overlays
+	pub fn tile_kinds(&self) -> impl Iterator<Item = TileKind> + '_ {
+		[TileKind::Map, TileKind::Lightmap]
+			.into_iter()
+			.chain(self.overlays.iter().copied())
+	}
+
+	/// Returns the enabled overlays generated from the region data
+	pub fn region_overlays(&self) -> impl Iterator<Item = TileKind> + '_ {
+		self.overlays
+			.iter()
+			.copied()
+			.filter(|kind| !kind.is_per_level() && !kind.is_rendered_with_map() && !kind.is_data())
+	}
+
+	/// Returns the enabled data tile kinds
+	pub fn data_tiles(&self) -> impl Iterator<Item = TileKind> + '_ {
+		self.overlays.iter().copied().filter(|kind| kind.is_data())
+	}
+
+	/// Returns the enabled alternative maps and overlays rendered together
+	/// with the regular map tiles
+	pub fn rendered_with_map(&self) -> impl Iterator<Item = TileKind> + '_ {
+		self.overlays
+			.iter()
+			.copied()
+			.filter(|kind| kind.is_rendered_with_map())
+	}
+
+	/// Returns the enabled overlays rendered separately for each mipmap level
+	pub fn level_overlays(&self) -> impl Iterator<Item = TileKind> + '_ {
+		self.overlays
+			.iter()
+			.copied()
+			.filter(|kind| kind.is_per_level())
+	}
+
+	/// Returns the tile kinds generated from the region data along with the
+	/// processed data
+	pub fn region_tile_kinds(&self) -> impl Iterator<Item = TileKind> + '_ {
+		std::iter::once(TileKind::Lightmap)
+			.chain(self.region_overlays())
+			.chain(self.data_tiles())
+	}
+
+	/// Returns the path of the [DataPalette] of a data tile kind
+	fn palette_path(output_dir: &Path, kind: TileKind) -> PathBuf {
+		output_dir.join(kind.name()).join("palette.json")
+	}
+
+	/// Loads the [DataPalette] of a data tile kind if it is enabled
+	fn load_palette(&self, output_dir: &Path, kind: TileKind) -> Result<Arc<DataPalette>> {
+		Ok(Arc::new(if self.overlays.contains(&kind) {
+			DataPalette::load(&Self::palette_path(output_dir, kind))?
+		} else {
+			DataPalette::default()
+		}))
+	}
+
+	/// Returns the [DataPalette] of an enabled data tile kind storing palette
+	/// indices
+	pub fn data_palette(&self, kind: TileKind) -> Option<&DataPalette> {
+		if !self.overlays.contains(&kind) {
+			return None;
+		}
+		match kind {
+			TileKind::BiomeData => Some(&self.biome_data_palette),
+			TileKind::BlockData => Some(&self.block_data_palette),
+			_ => None,
+		}
+	}
+
+	/// Writes the [DataPalette] of a data tile kind to its tile directory
+	///
+	/// Must be called before writing data tiles referencing new palette
+	/// values. Does nothing for data tile kinds without a palette or if the
+	/// palette is unchanged.
+	pub fn write_palette(&self, kind: TileKind) -> Result<()> {
+		let Some(palette) = self.data_palette(kind) else {
+			return Ok(());
+		};
+		let path = Self::palette_path(&self.output_dir, kind);
+		palette.save(&path, |status| self.store_output(&path, status))
+	}
+
 	/// Constructs the path of an output tile image
 	pub fn tile_path(&self, kind: TileKind, level: usize, coords: TileCoords) -> PathBuf {
 		let filename = coord_filename(coords, "png");
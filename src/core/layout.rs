@@ -0,0 +1,577 @@
+//! Discovery of the worlds and dimensions of a Minecraft save
+//!
+//! Vanilla worlds store the Nether and the End in the `DIM-1` and `DIM1`
+//! subdirectories of the world directory. Bukkit-based servers (Spigot, Paper)
+//! use separate sibling world directories instead, so the data of all three
+//! dimensions can only be found when the server root directory is passed
+//! as input. Servers may also host multiple worlds (for example using the
+//! Multiverse plugin), which are discovered in the same way.
+
+use std::{
+	collections::BTreeMap,
+	io::Read,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use anyhow::{Context, Result};
+
+use super::common::Dimension;
+use crate::{
+	io::input::{InputSource, ReadSeek},
+	world::{bedrock, cubic, de},
+};
+
+/// Location of the save data of a single dimension
+#[derive(Debug, Clone)]
+pub struct DimensionLayout {
+	/// The dimension
+	pub dimension: Dimension,
+	/// Path of the region directory, relative to the [InputSource]
+	///
+	/// For Bedrock Edition worlds, this is the database directory.
+	pub region_dir: PathBuf,
+	/// File extension of the region files
+	///
+	/// `mca` for Anvil, `mcr` for pre-Anvil MCRegion, and `linear` for the
+	/// LinearRegionFormat of some Paper forks. Empty for Bedrock Edition worlds.
+	pub extension: &'static str,
+	/// Height range from the dimension type, if known
+	pub height: Option<de::DimensionType>,
+	/// Cube index of a dimension saved by the Cubic Chunks mod
+	///
+	/// For Cubic Chunks worlds, *region_dir* is the dimension directory
+	/// containing the `region2d` and `region3d` directories.
+	pub cubic: Option<Arc<cubic::World>>,
+}
+
+/// Locations of the save data of a world
+#[derive(Clone)]
+pub struct WorldLayout {
+	/// Name of the world directory, relative to the [InputSource]
+	///
+	/// Empty when the input is the world directory itself.
+	pub name: String,
+	/// Path of the `level.dat` file, relative to the [InputSource]
+	pub level_dat_path: PathBuf,
+	/// All dimensions containing region data
+	///
+	/// The overworld is always included as the first entry.
+	pub dimensions: Vec<DimensionLayout>,
+	/// Database of a Bedrock Edition world
+	///
+	/// All dimensions of a Bedrock world are stored in the same database.
+	pub bedrock: Option<Arc<bedrock::World>>,
+}
+
+/// Additional dimensions with the suffix of their Bukkit world directory and
+/// their subdirectory inside the world directory
+const DIMENSION_DIRS: &[(Dimension, &str, &str)] = &[
+	(Dimension::Nether, "_nether", "DIM-1"),
+	(Dimension::End, "_the_end", "DIM1"),
+];
+
+/// Checks whether a file exists in the [InputSource]
+fn file_exists(input: &dyn InputSource, path: &Path) -> bool {
+	input.modified(path).is_ok()
+}
+
+/// Returns the extension of the region files in a directory of the [InputSource]
+///
+/// Anvil (`.mca`) files take precedence over MCRegion (`.mcr`) files, as the
+/// latter are kept when Minecraft converts a world to the Anvil format.
+fn region_extension(input: &dyn InputSource, dir: &Path) -> Option<&'static str> {
+	let files = input.list_files(dir).ok()?;
+	["mca", "linear", "mcr"].into_iter().find(|ext| {
+		files
+			.iter()
+			.any(|file| Path::new(file).extension() == Some(ext.as_ref()))
+	})
+}
+
+/// Opens the cube index of a dimension directory saved by the Cubic Chunks mod
+///
+/// Returns [None] if the directory does not contain any cube regions.
+fn cubic_world(input: &Arc<dyn InputSource>, dir: &Path) -> Option<Arc<cubic::World>> {
+	let world = cubic::World::open(input.clone(), dir).ok()?;
+	world.regions().next()?;
+	Some(Arc::new(world))
+}
+
+/// Builds the [DimensionLayout] for a dimension directory
+///
+/// Returns [None] if the directory does not contain any region data.
+fn dimension_layout(
+	input: &Arc<dyn InputSource>,
+	dimension: Dimension,
+	dir: &Path,
+	height: Option<de::DimensionType>,
+) -> Option<DimensionLayout> {
+	if let Some(world) = cubic_world(input, dir) {
+		return Some(DimensionLayout {
+			dimension,
+			region_dir: dir.to_path_buf(),
+			extension: "3dr",
+			height: None,
+			cubic: Some(world),
+		});
+	}
+
+	let region_dir = dir.join("region");
+	let extension = region_extension(&**input, &region_dir)?;
+	Some(DimensionLayout {
+		dimension,
+		region_dir,
+		extension,
+		height,
+		cubic: None,
+	})
+}
+
+/// Reads the name of the main world from the `server.properties` file
+/// of a server root directory
+///
+/// Returns the server's default of `world` when the file or setting is missing.
+fn level_name(input: &dyn InputSource) -> String {
+	let mut data = String::new();
+	if let Ok(mut file) = input.open(Path::new("server.properties")) {
+		if file.read_to_string(&mut data).is_err() {
+			data.clear();
+		}
+	}
+
+	data.lines()
+		.filter_map(|line| line.trim().strip_prefix("level-name"))
+		.filter_map(|value| value.trim_start().strip_prefix('='))
+		.map(|value| value.trim().to_string())
+		.find(|value| !value.is_empty())
+		.unwrap_or_else(|| "world".to_string())
+}
+
+/// Height ranges and ceilings of the built-in dimension types
+const DIMENSION_TYPES: &[(&str, de::DimensionType)] = &[
+	(
+		"minecraft:overworld",
+		de::DimensionType {
+			min_y: -64,
+			height: 384,
+			has_ceiling: false,
+		},
+	),
+	(
+		"minecraft:overworld_caves",
+		de::DimensionType {
+			min_y: -64,
+			height: 384,
+			has_ceiling: false,
+		},
+	),
+	(
+		"minecraft:the_nether",
+		de::DimensionType {
+			min_y: 0,
+			height: 256,
+			has_ceiling: true,
+		},
+	),
+	(
+		"minecraft:the_end",
+		de::DimensionType {
+			min_y: 0,
+			height: 256,
+			has_ceiling: false,
+		},
+	),
+];
+
+/// A datapack of a world
+///
+/// Datapacks are either unpacked directories or zip files in the `datapacks`
+/// directory of a world.
+enum Datapack<'a> {
+	/// Unpacked datapack directory, relative to the [InputSource]
+	Dir(&'a dyn InputSource, PathBuf),
+	/// Zipped datapack
+	Zip(zip::ZipArchive<Box<dyn ReadSeek>>),
+}
+
+impl<'a> Datapack<'a> {
+	/// Opens all enabled datapacks of a world, ordered by decreasing priority
+	///
+	/// The order is taken from the list of enabled datapacks in the
+	/// `level.dat`, in which later datapacks override earlier ones. Datapacks
+	/// that are disabled there are skipped. Datapacks that aren't listed at
+	/// all were added after the world was last loaded; as the game enables
+	/// them with the highest priority on the next load, they come first,
+	/// sorted by their file names. Datapacks that cannot be opened are
+	/// skipped.
+	fn open_all(input: &'a dyn InputSource, world_dir: &Path) -> Vec<Self> {
+		let datapacks_dir = world_dir.join("datapacks");
+		let data_packs = (|| -> Result<_> {
+			let file = input.open(&world_dir.join("level.dat"))?;
+			let level_dat: de::LevelDat = crate::nbt::data::from_reader(file)?;
+			Ok(level_dat.data.data_packs.unwrap_or_default())
+		})()
+		.unwrap_or_default();
+		// World datapacks are listed with a `file/` prefix
+		let position = |list: &[String], pack: &str| {
+			list.iter()
+				.position(|id| id.strip_prefix("file/") == Some(pack))
+		};
+
+		let mut packs = input.list_dirs(&datapacks_dir).unwrap_or_default();
+		packs.extend(
+			input
+				.list_files(&datapacks_dir)
+				.unwrap_or_default()
+				.into_iter()
+				.filter(|pack| pack.ends_with(".zip")),
+		);
+		packs.retain(|pack| position(&data_packs.disabled, pack).is_none());
+		packs.sort();
+		packs.sort_by_key(|pack| {
+			std::cmp::Reverse(position(&data_packs.enabled, pack).unwrap_or(usize::MAX))
+		});
+
+		packs
+			.iter()
+			.filter_map(|pack| {
+				let path = datapacks_dir.join(pack);
+				if pack.ends_with(".zip") {
+					let archive = zip::ZipArchive::new(input.open(&path).ok()?).ok()?;
+					Some(Datapack::Zip(archive))
+				} else {
+					Some(Datapack::Dir(input, path))
+				}
+			})
+			.collect()
+	}
+
+	/// Reads a file of the datapack
+	fn read(&mut self, file: &str) -> Option<Vec<u8>> {
+		let mut data = Vec::new();
+		match self {
+			Datapack::Dir(input, path) => {
+				input
+					.open(&path.join(file))
+					.ok()?
+					.read_to_end(&mut data)
+					.ok()?;
+			}
+			Datapack::Zip(archive) => {
+				archive.by_name(file).ok()?.read_to_end(&mut data).ok()?;
+			}
+		}
+		Some(data)
+	}
+
+	/// Lists the namespaces of the datapack's `data` directory
+	fn namespaces(&self) -> Vec<String> {
+		let mut namespaces = match self {
+			Datapack::Dir(input, path) => input.list_dirs(&path.join("data")).unwrap_or_default(),
+			Datapack::Zip(archive) => archive
+				.file_names()
+				.filter_map(|name| name.strip_prefix("data/")?.split_once('/'))
+				.map(|(namespace, _)| namespace.to_string())
+				.collect(),
+		};
+		namespaces.sort();
+		namespaces.dedup();
+		namespaces
+	}
+
+	/// Lists all files below a directory of the datapack, including
+	/// subdirectories
+	///
+	/// The returned paths are relative to the datapack root.
+	fn list_files(&self, dir: &str) -> Vec<String> {
+		match self {
+			Datapack::Dir(input, path) => {
+				let mut files = Vec::new();
+				let mut dirs = vec![dir.to_string()];
+				while let Some(dir) = dirs.pop() {
+					let full_dir = path.join(&dir);
+					files.extend(
+						input
+							.list_files(&full_dir)
+							.unwrap_or_default()
+							.into_iter()
+							.map(|file| format!("{}/{}", dir, file)),
+					);
+					dirs.extend(
+						input
+							.list_dirs(&full_dir)
+							.unwrap_or_default()
+							.into_iter()
+							.map(|subdir| format!("{}/{}", dir, subdir)),
+					);
+				}
+				files
+			}
+			Datapack::Zip(archive) => {
+				let prefix = format!("{}/", dir);
+				archive
+					.file_names()
+					.filter(|name| name.starts_with(&prefix) && !name.ends_with('/'))
+					.map(String::from)
+					.collect()
+			}
+		}
+	}
+}
+
+/// Looks up a dimension type provided by a datapack of a world
+///
+/// Both unpacked datapacks and zip files in the `datapacks` directory
+/// are searched, in the order of their priority (see [Datapack::open_all]).
+fn datapack_dimension_type(
+	input: &dyn InputSource,
+	world_dir: &Path,
+	id: &str,
+) -> Option<de::DimensionType> {
+	let (namespace, name) = id.split_once(':').unwrap_or(("minecraft", id));
+	let file = format!("data/{}/dimension_type/{}.json", namespace, name);
+
+	Datapack::open_all(input, world_dir)
+		.iter_mut()
+		.find_map(|pack| serde_json::from_slice(&pack.read(&file)?).ok())
+}
+
+/// Collects the custom biomes defined by the datapacks of a world
+///
+/// Biomes are read from `data/<namespace>/worldgen/biome/` of all unpacked
+/// and zipped datapacks and returned with their namespaced IDs. When multiple
+/// datapacks define the same biome, the datapack with the highest priority
+/// takes precedence, like for dimension types. Malformed definitions are
+/// skipped with a warning.
+pub fn datapack_biomes(
+	input: &dyn InputSource,
+	world_dir: &Path,
+) -> Vec<(String, de::WorldgenBiome)> {
+	let mut biomes = BTreeMap::new();
+
+	for mut pack in Datapack::open_all(input, world_dir) {
+		for namespace in pack.namespaces() {
+			let dir = format!("data/{}/worldgen/biome", namespace);
+			let mut files = pack.list_files(&dir);
+			files.sort();
+
+			for file in files {
+				let Some(name) = file
+					.strip_prefix(&dir)
+					.and_then(|name| name.strip_prefix('/')?.strip_suffix(".json"))
+				else {
+					continue;
+				};
+
+				let id = format!("{}:{}", namespace, name);
+				if biomes.contains_key(&id) {
+					continue;
+				}
+				let Some(data) = pack.read(&file) else {
+					continue;
+				};
+				match serde_json::from_slice(&data) {
+					Ok(biome) => {
+						biomes.insert(id, biome);
+					}
+					Err(err) => eprintln!("Skipping datapack biome {}: {}", id, err),
+				}
+			}
+		}
+	}
+
+	biomes.into_iter().collect()
+}
+
+/// Determines the height ranges of the dimensions of a world
+///
+/// The dimension types are taken from the world generation settings in
+/// `level.dat`, resolving references to built-in and datapack dimension types.
+/// Dimensions are missing from the returned list when their height range
+/// could not be determined.
+fn dimension_heights(
+	input: &dyn InputSource,
+	world_dir: &Path,
+) -> Result<Vec<(Dimension, de::DimensionType)>> {
+	let level_dat: de::LevelDat = (|| -> Result<_> {
+		let file = input.open(&world_dir.join("level.dat"))?;
+		Ok(crate::nbt::data::from_reader(file)?)
+	})()
+	.context("Failed to read level.dat")?;
+
+	let Some(settings) = level_dat.data.world_gen_settings else {
+		return Ok(Vec::new());
+	};
+
+	Ok([Dimension::Overworld, Dimension::Nether, Dimension::End]
+		.into_iter()
+		.filter_map(|dimension| {
+			let dimension_type = match &settings.dimensions.get(dimension.id())?.dimension_type {
+				de::DimensionTypeRef::Inline(dimension_type) => *dimension_type,
+				de::DimensionTypeRef::Id(id) => DIMENSION_TYPES
+					.iter()
+					.find(|(builtin, _)| builtin == id)
+					.map(|&(_, dimension_type)| dimension_type)
+					.or_else(|| datapack_dimension_type(input, world_dir, id))?,
+			};
+			Some((dimension, dimension_type))
+		})
+		.collect())
+}
+
+/// Discovers the worlds and dimensions of the save data of an [InputSource]
+///
+/// The input can either be a world directory (containing `level.dat`) or
+/// the root directory of a server. In the latter case, all subdirectories
+/// containing a `level.dat` are considered worlds, with the main world from
+/// `server.properties` listed first. Bukkit-style `_nether` and `_the_end`
+/// sibling directories are treated as dimensions of the corresponding world
+/// rather than separate worlds.
+pub fn discover(input: &Arc<dyn InputSource>) -> Result<Vec<WorldLayout>> {
+	if file_exists(&**input, Path::new("level.dat")) {
+		return Ok(vec![WorldLayout::from_world_dir(input, "")?]);
+	}
+
+	// Bedrock Dedicated Server keeps its worlds in a `worlds` subdirectory
+	let bds_worlds = input
+		.list_dirs(Path::new("worlds"))
+		.unwrap_or_default()
+		.into_iter()
+		.map(|name| format!("worlds/{}", name));
+	let mut names: Vec<_> = input
+		.list_dirs(Path::new(""))
+		.unwrap_or_default()
+		.into_iter()
+		.chain(bds_worlds)
+		.filter(|name| file_exists(&**input, &Path::new(name).join("level.dat")))
+		.collect();
+	names.sort();
+
+	let all = names.clone();
+	names.retain(|name| {
+		!DIMENSION_DIRS.iter().any(|(_, suffix, _)| {
+			name.strip_suffix(suffix)
+				.is_some_and(|base| all.iter().any(|other| other == base))
+		})
+	});
+
+	let level_name = level_name(&**input);
+	if let Some(pos) = names
+		.iter()
+		.position(|name| name.strip_prefix("worlds/").unwrap_or(name) == level_name)
+	{
+		let name = names.remove(pos);
+		names.insert(0, name);
+	}
+
+	if names.is_empty() {
+		// Neither a world nor a server directory; use the input as a world
+		// directory to get the usual error messages for missing files
+		return Ok(vec![WorldLayout::from_world_dir(input, "")?]);
+	}
+
+	names
+		.iter()
+		.map(|name| {
+			println!("Found world {}", name);
+			WorldLayout::from_world_dir(input, name)
+		})
+		.collect()
+}
+
+impl WorldLayout {
+	/// Builds the [WorldLayout] for a world directory
+	///
+	/// *name* is the name of the world directory relative to the input root,
+	/// or empty when the input is the world directory itself. In the former
+	/// case, the Bukkit sibling directories derived from the name are
+	/// considered as well.
+	fn from_world_dir(input: &Arc<dyn InputSource>, name: &str) -> Result<Self> {
+		let world_dir = Path::new(name);
+
+		let db_dir = world_dir.join("db");
+		if file_exists(&**input, &db_dir.join("CURRENT")) {
+			return Self::from_bedrock_world(input, world_dir, &db_dir);
+		}
+
+		let heights = dimension_heights(&**input, world_dir).unwrap_or_default();
+		let height = |dimension| {
+			heights
+				.iter()
+				.find(|&&(other, _)| other == dimension)
+				.map(|&(_, height)| height)
+		};
+
+		let overworld = dimension_layout(
+			input,
+			Dimension::Overworld,
+			world_dir,
+			height(Dimension::Overworld),
+		)
+		.unwrap_or_else(|| DimensionLayout {
+			dimension: Dimension::Overworld,
+			region_dir: world_dir.join("region"),
+			extension: "mca",
+			height: height(Dimension::Overworld),
+			cubic: None,
+		});
+		let mut dimensions = vec![overworld];
+
+		for &(dimension, suffix, dim_dir) in DIMENSION_DIRS {
+			let mut candidates = vec![world_dir.join(dim_dir)];
+			if !name.is_empty() {
+				candidates.push(Path::new(&format!("{}{}", name, suffix)).join(dim_dir));
+			}
+
+			if let Some(layout) = candidates
+				.iter()
+				.find_map(|dir| dimension_layout(input, dimension, dir, height(dimension)))
+			{
+				dimensions.push(layout);
+			}
+		}
+
+		Ok(WorldLayout {
+			name: name.to_string(),
+			level_dat_path: world_dir.join("level.dat"),
+			dimensions,
+			bedrock: None,
+		})
+	}
+
+	/// Builds the [WorldLayout] for a Bedrock Edition world
+	///
+	/// The database is opened right away to find out which dimensions
+	/// contain chunk data.
+	fn from_bedrock_world(
+		input: &Arc<dyn InputSource>,
+		world_dir: &Path,
+		db_dir: &Path,
+	) -> Result<Self> {
+		println!("Reading Bedrock world database");
+		let world = bedrock::World::open(input.clone(), db_dir)?;
+
+		let dimensions = [Dimension::Overworld, Dimension::Nether, Dimension::End]
+			.into_iter()
+			.filter(|&dimension| {
+				dimension == Dimension::Overworld
+					|| world.regions(dimension.bedrock_id()).next().is_some()
+			})
+			.map(|dimension| DimensionLayout {
+				dimension,
+				region_dir: db_dir.to_path_buf(),
+				extension: "",
+				height: None,
+				cubic: None,
+			})
+			.collect();
+
+		Ok(WorldLayout {
+			name: world_dir.to_string_lossy().into_owned(),
+			level_dat_path: world_dir.join("level.dat"),
+			dimensions,
+			bedrock: Some(Arc::new(world)),
+		})
+	}
+}
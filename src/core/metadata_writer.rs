@@ -1,9 +1,19 @@
 //! The [MetadataWriter] and related types
 
+use std::{
+	collections::BTreeMap,
+	path::Path,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
 use anyhow::{Context, Result};
 use serde::Serialize;
 
-use crate::{core::common::*, io::fs, world::de};
+use crate::{
+	core::{common::*, layout::WorldLayout, overlay_renderer::OverlayRenderer},
+	io::fs,
+	world::de,
+};
 
 /// Minimum and maximum X and Z tile coordinates for a mipmap level
 #[derive(Debug, Serialize)]
@@ -26,10 +36,19 @@ struct Mipmap<'t> {
 	bounds: Bounds,
 	/// Map of populated tiles for the mipmap level
 	regions: &'t TileCoordMap,
+	/// Time of the last update of any tile of the mipmap level, in seconds
+	/// since the Unix epoch
+	updated: u64,
+	/// Update timestamps of the tiles, in the same order as *regions*
+	///
+	/// Can be used by the viewer to bypass outdated cached tiles.
+	timestamps: BTreeMap<i32, Vec<u64>>,
 }
 
 /// Initial spawn point for new players
-#[derive(Debug, Serialize)]
+///
+/// For the Nether and End, the corresponding point of the dimension is used.
+#[derive(Debug, Clone, Copy, Serialize)]
 struct Spawn {
 	/// Spawn X coordinate
 	x: i32,
@@ -37,13 +56,137 @@ struct Spawn {
 	z: i32,
 }
 
+/// Scale of the activity overlay, for display of a legend in the viewer
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ActivityScale {
+	/// Inhabited time in ticks at the lower bound of the scale
+	min_ticks: i64,
+	/// Inhabited time in ticks at the upper bound of the scale
+	max_ticks: i64,
+}
+
 /// Viewer metadata JSON data structure
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 struct Metadata<'t> {
+	/// Time of the generation of the metadata, in seconds since the Unix epoch
+	updated: u64,
+	/// Name of the dimension
+	dimension: &'static str,
+	/// Names of all rendered dimensions of the world
+	dimensions: Vec<&'static str>,
 	/// Tile information for each mipmap level
 	mipmaps: Vec<Mipmap<'t>>,
-	/// Initial spawn point for new players
-	spawn: Spawn,
+	/// Initial spawn point for new players, unless hidden
+	#[serde(skip_serializing_if = "Option::is_none")]
+	spawn: Option<Spawn>,
+	/// World seed, if published
+	///
+	/// Stored as a string, as JavaScript numbers can't represent all 64-bit
+	/// integers exactly.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	seed: Option<String>,
+	/// Names of the generated overlay tile sets
+	overlays: Vec<&'static str>,
+	/// Names of the generated data tile sets
+	///
+	/// Data tiles only exist for mipmap level 0.
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	data_tiles: Vec<&'static str>,
+	/// Versions of the tile sets, by name
+	///
+	/// Appended to the tile URLs by the viewer in addition to the tile
+	/// timestamps, which only change with the region data.
+	tile_versions: BTreeMap<&'static str, String>,
+	/// Biome IDs indexed by the values of the biome data tiles
+	#[serde(skip_serializing_if = "Option::is_none")]
+	biome_palette: Option<Vec<String>>,
+	/// Block IDs indexed by the values of the block data tiles
+	#[serde(skip_serializing_if = "Option::is_none")]
+	block_palette: Option<Vec<String>>,
+	/// Name of the color theme of the map
+	theme: &'static str,
+	/// Initial view and zoom limits of the viewer
+	#[serde(skip_serializing_if = "ViewConfig::is_empty")]
+	view: ViewConfig,
+	/// Title, description and other information about the map
+	#[serde(flatten)]
+	info: &'t MapInfo,
+	/// Marker layers of the dimension
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	marker_layers: Vec<&'t MarkerLayer>,
+	/// Scale of the activity overlay, if generated
+	#[serde(skip_serializing_if = "Option::is_none")]
+	activity_scale: Option<ActivityScale>,
+	/// World border of the dimension, if set
+	#[serde(skip_serializing_if = "Option::is_none")]
+	world_border: Option<WorldBorder>,
+}
+
+/// Viewer index JSON data structure for data directories containing multiple worlds
+#[derive(Debug, Serialize)]
+struct Index<'t> {
+	/// Names of the world subdirectories, starting with the main world
+	worlds: Vec<&'t str>,
+}
+
+/// Entry of a dimension in the dimension index of a world
+#[derive(Debug, Serialize)]
+pub struct DimensionEntry {
+	/// Name of the dimension
+	name: &'static str,
+	/// Data directory of the dimension, relative to the world's data directory
+	path: String,
+	/// Initial spawn point of the dimension, unless hidden
+	#[serde(skip_serializing_if = "Option::is_none")]
+	spawn: Option<Spawn>,
+	/// Minimum and maximum tile coordinates of the full-resolution mipmap level
+	#[serde(skip_serializing_if = "Option::is_none")]
+	bounds: Option<Bounds>,
+}
+
+/// Dimension index JSON data structure
+#[derive(Debug, Serialize)]
+struct DimensionIndex<'t> {
+	/// Rendered dimensions of the world, starting with the overworld
+	dimensions: &'t [DimensionEntry],
+}
+
+/// Writes the index of the rendered dimensions of a world
+///
+/// Only used when multiple dimensions have been rendered. The index is
+/// stored as `dimensions.json` in the data directory of the world, next to
+/// the metadata file of the overworld; each dimension has its own metadata
+/// file.
+pub fn write_dimension_index(
+	config: &Config,
+	world_dir: &Path,
+	dimensions: &[DimensionEntry],
+) -> Result<()> {
+	let index = DimensionIndex { dimensions };
+
+	let path = world_dir.join("dimensions.json");
+	let ((), status) = fs::create_with_tmpfile(&path, |file| {
+		serde_json::to_writer(file, &index).context("Failed to write dimension index")
+	})?;
+	config.store_output(&path, status)
+}
+
+/// Writes the top-level viewer index listing all worlds
+///
+/// Only used when multiple worlds have been rendered into subdirectories of
+/// the data directory; each world has its own metadata file.
+pub fn write_index(config: &Config, worlds: &[WorldLayout]) -> Result<()> {
+	let index = Index {
+		worlds: worlds.iter().map(|world| world.name.as_str()).collect(),
+	};
+
+	let path = config.data_dir.join("info.json");
+	let ((), status) = fs::create_with_tmpfile(&path, |file| {
+		serde_json::to_writer(file, &index).context("Failed to write index")
+	})?;
+	config.store_output(&path, status)
 }
 
 /// The MetadataWriter is used to generate the viewer metadata file
@@ -60,8 +203,14 @@ impl<'a> MetadataWriter<'a> {
 		MetadataWriter { config, tiles }
 	}
 
-	/// Helper to construct a [Mipmap] data structure from a [TileCoordMap]
-	fn mipmap_entry(regions: &TileCoordMap) -> Mipmap {
+	/// Converts a [SystemTime] to seconds since the Unix epoch
+	fn unix_timestamp(time: SystemTime) -> u64 {
+		time.duration_since(UNIX_EPOCH)
+			.map_or(0, |duration| duration.as_secs())
+	}
+
+	/// Determines the minimum and maximum tile coordinates of a [TileCoordMap]
+	fn bounds(regions: &TileCoordMap) -> Bounds {
 		let mut min_x = i32::MAX;
 		let mut max_x = i32::MIN;
 		let mut min_z = i32::MAX;
@@ -85,45 +234,184 @@ impl<'a> MetadataWriter<'a> {
 			}
 		}
 
+		Bounds {
+			min_x,
+			max_x,
+			min_z,
+			max_z,
+		}
+	}
+
+	/// Helper to construct a [Mipmap] data structure from a [TileCoordMap]
+	///
+	/// The tile timestamps are read from the metadata files of the map tiles.
+	fn mipmap_entry(&self, level: usize, regions: &'a TileCoordMap) -> Mipmap<'a> {
+		let timestamps: BTreeMap<_, Vec<_>> = regions
+			.0
+			.iter()
+			.map(|(&z, xs)| {
+				let timestamps = xs
+					.iter()
+					.map(|&x| {
+						let path = self
+							.config
+							.tile_path(TileKind::Map, level, TileCoords { x, z });
+						fs::read_timestamp(&path, FILE_META_VERSION).map_or(0, Self::unix_timestamp)
+					})
+					.collect();
+				(z, timestamps)
+			})
+			.collect();
+		let updated = timestamps
+			.values()
+			.flatten()
+			.copied()
+			.max()
+			.unwrap_or_default();
+
 		Mipmap {
-			bounds: Bounds {
-				min_x,
-				max_x,
-				min_z,
-				max_z,
-			},
+			bounds: Self::bounds(regions),
 			regions,
+			updated,
+			timestamps,
 		}
 	}
 
-	/// Reads and deserializes the `level.dat` of the Minecraft save data
-	fn read_level_dat(&self) -> Result<de::LevelDat> {
-		crate::nbt::data::from_file(&self.config.level_dat_path).context("Failed to read level.dat")
+	/// Determines the versions of the generated tile sets
+	///
+	/// The version of a tile set changes when its tiles are generated again
+	/// without a change of the region data, for example with different
+	/// settings or an updated `level.dat`. Grid overlays never change.
+	fn tile_versions(&self) -> BTreeMap<&'static str, String> {
+		let level_dat_timestamp = self
+			.config
+			.input
+			.modified(&self.config.level_dat_path)
+			.map_or(0, Self::unix_timestamp);
+
+		self.config
+			.tile_kinds()
+			.filter_map(|kind| {
+				let version = if kind == TileKind::Map || kind.is_rendered_with_map() {
+					self.config.render_settings.0
+				} else if OverlayRenderer::uses_level_dat(kind) {
+					level_dat_timestamp
+				} else if kind.is_per_level() {
+					return None;
+				} else {
+					self.config.processing_settings.0
+				};
+				Some((kind.name(), format!("{:x}", version)))
+			})
+			.collect()
 	}
 
-	/// Generates [Spawn] data from a [de::LevelDat]
-	fn spawn(level_dat: &de::LevelDat) -> Spawn {
-		Spawn {
-			x: level_dat.data.spawn_x,
-			z: level_dat.data.spawn_z,
-		}
+	/// Generates [Spawn] data from a [de::LevelDatData]
+	///
+	/// The overworld spawn point is scaled down for the Nether. In the End,
+	/// the obsidian platform players arrive on is used. Returns [None] if the
+	/// spawn point is hidden.
+	fn spawn(&self, level_dat: &de::LevelDatData) -> Option<Spawn> {
+		let (x, z) = match self.config.spawn {
+			SpawnPoint::World => (level_dat.spawn_x, level_dat.spawn_z),
+			SpawnPoint::Hidden => return None,
+			SpawnPoint::Fixed(x, z) => (x, z),
+		};
+
+		Some(match self.config.dimension {
+			Dimension::Overworld => Spawn { x, z },
+			Dimension::Nether => Spawn {
+				x: x.div_euclid(8),
+				z: z.div_euclid(8),
+			},
+			Dimension::End => Spawn { x: 100, z: 0 },
+		})
 	}
 
 	/// Runs the viewer metadata file generation
-	pub fn run(self) -> Result<()> {
-		let level_dat = self.read_level_dat()?;
+	///
+	/// Returns the entry of the dimension for the dimension index of the
+	/// world.
+	pub fn run(self) -> Result<DimensionEntry> {
+		let level_dat = self.config.read_level_dat()?;
+		let spawn = self.spawn(&level_dat);
 
 		let mut metadata = Metadata {
+			updated: Self::unix_timestamp(SystemTime::now()),
+			dimension: self.config.dimension.name(),
+			dimensions: self
+				.config
+				.dimensions
+				.iter()
+				.map(|dimension| dimension.name())
+				.collect(),
 			mipmaps: Vec::new(),
-			spawn: Self::spawn(&level_dat),
+			spawn,
+			seed: level_dat
+				.seed()
+				.filter(|_| self.config.publish_seed)
+				.map(|seed| seed.to_string()),
+			overlays: self
+				.config
+				.overlays
+				.iter()
+				.filter(|kind| !kind.is_data())
+				.map(|kind| kind.name())
+				.collect(),
+			data_tiles: self.config.data_tiles().map(|kind| kind.name()).collect(),
+			tile_versions: self.tile_versions(),
+			biome_palette: self
+				.config
+				.data_palette(TileKind::BiomeData)
+				.map(DataPalette::names),
+			block_palette: self
+				.config
+				.data_palette(TileKind::BlockData)
+				.map(DataPalette::names),
+			theme: self.config.theme.name(),
+			view: self.config.view,
+			info: &self.config.info,
+			marker_layers: self
+				.config
+				.marker_layers
+				.iter()
+				.filter(|(dimension, _)| *dimension == self.config.dimension)
+				.map(|(_, layer)| layer)
+				.collect(),
+			activity_scale: self
+				.config
+				.overlays
+				.contains(&TileKind::Activity)
+				.then_some(ActivityScale {
+					min_ticks: ACTIVITY_MIN_TICKS,
+					max_ticks: ACTIVITY_MAX_TICKS,
+				}),
+			world_border: WorldBorder::new(&level_dat, self.config.dimension),
 		};
 
-		for tile_map in self.tiles.iter() {
-			metadata.mipmaps.push(Self::mipmap_entry(tile_map));
+		for kind in self.config.data_tiles() {
+			self.config.write_palette(kind)?;
 		}
 
-		fs::create_with_tmpfile(&self.config.metadata_path, |file| {
+		for (level, tile_map) in self.tiles.iter().enumerate() {
+			metadata.mipmaps.push(self.mipmap_entry(level, tile_map));
+		}
+
+		let ((), status) = fs::create_with_tmpfile(&self.config.metadata_path, |file| {
 			serde_json::to_writer(file, &metadata).context("Failed to write metadata")
+		})?;
+		self.config
+			.store_output(&self.config.metadata_path, status)?;
+
+		Ok(DimensionEntry {
+			name: self.config.dimension.name(),
+			path: self
+				.config
+				.dimension
+				.subdir()
+				.map_or_else(String::new, |subdir| format!("{}/", subdir)),
+			spawn,
+			bounds: self.tiles.first().map(Self::bounds),
 		})
 	}
 }
@@ -1,21 +1,30 @@
 //! Core functions of the MinedMap CLI
 
-mod common;
+pub mod common;
+pub mod layout;
 mod metadata_writer;
+mod overlay_renderer;
+mod overviewer_writer;
 mod region_group;
 mod region_processor;
+mod snapshot_archiver;
 mod tile_mipmapper;
 mod tile_renderer;
+#[cfg(feature = "viewer")]
+mod viewer;
 
 use std::path::PathBuf;
 
-use anyhow::{Context, Result};
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+use clap::{Parser, ValueEnum};
 use git_version::git_version;
 
-use common::Config;
+use common::{Config, Dimension, Theme, TileKind};
 use metadata_writer::MetadataWriter;
+use overlay_renderer::OverlayRenderer;
+use overviewer_writer::OverviewerWriter;
 use region_processor::RegionProcessor;
+use snapshot_archiver::SnapshotArchiver;
 use tile_mipmapper::TileMipmapper;
 use tile_renderer::TileRenderer;
 
@@ -27,8 +36,16 @@ const VERSION: &str = git_version!(
 
 /// Command line arguments for minedmap CLI
 #[derive(Debug, Parser)]
-#[command(about, version = VERSION.strip_prefix("v").unwrap())]
+#[command(
+	about,
+	version = VERSION.strip_prefix("v").unwrap(),
+	args_conflicts_with_subcommands = true,
+	subcommand_negates_reqs = true
+)]
 pub struct Args {
+	/// Auxiliary command to run instead of rendering a map
+	#[command(subcommand)]
+	pub command: Option<crate::tools::Command>,
 	/// Number of parallel threads to use for processing
 	///
 	/// If not given, only a single thread is used. Pass 0 to
@@ -36,9 +53,599 @@ pub struct Args {
 	#[arg(short, long)]
 	pub jobs: Option<usize>,
 	/// Minecraft save directory
-	pub input_dir: PathBuf,
+	///
+	/// Either a world directory or the root directory of a server can be
+	/// passed; the Nether and End dimensions are discovered automatically.
+	/// When a server directory contains multiple worlds, each world is
+	/// rendered into a subdirectory of the data directory.
+	/// Backup archives in .zip, .tar, .tar.gz or .tgz format can be passed
+	/// instead of a directory. When built with the sftp feature, a remote
+	/// save directory can be given as sftp://[user@]host[:port]/path.
+	#[arg(required = true)]
+	pub input_dir: Option<PathBuf>,
 	/// MinedMap data directory
-	pub output_dir: PathBuf,
+	#[arg(required = true)]
+	pub output_dir: Option<PathBuf>,
+	/// Additional world to merge into the map, given as PATH or PATH@X,Z
+	///
+	/// X and Z are the block coordinates the origin of the world is moved
+	/// to; they must be multiples of 512. Where multiple worlds contain the
+	/// same region, the main input takes precedence, followed by the merged
+	/// worlds in the order they are given.
+	#[arg(long, value_name = "PATH[@X,Z]")]
+	pub merge: Vec<String>,
+	/// Write a list of all added, modified and deleted viewer files to the given file
+	///
+	/// Each line contains a status letter (A, M or D) and a path relative
+	/// to the data directory, separated by a tab.
+	#[arg(long)]
+	pub change_list: Option<PathBuf>,
+	/// Skip chunks that fail to load instead of the whole region, writing a report to the given file
+	///
+	/// Each line of the report contains the path of the region file, the X
+	/// and Z coordinates of the chunk inside the region and the reason it
+	/// was skipped, separated by tabs. Only regions processed in the current
+	/// run are included; regions with skipped chunks are processed again in
+	/// every run, so their chunks are reported until they are repaired.
+	#[arg(long, value_name = "REPORT")]
+	pub skip_corrupt: Option<PathBuf>,
+	/// Write the unknown block types encountered to a report file
+	///
+	/// Each line of the report contains a block ID and the number of chunk
+	/// sections it was found in, separated by a tab. The block types of
+	/// unknown blocks are guessed from their names where possible, for example
+	/// using the color of oak planks for all blocks named `*_planks`.
+	#[arg(long, value_name = "REPORT")]
+	pub unknown_blocks: Option<PathBuf>,
+	/// Resolve chunks with overlapping data in corrupt region files by their timestamps
+	///
+	/// By default, the chunk stored first in the region file is kept. With
+	/// this option, the chunk that was modified last is kept instead. In
+	/// both cases, a warning is printed for the discarded chunks.
+	#[arg(long)]
+	pub prefer_newer_chunks: bool,
+	/// Generate an overlay showing the density of ores
+	///
+	/// The color intensity of each pixel of the overlay corresponds to the
+	/// number of ore blocks in the block column.
+	#[arg(long)]
+	pub ore_overlay: bool,
+	/// Comma-separated list of block types counted for the ore overlay
+	#[arg(
+		long,
+		value_name = "BLOCKS",
+		value_delimiter = ',',
+		default_values = [
+			"minecraft:diamond_ore",
+			"minecraft:deepslate_diamond_ore",
+			"minecraft:ancient_debris",
+		],
+	)]
+	pub ore_blocks: Vec<String>,
+	/// Generate an overlay showing where players spend their time
+	///
+	/// Chunks are colored by the total time players have spent in them, on
+	/// a logarithmic scale from 1 minute to 100 hours.
+	#[arg(long)]
+	pub activity_overlay: bool,
+	/// Generate an overlay marking unlit areas where hostile mobs can spawn
+	///
+	/// Blocks on the surface of the map are marked when there is no block
+	/// light (from torches or other light sources) above them.
+	#[arg(long)]
+	pub spawnable_overlay: bool,
+	/// Generate an overlay showing chunk boundaries, with emphasized region boundaries
+	#[arg(long)]
+	pub chunk_grid: bool,
+	/// Generate an overlay showing region boundaries, labeled with the names of the region files
+	#[arg(long)]
+	pub region_grid: bool,
+	/// Generate an overlay showing the slime chunks of the world
+	///
+	/// Slime chunks are determined from the world seed stored in the
+	/// level.dat. Only Java Edition worlds are supported.
+	#[arg(long)]
+	pub slime_chunks: bool,
+	/// Generate an overlay showing the spawn chunks, which stay loaded even without players nearby
+	///
+	/// The area is determined from the spawn point and the spawnChunkRadius
+	/// game rule. Only Java Edition worlds are supported.
+	#[arg(long)]
+	pub spawn_chunks: bool,
+	/// Generate an overlay showing the world border
+	///
+	/// The world border is always included in the viewer metadata, so the
+	/// viewer can draw it without this overlay; the overlay tiles are useful
+	/// when the map tiles are used by other tools.
+	#[arg(long)]
+	pub world_border: bool,
+	/// Generate a biome map, coloring each block column by its biome
+	///
+	/// The biome map can be selected as an alternative to the regular map in
+	/// the viewer.
+	#[arg(long)]
+	pub biome_map: bool,
+	/// Color theme of the map
+	///
+	/// The colorblind theme replaces the colors of the biome map; the other
+	/// themes adjust the colors of the regular map and can be combined with
+	/// --brightness, --contrast, --saturation and --gamma.
+	#[arg(long, value_enum, default_value_t = Theme::Default)]
+	pub theme: Theme,
+	/// JSON file overriding the colors of the biome map
+	///
+	/// The file must contain an object mapping biome IDs to colors in
+	/// `#rrggbb` notation, for example `{"minecraft:plains": "#8db360"}`.
+	/// Biomes not listed in the file keep their default colors.
+	#[arg(long, value_name = "FILE")]
+	pub biome_colors: Option<PathBuf>,
+	/// JSON file overriding the grass, foliage and water colors of biomes
+	///
+	/// The file must contain an object mapping biome IDs to objects with
+	/// optional "grass", "foliage" and "water" colors in `#rrggbb` notation
+	/// and "temperature" and "downfall" values, for example
+	/// `{"minecraft:swamp": {"water": "#4c6559"}}`. Unknown biomes, for example
+	/// from mods, are added based on the plains biome.
+	#[arg(long, value_name = "FILE")]
+	pub biome_overrides: Option<PathBuf>,
+	/// JSON file or directory of JSON files with block definitions of mods
+	///
+	/// Each file must contain an object with a "namespace" and "blocks" in the
+	/// format of --block-overrides, for example `{"namespace": "create",
+	/// "blocks": {"andesite_casing": {"color": "#a0a29a"}}}`. The namespace is
+	/// added to all block IDs given without one. Can be passed multiple times;
+	/// --block-overrides is applied after all packs.
+	#[arg(long, value_name = "PATH")]
+	pub block_packs: Vec<PathBuf>,
+	/// JSON file adding or overriding block types
+	///
+	/// The file must contain an object mapping block IDs to objects with an
+	/// optional "color" in `#rrggbb` notation and optional "opaque", "grass",
+	/// "foliage", "birch", "spruce" and "water" flags, for example
+	/// `{"minecraft:stone": {"color": "#808080"}}`. Unknown blocks, for example
+	/// from new Minecraft versions or mods, are added as opaque blocks and must
+	/// specify a color.
+	#[arg(long, value_name = "FILE")]
+	pub block_overrides: Option<PathBuf>,
+	/// JSON file mapping block IDs to the IDs of the block types to render them as
+	///
+	/// The file must contain an object mapping block IDs to other block IDs,
+	/// for example `{"mymod:basalt_cobble": "minecraft:cobblestone"}`, which is
+	/// a lightweight way to handle renamed or modded blocks. The target blocks
+	/// may be defined by --block-packs or --block-overrides.
+	#[arg(long, value_name = "FILE")]
+	pub block_aliases: Option<PathBuf>,
+	/// Generate a heightmap, shading each block column by its elevation
+	///
+	/// The heightmap can be selected as an alternative to the regular map in
+	/// the viewer. Water is ignored, showing the elevation of the ground below.
+	#[arg(long)]
+	pub heightmap: bool,
+	/// Generate an overlay with contour lines of the ground elevation
+	///
+	/// Every fifth contour line is emphasized.
+	#[arg(long)]
+	pub contours: bool,
+	/// Generate a terrain map, tinting the ground by its elevation
+	///
+	/// The terrain map is shaded by the slope of the ground and can be
+	/// selected as an alternative to the regular map in the viewer.
+	#[arg(long)]
+	pub terrain_map: bool,
+	/// Generate a cave map, showing the floors of the topmost caves
+	///
+	/// For each block column, the first air pocket below the surface is
+	/// searched, and its floor is colored by its height. The cave map can be
+	/// selected as an alternative to the regular map in the viewer.
+	#[arg(long)]
+	pub cave_map: bool,
+	/// Generate a night map, lit by torches, lava and other light sources
+	///
+	/// The map is darkened, with blocks brightened by the block light above
+	/// them. Blocks below the ceiling of the Nether or below hidden blocks
+	/// are not lit by the moon. The night map can be selected as an
+	/// alternative to the regular map in the viewer.
+	#[arg(long)]
+	pub night_map: bool,
+	/// Generate an ocean floor map, showing the ground below water
+	///
+	/// Water is left out, and the ground below it is tinted blue by the depth
+	/// of the water. The ocean floor map can be selected as an alternative to
+	/// the regular map in the viewer.
+	#[arg(long)]
+	pub ocean_floor_map: bool,
+	/// Generate an isometric map, showing the terrain from the south at an
+	/// oblique angle
+	///
+	/// Block columns are drawn with their south faces, so hills, cliffs and
+	/// buildings stand out in a classic 2.5D style. The isometric map can be
+	/// selected as an alternative to the regular map in the viewer; as
+	/// elevated terrain is shifted north, markers and coordinates do not
+	/// match it exactly.
+	#[arg(long)]
+	pub isometric_map: bool,
+	/// Generate data tiles encoding the surface height of each block column
+	///
+	/// The tiles are 16-bit grayscale PNGs storing the Y coordinate of the
+	/// surface (including water) plus 32768, with transparent pixels where no
+	/// data exists. Data tiles are only generated for the full-resolution
+	/// zoom level.
+	#[arg(long)]
+	pub height_data: bool,
+	/// Generate data tiles encoding the biome of each block column
+	///
+	/// The tiles are 16-bit grayscale PNGs storing an index into the list of
+	/// biome IDs written to `biomedata/palette.json` and the viewer metadata,
+	/// with transparent pixels where no data exists. The viewer shows the
+	/// biome under the cursor.
+	#[arg(long)]
+	pub biome_data: bool,
+	/// Generate data tiles encoding the top block and height of each block column
+	///
+	/// The tiles are 16-bit RGBA PNGs. The red channel stores an index into
+	/// the list of block IDs written to `blockdata/palette.json` and the
+	/// viewer metadata, the green channel the Y coordinate of the surface
+	/// plus 32768. Pixels without data are transparent. The viewer shows the
+	/// block and height under the cursor.
+	#[arg(long)]
+	pub block_data: bool,
+	/// Ignore all blocks above the given Y coordinate
+	///
+	/// Renders the world as if it was cut off at this height, for example
+	/// to look below the sea or a skyblock platform. Disables skipping the
+	/// ceiling of the Nether.
+	#[arg(long, value_name = "Y", allow_hyphen_values = true)]
+	pub max_y: Option<i32>,
+	/// Comma-separated list of block types to hide from the map
+	///
+	/// Hidden blocks are treated like air when searching for the topmost
+	/// block, so hiding natural blocks like stone and dirt results in an
+	/// x-ray map of builds and ores.
+	#[arg(long, value_name = "BLOCKS", value_delimiter = ',')]
+	pub hide_blocks: Vec<String>,
+	/// Hide plants like grass, flowers, crops and leaves from the map
+	///
+	/// Reveals the terrain, paths and builds below vegetation.
+	#[arg(long)]
+	pub hide_vegetation: bool,
+	/// Comma-separated list of block types hidden by --hide-vegetation
+	///
+	/// Replaces the default list of grass, flower, crop and leaf blocks.
+	#[arg(
+		long,
+		value_name = "BLOCKS",
+		value_delimiter = ',',
+		requires = "hide_vegetation"
+	)]
+	pub vegetation_blocks: Vec<String>,
+	/// Show the ground below trees through leaves with the given opacity
+	///
+	/// Leaves are blended with the ground below them instead of hiding it,
+	/// so paths and builds below forests remain visible.
+	#[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(i32).range(0..100))]
+	pub canopy_opacity: Option<i32>,
+	/// Show the blocks below glass and ice, tinted by the translucent blocks
+	#[arg(long)]
+	pub translucent: bool,
+	/// Comma-separated list of block types treated as translucent by --translucent
+	///
+	/// Replaces the default list of glass, glass pane and ice blocks.
+	#[arg(
+		long,
+		value_name = "BLOCKS",
+		value_delimiter = ',',
+		requires = "translucent"
+	)]
+	pub translucent_blocks: Vec<String>,
+	/// Radius of the area of block columns whose biome colors are blended
+	///
+	/// Grass, foliage and water colors of neighboring biomes are blended to
+	/// avoid hard edges at biome borders. 0 disables blending, 1 blends 3x3
+	/// columns, 2 blends 5x5 columns.
+	#[arg(long, value_name = "RADIUS", default_value_t = 2, value_parser = clap::value_parser!(u8).range(0..=2))]
+	pub biome_blend: u8,
+	/// Vertical distance of contour lines in blocks
+	#[arg(long, value_name = "BLOCKS", default_value_t = 8, value_parser = clap::value_parser!(i32).range(1..))]
+	pub contour_interval: i32,
+	/// Shade the map by the slope of the ground instead of its height
+	///
+	/// Slopes facing the sun are brightened, and slopes facing away from it
+	/// are darkened, giving the terrain a three-dimensional look.
+	#[arg(long)]
+	pub hillshade: bool,
+	/// Direction of the sun used for hillshading, in degrees clockwise from north
+	#[arg(long, value_name = "DEGREES", default_value_t = 315, value_parser = clap::value_parser!(i32).range(0..360))]
+	pub sun_azimuth: i32,
+	/// Height of the sun used for hillshading, in degrees above the horizon
+	#[arg(long, value_name = "DEGREES", default_value_t = 45, value_parser = clap::value_parser!(i32).range(1..=90))]
+	pub sun_elevation: i32,
+	/// Darken ravines, cave entrances and other crevices by the given percentage
+	///
+	/// Block columns surrounded by higher ground are darkened by up to the
+	/// given percentage, depending on how much of the sky is hidden by the
+	/// surrounding terrain.
+	#[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(i32).range(1..=100))]
+	pub ambient_occlusion: Option<i32>,
+	/// Let mountains and tall builds cast shadows in the direction of the sun
+	///
+	/// The position of the sun is set by --sun-azimuth and --sun-elevation.
+	#[arg(long)]
+	pub shadows: bool,
+	/// Color water by the depth of the water column
+	///
+	/// Water is colored with a gradient from --water-colors instead of the
+	/// biome water colors.
+	#[arg(long)]
+	pub water_shading: bool,
+	/// Colors of shallow and deep water for --water-shading, in #rrggbb notation
+	#[arg(
+		long,
+		value_name = "SHALLOW,DEEP",
+		value_delimiter = ',',
+		default_values = ["#3fb8af", "#0b1f5c"],
+	)]
+	pub water_colors: Vec<String>,
+	/// Water depth in blocks at which the deep water color is reached
+	#[arg(long, value_name = "BLOCKS", default_value_t = 32, value_parser = clap::value_parser!(i32).range(1..))]
+	pub water_depth: i32,
+	/// Show the ground below water up to the given depth with --water-shading
+	///
+	/// The ground fades out as the water gets deeper.
+	#[arg(long, value_name = "BLOCKS", value_parser = clap::value_parser!(i32).range(1..))]
+	pub water_floor_cutoff: Option<i32>,
+	/// Gamma correction applied to the final map colors
+	///
+	/// Values above 1 brighten dark colors, values below 1 darken them.
+	#[arg(long, value_name = "GAMMA", default_value_t = 1.0)]
+	pub gamma: f32,
+	/// Brightness of the final map colors in percent
+	#[arg(long, value_name = "PERCENT", default_value_t = 100, value_parser = clap::value_parser!(i32).range(0..))]
+	pub brightness: i32,
+	/// Contrast of the final map colors in percent
+	#[arg(long, value_name = "PERCENT", default_value_t = 100, value_parser = clap::value_parser!(i32).range(0..))]
+	pub contrast: i32,
+	/// Saturation of the final map colors in percent
+	///
+	/// 0 results in a grayscale map.
+	#[arg(long, value_name = "PERCENT", default_value_t = 100, value_parser = clap::value_parser!(i32).range(0..))]
+	pub saturation: i32,
+	/// Block coordinates the viewer is centered on initially, given as X,Z
+	///
+	/// Only applies to the overworld. By default, the viewer is centered on
+	/// the world spawn.
+	#[arg(long, value_name = "X,Z", value_parser = parse_coords, allow_hyphen_values = true)]
+	pub view_center: Option<(i32, i32)>,
+	/// Initial zoom level of the viewer
+	///
+	/// At level 0, a block is shown as a single pixel; each level above or
+	/// below zooms in or out by a factor of 2.
+	#[arg(long, value_name = "LEVEL", allow_negative_numbers = true)]
+	pub view_zoom: Option<i32>,
+	/// Lowest zoom level the viewer can be zoomed out to
+	///
+	/// By default, the map can be zoomed out until it fits on a single tile.
+	#[arg(long, value_name = "LEVEL", allow_negative_numbers = true)]
+	pub min_zoom: Option<i32>,
+	/// Highest zoom level the viewer can be zoomed in to
+	///
+	/// Defaults to 3.
+	#[arg(long, value_name = "LEVEL", allow_negative_numbers = true)]
+	pub max_zoom: Option<i32>,
+	/// Omit the spawn point of the world from the viewer metadata
+	///
+	/// Without a spawn point, the viewer is centered on the origin unless
+	/// --view-center is given.
+	#[arg(long, conflicts_with_all = ["spawn", "spawn_chunks"])]
+	pub hide_spawn: bool,
+	/// Spawn point published in the viewer metadata instead of the actual
+	/// spawn point of the world, given as X,Z
+	///
+	/// Like --view-center, the coordinates are scaled down for the Nether.
+	#[arg(long, value_name = "X,Z", value_parser = parse_coords, allow_hyphen_values = true, conflicts_with = "spawn_chunks")]
+	pub spawn: Option<(i32, i32)>,
+	/// Include the world seed in the viewer metadata
+	///
+	/// The seed is shown by the viewer, e.g. for use with seed map tools.
+	#[arg(long)]
+	pub publish_seed: bool,
+	/// Archive the viewer files modified in each run as a dated snapshot
+	///
+	/// Snapshots are stored in the snapshots directory of the data directory
+	/// and listed in its index.json, e.g. for time-lapse playback. The first
+	/// snapshot contains the complete map; further snapshots only contain the
+	/// tiles and metadata that have changed.
+	#[arg(long)]
+	pub snapshots: bool,
+	/// Maximum number of snapshots to keep
+	///
+	/// When older snapshots are removed, their files are merged into the
+	/// oldest remaining snapshot.
+	#[arg(long, value_name = "N", requires = "snapshots", value_parser = clap::value_parser!(u32).range(1..))]
+	pub keep_snapshots: Option<u32>,
+	/// Maximum age of kept snapshots in days
+	///
+	/// The newest snapshot is always kept.
+	#[arg(long, value_name = "DAYS", requires = "snapshots")]
+	pub snapshot_max_age: Option<u64>,
+	/// Additionally store the tiles in the layout of Minecraft Overviewer
+	///
+	/// The tiles of each layer are written to the overviewer/<layer>
+	/// directory of each dimension, named like the tiles of an Overviewer
+	/// tileset with 512x512 pixel tiles and one zoom level per mipmap level.
+	#[arg(long)]
+	pub overviewer_layout: bool,
+	/// JSON file with translations of the viewer labels
+	///
+	/// The file must contain an object mapping locales like "de" or "pt-BR"
+	/// to objects with optional "layers", "dimensions" and "ui" objects, each
+	/// mapping names to translated labels, for example
+	/// `{"de": {"layers": {"map": "Blöcke"}, "ui": {"biome": "Biom"}}}`. The
+	/// viewer uses the translations of the preferred language of the browser.
+	#[arg(long, value_name = "FILE")]
+	pub labels: Option<PathBuf>,
+	/// Marker file of the squaremap or Pl3xMap plugin to show in the viewer
+	///
+	/// The file must contain a list of marker layers like the markers.json
+	/// written by these plugins for each world. The markers are shown in the
+	/// overworld unless a dimension is given as DIMENSION=FILE. Can be
+	/// passed multiple times.
+	#[arg(long, value_name = "[DIMENSION=]FILE", value_parser = parse_markers)]
+	pub markers: Vec<(Dimension, PathBuf)>,
+	/// Title of the map, shown by the viewer
+	#[arg(long)]
+	pub title: Option<String>,
+	/// Description of the map, shown by the viewer below the title
+	#[arg(long)]
+	pub description: Option<String>,
+	/// Attribution shown by the viewer next to the MinedMap credit
+	///
+	/// May contain HTML links.
+	#[arg(long)]
+	pub attribution: Option<String>,
+	/// Additional entry for custom viewers, given as KEY=VALUE
+	///
+	/// Can be passed multiple times. The entries are written to the `extra`
+	/// object of the viewer metadata.
+	#[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+	pub extra: Vec<(String, String)>,
+	/// Write the viewer into the output directory as well
+	///
+	/// The output directory then contains a complete map site, with the map
+	/// data stored in its `data` subdirectory. The viewer files are only
+	/// written locally and not uploaded to other output targets.
+	#[cfg(feature = "viewer")]
+	#[arg(long)]
+	pub with_viewer: bool,
+	/// Upload generated tiles and metadata to the given S3 bucket
+	///
+	/// Credentials are taken from the AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY
+	/// environment variables or the AWS credentials file. The data directory
+	/// is still used to keep track of incremental updates.
+	#[cfg(feature = "s3")]
+	#[arg(long)]
+	pub s3_bucket: Option<String>,
+	/// Region of the S3 bucket
+	#[cfg(feature = "s3")]
+	#[arg(long, default_value = "us-east-1")]
+	pub s3_region: String,
+	/// Custom S3 endpoint URL for S3-compatible storage like MinIO
+	#[cfg(feature = "s3")]
+	#[arg(long)]
+	pub s3_endpoint: Option<String>,
+	/// Prefix for all uploaded object keys
+	#[cfg(feature = "s3")]
+	#[arg(long, default_value = "")]
+	pub s3_prefix: String,
+}
+
+/// Parses block coordinates in the format X,Z
+pub(crate) fn parse_coords(s: &str) -> Result<(i32, i32), String> {
+	let (x, z) = s
+		.split_once(',')
+		.ok_or_else(|| format!("Invalid coordinates {}, expected X,Z", s))?;
+	let parse = |v: &str| {
+		v.trim()
+			.parse()
+			.map_err(|_| format!("Invalid coordinates {}, expected X,Z", s))
+	};
+	Ok((parse(x)?, parse(z)?))
+}
+
+/// Returns the number of days between the Unix epoch and a date of the
+/// proleptic Gregorian calendar
+///
+/// *month* and *day* start at 1.
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+	// Based on http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+	let year = year - i64::from(month <= 2);
+	let era = year.div_euclid(400);
+	let yoe = year - era * 400;
+	let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+	let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+	era * 146097 + doe - 719468
+}
+
+/// Formats a timestamp in seconds since the Unix epoch as a UTC date and time
+pub(crate) fn format_timestamp(timestamp: u32) -> String {
+	let days = i64::from(timestamp / 86400);
+	let secs = timestamp % 86400;
+
+	// Based on http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+	let z = days + 719468;
+	let era = z.div_euclid(146097);
+	let doe = z - era * 146097;
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400 + i64::from(month <= 2);
+
+	format!(
+		"{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+		year,
+		month,
+		day,
+		secs / 3600,
+		secs / 60 % 60,
+		secs % 60,
+	)
+}
+
+/// Parses a metadata entry in the format KEY=VALUE
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+	let (key, value) = s
+		.split_once('=')
+		.ok_or_else(|| format!("Invalid entry {}, expected KEY=VALUE", s))?;
+	Ok((key.to_string(), value.to_string()))
+}
+
+/// Parses a marker file argument in the format [DIMENSION=]FILE
+///
+/// The argument is only split when the part before the first `=` is a
+/// dimension name, so file names containing `=` can be passed as well.
+fn parse_markers(s: &str) -> Result<(Dimension, PathBuf), String> {
+	if let Some((dimension, path)) = s.split_once('=') {
+		if let Ok(dimension) = Dimension::from_str(dimension, true) {
+			return Ok((dimension, path.into()));
+		}
+	}
+	Ok((Dimension::Overworld, s.into()))
+}
+
+/// Checks that a world can be stored in a subdirectory of the data directory
+///
+/// With multiple worlds, each world is stored in a subdirectory named like
+/// its world directory, which must not collide with the files and
+/// directories MinedMap creates in a data or dimension directory.
+fn check_world_name(name: &str) -> Result<()> {
+	let first = name.split('/').next().unwrap_or_default();
+	let collision = TileKind::ALL
+		.iter()
+		.map(|kind| kind.name())
+		.chain(
+			[Dimension::Nether, Dimension::End]
+				.into_iter()
+				.filter_map(Dimension::subdir),
+		)
+		.chain([
+			"processed",
+			"info.json",
+			"dimensions.json",
+			overviewer_writer::OVERVIEWER_DIR,
+			snapshot_archiver::SNAPSHOT_DIR,
+		])
+		.find(|&reserved| reserved == first);
+
+	if let Some(reserved) = collision {
+		bail!(
+			"World directory {} can't be rendered, as its name collides with the output file {}; please rename it",
+			name,
+			reserved
+		);
+	}
+
+	Ok(())
 }
 
 /// Configures the Rayon thread pool for parallel processing
@@ -51,8 +658,12 @@ fn setup_threads(num_threads: usize) -> Result<()> {
 
 /// MinedMap CLI main function
 pub fn cli() -> Result<()> {
-	let args = Args::parse();
-	let config = Config::new(&args);
+	let mut args = Args::parse();
+	if let Some(command) = args.command.take() {
+		return crate::tools::run(command);
+	}
+
+	let config = Config::new(&args)?;
 
 	setup_threads(config.num_threads)?;
 
@@ -60,10 +671,71 @@ pub fn cli() -> Result<()> {
 		.build()
 		.unwrap();
 
-	let regions = RegionProcessor::new(&config).run()?;
-	TileRenderer::new(&config, &rt, &regions).run()?;
-	let tiles = TileMipmapper::new(&config, &regions).run()?;
-	MetadataWriter::new(&config, &tiles).run()?;
+	let worlds = layout::discover(&config.input)?;
+	let multi_world = worlds.len() > 1;
+	if multi_world {
+		for world in &worlds {
+			check_world_name(&world.name)?;
+		}
+	}
+
+	for world in &worlds {
+		let config = config.for_world(world)?;
+
+		// With multiple worlds, each world is stored in a subdirectory
+		let world_dir = if multi_world {
+			println!("Rendering world {}", world.name);
+			config.data_dir.join(&world.name)
+		} else {
+			config.data_dir.clone()
+		};
+
+		let mut dimensions = Vec::new();
+		for dimension in &world.dimensions {
+			let config = config.for_dimension(&world_dir, world, dimension)?;
+			if dimension.dimension != Dimension::Overworld {
+				println!("Rendering dimension {}", dimension.dimension.name());
+			}
+
+			let regions = RegionProcessor::new(&config).run()?;
+			TileRenderer::new(&config, &rt, &regions).run()?;
+			let tiles = TileMipmapper::new(&config, &regions).run()?;
+			OverlayRenderer::new(&config, &tiles).run()?;
+			if config.overviewer_layout {
+				OverviewerWriter::new(&config, &tiles).run()?;
+			}
+			dimensions.push(MetadataWriter::new(&config, &tiles).run()?);
+		}
+
+		if dimensions.len() > 1 {
+			metadata_writer::write_dimension_index(&config, &world_dir, &dimensions)?;
+		}
+	}
+
+	if multi_world {
+		metadata_writer::write_index(&config, &worlds)?;
+	}
+
+	if let Some(policy) = config.snapshots {
+		SnapshotArchiver::new(&config, policy).run()?;
+	}
+
+	#[cfg(feature = "viewer")]
+	if let (true, Some(output_dir)) = (args.with_viewer, &args.output_dir) {
+		viewer::write(output_dir)?;
+	}
+
+	if let Some(change_list_path) = &config.change_list_path {
+		config.changes.write(change_list_path)?;
+	}
+
+	if let Some(report_path) = &config.skip_corrupt_path {
+		config.corrupt_chunks.write(report_path)?;
+	}
+
+	if let Some(report_path) = &config.unknown_blocks_path {
+		config.unknown_blocks.write(report_path)?;
+	}
 
 	Ok(())
 }
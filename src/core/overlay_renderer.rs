@@ -0,0 +1,568 @@
+//! The [OverlayRenderer]
+
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+
+use super::common::*;
+use crate::{io::fs, types::*, world::de};
+
+/// Tile width/height
+const N: u32 = (BLOCKS_PER_CHUNK * CHUNKS_PER_REGION) as u32;
+
+/// Minimum distance of grid lines in pixels
+const MIN_SPACING: u32 = 4;
+
+/// Color of region boundaries
+const REGION_COLOR: image::Rgba<u8> = image::Rgba([0, 0, 0, 160]);
+
+/// Width of a glyph of the label font in pixels
+const GLYPH_WIDTH: u32 = 5;
+/// Height of a glyph of the label font in pixels
+const GLYPH_HEIGHT: u32 = 7;
+
+/// Bitmap font for region labels
+///
+/// Each row of a glyph is stored in the lower [GLYPH_WIDTH] bits of a byte,
+/// with the most significant bit being the leftmost pixel.
+const GLYPHS: [(char, [u8; GLYPH_HEIGHT as usize]); 13] = [
+	('0', [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e]),
+	('1', [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e]),
+	('2', [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f]),
+	('3', [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e]),
+	('4', [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02]),
+	('5', [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e]),
+	('6', [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e]),
+	('7', [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+	('8', [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e]),
+	('9', [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c]),
+	('r', [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10]),
+	('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c]),
+	('-', [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00]),
+];
+
+/// Returns the width of a text rendered by [draw_label]
+fn label_width(text: &str, scale: u32) -> u32 {
+	let len = text.chars().count() as u32;
+	(len * (GLYPH_WIDTH + 1) + 3) * scale
+}
+
+/// Draws a text label onto an image
+///
+/// The text is drawn in white on a dark background box with its top left
+/// corner at the given coordinates. Characters without a glyph are left
+/// blank, and pixels outside of the image are ignored.
+fn draw_label(image: &mut image::RgbaImage, x: u32, y: u32, scale: u32, text: &str) {
+	/// Color of the label text
+	const TEXT_COLOR: image::Rgba<u8> = image::Rgba([255, 255, 255, 255]);
+	/// Color of the label background box
+	const BACKGROUND_COLOR: image::Rgba<u8> = image::Rgba([0, 0, 0, 128]);
+
+	let mut put_pixel = |px: u32, py: u32, color| {
+		if px < image.width() && py < image.height() {
+			image.put_pixel(px, py, color);
+		}
+	};
+
+	let width = label_width(text, scale);
+	let height = (GLYPH_HEIGHT + 4) * scale;
+	for py in y..y + height {
+		for px in x..x + width {
+			put_pixel(px, py, BACKGROUND_COLOR);
+		}
+	}
+
+	for (i, c) in text.chars().enumerate() {
+		let Some((_, glyph)) = GLYPHS.iter().find(|(glyph_char, _)| *glyph_char == c) else {
+			continue;
+		};
+		let glyph_x = x + (2 + i as u32 * (GLYPH_WIDTH + 1)) * scale;
+		let glyph_y = y + 2 * scale;
+
+		for (row, bits) in glyph.iter().enumerate() {
+			for col in 0..GLYPH_WIDTH {
+				if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+					continue;
+				}
+				for dy in 0..scale {
+					for dx in 0..scale {
+						put_pixel(
+							glyph_x + col * scale + dx,
+							glyph_y + row as u32 * scale + dy,
+							TEXT_COLOR,
+						);
+					}
+				}
+			}
+		}
+	}
+}
+
+/// Determines whether slimes can spawn in a chunk of a Java Edition world
+///
+/// Reimplements the check of the game, which seeds a `java.util.Random`
+/// from the world seed and the chunk coordinates and draws a number below 10.
+fn is_slime_chunk(seed: i64, x: i32, z: i32) -> bool {
+	/// Multiplier of the linear congruential generator of `java.util.Random`
+	const MULTIPLIER: i64 = 0x5deece66d;
+	/// Mask for the 48 bits of state of `java.util.Random`
+	const MASK: i64 = (1 << 48) - 1;
+
+	let chunk_seed = seed
+		.wrapping_add(x.wrapping_mul(x).wrapping_mul(0x4c1906).into())
+		.wrapping_add(x.wrapping_mul(0x5ac0db).into())
+		.wrapping_add(i64::from(z.wrapping_mul(z)).wrapping_mul(0x4307a7))
+		.wrapping_add(z.wrapping_mul(0x5f24f).into())
+		^ 0x3ad8025f;
+
+	// Equivalent of `new Random(chunk_seed).nextInt(10) == 0`
+	let mut state = (chunk_seed ^ MULTIPLIER) & MASK;
+	loop {
+		state = state.wrapping_mul(MULTIPLIER).wrapping_add(0xb) & MASK;
+		let bits = (state >> 17) as i32;
+		let value = bits % 10;
+		if bits.wrapping_sub(value).wrapping_add(9) >= 0 {
+			return value == 0;
+		}
+	}
+}
+
+/// Returns true if a pixel coordinate lies on a grid line with the given spacing
+///
+/// With `double` set, lines are drawn on both sides of the boundary to
+/// emphasize them. Lines are left out completely when the spacing is below
+/// [MIN_SPACING].
+fn is_grid_line(v: u32, spacing: u32, double: bool) -> bool {
+	if spacing < MIN_SPACING {
+		return false;
+	}
+	v.is_multiple_of(spacing) || (double && v % spacing == spacing - 1)
+}
+
+/// Area of the spawn chunks
+#[derive(Debug, Clone, Copy)]
+struct SpawnChunks {
+	/// X coordinate of the chunk containing the spawn point
+	x: i32,
+	/// Z coordinate of the chunk containing the spawn point
+	z: i32,
+	/// Radius of the area of loaded chunks around the spawn chunk
+	///
+	/// Entities are only processed in a radius that is smaller by 2.
+	radius: i32,
+}
+
+impl SpawnChunks {
+	/// First data version with the spawnChunkRadius game rule (24w03a)
+	const SPAWN_CHUNK_RADIUS_DATA_VERSION: u32 = 3804;
+	/// Default of the spawnChunkRadius game rule
+	const DEFAULT_RADIUS: i32 = 2;
+	/// Spawn chunk radius of Minecraft versions before the spawnChunkRadius game rule
+	const LEGACY_RADIUS: i32 = 11;
+
+	/// Determines the spawn chunk area from the `level.dat`
+	fn new(level_dat: &de::LevelDatData) -> Self {
+		let radius = match level_dat.game_rules.get("spawnChunkRadius") {
+			Some(fastnbt::Value::String(value)) => value.parse().ok(),
+			_ => None,
+		};
+		let radius = radius.unwrap_or_else(|| {
+			if level_dat.data_version.unwrap_or_default() >= Self::SPAWN_CHUNK_RADIUS_DATA_VERSION {
+				Self::DEFAULT_RADIUS
+			} else {
+				Self::LEGACY_RADIUS
+			}
+		});
+
+		SpawnChunks {
+			x: level_dat.spawn_x >> 4,
+			z: level_dat.spawn_z >> 4,
+			radius,
+		}
+	}
+
+	/// Returns the radius of the chunk area around the spawn chunk that
+	/// contains the given block coordinates
+	fn distance(&self, x: i64, z: i64) -> i64 {
+		let dx = (x >> 4) - i64::from(self.x);
+		let dz = (z >> 4) - i64::from(self.z);
+		dx.abs().max(dz.abs())
+	}
+}
+
+/// World properties used by overlays derived from the `level.dat`
+#[derive(Debug)]
+struct LevelInfo {
+	/// Time of the last modification of the `level.dat`
+	timestamp: SystemTime,
+	/// World seed used to determine slime chunks
+	slime_seed: Option<i64>,
+	/// Spawn chunk area
+	spawn_chunks: Option<SpawnChunks>,
+	/// World border of the dimension
+	world_border: Option<WorldBorder>,
+}
+
+/// Renders overlay tiles that are drawn separately for each mipmap level
+///
+/// Unlike the overlays generated from the region data, these overlays
+/// consist of thin lines that would become blurry when scaled down, so each
+/// mipmap level is rendered from scratch.
+pub struct OverlayRenderer<'a> {
+	/// Common MinedMap configuration from command line
+	config: &'a Config,
+	/// Map of populated tiles for each mipmap level
+	tiles: &'a [TileCoordMap],
+}
+
+impl<'a> OverlayRenderer<'a> {
+	/// Constructs a new OverlayRenderer
+	pub fn new(config: &'a Config, tiles: &'a [TileCoordMap]) -> Self {
+		OverlayRenderer { config, tiles }
+	}
+
+	/// Renders a chunk grid tile for a mipmap level
+	///
+	/// Region boundaries are drawn on both sides to emphasize them. Grid
+	/// lines are left out on mipmap levels where they would be too close to
+	/// each other.
+	fn render_chunk_grid(level: usize) -> image::RgbaImage {
+		/// Color of chunk boundaries
+		const CHUNK_COLOR: image::Rgba<u8> = image::Rgba([0, 0, 0, 64]);
+
+		let region_size = N.checked_shr(level as u32).unwrap_or(0);
+		let chunk_size = region_size / CHUNKS_PER_REGION as u32;
+
+		image::RgbaImage::from_fn(N, N, |x, z| {
+			if is_grid_line(x, region_size, true) || is_grid_line(z, region_size, true) {
+				REGION_COLOR
+			} else if is_grid_line(x, chunk_size, false) || is_grid_line(z, chunk_size, false) {
+				CHUNK_COLOR
+			} else {
+				image::Rgba([0, 0, 0, 0])
+			}
+		})
+	}
+
+	/// Renders a region grid tile for a mipmap level
+	///
+	/// Each region is labeled with the `r.X.Z` name of its region file in its
+	/// top left corner, as long as the label fits into the region on the
+	/// given mipmap level.
+	fn render_region_grid(level: usize, coords: TileCoords) -> image::RgbaImage {
+		let region_size = N.checked_shr(level as u32).unwrap_or(0);
+
+		let mut image = image::RgbaImage::from_fn(N, N, |x, z| {
+			if is_grid_line(x, region_size, true) || is_grid_line(z, region_size, true) {
+				REGION_COLOR
+			} else {
+				image::Rgba([0, 0, 0, 0])
+			}
+		});
+
+		// Even the shortest labels don't fit into smaller regions
+		if region_size < 32 {
+			return image;
+		}
+		let scale = if region_size >= N / 2 { 2 } else { 1 };
+
+		let regions = 1 << level;
+		for dz in 0..regions {
+			for dx in 0..regions {
+				let label = format!(
+					"r.{}.{}",
+					(coords.x << level) + dx,
+					(coords.z << level) + dz,
+				);
+				if label_width(&label, scale) + 8 > region_size {
+					continue;
+				}
+				draw_label(
+					&mut image,
+					dx as u32 * region_size + 4,
+					dz as u32 * region_size + 4,
+					scale,
+					&label,
+				);
+			}
+		}
+
+		image
+	}
+
+	/// Renders a slime chunk tile for a mipmap level
+	///
+	/// Slime chunks are only shown on mipmap levels where each chunk still
+	/// covers at least one pixel.
+	fn render_slime_chunks(
+		level: usize,
+		coords: TileCoords,
+		seed: Option<i64>,
+	) -> image::RgbaImage {
+		/// Color of slime chunks
+		const SLIME_COLOR: image::Rgba<u8> = image::Rgba([0, 255, 64, 96]);
+
+		let mut image = image::RgbaImage::new(N, N);
+
+		let Some(seed) = seed else {
+			return image;
+		};
+		let chunk_size = (BLOCKS_PER_CHUNK as u32)
+			.checked_shr(level as u32)
+			.unwrap_or(0);
+		if chunk_size == 0 {
+			return image;
+		}
+
+		let chunks = N / chunk_size;
+		for cz in 0..chunks {
+			for cx in 0..chunks {
+				let x = coords.x * chunks as i32 + cx as i32;
+				let z = coords.z * chunks as i32 + cz as i32;
+				if !is_slime_chunk(seed, x, z) {
+					continue;
+				}
+
+				for pz in cz * chunk_size..(cz + 1) * chunk_size {
+					for px in cx * chunk_size..(cx + 1) * chunk_size {
+						image.put_pixel(px, pz, SLIME_COLOR);
+					}
+				}
+			}
+		}
+
+		image
+	}
+
+	/// Renders a spawn chunk tile for a mipmap level
+	///
+	/// The area of loaded spawn chunks is highlighted, with a stronger
+	/// highlight for the inner area in which entities are processed.
+	fn render_spawn_chunks(
+		level: usize,
+		coords: TileCoords,
+		spawn_chunks: Option<SpawnChunks>,
+	) -> image::RgbaImage {
+		/// Color of loaded spawn chunks
+		const LOADED_COLOR: image::Rgba<u8> = image::Rgba([255, 160, 0, 48]);
+		/// Color of spawn chunks processing entities
+		const ENTITY_COLOR: image::Rgba<u8> = image::Rgba([255, 160, 0, 112]);
+
+		image::RgbaImage::from_fn(N, N, |x, z| {
+			let Some(spawn_chunks) = spawn_chunks else {
+				return image::Rgba([0, 0, 0, 0]);
+			};
+
+			// Block coordinates of the top left corner of the pixel
+			let block_x = (i64::from(coords.x) * i64::from(N) + i64::from(x)) << level;
+			let block_z = (i64::from(coords.z) * i64::from(N) + i64::from(z)) << level;
+
+			let distance = spawn_chunks.distance(block_x, block_z);
+			if distance <= i64::from(spawn_chunks.radius) - 2 {
+				ENTITY_COLOR
+			} else if distance <= i64::from(spawn_chunks.radius) {
+				LOADED_COLOR
+			} else {
+				image::Rgba([0, 0, 0, 0])
+			}
+		})
+	}
+
+	/// Renders a world border tile for a mipmap level
+	///
+	/// The border is drawn as a line of [BORDER_WIDTH] pixels just inside of
+	/// the playable area, independent of the mipmap level.
+	fn render_world_border(
+		level: usize,
+		coords: TileCoords,
+		world_border: Option<WorldBorder>,
+	) -> image::RgbaImage {
+		/// Color of the world border
+		const BORDER_COLOR: image::Rgba<u8> = image::Rgba([255, 0, 0, 224]);
+		/// Width of the world border line in pixels
+		const BORDER_WIDTH: f64 = 2.0;
+
+		let mut image = image::RgbaImage::new(N, N);
+
+		let Some(world_border) = world_border else {
+			return image;
+		};
+
+		// Bounds of the playable area in pixels, relative to the tile
+		let scale = f64::from(1u32 << level);
+		let ((min_x, max_x), (min_z, max_z)) = world_border.bounds();
+		let tile_x = f64::from(coords.x) * f64::from(N);
+		let tile_z = f64::from(coords.z) * f64::from(N);
+		let (min_x, max_x) = (min_x / scale - tile_x, max_x / scale - tile_x);
+		let (min_z, max_z) = (min_z / scale - tile_z, max_z / scale - tile_z);
+
+		for (z, row) in image.enumerate_rows_mut() {
+			let z = f64::from(z) + 0.5;
+			if z < min_z || z > max_z {
+				continue;
+			}
+
+			for (x, _, pixel) in row {
+				let x = f64::from(x) + 0.5;
+				if x < min_x || x > max_x {
+					continue;
+				}
+
+				let distance = (x - min_x).min(max_x - x).min(z - min_z).min(max_z - z);
+				if distance < BORDER_WIDTH {
+					*pixel = BORDER_COLOR;
+				}
+			}
+		}
+
+		image
+	}
+
+	/// Returns true for overlays derived from the properties stored in the `level.dat`
+	///
+	/// All other overlays don't depend on the save data at all.
+	pub(super) fn uses_level_dat(kind: TileKind) -> bool {
+		matches!(
+			kind,
+			TileKind::SlimeChunks | TileKind::SpawnChunks | TileKind::WorldBorder
+		)
+	}
+
+	/// Reads the world properties used by overlays derived from the `level.dat`
+	fn read_level_info(&self) -> Result<LevelInfo> {
+		let timestamp = self.config.input.modified(&self.config.level_dat_path)?;
+		let level_dat = self.config.read_level_dat()?;
+
+		let kinds: Vec<_> = self.config.level_overlays().collect();
+		let bedrock = self.config.is_bedrock();
+
+		let slime_seed = if !kinds.contains(&TileKind::SlimeChunks) {
+			None
+		} else if bedrock {
+			eprintln!("Slime chunks are not supported for Bedrock Edition worlds");
+			None
+		} else {
+			let seed = level_dat.seed();
+			if seed.is_none() {
+				eprintln!("World seed not found, slime chunks can't be determined");
+			}
+			seed
+		};
+
+		let spawn_chunks = if !kinds.contains(&TileKind::SpawnChunks) {
+			None
+		} else if bedrock {
+			eprintln!("Spawn chunks are not supported for Bedrock Edition worlds");
+			None
+		} else {
+			Some(SpawnChunks::new(&level_dat))
+		};
+
+		let world_border = WorldBorder::new(&level_dat, self.config.dimension);
+
+		Ok(LevelInfo {
+			timestamp,
+			slime_seed,
+			spawn_chunks,
+			world_border,
+		})
+	}
+
+	/// Renders and saves a single overlay tile
+	///
+	/// Tiles are only rendered again when the `level.dat` has been modified
+	/// for overlays derived from it; other overlays don't depend on the save
+	/// data, so existing tiles are never rendered again.
+	fn render_tile(
+		&self,
+		kind: TileKind,
+		level: usize,
+		coords: TileCoords,
+		level_info: &LevelInfo,
+	) -> Result<()> {
+		let input_timestamp = if Self::uses_level_dat(kind) {
+			level_info.timestamp
+		} else {
+			SystemTime::UNIX_EPOCH
+		};
+
+		let output_path = self.config.tile_path(kind, level, coords);
+		let output_timestamp = fs::read_timestamp(&output_path, FILE_META_VERSION);
+		if Some(input_timestamp) <= output_timestamp {
+			return Ok(());
+		}
+
+		println!(
+			"Rendering overlay tile {}",
+			output_path
+				.strip_prefix(&self.config.data_dir)
+				.expect("tile path must be in output directory")
+				.display(),
+		);
+
+		let image = match kind {
+			TileKind::ChunkGrid => Self::render_chunk_grid(level),
+			TileKind::RegionGrid => Self::render_region_grid(level, coords),
+			TileKind::SlimeChunks => {
+				Self::render_slime_chunks(level, coords, level_info.slime_seed)
+			}
+			TileKind::SpawnChunks => {
+				Self::render_spawn_chunks(level, coords, level_info.spawn_chunks)
+			}
+			TileKind::WorldBorder => {
+				Self::render_world_border(level, coords, level_info.world_border)
+			}
+			_ => unreachable!("not a per-level overlay: {:?}", kind),
+		};
+
+		fs::create_with_timestamp(
+			&output_path,
+			FILE_META_VERSION,
+			fs::SettingsHash::default(),
+			input_timestamp,
+			|file| {
+				image
+					.write_to(file, image::ImageFormat::Png)
+					.context("Failed to save image")
+			},
+			|status| self.config.store_output(&output_path, status),
+		)
+	}
+
+	/// Runs the overlay generation
+	pub fn run(self) -> Result<()> {
+		let kinds: Vec<_> = self.config.level_overlays().collect();
+		if kinds.is_empty() {
+			return Ok(());
+		}
+
+		let level_info = if kinds.iter().any(|&kind| Self::uses_level_dat(kind)) {
+			self.read_level_info()?
+		} else {
+			LevelInfo {
+				timestamp: SystemTime::UNIX_EPOCH,
+				slime_seed: None,
+				spawn_chunks: None,
+				world_border: None,
+			}
+		};
+
+		for (level, tiles) in self.tiles.iter().enumerate() {
+			for &kind in &kinds {
+				fs::create_dir_all(&self.config.tile_dir(kind, level))?;
+			}
+
+			tiles.0.par_iter().try_for_each(|(&z, xs)| {
+				xs.par_iter().try_for_each(|&x| {
+					for &kind in &kinds {
+						self.render_tile(kind, level, TileCoords { x, z }, &level_info)?;
+					}
+					anyhow::Ok(())
+				})
+			})?;
+		}
+
+		Ok(())
+	}
+}
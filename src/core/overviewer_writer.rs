@@ -0,0 +1,263 @@
+//! The [OverviewerWriter]
+
+use std::{
+	collections::HashSet,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+
+use super::common::*;
+use crate::{io::fs, types::*};
+
+/// Tile width/height
+const N: u32 = (BLOCKS_PER_CHUNK * CHUNKS_PER_REGION) as u32;
+
+/// Name of the directory of the tiles in the Overviewer layout
+pub(super) const OVERVIEWER_DIR: &str = "overviewer";
+
+/// Writes the map tiles in the tile layout of Minecraft Overviewer
+///
+/// Overviewer organizes its tiles as a quadtree of fixed depth centered on
+/// the origin. The whole map is shown by `base.png`; the tiles of each
+/// further zoom level are stored in the numbered subdirectories of the
+/// tile covering them, where 0 to 3 denote the upper-left, upper-right,
+/// lower-left and lower-right quadrant. MinedMap's mipmap levels are aligned
+/// to this quadtree, so all tiles except the base tile are copied unchanged.
+pub struct OverviewerWriter<'a> {
+	/// Common MinedMap configuration from command line
+	config: &'a Config,
+	/// Populated tiles of each mipmap level
+	tiles: &'a [TileCoordMap],
+}
+
+impl<'a> OverviewerWriter<'a> {
+	/// Constructs a new OverviewerWriter
+	pub fn new(config: &'a Config, tiles: &'a [TileCoordMap]) -> Self {
+		OverviewerWriter { config, tiles }
+	}
+
+	/// Returns the path of a tile in the Overviewer layout
+	///
+	/// *depth* is the Overviewer zoom level of the tile, with the base
+	/// tile at depth 0. *coords* are the coordinates of the MinedMap tile
+	/// at the corresponding mipmap level.
+	fn tile_path(&self, kind: TileKind, depth: usize, coords: TileCoords) -> PathBuf {
+		let mut path = self
+			.config
+			.output_dir
+			.join(OVERVIEWER_DIR)
+			.join(kind.name());
+		if depth == 0 {
+			return path.join("base.png");
+		}
+
+		let offset = 1 << (depth - 1);
+		let (x, z) = (coords.x + offset, coords.z + offset);
+		for bit in (1..depth).rev() {
+			path.push(Self::quadrant(x >> bit, z >> bit).to_string());
+		}
+		path.push(format!("{}.png", Self::quadrant(x, z)));
+		path
+	}
+
+	/// Returns the Overviewer quadrant number for the lowest bit of the
+	/// shifted tile coordinates
+	fn quadrant(x: i32, z: i32) -> i32 {
+		(x & 1) + 2 * (z & 1)
+	}
+
+	/// Copies a mipmap tile into the Overviewer layout
+	///
+	/// Returns the path of the copied tile, or [None] if the tile does not exist.
+	fn copy_tile(
+		&self,
+		kind: TileKind,
+		level: usize,
+		depth: usize,
+		coords: TileCoords,
+	) -> Result<Option<PathBuf>> {
+		let source_path = self.config.tile_path(kind, level, coords);
+		let Ok(input_timestamp) = fs::modified_timestamp(&source_path) else {
+			return Ok(None);
+		};
+
+		// The timestamps are compared for equality, as the tile at the same
+		// path may have been copied from a different source before the depth
+		// of the quadtree changed
+		let output_path = self.tile_path(kind, depth, coords);
+		let output_timestamp = fs::read_timestamp(&output_path, FILE_META_VERSION);
+		if Some(input_timestamp) == output_timestamp {
+			return Ok(Some(output_path));
+		}
+
+		if let Some(parent) = output_path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		fs::create_with_timestamp(
+			&output_path,
+			FILE_META_VERSION,
+			fs::SettingsHash::default(),
+			input_timestamp,
+			|file| {
+				let mut source = std::fs::File::open(&source_path).with_context(|| {
+					format!("Failed to open source tile {}", source_path.display())
+				})?;
+				std::io::copy(&mut source, file)?;
+				Ok(())
+			},
+			|status| self.config.store_output(&output_path, status),
+		)?;
+
+		Ok(Some(output_path))
+	}
+
+	/// Renders the base tile of the Overviewer layout from the tiles of the
+	/// topmost mipmap level
+	///
+	/// Returns the path of the base tile, or [None] if no tiles exist.
+	fn render_base<P: image::PixelWithColorType>(&self, kind: TileKind) -> Result<Option<PathBuf>>
+	where
+		[P::Subpixel]: image::EncodableLayout,
+		image::ImageBuffer<P, Vec<P::Subpixel>>: Into<image::DynamicImage>,
+	{
+		let level = self.tiles.len() - 1;
+		let sources: Vec<_> = [(0, 0), (0, 1), (1, 0), (1, 1)]
+			.into_iter()
+			.filter_map(|(dx, dz)| {
+				let path = self.config.tile_path(
+					kind,
+					level,
+					TileCoords {
+						x: dx - 1,
+						z: dz - 1,
+					},
+				);
+				let timestamp = fs::modified_timestamp(&path).ok()?;
+				Some(((dx, dz), path, timestamp))
+			})
+			.collect();
+
+		let Some(input_timestamp) = sources.iter().map(|(_, _, ts)| *ts).max() else {
+			return Ok(None);
+		};
+
+		let output_path = self.tile_path(kind, 0, TileCoords { x: 0, z: 0 });
+		let output_timestamp = fs::read_timestamp(&output_path, FILE_META_VERSION);
+		if Some(input_timestamp) == output_timestamp {
+			return Ok(Some(output_path));
+		}
+
+		let mut image: image::DynamicImage =
+			image::ImageBuffer::<P, Vec<P::Subpixel>>::new(N, N).into();
+
+		for ((dx, dz), source_path, _) in sources {
+			let source = image::open(&source_path).with_context(|| {
+				format!("Failed to read source image {}", source_path.display())
+			})?;
+			let resized = source.resize(N / 2, N / 2, image::imageops::FilterType::Triangle);
+			image::imageops::overlay(
+				&mut image,
+				&resized,
+				dx as i64 * (N / 2) as i64,
+				dz as i64 * (N / 2) as i64,
+			);
+		}
+
+		if let Some(parent) = output_path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		fs::create_with_timestamp(
+			&output_path,
+			FILE_META_VERSION,
+			fs::SettingsHash::default(),
+			input_timestamp,
+			|file| {
+				image
+					.write_to(file, image::ImageFormat::Png)
+					.context("Failed to save image")
+			},
+			|status| self.config.store_output(&output_path, status),
+		)?;
+
+		Ok(Some(output_path))
+	}
+
+	/// Removes tiles of the Overviewer layout that are not part of the map anymore
+	///
+	/// Besides tiles of removed regions, this removes all tiles of a previous
+	/// run when the depth of the quadtree has changed.
+	fn remove_stale(&self, dir: &Path, tiles: &HashSet<PathBuf>) -> Result<()> {
+		let Ok(entries) = dir.read_dir() else {
+			return Ok(());
+		};
+
+		for entry in entries.filter_map(|entry| entry.ok()) {
+			let path = entry.path();
+			if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+				self.remove_stale(&path, tiles)?;
+				// Only succeeds for directories that have become empty
+				let _ = std::fs::remove_dir(&path);
+				continue;
+			}
+			if path.extension().is_none_or(|ext| ext != "png") || tiles.contains(&path) {
+				continue;
+			}
+
+			println!(
+				"Removing stale tile {}",
+				path.strip_prefix(&self.config.data_dir)
+					.expect("tile path must be in output directory")
+					.display(),
+			);
+			self.config.remove_output(&path)?;
+		}
+
+		Ok(())
+	}
+
+	/// Runs the Overviewer layout generation
+	pub fn run(self) -> Result<()> {
+		let depth = self.tiles.len();
+
+		// Data tiles only exist for the full-resolution mipmap level and
+		// can't be displayed as images
+		for kind in self.config.tile_kinds().filter(|kind| !kind.is_data()) {
+			let mut written: HashSet<PathBuf> = self
+				.tiles
+				.par_iter()
+				.enumerate()
+				.map(|(level, tiles)| {
+					tiles
+						.0
+						.par_iter()
+						.flat_map_iter(|(&z, xs)| xs.iter().map(move |&x| TileCoords { x, z }))
+						.filter_map(|coords| {
+							self.copy_tile(kind, level, depth - level, coords)
+								.transpose()
+						})
+						.collect::<Result<Vec<_>>>()
+				})
+				.collect::<Result<Vec<_>>>()?
+				.into_iter()
+				.flatten()
+				.collect();
+
+			let base = match kind {
+				TileKind::Lightmap => self.render_base::<image::LumaA<u8>>(kind)?,
+				_ => self.render_base::<image::Rgba<u8>>(kind)?,
+			};
+			written.extend(base);
+
+			let dir = self
+				.config
+				.output_dir
+				.join(OVERVIEWER_DIR)
+				.join(kind.name());
+			self.remove_stale(&dir, &written)?;
+		}
+
+		Ok(())
+	}
+}
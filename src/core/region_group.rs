@@ -66,6 +66,24 @@ impl<T> RegionGroup<T> {
 		self.neighs.get((3 * x + z + 4) as usize)?.as_ref()
 	}
 
+	/// Returns the RegionGroup centered at a neighboring element
+	///
+	/// Elements outside of the original 3x3 neighborhood are unpopulated.
+	/// Returns None if the new center element is not populated.
+	pub fn shift(&self, dx: i8, dz: i8) -> Option<Self>
+	where
+		T: Clone,
+	{
+		let center = self.get(dx, dz)?.clone();
+		Some(RegionGroup::new(|x, z| {
+			if (x, z) == (0, 0) {
+				Some(center.clone())
+			} else {
+				self.get(x + dx, z + dz).cloned()
+			}
+		}))
+	}
+
 	/// Runs a closure on each element to construct a new RegionGroup
 	pub fn map<U, F>(self, mut f: F) -> RegionGroup<U>
 	where
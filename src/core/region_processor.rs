@@ -1,44 +1,79 @@
 //! The [RegionProcessor] and related functions
 
-use std::{ffi::OsStr, path::Path, time::SystemTime};
+use std::{
+	cell::RefCell,
+	collections::{BTreeMap, BTreeSet},
+	io::{Cursor, Read},
+	path::Path,
+	thread,
+	time::{Duration, SystemTime},
+};
 
 use anyhow::{Context, Result};
+use glam::Vec3;
 use indexmap::IndexSet;
 use rayon::prelude::*;
 
 use super::common::*;
 use crate::{
 	io::{fs, storage},
-	resource::{self, Biome},
+	nbt::region::{ChunkError, RegionError},
+	resource::BlockFlag,
 	types::*,
-	world::{
-		self,
-		layer::{self, LayerData},
-	},
+	world::{self, layer},
 };
 
-/// Parses a filename in the format r.X.Z.mca into the contained X and Z values
-fn parse_region_filename(file_name: &OsStr) -> Option<TileCoords> {
-	let parts: Vec<_> = file_name.to_str()?.split('.').collect();
-	let &["r", x, z, "mca"] = parts.as_slice() else {
-		return None;
-	};
-
-	Some(TileCoords {
-		x: x.parse().ok()?,
-		z: z.parse().ok()?,
-	})
+/// Chunks skipped while reading a region, with the reason they were skipped
+type SkippedChunks = Vec<(ChunkCoords, anyhow::Error)>;
+
+/// 16-bit data tile image
+type DataImage = image::ImageBuffer<image::LumaA<u16>, Vec<u16>>;
+
+/// 16-bit data tile image with multiple values per pixel
+type MultiDataImage = image::ImageBuffer<image::Rgba<u16>, Vec<u16>>;
+
+/// Tile images generated while reading a region
+struct RegionTiles {
+	/// Lightmap tile
+	lightmap: image::GrayAlphaImage,
+	/// Tiles of the enabled overlays
+	overlays: BTreeMap<TileKind, image::RgbaImage>,
+	/// Tiles of the enabled data tile kinds
+	data: BTreeMap<TileKind, DataImage>,
+	/// Block data tile, if enabled
+	block_data: Option<MultiDataImage>,
+}
+
+impl RegionTiles {
+	/// Creates empty tile images for the lightmap and all enabled overlays
+	fn new(config: &Config) -> Self {
+		/// Width/height of the region data
+		const N: u32 = (BLOCKS_PER_CHUNK * CHUNKS_PER_REGION) as u32;
+
+		RegionTiles {
+			lightmap: image::GrayAlphaImage::new(N, N),
+			overlays: config
+				.region_overlays()
+				.map(|kind| (kind, image::RgbaImage::new(N, N)))
+				.collect(),
+			data: config
+				.data_tiles()
+				.filter(|&kind| kind != TileKind::BlockData)
+				.map(|kind| (kind, DataImage::new(N, N)))
+				.collect(),
+			block_data: config
+				.overlays
+				.contains(&TileKind::BlockData)
+				.then(|| MultiDataImage::new(N, N)),
+		}
+	}
 }
 
 /// Type with methods for processing the regions of a Minecraft save directory
 ///
-/// The RegionProcessor builds lightmap tiles as well as processed region data
-/// consumed by subsequent generation steps.
+/// The RegionProcessor builds lightmap and overlay tiles as well as processed
+/// region data consumed by subsequent generation steps.
 pub struct RegionProcessor<'a> {
-	/// Registry of known block types
-	block_types: resource::BlockTypes,
-	/// Registry of known biome types
-	biome_types: resource::BiomeTypes,
 	/// Common MinedMap configuration from command line
 	config: &'a Config,
 }
@@ -46,51 +81,184 @@ pub struct RegionProcessor<'a> {
 impl<'a> RegionProcessor<'a> {
 	/// Constructs a new RegionProcessor
 	pub fn new(config: &'a Config) -> Self {
-		RegionProcessor {
-			block_types: resource::BlockTypes::default(),
-			biome_types: resource::BiomeTypes::default(),
-			config,
-		}
+		RegionProcessor { config }
 	}
 
 	/// Generates a list of all regions of the input Minecraft save data
-	fn collect_regions(&self) -> Result<Vec<TileCoords>> {
-		Ok(self
+	///
+	/// Maps the (offset) coordinates of each region to its [RegionSource] and
+	/// the region's coordinates in the source. When a region exists in multiple
+	/// sources, the first source wins.
+	fn collect_regions(&self) -> Result<BTreeMap<TileCoords, (&'a RegionSource, TileCoords)>> {
+		let mut regions = BTreeMap::new();
+
+		for source in &self.config.region_sources {
+			for coords in source.list_regions()? {
+				let offset_coords = TileCoords {
+					x: coords.x + source.offset.x,
+					z: coords.z + source.offset.z,
+				};
+				regions.entry(offset_coords).or_insert((source, coords));
+			}
+		}
+
+		Ok(regions)
+	}
+
+	/// Reads the stored timestamp of processed data or a region tile
+	///
+	/// Files generated with different processing settings are treated as
+	/// missing, so they are generated again.
+	fn read_timestamp(&self, path: &Path) -> Option<SystemTime> {
+		fs::read_timestamp_with_settings(path, FILE_META_VERSION, self.config.processing_settings)
+	}
+
+	/// Checks whether the processed data, lightmap or overlay tiles of a region are outdated
+	fn needs_update(
+		&self,
+		coords: TileCoords,
+		source: &RegionSource,
+		source_coords: TileCoords,
+	) -> bool {
+		let Ok(input_timestamp) = source.modified(source_coords) else {
+			return true;
+		};
+		let output_timestamp = self.read_timestamp(&self.config.processed_path(coords));
+		let tile_timestamps = self
 			.config
-			.region_dir
-			.read_dir()
-			.with_context(|| {
-				format!(
-					"Failed to read directory {}",
-					self.config.region_dir.display()
-				)
-			})?
-			.filter_map(|entry| entry.ok())
-			.filter(|entry| {
-				// We are only interested in regular files
-				matches!(
-					entry.file_type().map(|file_type| file_type.is_file()),
-					Ok(true)
-				)
-			})
-			.filter_map(|entry| parse_region_filename(&entry.file_name()))
-			.collect())
+			.region_tile_kinds()
+			.map(|kind| self.read_timestamp(&self.config.tile_path(kind, 0, coords)));
+
+		std::iter::once(output_timestamp)
+			.chain(tile_timestamps)
+			.any(|timestamp| Some(input_timestamp) > timestamp)
 	}
 
 	/// Processes a single chunk
+	///
+	/// The processed chunk is added to the region data, lightmap and overlay tiles.
 	fn process_chunk(
 		&self,
-		biome_list: &mut IndexSet<Biome>,
-		data: world::de::Chunk,
-	) -> Result<Option<LayerData>> {
-		let chunk = world::chunk::Chunk::new(&data, &self.block_types, &self.biome_types)?;
-		world::layer::top_layer(biome_list, &chunk)
+		processed_region: &mut ProcessedRegion,
+		tiles: &mut RegionTiles,
+		chunk_coords: ChunkCoords,
+		chunk: &world::chunk::Chunk,
+		inhabited_time: Option<i64>,
+	) -> Result<()> {
+		if self.config.unknown_blocks_path.is_some() {
+			for section in chunk.sections() {
+				let ids: BTreeSet<_> = section.section.unknown_block_ids().into_iter().collect();
+				for id in ids {
+					*processed_region
+						.unknown_blocks
+						.entry(String::from(id))
+						.or_default() += 1;
+				}
+			}
+		}
+
+		let Some(layer::LayerData {
+			blocks,
+			block_ids,
+			block_id_list,
+			biomes,
+			biome_ids,
+			block_light,
+			covered,
+			depths,
+			water_depths,
+			floors,
+			tints,
+		}) = world::layer::top_layer(
+			&mut processed_region.biome_list,
+			&self.config.biome_types,
+			chunk,
+			self.config.height_limits(),
+			self.config.hide_filter.as_deref(),
+			self.config.canopy_filter.as_deref(),
+			self.config.translucent_filter.as_deref(),
+		)?
+		else {
+			return Ok(());
+		};
+
+		if let Some(biome_data) = tiles.data.get_mut(&TileKind::BiomeData) {
+			let chunk_biome_data = self.render_chunk_biome_data(&biome_ids);
+			overlay_chunk(biome_data, &chunk_biome_data, chunk_coords);
+		}
+
+		if let Some(biome_map) = tiles.overlays.get_mut(&TileKind::Biomes) {
+			let chunk_biomes = self.render_chunk_biomes(&blocks, &biome_ids);
+			overlay_chunk(biome_map, &chunk_biomes, chunk_coords);
+		}
+
+		if let Some(heightmap) = tiles.overlays.get_mut(&TileKind::Heightmap) {
+			let chunk_heightmap = self.render_chunk_heightmap(&depths);
+			overlay_chunk(heightmap, &chunk_heightmap, chunk_coords);
+		}
+
+		if let Some(spawnable) = tiles.overlays.get_mut(&TileKind::Spawnable) {
+			let chunk_spawnable = Self::render_chunk_spawnable(&blocks, &block_light);
+			overlay_chunk(spawnable, &chunk_spawnable, chunk_coords);
+		}
+
+		let chunk_lightmap = Self::render_chunk_lightmap(&block_light);
+		overlay_chunk(&mut tiles.lightmap, &chunk_lightmap, chunk_coords);
+
+		let processed_chunk = Box::new(ProcessedChunk {
+			blocks,
+			biomes,
+			depths,
+			block_light,
+			covered,
+			water_depths,
+			floors,
+			tints,
+		});
+
+		if let Some(height_data) = tiles.data.get_mut(&TileKind::HeightData) {
+			let chunk_height_data = Self::render_chunk_height_data(&processed_chunk);
+			overlay_chunk(height_data, &chunk_height_data, chunk_coords);
+		}
+
+		if let Some(block_data) = &mut tiles.block_data {
+			let chunk_block_data =
+				self.render_chunk_block_data(&processed_chunk, &block_ids, &block_id_list);
+			overlay_chunk(block_data, &chunk_block_data, chunk_coords);
+		}
+
+		processed_region.chunks[chunk_coords] = Some(processed_chunk);
+
+		if let Some(ores) = tiles.overlays.get_mut(&TileKind::Ores) {
+			if let Some(counts) = layer::count_blocks(chunk, &self.config.ore_filter)? {
+				let chunk_ores = Self::render_chunk_ores(counts);
+				overlay_chunk(ores, &chunk_ores, chunk_coords);
+			}
+		}
+
+		if let Some(caves) = tiles.overlays.get_mut(&TileKind::Caves) {
+			if let Some(floors) = layer::cave_layer(chunk, self.config.height_limits())? {
+				let chunk_caves = Self::render_chunk_caves(&floors);
+				overlay_chunk(caves, &chunk_caves, chunk_coords);
+			}
+		}
+
+		if let Some(activity) = tiles.overlays.get_mut(&TileKind::Activity) {
+			if let Some(color) = inhabited_time.and_then(Self::activity_color) {
+				let chunk_activity = image::RgbaImage::from_pixel(
+					BLOCKS_PER_CHUNK as u32,
+					BLOCKS_PER_CHUNK as u32,
+					color,
+				);
+				overlay_chunk(activity, &chunk_activity, chunk_coords);
+			}
+		}
+
+		Ok(())
 	}
 
 	/// Renders a lightmap subtile from chunk block light data
-	fn render_chunk_lightmap(
-		block_light: Box<world::layer::BlockLightArray>,
-	) -> image::GrayAlphaImage {
+	fn render_chunk_lightmap(block_light: &world::layer::BlockLightArray) -> image::GrayAlphaImage {
 		/// Width/height of generated chunk lightmap
 		const N: u32 = BLOCKS_PER_CHUNK as u32;
 
@@ -104,87 +272,613 @@ impl<'a> RegionProcessor<'a> {
 		})
 	}
 
+	/// Renders a height data subtile from a processed chunk
+	///
+	/// The surface height is stored with an offset of 32768, so negative
+	/// Y coordinates can be represented.
+	fn render_chunk_height_data(chunk: &ProcessedChunk) -> DataImage {
+		/// Width/height of generated chunk subtile
+		const N: u32 = BLOCKS_PER_CHUNK as u32;
+
+		DataImage::from_fn(N, N, |x, z| {
+			let coords = LayerBlockCoords {
+				x: BlockX::new(x),
+				z: BlockZ::new(z),
+			};
+			match chunk.surface_height(coords) {
+				Some(height) => {
+					image::LumaA([(height + 32768).clamp(0, u16::MAX.into()) as u16, u16::MAX])
+				}
+				None => image::LumaA([0, 0]),
+			}
+		})
+	}
+
+	/// Renders a block data subtile from a processed chunk and its block IDs
+	///
+	/// Each pixel stores the index of the top block ID in the block data
+	/// palette and the surface height with an offset of 32768.
+	fn render_chunk_block_data(
+		&self,
+		chunk: &ProcessedChunk,
+		block_ids: &world::layer::BlockIdArray,
+		block_id_list: &IndexSet<String>,
+	) -> MultiDataImage {
+		/// Width/height of generated chunk subtile
+		const N: u32 = BLOCKS_PER_CHUNK as u32;
+
+		// Resolve the palette indices once per chunk
+		let palette_indices: Vec<_> = block_id_list
+			.iter()
+			.map(|id| self.config.block_data_palette.index(id))
+			.collect();
+
+		MultiDataImage::from_fn(N, N, |x, z| {
+			let coords = LayerBlockCoords {
+				x: BlockX::new(x),
+				z: BlockZ::new(z),
+			};
+			let index =
+				block_ids[coords].and_then(|index| palette_indices[usize::from(index.get()) - 1]);
+			match (index, chunk.surface_height(coords)) {
+				(Some(index), Some(height)) => image::Rgba([
+					index,
+					(height + 32768).clamp(0, u16::MAX.into()) as u16,
+					0,
+					u16::MAX,
+				]),
+				_ => image::Rgba([0, 0, 0, 0]),
+			}
+		})
+	}
+
+	/// Renders a biome data subtile from the top layer of a chunk
+	///
+	/// Each pixel stores the index of the biome ID in the biome data palette.
+	fn render_chunk_biome_data(&self, biome_ids: &world::layer::BiomeIdArray) -> DataImage {
+		/// Width/height of generated chunk subtile
+		const N: u32 = BLOCKS_PER_CHUNK as u32;
+
+		DataImage::from_fn(N, N, |x, z| {
+			let coords = LayerBlockCoords {
+				x: BlockX::new(x),
+				z: BlockZ::new(z),
+			};
+			match biome_ids[coords].and_then(|id| self.config.biome_data_palette.index(id)) {
+				Some(index) => image::LumaA([index, u16::MAX]),
+				None => image::LumaA([0, 0]),
+			}
+		})
+	}
+
+	/// Renders a biome map subtile from the top layer of a chunk
+	///
+	/// Columns with unknown biomes are rendered in gray.
+	fn render_chunk_biomes(
+		&self,
+		blocks: &world::layer::BlockArray,
+		biome_ids: &world::layer::BiomeIdArray,
+	) -> image::RgbaImage {
+		/// Width/height of generated chunk subtile
+		const N: u32 = BLOCKS_PER_CHUNK as u32;
+		/// Color of biomes missing from the palette
+		const UNKNOWN_COLOR: [u8; 3] = [128, 128, 128];
+
+		image::RgbaImage::from_fn(N, N, |x, z| {
+			let coords = LayerBlockCoords {
+				x: BlockX::new(x),
+				z: BlockZ::new(z),
+			};
+			if blocks[coords].is_none() {
+				return image::Rgba([0, 0, 0, 0]);
+			}
+			let [r, g, b] = biome_ids[coords]
+				.and_then(|id| self.config.biome_palette.color(id))
+				.unwrap_or(UNKNOWN_COLOR);
+			image::Rgba([r, g, b, 255])
+		})
+	}
+
+	/// Renders a heightmap subtile from the top layer of a chunk
+	///
+	/// The height range of the dimension is mapped to grayscale values from
+	/// black to white.
+	fn render_chunk_heightmap(&self, depths: &world::layer::DepthArray) -> image::RgbaImage {
+		/// Width/height of generated chunk subtile
+		const N: u32 = BLOCKS_PER_CHUNK as u32;
+		/// Height range used when the height of the dimension is unknown
+		const DEFAULT_HEIGHT: world::de::DimensionType = world::de::DimensionType {
+			min_y: -64,
+			height: 384,
+			has_ceiling: false,
+		};
+
+		let world::de::DimensionType { min_y, height, .. } =
+			self.config.height.unwrap_or(DEFAULT_HEIGHT);
+
+		image::RgbaImage::from_fn(N, N, |x, z| {
+			let Some(depth) = depths[LayerBlockCoords {
+				x: BlockX::new(x),
+				z: BlockZ::new(z),
+			}] else {
+				return image::Rgba([0, 0, 0, 0]);
+			};
+			let v = (depth.0.saturating_sub(min_y) as f32 / height.max(1) as f32).clamp(0.0, 1.0);
+			let v = (255.0 * v) as u8;
+			image::Rgba([v, v, v, 255])
+		})
+	}
+
+	/// Renders a cave map subtile from the cave floor heights of a chunk
+	///
+	/// Cave floors are colored by their height, from purple deep underground
+	/// to orange high up.
+	fn render_chunk_caves(floors: &world::layer::DepthArray) -> image::RgbaImage {
+		/// Width/height of generated chunk subtile
+		const N: u32 = BLOCKS_PER_CHUNK as u32;
+
+		let colors = [
+			(-64.0, Vec3::new(80.0, 0.0, 120.0)),
+			(0.0, Vec3::new(0.0, 80.0, 200.0)),
+			(32.0, Vec3::new(0.0, 180.0, 120.0)),
+			(64.0, Vec3::new(200.0, 200.0, 0.0)),
+			(128.0, Vec3::new(255.0, 100.0, 0.0)),
+		];
+
+		image::RgbaImage::from_fn(N, N, |x, z| {
+			let Some(floor) = floors[LayerBlockCoords {
+				x: BlockX::new(x),
+				z: BlockZ::new(z),
+			}] else {
+				return image::Rgba([0, 0, 0, 0]);
+			};
+			let [r, g, b] = gradient(&colors, floor.0 as f32).to_array();
+			image::Rgba([r as u8, g as u8, b as u8, 255])
+		})
+	}
+
+	/// Renders a mob spawning overlay subtile from the top layer of a chunk
+	///
+	/// Marks the top blocks hostile mobs can spawn on: opaque blocks other
+	/// than water and leaves with a block light level of 0 above them.
+	fn render_chunk_spawnable(
+		blocks: &world::layer::BlockArray,
+		block_light: &world::layer::BlockLightArray,
+	) -> image::RgbaImage {
+		/// Width/height of generated chunk overlay
+		const N: u32 = BLOCKS_PER_CHUNK as u32;
+
+		image::RgbaImage::from_fn(N, N, |x, z| {
+			let coords = LayerBlockCoords {
+				x: BlockX::new(x),
+				z: BlockZ::new(z),
+			};
+			let spawnable = blocks[coords].is_some_and(|block_type| {
+				block_type.is(BlockFlag::Opaque)
+					&& !block_type.is(BlockFlag::Water)
+					&& !block_type.is(BlockFlag::Foliage)
+			}) && block_light[coords] == 0;
+			if spawnable {
+				image::Rgba([160, 0, 255, 112])
+			} else {
+				image::Rgba([0, 0, 0, 0])
+			}
+		})
+	}
+
+	/// Renders an ore density overlay subtile from chunk block counts
+	///
+	/// Columns without ores are transparent; the opacity increases with the
+	/// number of ore blocks in the column.
+	fn render_chunk_ores(counts: Box<world::layer::BlockCountArray>) -> image::RgbaImage {
+		/// Width/height of generated chunk overlay
+		const N: u32 = BLOCKS_PER_CHUNK as u32;
+		/// Number of ore blocks at which a column is rendered fully opaque
+		const MAX_COUNT: u32 = 8;
+
+		image::RgbaImage::from_fn(N, N, |x, z| {
+			let count: u32 = counts[LayerBlockCoords {
+				x: BlockX::new(x),
+				z: BlockZ::new(z),
+			}]
+			.into();
+			if count == 0 {
+				return image::Rgba([0, 0, 0, 0]);
+			}
+			let alpha = 64 + 191 * (count.min(MAX_COUNT) - 1) / (MAX_COUNT - 1);
+			image::Rgba([255, 32, 0, alpha as u8])
+		})
+	}
+
+	/// Determines the activity overlay color of a chunk from its inhabited time
+	///
+	/// The inhabited time is mapped to a blue-cyan-green-yellow-red gradient on
+	/// a logarithmic scale between [ACTIVITY_MIN_TICKS] and [ACTIVITY_MAX_TICKS].
+	/// Returns [None] for chunks below the scale.
+	fn activity_color(inhabited_time: i64) -> Option<image::Rgba<u8>> {
+		/// Colors of the gradient, evenly spaced on the scale
+		const GRADIENT: [[f32; 3]; 5] = [
+			[0.0, 0.0, 255.0],
+			[0.0, 255.0, 255.0],
+			[0.0, 255.0, 0.0],
+			[255.0, 255.0, 0.0],
+			[255.0, 0.0, 0.0],
+		];
+
+		if inhabited_time < ACTIVITY_MIN_TICKS {
+			return None;
+		}
+
+		let pos = ((inhabited_time as f32).ln() - (ACTIVITY_MIN_TICKS as f32).ln())
+			/ ((ACTIVITY_MAX_TICKS as f32).ln() - (ACTIVITY_MIN_TICKS as f32).ln());
+		let pos = pos.clamp(0.0, 1.0) * (GRADIENT.len() - 1) as f32;
+		let index = (pos as usize).min(GRADIENT.len() - 2);
+		let t = pos - index as f32;
+
+		let [r, g, b] = std::array::from_fn(|i| {
+			(GRADIENT[index][i] * (1.0 - t) + GRADIENT[index + 1][i] * t) as u8
+		});
+		Some(image::Rgba([r, g, b, 160]))
+	}
+
 	/// Saves processed region data
 	///
 	/// The timestamp is the time of the last modification of the input region data.
 	fn save_region(
+		&self,
 		path: &Path,
 		processed_region: &ProcessedRegion,
 		timestamp: SystemTime,
 	) -> Result<()> {
-		storage::write(path, processed_region, FILE_META_VERSION, timestamp)
+		storage::write(
+			path,
+			processed_region,
+			FILE_META_VERSION,
+			self.config.processing_settings,
+			timestamp,
+		)
 	}
 
-	/// Saves a lightmap tile
+	/// Saves a lightmap or overlay tile
 	///
 	/// The timestamp is the time of the last modification of the input region data.
-	fn save_lightmap(
+	fn save_tile<P>(
+		&self,
 		path: &Path,
-		lightmap: &image::GrayAlphaImage,
+		tile: &image::ImageBuffer<P, Vec<P::Subpixel>>,
 		timestamp: SystemTime,
-	) -> Result<()> {
-		fs::create_with_timestamp(path, FILE_META_VERSION, timestamp, |file| {
-			lightmap
-				.write_to(file, image::ImageFormat::Png)
-				.context("Failed to save image")
-		})
+	) -> Result<()>
+	where
+		P: image::PixelWithColorType,
+		[P::Subpixel]: image::EncodableLayout,
+	{
+		fs::create_with_timestamp(
+			path,
+			FILE_META_VERSION,
+			self.config.processing_settings,
+			timestamp,
+			|file| {
+				tile.write_to(file, image::ImageFormat::Png)
+					.context("Failed to save image")
+			},
+			|status| self.config.store_output(path, status),
+		)
 	}
 
-	/// Processes a single region file
-	fn process_region(&self, coords: TileCoords) -> Result<()> {
-		/// Width/height of the region data
-		const N: u32 = (BLOCKS_PER_CHUNK * CHUNKS_PER_REGION) as u32;
-
+	/// Reads and processes the chunks of a region
+	///
+	/// Returns the processed region data and the region's tiles. When
+	/// corrupt chunks are skipped, the coordinates of the skipped chunks and
+	/// the errors are returned as well.
+	fn read_region(
+		&self,
+		source: &RegionSource,
+		source_coords: TileCoords,
+	) -> Result<(ProcessedRegion, RegionTiles, SkippedChunks)> {
 		let mut processed_region = ProcessedRegion::default();
-		let mut lightmap = image::GrayAlphaImage::new(N, N);
+		let mut tiles = RegionTiles::new(self.config);
+		let skipped = RefCell::new(SkippedChunks::new());
+
+		// Errors of single chunks are either passed through or recorded,
+		// depending on the --skip-corrupt option
+		let skip = |chunk_coords, err: anyhow::Error| -> Result<()> {
+			if self.config.skip_corrupt_path.is_none() {
+				return Err(err);
+			}
+			skipped.borrow_mut().push((chunk_coords, err));
+			Ok(())
+		};
+
+		match &source.format {
+			RegionFormat::Anvil | RegionFormat::McRegion | RegionFormat::Linear => {
+				// Read the whole file at once to minimize the time window in which
+				// a running server can modify it while we are parsing it
+				let mut data = Vec::new();
+				source
+					.input
+					.open(&source.region_path(source_coords))?
+					.read_to_end(&mut data)
+					.context("Failed to read region file")?;
+
+				let mut process = |chunk_coords, data: world::de::Chunk| {
+					(|| -> Result<()> {
+						let chunk = world::chunk::Chunk::new(
+							&data,
+							&self.config.block_types,
+							&self.config.biome_types,
+						)?;
+						self.process_chunk(
+							&mut processed_region,
+							&mut tiles,
+							chunk_coords,
+							&chunk,
+							data.inhabited_time,
+						)
+					})()
+					.with_context(|| format!("Failed to process chunk {:?}", chunk_coords))
+				};
+
+				// Chunks are decoded from the decompressed data, borrowing
+				// strings and arrays from it instead of copying them
+				let mut process_data = |chunk_coords, data: &[u8]| -> Result<()> {
+					let chunk =
+						crate::nbt::data::from_bytes(data).map_err(|err| RegionError::Chunk {
+							coords: chunk_coords,
+							source: err.into(),
+						})?;
+					process(chunk_coords, chunk)
+				};
+
+				let external_chunks = |chunk_coords| {
+					let filename = crate::nbt::region::external_chunk_filename(
+						source_coords.x,
+						source_coords.z,
+						chunk_coords,
+					);
+					let mut data = Vec::new();
+					source
+						.input
+						.open(&source.region_dir.join(filename))
+						.map_err(std::io::Error::other)?
+						.read_to_end(&mut data)?;
+					Ok(data)
+				};
+
+				// Chunks with invalid header entries are skipped with a warning
+				let open_region = || -> Result<_> {
+					let mut region = crate::nbt::region::from_reader(Cursor::new(data.as_slice()))
+						.with_external_chunks(external_chunks)
+						.with_overlap_resolution(self.config.overlap_resolution);
+					for err in region.invalid_chunks()? {
+						match err.chunk_coords() {
+							Some(chunk_coords) if self.config.skip_corrupt_path.is_some() => {
+								skipped.borrow_mut().push((chunk_coords, err.into()))
+							}
+							_ => eprintln!(
+								"Skipping chunk of region r.{}.{}: {}",
+								source_coords.x, source_coords.z, err
+							),
+						}
+					}
+					Ok(region)
+				};
+
+				match source.format {
+					RegionFormat::McRegion => open_region()?.foreach_chunk_result(
+						|chunk_coords, data: Result<world::mcregion::Chunk, ChunkError>| {
+							(|| -> Result<()> {
+								let data = data.map_err(|source| RegionError::Chunk {
+									coords: chunk_coords,
+									source,
+								})?;
+								let data = data.into_anvil().with_context(|| {
+									format!("Failed to convert chunk {:?}", chunk_coords)
+								})?;
+								process(chunk_coords, data)
+							})()
+							.or_else(|err| skip(chunk_coords, err))
+						},
+					)?,
+					#[cfg(feature = "zstd")]
+					RegionFormat::Linear => crate::nbt::linear::from_reader(data.as_slice()).foreach_chunk_data(
+						|chunk_coords, data| {
+							process_data(chunk_coords, data).or_else(|err| skip(chunk_coords, err))
+						},
+					)?,
+					#[cfg(not(feature = "zstd"))]
+					RegionFormat::Linear => {
+						anyhow::bail!("Reading linear region files requires the zstd feature")
+					}
+					_ => open_region()?.foreach_chunk_raw_result(|chunk_coords, raw| {
+						(|| -> Result<()> {
+							let data = raw.and_then(|raw| raw.decompress()).map_err(|source| {
+								RegionError::Chunk {
+									coords: chunk_coords,
+									source,
+								}
+							})?;
+							process_data(chunk_coords, &data)
+						})()
+						.or_else(|err| skip(chunk_coords, err))
+					})?,
+				}
+			}
+			RegionFormat::Bedrock(world, dimension) => {
+				world.foreach_chunk(
+					*dimension,
+					source_coords.x,
+					source_coords.z,
+					|chunk_coords, data| {
+						(|| -> Result<()> {
+							let chunk = world::chunk::Chunk::new_bedrock(
+								&data,
+								&self.config.block_types,
+								&self.config.biome_types,
+							)?;
+							self.process_chunk(
+								&mut processed_region,
+								&mut tiles,
+								chunk_coords,
+								&chunk,
+								None,
+							)
+						})()
+						.with_context(|| format!("Failed to process chunk {:?}", chunk_coords))
+						.or_else(|err| skip(chunk_coords, err))
+					},
+				)?;
+			}
+			RegionFormat::Cubic(world) => {
+				world.foreach_chunk(source_coords.x, source_coords.z, |chunk_coords, data| {
+					(|| -> Result<()> {
+						let chunk = world::chunk::Chunk::new(
+							&data,
+							&self.config.block_types,
+							&self.config.biome_types,
+						)?;
+						self.process_chunk(
+							&mut processed_region,
+							&mut tiles,
+							chunk_coords,
+							&chunk,
+							data.inhabited_time,
+						)
+					})()
+					.with_context(|| format!("Failed to process chunk {:?}", chunk_coords))
+					.or_else(|err| skip(chunk_coords, err))
+				})?;
+			}
+		}
+
+		Ok((processed_region, tiles, skipped.into_inner()))
+	}
+
+	/// Processes a single region file
+	///
+	/// Region files of a running server may be rewritten while they are read,
+	/// resulting in inconsistent data. When reading a region fails and its
+	/// modification time has changed after a short delay, reading is retried.
+	fn process_region(
+		&self,
+		coords: TileCoords,
+		source: &RegionSource,
+		source_coords: TileCoords,
+	) -> Result<()> {
+		/// Maximum number of attempts to read a region that is being modified
+		const ATTEMPTS: usize = 3;
+		/// Delay before checking whether a failed region has been modified
+		const RETRY_DELAY: Duration = Duration::from_secs(2);
 
-		let path = self.config.region_path(coords);
-		let input_timestamp = fs::modified_timestamp(&path)?;
+		let mut input_timestamp = source.modified(source_coords)?;
 
 		let output_path = self.config.processed_path(coords);
-		let output_timestamp = fs::read_timestamp(&output_path, FILE_META_VERSION);
-		let lightmap_path = self.config.tile_path(TileKind::Lightmap, 0, coords);
-		let lightmap_timestamp = fs::read_timestamp(&lightmap_path, FILE_META_VERSION);
+		let output_timestamp = self.read_timestamp(&output_path);
+		let tile_timestamps: Vec<_> = self
+			.config
+			.region_tile_kinds()
+			.map(|kind| {
+				let path = self.config.tile_path(kind, 0, coords);
+				let timestamp = self.read_timestamp(&path);
+				(kind, path, timestamp)
+			})
+			.collect();
 
-		if Some(input_timestamp) <= output_timestamp && Some(input_timestamp) <= lightmap_timestamp
+		if Some(input_timestamp) <= output_timestamp
+			&& tile_timestamps
+				.iter()
+				.all(|(_, _, timestamp)| Some(input_timestamp) <= *timestamp)
 		{
 			println!("Skipping unchanged region r.{}.{}.mca", coords.x, coords.z);
+			// The report includes the unknown blocks of all regions, not
+			// only those processed in the current run
+			if self.config.unknown_blocks_path.is_some() {
+				let processed_region: ProcessedRegion = storage::read(&output_path)?;
+				self.config
+					.unknown_blocks
+					.merge(&processed_region.unknown_blocks);
+			}
 			return Ok(());
 		}
 
 		println!("Processing region r.{}.{}.mca", coords.x, coords.z);
 
-		crate::nbt::region::from_file(path)?.foreach_chunk(
-			|chunk_coords, data: world::de::Chunk| {
-				let Some(layer::LayerData {
-					blocks,
-					biomes,
-					block_light,
-					depths,
-				}) = self
-					.process_chunk(&mut processed_region.biome_list, data)
-					.with_context(|| format!("Failed to process chunk {:?}", chunk_coords))?
-				else {
-					return Ok(());
-				};
-				processed_region.chunks[chunk_coords] = Some(Box::new(ProcessedChunk {
-					blocks,
-					biomes,
-					depths,
-				}));
+		let mut attempt = 1;
+		let (processed_region, tiles, skipped) = loop {
+			let err = match self.read_region(source, source_coords) {
+				Ok(ret) => break ret,
+				Err(err) => err,
+			};
+			if attempt == ATTEMPTS {
+				return Err(err);
+			}
 
-				let chunk_lightmap = Self::render_chunk_lightmap(block_light);
-				overlay_chunk(&mut lightmap, &chunk_lightmap, chunk_coords);
+			thread::sleep(RETRY_DELAY);
+			let timestamp = source.modified(source_coords)?;
+			if timestamp == input_timestamp {
+				return Err(err);
+			}
 
-				Ok(())
-			},
-		)?;
+			eprintln!(
+				"Region r.{}.{}.mca was modified while reading, retrying: {:?}",
+				coords.x, coords.z, err
+			);
+			input_timestamp = timestamp;
+			attempt += 1;
+		};
+
+		// The outputs of a region with skipped chunks are stored with an
+		// outdated timestamp, so the region is processed again in the next
+		// run instead of being considered up-to-date
+		let stored_timestamp = if skipped.is_empty() {
+			input_timestamp
+		} else {
+			SystemTime::UNIX_EPOCH
+		};
+
+		// Skipped chunks are only recorded once the region was read
+		// successfully, so retries don't result in duplicate warnings
+		let region_path = source.region_path(source_coords);
+		for (chunk_coords, err) in skipped {
+			eprintln!(
+				"Skipping corrupt chunk {:?} of region r.{}.{}.mca: {:#}",
+				chunk_coords, coords.x, coords.z, err
+			);
+			self.config.corrupt_chunks.record(
+				region_path.clone(),
+				chunk_coords,
+				format!("{:#}", err),
+			);
+		}
+
+		self.config
+			.unknown_blocks
+			.merge(&processed_region.unknown_blocks);
 
 		if Some(input_timestamp) > output_timestamp {
-			Self::save_region(&output_path, &processed_region, input_timestamp)?;
+			self.save_region(&output_path, &processed_region, stored_timestamp)?;
 		}
-		if Some(input_timestamp) > lightmap_timestamp {
-			Self::save_lightmap(&lightmap_path, &lightmap, input_timestamp)?;
+		for (kind, path, timestamp) in &tile_timestamps {
+			if Some(input_timestamp) <= *timestamp {
+				continue;
+			}
+			// The palette indices of the data tiles must never refer to
+			// values missing from the stored palette
+			self.config.write_palette(*kind)?;
+			match kind {
+				TileKind::Lightmap => self.save_tile(path, &tiles.lightmap, stored_timestamp)?,
+				TileKind::BlockData => self.save_tile(
+					path,
+					tiles
+						.block_data
+						.as_ref()
+						.expect("block data tile not generated"),
+					stored_timestamp,
+				)?,
+				kind if kind.is_data() => {
+					self.save_tile(path, &tiles.data[kind], stored_timestamp)?
+				}
+				kind => self.save_tile(path, &tiles.overlays[kind], stored_timestamp)?,
+			}
 		}
 
 		Ok(())
@@ -192,21 +886,56 @@ impl<'a> RegionProcessor<'a> {
 
 	/// Iterates over all region files of a Minecraft save directory
 	///
-	/// Returns a list of the coordinates of all processed regions
+	/// Returns a list of the coordinates of all processed regions. Regions
+	/// that failed to process are only included if processed data from a
+	/// previous run exists.
 	pub fn run(self) -> Result<Vec<TileCoords>> {
-		let mut regions = self.collect_regions()?;
+		let mut regions: Vec<_> = self.collect_regions()?.into_iter().collect();
 
 		// Sort regions in a zig-zag pattern to optimize cache usage
-		regions.sort_unstable_by_key(|&TileCoords { x, z }| (x, if x % 2 == 0 { z } else { -z }));
+		regions
+			.sort_unstable_by_key(|&(TileCoords { x, z }, _)| (x, if x % 2 == 0 { z } else { -z }));
 
 		fs::create_dir_all(&self.config.processed_dir)?;
-		fs::create_dir_all(&self.config.tile_dir(TileKind::Lightmap, 0))?;
+		for kind in self.config.region_tile_kinds() {
+			fs::create_dir_all(&self.config.tile_dir(kind, 0))?;
+		}
 
-		regions.par_iter().for_each(|&coords| {
-			if let Err(err) = self.process_region(coords) {
-				eprintln!("Failed to process region {:?}: {:?}", coords, err);
+		for source in &self.config.region_sources {
+			// Bedrock and Cubic Chunks worlds are not stored as one file per region
+			if matches!(
+				source.format,
+				RegionFormat::Bedrock(..) | RegionFormat::Cubic(..)
+			) {
+				continue;
 			}
-		});
+			source.input.prefetch(
+				&mut regions
+					.iter()
+					.filter(|(_, (region_source, _))| std::ptr::eq(*region_source, source))
+					.filter(|(coords, (_, source_coords))| {
+						self.needs_update(*coords, source, *source_coords)
+					})
+					.map(|(_, (_, source_coords))| source.region_path(*source_coords)),
+			);
+		}
+
+		let regions = regions
+			.into_par_iter()
+			.filter_map(|(coords, (source, source_coords))| {
+				let Err(err) = self.process_region(coords, source, source_coords) else {
+					return Some(coords);
+				};
+				eprintln!("Failed to process region {:?}: {:?}", coords, err);
+
+				// Keep rendering regions from previously processed data
+				// when they can't be read temporarily
+				self.config
+					.processed_path(coords)
+					.exists()
+					.then_some(coords)
+			})
+			.collect();
 
 		Ok(regions)
 	}
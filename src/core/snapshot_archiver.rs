@@ -0,0 +1,256 @@
+//! The [SnapshotArchiver] and related types
+
+use std::{
+	collections::BTreeSet,
+	io::Write,
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::common::*;
+use crate::io::fs;
+
+/// Name of the snapshot directory in the data directory
+pub(super) const SNAPSHOT_DIR: &str = "snapshots";
+
+/// Entry of a snapshot in the snapshot index
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+	/// Name of the snapshot directory
+	name: String,
+	/// Time of the snapshot, in seconds since the Unix epoch
+	timestamp: u64,
+	/// Viewer files stored in the snapshot, relative to the data directory
+	files: BTreeSet<String>,
+	/// Viewer files removed in the run of the snapshot
+	#[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+	deleted: BTreeSet<String>,
+}
+
+/// Snapshot index JSON data structure
+///
+/// Each snapshot only contains the files modified in its run, except for
+/// the oldest snapshot, which contains the complete map at its time. The
+/// state of the map at the time of any snapshot is given by the latest
+/// version of each file up to that snapshot.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SnapshotIndex {
+	/// Snapshots from oldest to newest
+	snapshots: Vec<Snapshot>,
+}
+
+/// Archives the viewer files modified in a run as a dated snapshot
+pub struct SnapshotArchiver<'a> {
+	/// Common MinedMap configuration from command line
+	config: &'a Config,
+	/// Retention policy of the archive
+	policy: SnapshotPolicy,
+}
+
+impl<'a> SnapshotArchiver<'a> {
+	/// Constructs a new SnapshotArchiver
+	pub fn new(config: &'a Config, policy: SnapshotPolicy) -> Self {
+		SnapshotArchiver { config, policy }
+	}
+
+	/// Returns the directory of all snapshots
+	fn snapshot_dir(&self) -> PathBuf {
+		self.config.data_dir.join(SNAPSHOT_DIR)
+	}
+
+	/// Returns the directory name of a snapshot taken at the given time
+	///
+	/// A counter is appended when another snapshot of the same name exists.
+	fn snapshot_name(index: &SnapshotIndex, timestamp: u64) -> String {
+		let date = super::format_timestamp(timestamp.try_into().unwrap_or(u32::MAX))
+			.replace(' ', "_")
+			.replace(':', "-");
+
+		let exists = |name: &str| index.snapshots.iter().any(|s| s.name == name);
+		let mut name = date.clone();
+		let mut counter = 1;
+		while exists(&name) {
+			counter += 1;
+			name = format!("{}.{}", date, counter);
+		}
+		name
+	}
+
+	/// Stores a file in a snapshot
+	///
+	/// Viewer files are always replaced rather than modified in place, so
+	/// the file is hard-linked where possible, falling back to copying it.
+	fn archive_file(source: &Path, dest: &Path) -> Result<()> {
+		if let Some(parent) = dest.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		if std::fs::hard_link(source, dest).is_ok() {
+			return Ok(());
+		}
+		std::fs::copy(source, dest)
+			.with_context(|| format!("Failed to archive file {}", source.display()))?;
+		Ok(())
+	}
+
+	/// Collects all viewer files in a directory and its subdirectories
+	///
+	/// Processed region data and the snapshots themselves are skipped.
+	fn list_files(&self, dir: &Path, files: &mut Vec<(String, FileChange)>) -> Result<()> {
+		let entries = dir
+			.read_dir()
+			.with_context(|| format!("Failed to read directory {}", dir.display()))?;
+
+		for entry in entries.filter_map(|entry| entry.ok()) {
+			let path = entry.path();
+			if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+				if path.ends_with("processed") || path == self.snapshot_dir() {
+					continue;
+				}
+				self.list_files(&path, files)?;
+				continue;
+			}
+			if !path
+				.extension()
+				.is_some_and(|ext| ext == "png" || ext == "json")
+			{
+				continue;
+			}
+
+			let key = path
+				.strip_prefix(&self.config.data_dir)
+				.expect("viewer file must be in data directory")
+				.components()
+				.map(|component| component.as_os_str().to_string_lossy())
+				.collect::<Vec<_>>()
+				.join("/");
+			files.push((key, FileChange::Added));
+		}
+
+		Ok(())
+	}
+
+	/// Creates a snapshot of the files modified in the current run
+	///
+	/// The first snapshot contains all viewer files, so the map can be
+	/// restored completely even if the snapshots were enabled for an
+	/// existing map.
+	fn archive(&self, index: &mut SnapshotIndex, timestamp: u64) -> Result<()> {
+		let changes = if index.snapshots.is_empty() {
+			let mut files = Vec::new();
+			self.list_files(&self.config.data_dir, &mut files)?;
+			files
+		} else {
+			self.config.changes.entries()
+		};
+		if changes.is_empty() {
+			return Ok(());
+		}
+
+		let name = Self::snapshot_name(index, timestamp);
+		println!("Archiving snapshot {}", name);
+
+		let dir = self.snapshot_dir().join(&name);
+		let mut snapshot = Snapshot {
+			name,
+			timestamp,
+			files: BTreeSet::new(),
+			deleted: BTreeSet::new(),
+		};
+
+		for (key, change) in changes {
+			if change == FileChange::Deleted {
+				snapshot.deleted.insert(key);
+				continue;
+			}
+			Self::archive_file(&self.config.data_dir.join(&key), &dir.join(&key))?;
+			snapshot.files.insert(key);
+		}
+
+		index.snapshots.push(snapshot);
+		Ok(())
+	}
+
+	/// Returns true if the oldest snapshot must be removed according to the
+	/// retention policy
+	///
+	/// The newest snapshot is always kept.
+	fn must_prune(&self, index: &SnapshotIndex, now: u64) -> bool {
+		let (Some(oldest), true) = (index.snapshots.first(), index.snapshots.len() > 1) else {
+			return false;
+		};
+
+		self.policy
+			.keep
+			.is_some_and(|keep| index.snapshots.len() > keep)
+			|| self
+				.policy
+				.max_age
+				.is_some_and(|max_age| oldest.timestamp.saturating_add(max_age.as_secs()) < now)
+	}
+
+	/// Removes the oldest snapshot
+	///
+	/// Files of the removed snapshot that have not been modified or deleted
+	/// in the next snapshot are moved there, so the next snapshot then
+	/// contains the complete map at its time.
+	fn prune_oldest(&self, index: &mut SnapshotIndex) -> Result<()> {
+		let oldest = index.snapshots.remove(0);
+		let next = &mut index.snapshots[0];
+		println!("Pruning snapshot {}", oldest.name);
+
+		let oldest_dir = self.snapshot_dir().join(&oldest.name);
+		let next_dir = self.snapshot_dir().join(&next.name);
+
+		for key in oldest.files {
+			if next.files.contains(&key) || next.deleted.contains(&key) {
+				continue;
+			}
+			let dest = next_dir.join(&key);
+			if let Some(parent) = dest.parent() {
+				fs::create_dir_all(parent)?;
+			}
+			fs::rename(&oldest_dir.join(&key), &dest)?;
+			next.files.insert(key);
+		}
+		next.deleted.clear();
+
+		std::fs::remove_dir_all(&oldest_dir)
+			.with_context(|| format!("Failed to remove snapshot {}", oldest_dir.display()))
+	}
+
+	/// Runs the snapshot archival and pruning
+	pub fn run(self) -> Result<()> {
+		let index_path = self.snapshot_dir().join("index.json");
+		let mut index: SnapshotIndex = std::fs::read(&index_path)
+			.ok()
+			.and_then(|data| serde_json::from_slice(&data).ok())
+			.unwrap_or_default();
+
+		let now = SystemTime::now()
+			.duration_since(SystemTime::UNIX_EPOCH)
+			.unwrap_or(Duration::ZERO)
+			.as_secs();
+
+		self.archive(&mut index, now)?;
+
+		while self.must_prune(&index, now) {
+			self.prune_oldest(&mut index)?;
+		}
+
+		if index.snapshots.is_empty() {
+			return Ok(());
+		}
+
+		fs::create_dir_all(&self.snapshot_dir())?;
+		fs::create_with_tmpfile(&index_path, |file| {
+			serde_json::to_writer(&mut *file, &index)?;
+			file.flush()?;
+			Ok(())
+		})?;
+
+		Ok(())
+	}
+}
@@ -99,7 +99,7 @@ impl<'a> TileMipmapper<'a> {
 			println!(
 				"Skipping unchanged mipmap tile {}",
 				output_path
-					.strip_prefix(&self.config.output_dir)
+					.strip_prefix(&self.config.data_dir)
 					.expect("tile path must be in output directory")
 					.display(),
 			);
@@ -109,7 +109,7 @@ impl<'a> TileMipmapper<'a> {
 		println!(
 			"Rendering mipmap tile {}",
 			output_path
-				.strip_prefix(&self.config.output_dir)
+				.strip_prefix(&self.config.data_dir)
 				.expect("tile path must be in output directory")
 				.display(),
 		);
@@ -138,11 +138,48 @@ impl<'a> TileMipmapper<'a> {
 			);
 		}
 
-		fs::create_with_timestamp(&output_path, FILE_META_VERSION, input_timestamp, |file| {
-			image
-				.write_to(file, image::ImageFormat::Png)
-				.context("Failed to save image")
-		})
+		fs::create_with_timestamp(
+			&output_path,
+			FILE_META_VERSION,
+			fs::SettingsHash::default(),
+			input_timestamp,
+			|file| {
+				image
+					.write_to(file, image::ImageFormat::Png)
+					.context("Failed to save image")
+			},
+			|status| self.config.store_output(&output_path, status),
+		)
+	}
+
+	/// Removes tiles of a mipmap level that are not part of the map anymore
+	///
+	/// Tiles become stale when the corresponding region files have been
+	/// removed from the save data.
+	fn remove_stale(&self, kind: TileKind, level: usize, tiles: &TileCoordMap) -> Result<()> {
+		let Ok(entries) = self.config.tile_dir(kind, level).read_dir() else {
+			return Ok(());
+		};
+
+		for entry in entries.filter_map(|entry| entry.ok()) {
+			let Some(coords) = parse_coord_filename(&entry.file_name(), "png") else {
+				continue;
+			};
+			if tiles.contains(coords) {
+				continue;
+			}
+
+			let path = entry.path();
+			println!(
+				"Removing stale tile {}",
+				path.strip_prefix(&self.config.data_dir)
+					.expect("tile path must be in output directory")
+					.display(),
+			);
+			self.config.remove_output(&path)?;
+		}
+
+		Ok(())
 	}
 
 	/// Runs the mipmap generation
@@ -165,7 +202,15 @@ impl<'a> TileMipmapper<'a> {
 			}
 
 			fs::create_dir_all(&self.config.tile_dir(TileKind::Map, level))?;
-			fs::create_dir_all(&self.config.tile_dir(TileKind::Lightmap, level))?;
+			for kind in self.config.rendered_with_map() {
+				fs::create_dir_all(&self.config.tile_dir(kind, level))?;
+			}
+			for kind in self.config.region_tile_kinds() {
+				if kind.is_data() {
+					continue;
+				}
+				fs::create_dir_all(&self.config.tile_dir(kind, level))?;
+			}
 
 			let next = Self::map_coords(prev);
 
@@ -173,12 +218,18 @@ impl<'a> TileMipmapper<'a> {
 				xs.par_iter().try_for_each(|&x| {
 					let coords = TileCoords { x, z };
 					self.render_mipmap::<image::Rgba<u8>>(TileKind::Map, level, coords, prev)?;
+					for kind in self.config.rendered_with_map() {
+						self.render_mipmap::<image::Rgba<u8>>(kind, level, coords, prev)?;
+					}
 					self.render_mipmap::<image::LumaA<u8>>(
 						TileKind::Lightmap,
 						level,
 						coords,
 						prev,
 					)?;
+					for kind in self.config.region_overlays() {
+						self.render_mipmap::<image::Rgba<u8>>(kind, level, coords, prev)?;
+					}
 					anyhow::Ok(())
 				})
 			})?;
@@ -186,6 +237,12 @@ impl<'a> TileMipmapper<'a> {
 			tile_stack.push(next);
 		}
 
+		for (level, tiles) in tile_stack.iter().enumerate() {
+			for kind in self.config.tile_kinds() {
+				self.remove_stale(kind, level, tiles)?;
+			}
+		}
+
 		Ok(tile_stack)
 	}
 }
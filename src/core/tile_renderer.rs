@@ -1,6 +1,7 @@
 //! The [TileRenderer] and related types and functions
 
 use std::{
+	collections::BTreeMap,
 	num::NonZeroUsize,
 	path::PathBuf,
 	sync::{Arc, Mutex},
@@ -16,7 +17,7 @@ use tokio::sync::OnceCell;
 use super::{common::*, region_group::RegionGroup};
 use crate::{
 	io::{fs, storage},
-	resource::{block_color, needs_biome},
+	resource::{base_block_color, block_color, needs_biome, BlockFlag},
 	types::*,
 	util::coord_offset,
 };
@@ -24,20 +25,20 @@ use crate::{
 /// Type for referencing loaded [ProcessedRegion] data
 type RegionRef = Arc<ProcessedRegion>;
 
-/// Returns the index of the biome at a block coordinate
+/// Returns the processed chunk containing a block column
 ///
 /// The passed chunk and block coordinates relative to the center of the
 /// region group is offset by *dx* and *dz*.
 ///
 /// The returned tuple contains the relative region coordinates the offset coordinate
-/// ends up in (in the range -1..1) and the index in that region's biome list.
-fn biome_at(
+/// ends up in (in the range -1..1), the chunk and the block coordinates in that chunk.
+fn column_at(
 	region_group: &RegionGroup<RegionRef>,
 	chunk: ChunkCoords,
 	block: LayerBlockCoords,
 	dx: i32,
 	dz: i32,
-) -> Option<(i8, i8, u16)> {
+) -> Option<(i8, i8, &ProcessedChunk, LayerBlockCoords)> {
 	let (region_x, chunk_x, block_x) = coord_offset(chunk.x, block.x, dx);
 	let (region_z, chunk_z, block_z) = coord_offset(chunk.z, block.z, dz);
 	let chunk = ChunkCoords {
@@ -49,11 +50,160 @@ fn biome_at(
 		z: block_z,
 	};
 	let region = region_group.get(region_x, region_z)?;
-	Some((
-		region_x,
-		region_z,
-		region.chunks[chunk].as_ref()?.biomes[block]?.get() - 1,
-	))
+	Some((region_x, region_z, region.chunks[chunk].as_deref()?, block))
+}
+
+/// Returns the index of the biome at a block coordinate
+///
+/// The passed chunk and block coordinates relative to the center of the
+/// region group is offset by *dx* and *dz*.
+///
+/// The returned tuple contains the relative region coordinates the offset coordinate
+/// ends up in (in the range -1..1) and the index in that region's biome list.
+fn biome_at(
+	region_group: &RegionGroup<RegionRef>,
+	chunk: ChunkCoords,
+	block: LayerBlockCoords,
+	dx: i32,
+	dz: i32,
+) -> Option<(i8, i8, u16)> {
+	let (region_x, region_z, chunk, block) = column_at(region_group, chunk, block, dx, dz)?;
+	Some((region_x, region_z, chunk.biomes[block]?.get() - 1))
+}
+
+/// Returns the height of the ground at a block coordinate
+///
+/// The passed chunk and block coordinates relative to the center of the
+/// region group is offset by *dx* and *dz*.
+fn height_at(
+	region_group: &RegionGroup<RegionRef>,
+	chunk: ChunkCoords,
+	block: LayerBlockCoords,
+	dx: i32,
+	dz: i32,
+) -> Option<f32> {
+	let (_, _, chunk, block) = column_at(region_group, chunk, block, dx, dz)?;
+	Some(chunk.depths[block]?.0 as f32)
+}
+
+/// Determines how much a block column is occluded by the terrain around it
+///
+/// For each of 8 directions, the steepest angle of elevation towards the
+/// surrounding ground within a few blocks is determined. The returned value
+/// is the average of these angles, scaled from 0 (no higher neighbors) to 1
+/// (surrounded by vertical walls).
+fn ambient_occlusion(
+	region_group: &RegionGroup<RegionRef>,
+	chunk: ChunkCoords,
+	block: LayerBlockCoords,
+	height: f32,
+) -> f32 {
+	/// Directions in which the horizon is searched
+	const DIRECTIONS: [(i32, i32); 8] = [
+		(1, 0),
+		(1, 1),
+		(0, 1),
+		(-1, 1),
+		(-1, 0),
+		(-1, -1),
+		(0, -1),
+		(1, -1),
+	];
+	/// Maximum distance of columns considered in each direction, in blocks
+	const RADIUS: i32 = 4;
+
+	let total: f32 = DIRECTIONS
+		.iter()
+		.map(|&(dx, dz)| {
+			let distance = ((dx * dx + dz * dz) as f32).sqrt();
+			(1..=RADIUS)
+				.filter_map(|step| {
+					let neighbor = height_at(region_group, chunk, block, dx * step, dz * step)?;
+					Some((neighbor - height) / (distance * step as f32))
+				})
+				.fold(0.0, f32::max)
+				.atan()
+		})
+		.sum();
+
+	total / (DIRECTIONS.len() as f32 * std::f32::consts::FRAC_PI_2)
+}
+
+/// Determines how much a block column lies in the shadow of the terrain
+///
+/// The ground is followed from the column towards the sun. The returned value
+/// is 0 when the ray towards the sun is unobstructed, and reaches 1 when it
+/// passes a few blocks below the ground, resulting in soft shadow edges.
+fn cast_shadow(
+	region_group: &RegionGroup<RegionRef>,
+	chunk: ChunkCoords,
+	block: LayerBlockCoords,
+	height: f32,
+	sun: &Hillshade,
+) -> f32 {
+	/// Maximum horizontal length of shadows in blocks
+	const MAX_DISTANCE: i32 = 64;
+	/// Depth of the ray below the ground at which the shadow is fully dark
+	const PENUMBRA: f32 = 4.0;
+	/// Maximum height of any terrain
+	const MAX_HEIGHT: f32 = 320.0;
+
+	let light = sun.light();
+	let horizontal = light.truncate().length();
+	if horizontal < 1e-3 {
+		return 0.0;
+	}
+	let direction = light.truncate() / horizontal;
+	let rise = light.z / horizontal;
+
+	let mut shadow: f32 = 0.0;
+	for step in 1..=MAX_DISTANCE {
+		let ray = height + step as f32 * rise;
+		if ray > MAX_HEIGHT {
+			break;
+		}
+
+		let offset = direction * step as f32;
+		let Some(neighbor) = height_at(
+			region_group,
+			chunk,
+			block,
+			offset.x.round() as i32,
+			offset.y.round() as i32,
+		) else {
+			continue;
+		};
+		shadow = shadow.max(((neighbor - ray) / PENUMBRA).clamp(0.0, 1.0));
+		if shadow >= 1.0 {
+			break;
+		}
+	}
+
+	shadow
+}
+
+/// Height at which block columns are shown at their map position on the
+/// isometric map
+const ISO_BASE_HEIGHT: i32 = 64;
+
+/// Maximum number of pixels a block column is shifted on the isometric map
+///
+/// Limits the rows of the neighboring regions that need to be considered
+/// for an isometric tile.
+const ISO_MAX_SHIFT: i32 = 256;
+
+/// Brightness of the south faces of block columns on the isometric map
+const ISO_SIDE_SHADE: f32 = 0.6;
+
+/// Returns the number of pixels a block column is shifted north on the
+/// isometric map
+///
+/// Every two blocks of height above [ISO_BASE_HEIGHT] move the column up by
+/// one pixel.
+fn iso_shift(height: i32) -> i32 {
+	(height - ISO_BASE_HEIGHT)
+		.div_euclid(2)
+		.clamp(-ISO_MAX_SHIFT, ISO_MAX_SHIFT)
 }
 
 /// The TileRenderer generates map tiles from processed region data
@@ -122,7 +272,12 @@ impl<'a> TileRenderer<'a> {
 	}
 
 	/// Computes the color of a tile pixel
+	///
+	/// When hillshading is enabled, land is shaded by its slope instead of its
+	/// height; water is always shaded by its depth. With ambient occlusion,
+	/// land is additionally darkened by the terrain surrounding it.
 	fn block_color_at(
+		&self,
 		region_group: &RegionGroup<RegionRef>,
 		chunk: &ProcessedChunk,
 		chunk_coords: ChunkCoords,
@@ -141,21 +296,65 @@ impl<'a> TileRenderer<'a> {
 		/// The kernel is mirrored in X und Z direction to build the full 5x5
 		/// smoothing kernel.
 		const SMOOTH: [[f32; 3]; 3] = [[41.0, 26.0, 7.0], [26.0, 16.0, 4.0], [7.0, 4.0, 1.0]];
-		/// Maximum X coordinate offset to take into account for biome smoothing
-		const X: isize = SMOOTH[0].len() as isize - 1;
-		/// Maximum Z coordinate offset to take into account for biome smoothing
-		const Z: isize = SMOOTH.len() as isize - 1;
 
 		let block = chunk.blocks[block_coords]?;
 		let depth = chunk.depths[block_coords]?;
 
+		let water = block.is(BlockFlag::Water);
+		if let Some(water_shading) = self.config.water_shading.as_ref().filter(|_| water) {
+			return Some(Self::shaded_water_color(
+				region_group,
+				chunk,
+				block_coords,
+				water_shading,
+			));
+		}
+
+		let height = depth.0 as f32;
+		let hillshade = Some(&self.config.sun).filter(|_| self.config.hillshade && !water);
+		let mut shade = hillshade.map_or(1.0, |hillshade| {
+			// Missing neighbors are treated as flat ground
+			let neighbor = |dx, dz| {
+				height_at(region_group, chunk_coords, block_coords, dx, dz).unwrap_or(height)
+			};
+			let dx = (neighbor(1, 0) - neighbor(-1, 0)) / 2.0;
+			let dz = (neighbor(0, 1) - neighbor(0, -1)) / 2.0;
+			hillshade.shade(dx, dz)
+		});
+		if self.config.shadows && !water {
+			let shadow = cast_shadow(
+				region_group,
+				chunk_coords,
+				block_coords,
+				height,
+				&self.config.sun,
+			);
+			shade *= 1.0 - 0.4 * shadow;
+		}
+		if let Some(strength) = self.config.ambient_occlusion.filter(|_| !water) {
+			let occlusion = ambient_occlusion(region_group, chunk_coords, block_coords, height);
+			shade *= 1.0 - strength * occlusion;
+		}
+		let color_in = |biome| {
+			if hillshade.is_some() {
+				base_block_color(block, biome, depth.0 as f32)
+			} else {
+				block_color(block, biome, depth.0 as f32)
+			}
+		};
+
 		if !needs_biome(block) {
-			return Some(block_color(block, None, depth.0 as f32));
+			return Some(
+				shade * self.canopy_color(region_group, chunk, block_coords, color_in(None)),
+			);
 		}
 
+		// Maximum coordinate offset to take into account for biome smoothing
+		let radius = self.config.biome_blend as isize;
+
 		let mut weights = rustc_hash::FxHashMap::<u32, ((i8, i8, u16), f32)>::default();
-		for dz in -Z..=Z {
-			for dx in -X..=X {
+		for dz in -radius..=radius {
+			for dx in -radius..=radius {
 				let w = SMOOTH[dz.unsigned_abs()][dx.unsigned_abs()];
 				if w == 0.0 {
 					continue;
@@ -189,15 +388,243 @@ impl<'a> TileRenderer<'a> {
 			let biome = region.biome_list.get_index(index.into())?;
 
 			total += w;
-			color += w * block_color(block, Some(biome), depth.0 as f32);
+			color += w * color_in(Some(biome));
+		}
+
+		Some(shade * self.canopy_color(region_group, chunk, block_coords, color / total))
+	}
+
+	/// Blends the color of leaves with the ground below them
+	///
+	/// Returns the passed color unchanged if the canopy opacity is not
+	/// configured, or if the column is not covered by leaves.
+	fn canopy_color(
+		&self,
+		region_group: &RegionGroup<RegionRef>,
+		chunk: &ProcessedChunk,
+		block_coords: LayerBlockCoords,
+		color: Vec3,
+	) -> Vec3 {
+		let leaves = chunk.blocks[block_coords].is_some_and(|block| !block.is(BlockFlag::Water));
+		let Some(opacity) = self.config.canopy_opacity.filter(|_| leaves) else {
+			return color;
+		};
+		let Some(floor_color) = Self::floor_color(region_group, chunk, block_coords) else {
+			return color;
+		};
+
+		floor_color.lerp(color, opacity)
+	}
+
+	/// Tints the color of a block by the translucent block above it
+	///
+	/// Returns the passed color unchanged if there is no translucent block
+	/// above the top block.
+	fn tinted_color(chunk: &ProcessedChunk, block_coords: LayerBlockCoords, color: Vec3) -> Vec3 {
+		/// Strength of the tint of translucent blocks
+		const STRENGTH: f32 = 0.6;
+
+		let Some(tint) = chunk.tints[block_coords].filter(|&tint| !needs_biome(tint)) else {
+			return color;
+		};
+		let tint = base_block_color(tint, None, 0.0) / 255.0;
+
+		color.lerp(color * tint, STRENGTH)
+	}
+
+	/// Computes the color of a water column from its depth
+	///
+	/// When a floor cutoff is configured, the ground below shallow water
+	/// is blended in, fading out with increasing depth.
+	fn shaded_water_color(
+		region_group: &RegionGroup<RegionRef>,
+		chunk: &ProcessedChunk,
+		block_coords: LayerBlockCoords,
+		water_shading: &WaterShading,
+	) -> Vec3 {
+		let depth = f32::from(chunk.water_depths[block_coords]);
+		let water = water_shading.color(depth);
+
+		let Some(cutoff) = water_shading.floor_cutoff.filter(|&cutoff| depth < cutoff) else {
+			return water;
+		};
+		let Some(floor_color) = Self::floor_color(region_group, chunk, block_coords) else {
+			return water;
+		};
+
+		floor_color.lerp(water, 0.25 + 0.75 * depth / cutoff)
+	}
+
+	/// Computes the color of the ground below water or leaves
+	///
+	/// Returns [None] for columns without water or see-through leaves, or when
+	/// the ground below them is unknown.
+	fn floor_color(
+		region_group: &RegionGroup<RegionRef>,
+		chunk: &ProcessedChunk,
+		block_coords: LayerBlockCoords,
+	) -> Option<Vec3> {
+		let floor = chunk.floors[block_coords]?;
+		let height = chunk.depths[block_coords]?;
+
+		let biome = chunk.biomes[block_coords].and_then(|index| {
+			region_group
+				.center()
+				.biome_list
+				.get_index(usize::from(index.get() - 1))
+		});
+		if needs_biome(floor) && biome.is_none() {
+			return None;
+		}
+
+		Some(block_color(floor, biome, height.0 as f32))
+	}
+
+	/// Computes the color of an ocean floor map pixel from its regular map color
+	///
+	/// Water is left out, showing the ground below it tinted by the depth of
+	/// the water. Land keeps its regular color.
+	fn ocean_floor_color(
+		region_group: &RegionGroup<RegionRef>,
+		chunk: &ProcessedChunk,
+		block_coords: LayerBlockCoords,
+		color: Vec3,
+	) -> Vec3 {
+		/// Color the ground is tinted with in deep water
+		const TINT: Vec3 = Vec3::new(20.0, 40.0, 110.0);
+		/// Water depth in blocks at which the tint reaches its full strength
+		const DEPTH: f32 = 48.0;
+		/// Strength of the tint in deep water
+		const MAX_TINT: f32 = 0.75;
+
+		let depth = f32::from(chunk.water_depths[block_coords]);
+		if depth == 0.0 {
+			return color;
+		}
+		let Some(floor_color) = Self::floor_color(region_group, chunk, block_coords) else {
+			return color;
+		};
+
+		floor_color.lerp(TINT, (depth / DEPTH).min(1.0) * MAX_TINT)
+	}
+
+	/// Computes the color of a night map pixel from its regular map color
+	///
+	/// The color is darkened to moonlight, or to almost black for covered
+	/// columns, and brightened by the block light above the block.
+	fn night_color(chunk: &ProcessedChunk, block_coords: LayerBlockCoords, color: Vec3) -> Vec3 {
+		/// Light factor of blocks exposed to the sky
+		const MOONLIGHT: Vec3 = Vec3::new(0.2, 0.23, 0.35);
+		/// Light factor of covered blocks
+		const DARKNESS: Vec3 = Vec3::new(0.05, 0.05, 0.07);
+		/// Light factor of blocks at full block light
+		const TORCHLIGHT: Vec3 = Vec3::new(1.0, 0.8, 0.55);
+
+		let ambient = if chunk.covered[block_coords] {
+			DARKNESS
+		} else {
+			MOONLIGHT
+		};
+		let light = f32::from(chunk.block_light[block_coords]) / 15.0;
+
+		(color * (ambient + light * light * TORCHLIGHT)).min(Vec3::splat(255.0))
+	}
+
+	/// Computes the color of a contour line overlay pixel
+	///
+	/// A contour line is drawn on the upper side of each step in the ground
+	/// elevation that crosses a multiple of the contour interval. Returns
+	/// [None] for transparent pixels.
+	fn contour_color(
+		&self,
+		region_group: &RegionGroup<RegionRef>,
+		chunk: &ProcessedChunk,
+		chunk_coords: ChunkCoords,
+		block_coords: LayerBlockCoords,
+	) -> Option<image::Rgba<u8>> {
+		/// Color of contour lines
+		const LINE_COLOR: image::Rgba<u8> = image::Rgba([64, 32, 0, 128]);
+		/// Color of emphasized contour lines
+		const INDEX_COLOR: image::Rgba<u8> = image::Rgba([64, 32, 0, 224]);
+		/// Every INDEX_LINES-th contour line is emphasized
+		const INDEX_LINES: i32 = 5;
+
+		let interval = self.config.contour_interval;
+		let level = chunk.depths[block_coords]?.0.div_euclid(interval);
+
+		// Contour lines are crossed towards lower neighbors only
+		let mut index = false;
+		let mut line = false;
+		for (dx, dz) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+			let Some(neighbor) = height_at(region_group, chunk_coords, block_coords, dx, dz) else {
+				continue;
+			};
+			let neighbor_level = (neighbor as i32).div_euclid(interval);
+			if neighbor_level >= level {
+				continue;
+			}
+			line = true;
+			index |= level.div_euclid(INDEX_LINES) > neighbor_level.div_euclid(INDEX_LINES);
 		}
 
-		Some(color / total)
+		if index {
+			Some(INDEX_COLOR)
+		} else if line {
+			Some(LINE_COLOR)
+		} else {
+			None
+		}
 	}
 
-	/// Renders a chunk subtile into a region tile image
+	/// Computes the color of an elevation-tinted terrain map pixel
+	///
+	/// Land is colored by its elevation from green to white and shaded by
+	/// its slope, lit from the configured sun direction. Water is colored by
+	/// its depth below sea level.
+	fn terrain_color(
+		&self,
+		region_group: &RegionGroup<RegionRef>,
+		chunk: &ProcessedChunk,
+		chunk_coords: ChunkCoords,
+		block_coords: LayerBlockCoords,
+	) -> Option<Vec3> {
+		/// Y coordinate of the water surface of the sea
+		const SEA_LEVEL: f32 = 62.0;
+
+		let land_colors = [
+			(SEA_LEVEL, Vec3::new(72.0, 140.0, 60.0)),
+			(80.0, Vec3::new(120.0, 170.0, 80.0)),
+			(100.0, Vec3::new(190.0, 180.0, 110.0)),
+			(130.0, Vec3::new(150.0, 110.0, 70.0)),
+			(170.0, Vec3::new(130.0, 120.0, 110.0)),
+			(210.0, Vec3::new(245.0, 245.0, 245.0)),
+		];
+		let water_colors = [
+			(0.0, Vec3::new(90.0, 160.0, 230.0)),
+			(40.0, Vec3::new(20.0, 50.0, 140.0)),
+		];
+
+		let block = chunk.blocks[block_coords]?;
+		let height = chunk.depths[block_coords]?.0 as f32;
+
+		if block.is(BlockFlag::Water) {
+			return Some(gradient(&water_colors, SEA_LEVEL - height));
+		}
+
+		// Missing neighbors are treated as flat ground
+		let neighbor =
+			|dx, dz| height_at(region_group, chunk_coords, block_coords, dx, dz).unwrap_or(height);
+		let dx = (neighbor(1, 0) - neighbor(-1, 0)) / 2.0;
+		let dz = (neighbor(0, 1) - neighbor(0, -1)) / 2.0;
+
+		Some(gradient(&land_colors, height) * self.config.sun.shade(dx, dz))
+	}
+
+	/// Renders a chunk subtile into the region tile images of the map and
+	/// the tile kinds rendered with it
 	fn render_chunk(
-		image: &mut image::RgbaImage,
+		&self,
+		tiles: &mut BTreeMap<TileKind, image::RgbaImage>,
 		region_group: &RegionGroup<RegionRef>,
 		chunk: &ProcessedChunk,
 		chunk_coords: ChunkCoords,
@@ -205,30 +632,171 @@ impl<'a> TileRenderer<'a> {
 		/// Width/height of a chunk subtile
 		const N: u32 = BLOCKS_PER_CHUNK as u32;
 
-		let chunk_image = image::RgbaImage::from_fn(N, N, |x, z| {
-			let block_coords = LayerBlockCoords {
-				x: BlockX::new(x),
-				z: BlockZ::new(z),
-			};
-			let color = Self::block_color_at(region_group, chunk, chunk_coords, block_coords);
-			image::Rgba(
-				color
-					.map(|c| [c[0] as u8, c[1] as u8, c[2] as u8, 255])
-					.unwrap_or_default(),
-			)
-		});
-		overlay_chunk(image, &chunk_image, chunk_coords);
+		let mut chunk_tiles: BTreeMap<_, _> = tiles
+			.keys()
+			.map(|&kind| (kind, image::RgbaImage::new(N, N)))
+			.collect();
+
+		for z in 0..N {
+			for x in 0..N {
+				let block_coords = LayerBlockCoords {
+					x: BlockX::new(x),
+					z: BlockZ::new(z),
+				};
+				let color = self
+					.block_color_at(region_group, chunk, chunk_coords, block_coords)
+					.map(|color| Self::tinted_color(chunk, block_coords, color));
+
+				for (&kind, chunk_image) in &mut chunk_tiles {
+					let value = match kind {
+						TileKind::Map => color.map(|color| self.pixel(color)),
+						TileKind::Night => color
+							.map(|color| self.pixel(Self::night_color(chunk, block_coords, color))),
+						TileKind::OceanFloor => color.map(|color| {
+							self.pixel(Self::ocean_floor_color(
+								region_group,
+								chunk,
+								block_coords,
+								color,
+							))
+						}),
+						TileKind::Terrain => self
+							.terrain_color(region_group, chunk, chunk_coords, block_coords)
+							.map(|color| self.pixel(color)),
+						TileKind::Contours => {
+							self.contour_color(region_group, chunk, chunk_coords, block_coords)
+						}
+						_ => unreachable!("{:?} tiles are not rendered with the map", kind),
+					};
+					if let Some(value) = value {
+						chunk_image.put_pixel(x, z, value);
+					}
+				}
+			}
+		}
+
+		for (kind, chunk_image) in chunk_tiles {
+			let image = tiles.get_mut(&kind).expect("tile image must exist");
+			overlay_chunk(image, &chunk_image, chunk_coords);
+		}
 	}
 
-	/// Renders a region tile image
-	fn render_region(image: &mut image::RgbaImage, region_group: &RegionGroup<RegionRef>) {
+	/// Converts a color to an opaque pixel, applying the color adjustment
+	fn pixel(&self, color: Vec3) -> image::Rgba<u8> {
+		let color = self
+			.config
+			.color_adjustment
+			.map_or(color, |adjustment| adjustment.apply(color));
+		let [r, g, b] = color.clamp(Vec3::ZERO, Vec3::splat(255.0)).to_array();
+		image::Rgba([r as u8, g as u8, b as u8, 255])
+	}
+
+	/// Renders the region tile images of the map and the tile kinds rendered
+	/// with it
+	fn render_region(
+		&self,
+		tiles: &mut BTreeMap<TileKind, image::RgbaImage>,
+		region_group: &RegionGroup<RegionRef>,
+	) {
 		for (coords, chunk) in region_group.center().chunks.iter() {
 			let Some(chunk) = chunk else {
 				continue;
 			};
 
-			Self::render_chunk(image, region_group, chunk, coords);
+			self.render_chunk(tiles, region_group, chunk, coords);
+		}
+	}
+
+	/// Renders the isometric tile image of a region
+	///
+	/// The isometric map shows the terrain from the south: each block column
+	/// is shifted north by its height, and its south face is drawn down to
+	/// the top of the next column. Columns are drawn from north to south, so
+	/// closer columns cover the ones behind them. As columns may be shifted
+	/// into neighboring tiles, the adjacent regions to the north and south
+	/// are drawn as well.
+	///
+	/// The colors of the columns of the center region are taken from the
+	/// already rendered *map* tile.
+	fn render_isometric(
+		&self,
+		region_group: &RegionGroup<RegionRef>,
+		map: &image::RgbaImage,
+	) -> image::RgbaImage {
+		/// Width/height of a tile image
+		const N: i32 = (BLOCKS_PER_CHUNK * CHUNKS_PER_REGION) as i32;
+
+		// Region groups centered at the northern and southern neighbors, used
+		// to compute the colors of their block columns
+		let groups = [region_group.shift(0, -1), region_group.shift(0, 1)];
+
+		let column = |x: i32, z: i32| {
+			let (_, chunk_x, block_x) = coord_offset(ChunkX::new(0), BlockX::new(0), x);
+			let (region_z, chunk_z, block_z) = coord_offset(ChunkZ::new(0), BlockZ::new(0), z);
+			let chunk_coords = ChunkCoords {
+				x: chunk_x,
+				z: chunk_z,
+			};
+			let block_coords = LayerBlockCoords {
+				x: block_x,
+				z: block_z,
+			};
+			let chunk = region_group.get(0, region_z)?.chunks[chunk_coords].as_deref()?;
+			Some((region_z, chunk, chunk_coords, block_coords))
+		};
+		let height = |x: i32, z: i32| {
+			let (_, chunk, _, block_coords) = column(x, z)?;
+			chunk.surface_height(block_coords)
+		};
+		let color = |x: i32, z: i32| {
+			let (region_z, chunk, chunk_coords, block_coords) = column(x, z)?;
+			if region_z == 0 {
+				let pixel = *map.get_pixel(x as u32, z as u32);
+				return (pixel[3] != 0).then_some(pixel);
+			}
+			let group = groups[usize::from(region_z > 0)].as_ref()?;
+			let color = self.block_color_at(group, chunk, chunk_coords, block_coords)?;
+			Some(self.pixel(Self::tinted_color(chunk, block_coords, color)))
+		};
+
+		let mut image = image::RgbaImage::new(N as u32, N as u32);
+
+		for x in 0..N {
+			let mut next_height = height(x, -ISO_MAX_SHIFT);
+
+			for z in -ISO_MAX_SHIFT..N + ISO_MAX_SHIFT {
+				let current_height = next_height;
+				next_height = height(x, z + 1);
+				let Some(current_height) = current_height else {
+					continue;
+				};
+
+				let top = z - iso_shift(current_height);
+				let bottom = next_height
+					.map_or(top + 1, |next_height| z + 1 - iso_shift(next_height))
+					.max(top + 1);
+				if bottom <= 0 || top >= N {
+					continue;
+				}
+
+				let Some(pixel) = color(x, z) else {
+					continue;
+				};
+				if top >= 0 {
+					image.put_pixel(x as u32, top as u32, pixel);
+				}
+
+				let mut side = pixel;
+				for value in &mut side.0[..3] {
+					*value = (f32::from(*value) * ISO_SIDE_SHADE) as u8;
+				}
+				for row in (top + 1).max(0)..bottom.min(N) {
+					image.put_pixel(x as u32, row as u32, side);
+				}
+			}
 		}
+
+		image
 	}
 
 	/// Returns the filename of the processed data for a region and the time of its last modification
@@ -269,13 +837,23 @@ impl<'a> TileRenderer<'a> {
 		let (processed_paths, processed_timestamp) = self.processed_sources(coords)?;
 
 		let output_path = self.config.tile_path(TileKind::Map, 0, coords);
-		let output_timestamp = fs::read_timestamp(&output_path, FILE_META_VERSION);
+		let output_timestamp = std::iter::once(TileKind::Map)
+			.chain(self.config.rendered_with_map())
+			.map(|kind| {
+				fs::read_timestamp_with_settings(
+					&self.config.tile_path(kind, 0, coords),
+					FILE_META_VERSION,
+					self.config.render_settings,
+				)
+			})
+			.min()
+			.flatten();
 
 		if Some(processed_timestamp) <= output_timestamp {
 			println!(
 				"Skipping unchanged tile {}",
 				output_path
-					.strip_prefix(&self.config.output_dir)
+					.strip_prefix(&self.config.data_dir)
 					.expect("tile path must be in output directory")
 					.display(),
 			);
@@ -285,7 +863,7 @@ impl<'a> TileRenderer<'a> {
 		println!(
 			"Rendering tile {}",
 			output_path
-				.strip_prefix(&self.config.output_dir)
+				.strip_prefix(&self.config.data_dir)
 				.expect("tile path must be in output directory")
 				.display(),
 		);
@@ -294,24 +872,53 @@ impl<'a> TileRenderer<'a> {
 			.rt
 			.block_on(self.load_region_group(processed_paths))
 			.with_context(|| format!("Region {:?} from previous step must be loadable", coords))?;
-		let mut image = image::RgbaImage::new(N, N);
-		Self::render_region(&mut image, &region_group);
+		let mut tiles: BTreeMap<_, _> = std::iter::once(TileKind::Map)
+			.chain(self.config.rendered_with_map())
+			.filter(|&kind| kind != TileKind::Isometric)
+			.map(|kind| (kind, image::RgbaImage::new(N, N)))
+			.collect();
+		self.render_region(&mut tiles, &region_group);
+
+		if self.config.overlays.contains(&TileKind::Isometric) {
+			let image = self.render_isometric(&region_group, &tiles[&TileKind::Map]);
+			tiles.insert(TileKind::Isometric, image);
+		}
+
+		for (kind, image) in &tiles {
+			let path = self.config.tile_path(*kind, 0, coords);
+			self.save_tile(&path, image, processed_timestamp)?;
+		}
+
+		Ok(())
+	}
 
+	/// Saves a rendered tile image
+	fn save_tile(
+		&self,
+		path: &std::path::Path,
+		image: &image::RgbaImage,
+		timestamp: SystemTime,
+	) -> Result<()> {
 		fs::create_with_timestamp(
-			&output_path,
+			path,
 			FILE_META_VERSION,
-			processed_timestamp,
+			self.config.render_settings,
+			timestamp,
 			|file| {
 				image
 					.write_to(file, image::ImageFormat::Png)
 					.context("Failed to save image")
 			},
+			|status| self.config.store_output(path, status),
 		)
 	}
 
 	/// Runs the tile generation
 	pub fn run(self) -> Result<()> {
 		fs::create_dir_all(&self.config.tile_dir(TileKind::Map, 0))?;
+		for kind in self.config.rendered_with_map() {
+			fs::create_dir_all(&self.config.tile_dir(kind, 0))?;
+		}
 
 		// Use par_bridge to process items in order (for better use of region cache)
 		self.regions.iter().par_bridge().try_for_each(|&coords| {
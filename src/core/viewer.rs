@@ -0,0 +1,61 @@
+//! The [write] function and related structures
+
+use std::{io::Write, path::Path};
+
+use anyhow::Result;
+
+use crate::io::fs;
+
+/// Static files of the viewer, embedded into the binary
+///
+/// Each entry consists of the path relative to the viewer directory and
+/// the file contents.
+const FILES: &[(&str, &[u8])] = &[
+	("index.html", include_bytes!("../../viewer/index.html")),
+	("MinedMap.js", include_bytes!("../../viewer/MinedMap.js")),
+	(
+		"leaflet-1.6.0/leaflet.css",
+		include_bytes!("../../viewer/leaflet-1.6.0/leaflet.css"),
+	),
+	(
+		"leaflet-1.6.0/leaflet.js",
+		include_bytes!("../../viewer/leaflet-1.6.0/leaflet.js"),
+	),
+	(
+		"leaflet-1.6.0/images/layers.png",
+		include_bytes!("../../viewer/leaflet-1.6.0/images/layers.png"),
+	),
+	(
+		"leaflet-1.6.0/images/layers-2x.png",
+		include_bytes!("../../viewer/leaflet-1.6.0/images/layers-2x.png"),
+	),
+	(
+		"leaflet-1.6.0/images/marker-icon.png",
+		include_bytes!("../../viewer/leaflet-1.6.0/images/marker-icon.png"),
+	),
+	(
+		"leaflet-1.6.0/images/marker-icon-2x.png",
+		include_bytes!("../../viewer/leaflet-1.6.0/images/marker-icon-2x.png"),
+	),
+	(
+		"leaflet-1.6.0/images/marker-shadow.png",
+		include_bytes!("../../viewer/leaflet-1.6.0/images/marker-shadow.png"),
+	),
+];
+
+/// Writes the embedded viewer files into the given site directory
+///
+/// Files are only replaced when their contents have changed, so the
+/// timestamps of the viewer files are kept across runs of the same
+/// MinedMap version.
+pub fn write(site_dir: &Path) -> Result<()> {
+	for (name, contents) in FILES {
+		let path = site_dir.join(name);
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		fs::create_with_tmpfile(&path, |file| Ok(file.write_all(contents)?))?;
+	}
+
+	Ok(())
+}
@@ -16,11 +16,21 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub struct FileMetaVersion(pub u32);
 
+/// Hash of the settings a generated file depends on
+///
+/// Deserialized metadata with a different settings hash are considered
+/// outdated when read using [read_timestamp_with_settings].
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SettingsHash(pub u64);
+
 /// Metadata stored with generated files to track required incremental updates
 #[derive(Debug, Serialize, Deserialize)]
 struct FileMeta {
 	/// Version of data described by the FileMeta
 	version: FileMetaVersion,
+	/// Hash of the settings used to generate the file described by the FileMeta
+	#[serde(default)]
+	settings: SettingsHash,
 	/// Timestamp stored with generated data
 	///
 	/// This timestamp is always the time of last modification of the inputs
@@ -28,6 +38,17 @@ struct FileMeta {
 	timestamp: SystemTime,
 }
 
+/// Effect of writing a file with [create_with_tmpfile]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+	/// The file did not exist before
+	Created,
+	/// The file existed, but its contents have changed
+	Updated,
+	/// The file existed with the same contents and has not been touched
+	Unchanged,
+}
+
 /// Helper for creating suffixed file paths
 fn suffix_name(path: &Path, suffix: &str) -> PathBuf {
 	let mut file_name = path.file_name().unwrap_or_default().to_os_string();
@@ -111,7 +132,10 @@ pub fn equal(path1: &Path, path2: &Path) -> Result<bool> {
 /// when the function is interrupted. In addition, the old and new contents of
 /// the file are compared if a file with the same name already exists, and the
 /// file timestamp is only updated if the contents have changed.
-pub fn create_with_tmpfile<T, F>(path: &Path, f: F) -> Result<T>
+///
+/// Returns the value returned by the passed function as well as a [FileStatus]
+/// describing whether the file was actually modified.
+pub fn create_with_tmpfile<T, F>(path: &Path, f: F) -> Result<(T, FileStatus)>
 where
 	F: FnOnce(&mut BufWriter<File>) -> Result<T>,
 {
@@ -120,11 +144,14 @@ where
 
 	let ret = (|| {
 		let ret = create(&tmp_path, f)?;
-		if !matches!(equal(path, &tmp_path), Result::Ok(true)) {
-			rename(&tmp_path, path)?;
-			cleanup = false;
-		}
-		Ok(ret)
+		let status = match equal(path, &tmp_path) {
+			Result::Ok(true) => return Ok((ret, FileStatus::Unchanged)),
+			Result::Ok(false) => FileStatus::Updated,
+			Err(_) => FileStatus::Created,
+		};
+		rename(&tmp_path, path)?;
+		cleanup = false;
+		Ok((ret, status))
 	})();
 
 	if cleanup {
@@ -146,9 +173,8 @@ pub fn modified_timestamp(path: &Path) -> Result<SystemTime> {
 		})
 }
 
-/// Reads the stored timestamp from file metadata for a file previously written
-/// using [create_with_timestamp]
-pub fn read_timestamp(path: &Path, version: FileMetaVersion) -> Option<SystemTime> {
+/// Reads the file metadata for a file previously written using [create_with_timestamp]
+fn read_meta(path: &Path, version: FileMetaVersion) -> Option<FileMeta> {
 	let meta_path = metafile_name(path);
 	let mut file = BufReader::new(fs::File::open(meta_path).ok()?);
 
@@ -157,30 +183,81 @@ pub fn read_timestamp(path: &Path, version: FileMetaVersion) -> Option<SystemTim
 		return None;
 	}
 
-	Some(meta.timestamp)
+	Some(meta)
+}
+
+/// Reads the stored timestamp from file metadata for a file previously written
+/// using [create_with_timestamp]
+///
+/// The settings the file was generated with are ignored.
+pub fn read_timestamp(path: &Path, version: FileMetaVersion) -> Option<SystemTime> {
+	read_meta(path, version).map(|meta| meta.timestamp)
+}
+
+/// Reads the stored timestamp from file metadata like [read_timestamp], but
+/// only if the file was generated with the given settings
+pub fn read_timestamp_with_settings(
+	path: &Path,
+	version: FileMetaVersion,
+	settings: SettingsHash,
+) -> Option<SystemTime> {
+	read_meta(path, version)
+		.filter(|meta| meta.settings == settings)
+		.map(|meta| meta.timestamp)
 }
 
 /// Creates a new file, temporarily storing its contents in a temporary file
 /// like [create_with_tmpfile], and storing a timestamp in a metadata file
 /// if successful
 ///
-/// The timestamp can be retrieved later using [read_timestamp].
-pub fn create_with_timestamp<T, F>(
+/// The new file is passed on by calling *store* with its [FileStatus] before
+/// the metadata file is written, so a file that failed to be stored is
+/// considered outdated and generated again. For the same reason, an existing
+/// file without metadata is reported as [FileStatus::Updated] even if its
+/// contents are unchanged.
+///
+/// The timestamp can be retrieved later using [read_timestamp] or
+/// [read_timestamp_with_settings].
+pub fn create_with_timestamp<T, F, S>(
 	path: &Path,
 	version: FileMetaVersion,
+	settings: SettingsHash,
 	timestamp: SystemTime,
 	f: F,
+	store: S,
 ) -> Result<T>
 where
 	F: FnOnce(&mut BufWriter<File>) -> Result<T>,
+	S: FnOnce(FileStatus) -> Result<()>,
 {
-	let ret = create_with_tmpfile(path, f)?;
-
 	let meta_path = metafile_name(path);
+	let had_meta = fs::remove_file(&meta_path).is_ok();
+
+	let (ret, status) = create_with_tmpfile(path, f)?;
+	store(match status {
+		FileStatus::Unchanged if !had_meta => FileStatus::Updated,
+		status => status,
+	})?;
+
 	create(&meta_path, |file| {
-		serde_json::to_writer(file, &FileMeta { version, timestamp })?;
+		serde_json::to_writer(
+			file,
+			&FileMeta {
+				version,
+				settings,
+				timestamp,
+			},
+		)?;
 		Ok(())
 	})?;
 
 	Ok(ret)
 }
+
+/// Removes a file previously written using [create_with_timestamp] together
+/// with its metadata file
+pub fn remove_with_timestamp(path: &Path) -> Result<()> {
+	fs::remove_file(path).with_context(|| format!("Failed to remove file {}", path.display()))?;
+	let _ = fs::remove_file(metafile_name(path));
+	Ok(())
+}
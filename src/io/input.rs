@@ -0,0 +1,374 @@
+//! Access to Minecraft save data
+//!
+//! Save data can be read from a regular directory or directly from a backup
+//! archive. All paths passed to an [InputSource] are relative to the root
+//! of the save data (the directory containing `level.dat`).
+
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fs::{self, File},
+	io::{self, Cursor, Read, Seek, SeekFrom},
+	path::{Path, PathBuf},
+	sync::Mutex,
+	time::{Duration, SystemTime},
+};
+
+use anyhow::{bail, Context, Result};
+
+/// Helper trait for readers returned by [InputSource::open]
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// A source of Minecraft save data
+pub trait InputSource: Send + Sync {
+	/// Returns the names of all regular files in a directory of the save data
+	fn list_files(&self, dir: &Path) -> Result<Vec<String>>;
+	/// Returns the names of all subdirectories of a directory of the save data
+	fn list_dirs(&self, dir: &Path) -> Result<Vec<String>>;
+	/// Returns the time of last modification of a file
+	fn modified(&self, path: &Path) -> Result<SystemTime>;
+	/// Opens a file for reading
+	fn open(&self, path: &Path) -> Result<Box<dyn ReadSeek>>;
+	/// Announces files that are going to be opened soon
+	///
+	/// Sources for which access is slow can start fetching the data in the
+	/// background. The iterator is only consumed by sources supporting
+	/// prefetching.
+	fn prefetch(&self, _paths: &mut dyn Iterator<Item = PathBuf>) {}
+}
+
+/// Opens the save data at the given path
+///
+/// Directories and `.zip`, `.tar`, `.tar.gz` and `.tgz` archives are supported.
+/// When the `sftp` feature is enabled, `sftp://` URLs can be passed as well;
+/// *num_threads* is used to determine the number of parallel connections.
+pub fn open(path: &Path, num_threads: usize) -> Result<Box<dyn InputSource>> {
+	#[cfg(feature = "sftp")]
+	if let Some(url) = path.to_str().filter(|url| url.starts_with("sftp://")) {
+		return Ok(Box::new(super::sftp::SftpSource::open(url, num_threads)?));
+	}
+	#[cfg(not(feature = "sftp"))]
+	let _ = num_threads;
+
+	if path.is_dir() {
+		return Ok(Box::new(DirSource(path.to_path_buf())));
+	}
+
+	let name = path
+		.file_name()
+		.and_then(|name| name.to_str())
+		.unwrap_or_default()
+		.to_ascii_lowercase();
+
+	Ok(if name.ends_with(".zip") {
+		Box::new(ArchiveSource::open_zip(path)?)
+	} else if name.ends_with(".tar") {
+		Box::new(ArchiveSource::open_tar(path)?)
+	} else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+		Box::new(ArchiveSource::open_tar_gz(path)?)
+	} else {
+		bail!("Unsupported input {}", path.display());
+	})
+}
+
+/// [InputSource] for save data stored in a directory
+#[derive(Debug)]
+pub struct DirSource(PathBuf);
+
+impl DirSource {
+	/// Returns the names of all entries of a directory matching a file type predicate
+	fn list_entries(&self, dir: &Path, f: impl Fn(fs::FileType) -> bool) -> Result<Vec<String>> {
+		let path = self.0.join(dir);
+		Ok(path
+			.read_dir()
+			.with_context(|| format!("Failed to read directory {}", path.display()))?
+			.filter_map(|entry| entry.ok())
+			.filter(|entry| matches!(entry.file_type().map(&f), Ok(true)))
+			.filter_map(|entry| entry.file_name().into_string().ok())
+			.collect())
+	}
+}
+
+impl InputSource for DirSource {
+	fn list_files(&self, dir: &Path) -> Result<Vec<String>> {
+		// We are only interested in regular files
+		self.list_entries(dir, |file_type| file_type.is_file())
+	}
+
+	fn list_dirs(&self, dir: &Path) -> Result<Vec<String>> {
+		self.list_entries(dir, |file_type| file_type.is_dir())
+	}
+
+	fn modified(&self, path: &Path) -> Result<SystemTime> {
+		super::fs::modified_timestamp(&self.0.join(path))
+	}
+
+	fn open(&self, path: &Path) -> Result<Box<dyn ReadSeek>> {
+		let path = self.0.join(path);
+		let file =
+			File::open(&path).with_context(|| format!("Failed to open file {}", path.display()))?;
+		Ok(Box::new(file))
+	}
+}
+
+/// Location of a file inside an archive
+#[derive(Debug, Clone, Copy)]
+enum ArchiveEntryLocation {
+	/// Index of a file in a ZIP archive
+	Zip(usize),
+	/// Offset and size of a file in a tar archive
+	Tar(u64, u64),
+}
+
+/// Metadata of a file inside an archive
+#[derive(Debug, Clone, Copy)]
+struct ArchiveEntry {
+	/// Location of the file data
+	location: ArchiveEntryLocation,
+	/// Time of last modification
+	modified: SystemTime,
+}
+
+/// Open archive file
+enum Archive {
+	/// ZIP archive
+	Zip(Mutex<zip::ZipArchive<File>>),
+	/// Uncompressed tar archive
+	Tar(Mutex<File>),
+}
+
+/// [InputSource] for save data stored in a backup archive
+///
+/// The archive is indexed when it is opened. Files are decompressed into
+/// memory when they are opened for reading.
+pub struct ArchiveSource {
+	/// The open archive
+	archive: Archive,
+	/// Map of paths relative to the save data root to archive entries
+	entries: BTreeMap<PathBuf, ArchiveEntry>,
+	/// Path of a temporary file to remove when the source is dropped
+	tmpfile: Option<PathBuf>,
+}
+
+/// Converts a timestamp in MS-DOS format as stored in ZIP archives to a [SystemTime]
+///
+/// The timestamp is interpreted as UTC.
+fn dos_timestamp(datetime: zip::DateTime) -> SystemTime {
+	let days = crate::core::days_from_civil(
+		datetime.year().into(),
+		datetime.month().into(),
+		datetime.day().into(),
+	);
+	let secs = days * 86400
+		+ i64::from(datetime.hour()) * 3600
+		+ i64::from(datetime.minute()) * 60
+		+ i64::from(datetime.second());
+	SystemTime::UNIX_EPOCH + Duration::from_secs(secs.try_into().unwrap_or_default())
+}
+
+impl ArchiveSource {
+	/// Creates an [ArchiveSource] from a list of entries with paths relative
+	/// to the archive root
+	///
+	/// The save data root is determined by looking for the `level.dat` file
+	/// closest to the archive root. When there are multiple `level.dat` files
+	/// at the same level (for example in the backup of a server with several
+	/// worlds), the parent directory of these worlds is used as the root.
+	fn new(
+		archive: Archive,
+		entries: Vec<(PathBuf, ArchiveEntry)>,
+		tmpfile: Option<PathBuf>,
+	) -> Result<Self> {
+		let worlds: Vec<_> = entries
+			.iter()
+			.filter(|(path, _)| path.file_name() == Some("level.dat".as_ref()))
+			.filter_map(|(path, _)| path.parent())
+			.collect();
+		let depth = worlds
+			.iter()
+			.map(|root| root.components().count())
+			.min()
+			.context("No level.dat found in archive")?;
+		let mut top = worlds
+			.into_iter()
+			.filter(|root| root.components().count() == depth);
+		let first = top.next().expect("level.dat must exist at minimum depth");
+		let root = match (top.next(), first.parent()) {
+			(Some(_), Some(parent)) => parent,
+			_ => first,
+		}
+		.to_path_buf();
+
+		let entries = entries
+			.into_iter()
+			.filter_map(|(path, entry)| Some((path.strip_prefix(&root).ok()?.to_path_buf(), entry)))
+			.collect();
+
+		Ok(ArchiveSource {
+			archive,
+			entries,
+			tmpfile,
+		})
+	}
+
+	/// Opens a ZIP archive
+	pub fn open_zip(path: &Path) -> Result<Self> {
+		(|| -> Result<Self> {
+			let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+
+			let mut entries = Vec::new();
+			for index in 0..archive.len() {
+				let file = archive.by_index_raw(index)?;
+				if !file.is_file() {
+					continue;
+				}
+				let Some(name) = file.enclosed_name() else {
+					continue;
+				};
+				entries.push((
+					name.to_path_buf(),
+					ArchiveEntry {
+						location: ArchiveEntryLocation::Zip(index),
+						modified: dos_timestamp(file.last_modified()),
+					},
+				));
+			}
+
+			Self::new(Archive::Zip(Mutex::new(archive)), entries, None)
+		})()
+		.with_context(|| format!("Failed to open ZIP archive {}", path.display()))
+	}
+
+	/// Indexes an uncompressed tar archive
+	fn index_tar(file: &mut File) -> Result<Vec<(PathBuf, ArchiveEntry)>> {
+		let mut archive = tar::Archive::new(file);
+
+		let mut entries = Vec::new();
+		for entry in archive.entries_with_seek()? {
+			let entry = entry?;
+			if !entry.header().entry_type().is_file() {
+				continue;
+			}
+			let path = entry.path()?.into_owned();
+			let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(entry.header().mtime()?);
+			entries.push((
+				path,
+				ArchiveEntry {
+					location: ArchiveEntryLocation::Tar(entry.raw_file_position(), entry.size()),
+					modified,
+				},
+			));
+		}
+
+		Ok(entries)
+	}
+
+	/// Opens an uncompressed tar archive
+	pub fn open_tar(path: &Path) -> Result<Self> {
+		(|| -> Result<Self> {
+			let mut file = File::open(path)?;
+			let entries = Self::index_tar(&mut file)?;
+			Self::new(Archive::Tar(Mutex::new(file)), entries, None)
+		})()
+		.with_context(|| format!("Failed to open tar archive {}", path.display()))
+	}
+
+	/// Opens a gzip-compressed tar archive
+	///
+	/// As gzip streams do not allow random access, the archive is decompressed
+	/// into a temporary file first.
+	pub fn open_tar_gz(path: &Path) -> Result<Self> {
+		let tmp_path = std::env::temp_dir().join(format!("minedmap-{}.tar", std::process::id()));
+
+		let ret = (|| -> Result<Self> {
+			let mut decoder = flate2::read::GzDecoder::new(io::BufReader::new(File::open(path)?));
+			let mut file = fs::OpenOptions::new()
+				.read(true)
+				.write(true)
+				.create_new(true)
+				.open(&tmp_path)?;
+			io::copy(&mut decoder, &mut file)?;
+
+			file.rewind()?;
+			let entries = Self::index_tar(&mut file)?;
+			Self::new(
+				Archive::Tar(Mutex::new(file)),
+				entries,
+				Some(tmp_path.clone()),
+			)
+		})()
+		.with_context(|| format!("Failed to open tar archive {}", path.display()));
+
+		if ret.is_err() {
+			let _ = fs::remove_file(&tmp_path);
+		}
+
+		ret
+	}
+
+	/// Looks up an archive entry
+	fn entry(&self, path: &Path) -> Result<&ArchiveEntry> {
+		self.entries
+			.get(path)
+			.with_context(|| format!("File {} not found in archive", path.display()))
+	}
+}
+
+impl Drop for ArchiveSource {
+	fn drop(&mut self) {
+		if let Some(tmpfile) = &self.tmpfile {
+			let _ = fs::remove_file(tmpfile);
+		}
+	}
+}
+
+impl InputSource for ArchiveSource {
+	fn list_files(&self, dir: &Path) -> Result<Vec<String>> {
+		Ok(self
+			.entries
+			.keys()
+			.filter(|path| path.parent() == Some(dir))
+			.filter_map(|path| path.file_name()?.to_str())
+			.map(String::from)
+			.collect())
+	}
+
+	fn list_dirs(&self, dir: &Path) -> Result<Vec<String>> {
+		let dirs: BTreeSet<_> = self
+			.entries
+			.keys()
+			.filter_map(|path| path.parent()?.strip_prefix(dir).ok())
+			.filter_map(|path| path.iter().next()?.to_str())
+			.collect();
+		Ok(dirs.into_iter().map(String::from).collect())
+	}
+
+	fn modified(&self, path: &Path) -> Result<SystemTime> {
+		Ok(self.entry(path)?.modified)
+	}
+
+	fn open(&self, path: &Path) -> Result<Box<dyn ReadSeek>> {
+		let entry = self.entry(path)?;
+
+		let data = (|| -> Result<Vec<u8>> {
+			let mut data = Vec::new();
+			match (&self.archive, entry.location) {
+				(Archive::Zip(archive), ArchiveEntryLocation::Zip(index)) => {
+					let mut archive = archive.lock().unwrap();
+					archive.by_index(index)?.read_to_end(&mut data)?;
+				}
+				(Archive::Tar(file), ArchiveEntryLocation::Tar(offset, size)) => {
+					let mut file = file.lock().unwrap();
+					file.seek(SeekFrom::Start(offset))?;
+					(&mut *file).take(size).read_to_end(&mut data)?;
+				}
+				_ => unreachable!(),
+			}
+			Ok(data)
+		})()
+		.with_context(|| format!("Failed to read {} from archive", path.display()))?;
+
+		Ok(Box::new(Cursor::new(data)))
+	}
+}
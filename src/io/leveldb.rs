@@ -0,0 +1,518 @@
+//! Read-only access to LevelDB databases
+//!
+//! Minecraft Bedrock Edition stores its world data in a LevelDB database
+//! with zlib-compressed tables. Rather than using a full LevelDB
+//! implementation, which would need write access to the database directory,
+//! the tables and logs listed in the current manifest are indexed directly.
+//! This allows to read the data of a running server or from a backup archive.
+
+use std::{
+	collections::{BTreeMap, HashMap},
+	io::{Read, Seek, SeekFrom},
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::SystemTime,
+};
+
+use anyhow::{bail, Context, Result};
+use flate2::read::{DeflateDecoder, ZlibDecoder};
+
+use super::input::InputSource;
+use crate::nbt::region;
+
+/// Magic number at the end of a LevelDB table file
+const TABLE_MAGIC: u64 = 0xdb4775248b80fb57;
+/// Size of the footer of a table file
+const FOOTER_SIZE: usize = 48;
+/// Size of the trailer following each table block
+const BLOCK_TRAILER_SIZE: usize = 5;
+/// Size of the blocks of log files
+const LOG_BLOCK_SIZE: usize = 32768;
+/// Size of the header of each log record
+const LOG_HEADER_SIZE: usize = 7;
+
+/// Reads a variable-length integer from the start of a buffer
+fn varint(buf: &mut &[u8]) -> Result<u64> {
+	let mut value = 0;
+	for shift in (0..64).step_by(7) {
+		let (&byte, rest) = buf.split_first().context("Truncated varint")?;
+		*buf = rest;
+		value |= u64::from(byte & 0x7f) << shift;
+		if byte & 0x80 == 0 {
+			return Ok(value);
+		}
+	}
+	bail!("Invalid varint");
+}
+
+/// Reads a length-prefixed byte string from the start of a buffer
+fn slice<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8]> {
+	let len = varint(buf)? as usize;
+	if buf.len() < len {
+		bail!("Truncated data");
+	}
+	let (value, rest) = buf.split_at(len);
+	*buf = rest;
+	Ok(value)
+}
+
+/// Location of a block in a table file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct BlockHandle {
+	/// Offset of the block in the file
+	offset: u64,
+	/// Size of the block, without trailer
+	size: u64,
+}
+
+impl BlockHandle {
+	/// Decodes a [BlockHandle] from the start of a buffer
+	fn decode(buf: &mut &[u8]) -> Result<Self> {
+		Ok(BlockHandle {
+			offset: varint(buf)?,
+			size: varint(buf)?,
+		})
+	}
+}
+
+/// Decodes the key/value entries of a table block
+fn block_entries(block: &[u8]) -> Result<Vec<(Vec<u8>, &[u8])>> {
+	(|| -> Result<_> {
+		let num_restarts = u32::from_le_bytes(
+			block
+				.get(block.len().wrapping_sub(4)..)
+				.context("Truncated block")?
+				.try_into()
+				.unwrap(),
+		) as usize;
+		let restarts_len = 4 * (num_restarts + 1);
+		if block.len() < restarts_len {
+			bail!("Truncated block");
+		}
+		let mut buf = &block[..block.len() - restarts_len];
+
+		let mut entries = Vec::new();
+		let mut key = Vec::new();
+		while !buf.is_empty() {
+			let shared = varint(&mut buf)? as usize;
+			let non_shared = varint(&mut buf)? as usize;
+			let value_len = varint(&mut buf)? as usize;
+			let len = non_shared
+				.checked_add(value_len)
+				.filter(|&len| shared <= key.len() && len <= buf.len())
+				.context("Invalid block entry")?;
+			key.truncate(shared);
+			key.extend_from_slice(&buf[..non_shared]);
+			entries.push((key.clone(), &buf[non_shared..len]));
+			buf = &buf[len..];
+		}
+		Ok(entries)
+	})()
+	.context("Failed to decode table block")
+}
+
+/// Splits a LevelDB internal key into the user key, the sequence number and
+/// a flag that is false for deletion markers
+fn parse_internal_key(key: &[u8]) -> Result<(&[u8], u64, bool)> {
+	if key.len() < 8 {
+		bail!("Invalid internal key");
+	}
+	let (user_key, tag) = key.split_at(key.len() - 8);
+	let tag = u64::from_le_bytes(tag.try_into().unwrap());
+	Ok((user_key, tag >> 8, tag & 0xff == 1))
+}
+
+/// Reassembles the records of a LevelDB log file
+fn log_records(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+	let mut records = Vec::new();
+	let mut record = Vec::new();
+
+	for block in data.chunks(LOG_BLOCK_SIZE) {
+		let mut buf = block;
+		while buf.len() >= LOG_HEADER_SIZE {
+			let len = u16::from_le_bytes(buf[4..6].try_into().unwrap()) as usize;
+			let kind = buf[6];
+			if kind == 0 && len == 0 {
+				// Preallocated, unused space
+				break;
+			}
+			let Some(payload) = buf.get(LOG_HEADER_SIZE..LOG_HEADER_SIZE + len) else {
+				// Truncated by a write in progress
+				return Ok(records);
+			};
+			buf = &buf[LOG_HEADER_SIZE + len..];
+
+			match kind {
+				// Full record
+				1 => records.push(payload.to_vec()),
+				// First fragment
+				2 => record = payload.to_vec(),
+				// Middle fragment
+				3 => record.extend_from_slice(payload),
+				// Last fragment
+				4 => {
+					record.extend_from_slice(payload);
+					records.push(std::mem::take(&mut record));
+				}
+				_ => bail!("Invalid log record type {}", kind),
+			}
+		}
+	}
+
+	Ok(records)
+}
+
+/// Location of the value of a database entry
+#[derive(Debug, Clone)]
+enum Value {
+	/// Value stored in a block of a table file
+	Table(usize, BlockHandle),
+	/// Value read from a log file
+	Log(usize, Arc<[u8]>),
+}
+
+impl Value {
+	/// Returns the index of the file containing the value
+	fn file(&self) -> usize {
+		match self {
+			Value::Table(file, _) | Value::Log(file, _) => *file,
+		}
+	}
+}
+
+/// Newest version of a database entry
+#[derive(Debug, Clone)]
+struct Entry {
+	/// Sequence number of the entry
+	seq: u64,
+	/// Value of the entry, [None] for deleted entries
+	value: Option<Value>,
+}
+
+/// Table or log file of a database
+#[derive(Debug)]
+struct DbFile {
+	/// Path of the file, relative to the [InputSource]
+	path: PathBuf,
+	/// Time of last modification of the file
+	modified: SystemTime,
+}
+
+/// Read-only snapshot of a LevelDB database
+///
+/// The database is indexed when it is opened; values are read from the
+/// underlying [InputSource] when they are requested.
+pub struct Database {
+	/// Source of the database files
+	input: Arc<dyn InputSource>,
+	/// Table and log files of the database
+	files: Vec<DbFile>,
+	/// Map of keys to the newest versions of their entries
+	entries: BTreeMap<Vec<u8>, Entry>,
+}
+
+/// Files making up the current version of a database
+#[derive(Debug, Default)]
+struct Manifest {
+	/// Numbers of the live table files
+	tables: Vec<u64>,
+	/// Minimum number of live log files
+	log_number: u64,
+}
+
+impl Database {
+	/// Opens the database in a directory of an [InputSource]
+	pub fn open(input: Arc<dyn InputSource>, dir: &Path) -> Result<Self> {
+		(|| -> Result<Self> {
+			let mut db = Database {
+				input,
+				files: Vec::new(),
+				entries: BTreeMap::new(),
+			};
+
+			let manifest = db.read_manifest(dir)?;
+			let mut files: Vec<_> = db.input.list_files(dir)?;
+			files.sort();
+
+			for name in files {
+				let Some((number, ext)) = name.split_once('.') else {
+					continue;
+				};
+				let Ok(number) = number.parse::<u64>() else {
+					continue;
+				};
+				let path = dir.join(&name);
+				match ext {
+					"ldb" | "sst" if manifest.tables.contains(&number) => db.index_table(&path)?,
+					"log" if number >= manifest.log_number => db.index_log(&path)?,
+					_ => {}
+				}
+			}
+
+			Ok(db)
+		})()
+		.with_context(|| format!("Failed to open database {}", dir.display()))
+	}
+
+	/// Reads the list of live files from the current manifest
+	fn read_manifest(&self, dir: &Path) -> Result<Manifest> {
+		let mut current = String::new();
+		self.input
+			.open(&dir.join("CURRENT"))?
+			.read_to_string(&mut current)
+			.context("Failed to read CURRENT file")?;
+
+		let mut data = Vec::new();
+		self.input
+			.open(&dir.join(current.trim()))?
+			.read_to_end(&mut data)
+			.context("Failed to read manifest")?;
+
+		let mut tables = Vec::new();
+		let mut log_number = 0;
+
+		for record in log_records(&data)? {
+			let mut buf = record.as_slice();
+			while !buf.is_empty() {
+				match varint(&mut buf)? {
+					// Comparator name
+					1 => {
+						slice(&mut buf)?;
+					}
+					// Log number
+					2 => log_number = varint(&mut buf)?,
+					// Next file number, last sequence number, previous log number
+					3 | 4 | 9 => {
+						varint(&mut buf)?;
+					}
+					// Compaction pointer
+					5 => {
+						varint(&mut buf)?;
+						slice(&mut buf)?;
+					}
+					// Deleted file
+					6 => {
+						varint(&mut buf)?;
+						let number = varint(&mut buf)?;
+						tables.retain(|&table| table != number);
+					}
+					// New file
+					7 => {
+						varint(&mut buf)?;
+						tables.push(varint(&mut buf)?);
+						varint(&mut buf)?;
+						slice(&mut buf)?;
+						slice(&mut buf)?;
+					}
+					tag => bail!("Unknown manifest entry {}", tag),
+				}
+			}
+		}
+
+		Ok(Manifest { tables, log_number })
+	}
+
+	/// Adds a file to the list of database files, returning its index
+	fn add_file(&mut self, path: &Path) -> Result<usize> {
+		let modified = self.input.modified(path)?;
+		self.files.push(DbFile {
+			path: path.to_path_buf(),
+			modified,
+		});
+		Ok(self.files.len() - 1)
+	}
+
+	/// Records an entry if it is newer than the known version of its key
+	fn insert(&mut self, key: &[u8], entry: Entry) {
+		match self.entries.get_mut(key) {
+			Some(old) if old.seq >= entry.seq => {}
+			Some(old) => *old = entry,
+			None => {
+				self.entries.insert(key.to_vec(), entry);
+			}
+		}
+	}
+
+	/// Reads and decompresses a block of a table file
+	///
+	/// The block handle is checked against the size of the file before
+	/// allocating the buffer, so corrupt handles can't exhaust memory.
+	fn read_block(file: &mut dyn super::input::ReadSeek, handle: BlockHandle) -> Result<Vec<u8>> {
+		let len = file.seek(SeekFrom::End(0))?;
+		if handle
+			.offset
+			.checked_add(handle.size)
+			.and_then(|end| end.checked_add(BLOCK_TRAILER_SIZE as u64))
+			.is_none_or(|end| end > len)
+		{
+			bail!("Invalid table block handle");
+		}
+
+		let mut data = vec![0; handle.size as usize + BLOCK_TRAILER_SIZE];
+		file.seek(SeekFrom::Start(handle.offset))?;
+		file.read_exact(&mut data)
+			.context("Failed to read table block")?;
+
+		let compression = data[handle.size as usize];
+		data.truncate(handle.size as usize);
+
+		let limit = region::decompressed_limit(data.len());
+		let decompressed = match compression {
+			0 => return Ok(data),
+			2 => region::read_to_limit(ZlibDecoder::new(data.as_slice()), limit),
+			4 => region::read_to_limit(DeflateDecoder::new(data.as_slice()), limit),
+			_ => bail!("Unsupported block compression {}", compression),
+		}
+		.context("Failed to decompress table block")?;
+
+		decompressed
+			.with_context(|| format!("Decompressed table block exceeds limit of {limit} bytes"))
+	}
+
+	/// Indexes the entries of a table file
+	fn index_table(&mut self, path: &Path) -> Result<()> {
+		(|| -> Result<()> {
+			let index = self.add_file(path)?;
+			let mut file = self.input.open(path)?;
+
+			let len = file.seek(SeekFrom::End(0))?;
+			if len < FOOTER_SIZE as u64 {
+				bail!("Truncated table file");
+			}
+			let mut footer = [0; FOOTER_SIZE];
+			file.seek(SeekFrom::Start(len - FOOTER_SIZE as u64))?;
+			file.read_exact(&mut footer)?;
+			let magic = u64::from_le_bytes(footer[FOOTER_SIZE - 8..].try_into().unwrap());
+			if magic != TABLE_MAGIC {
+				bail!("Invalid table file");
+			}
+			let mut buf = &footer[..];
+			let _metaindex = BlockHandle::decode(&mut buf)?;
+			let index_handle = BlockHandle::decode(&mut buf)?;
+
+			let index_block = Self::read_block(&mut *file, index_handle)?;
+			for (_, mut value) in block_entries(&index_block)? {
+				let handle = BlockHandle::decode(&mut value)?;
+				let block = Self::read_block(&mut *file, handle)?;
+				for (key, _) in block_entries(&block)? {
+					let (user_key, seq, is_value) = parse_internal_key(&key)?;
+					let value = is_value.then_some(Value::Table(index, handle));
+					self.insert(user_key, Entry { seq, value });
+				}
+			}
+
+			Ok(())
+		})()
+		.with_context(|| format!("Failed to index table {}", path.display()))
+	}
+
+	/// Indexes the entries of a log file
+	fn index_log(&mut self, path: &Path) -> Result<()> {
+		(|| -> Result<()> {
+			let index = self.add_file(path)?;
+			let mut data = Vec::new();
+			self.input.open(path)?.read_to_end(&mut data)?;
+
+			for record in log_records(&data)? {
+				if record.len() < 12 {
+					bail!("Truncated write batch");
+				}
+				let (header, mut buf) = record.split_at(12);
+				let mut seq = u64::from_le_bytes(header[..8].try_into().unwrap());
+
+				while let Some((&kind, rest)) = buf.split_first() {
+					buf = rest;
+					let key = slice(&mut buf)?;
+					let value = match kind {
+						0 => None,
+						1 => Some(Value::Log(index, slice(&mut buf)?.into())),
+						_ => bail!("Invalid write batch entry"),
+					};
+					self.insert(key, Entry { seq, value });
+					seq += 1;
+				}
+			}
+
+			Ok(())
+		})()
+		.with_context(|| format!("Failed to index log {}", path.display()))
+	}
+
+	/// Returns an iterator over the keys of all live entries
+	pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+		self.entries
+			.iter()
+			.filter(|(_, entry)| entry.value.is_some())
+			.map(|(key, _)| key.as_slice())
+	}
+
+	/// Returns an iterator over the keys of all live entries starting with a prefix
+	pub fn keys_with_prefix<'a>(&'a self, prefix: &'a [u8]) -> impl Iterator<Item = &'a [u8]> {
+		self.entries
+			.range(prefix.to_vec()..)
+			.take_while(move |(key, _)| key.starts_with(prefix))
+			.filter(|(_, entry)| entry.value.is_some())
+			.map(|(key, _)| key.as_slice())
+	}
+
+	/// Returns the time of last modification of the file containing the value
+	/// of a key
+	pub fn modified(&self, key: &[u8]) -> Option<SystemTime> {
+		let value = self.entries.get(key)?.value.as_ref()?;
+		Some(self.files[value.file()].modified)
+	}
+
+	/// Reads the values of a list of keys
+	///
+	/// Keys without a value are skipped. Each table block is only read once,
+	/// so keys stored close to each other should be requested together.
+	pub fn get_many<'k>(&self, keys: &[&'k [u8]]) -> Result<Vec<(&'k [u8], Vec<u8>)>> {
+		let mut values = Vec::new();
+		// Requested keys with their sequence numbers, grouped by table block
+		let mut blocks = HashMap::<_, Vec<(&'k [u8], u64)>>::new();
+
+		for &key in keys {
+			let Some(entry) = self.entries.get(key) else {
+				continue;
+			};
+			match &entry.value {
+				Some(Value::Table(file, handle)) => blocks
+					.entry((*file, *handle))
+					.or_default()
+					.push((key, entry.seq)),
+				Some(Value::Log(_, value)) => values.push((key, value.to_vec())),
+				None => {}
+			}
+		}
+
+		// Each table file is only opened once for all of its blocks
+		let mut tables = BTreeMap::<_, Vec<_>>::new();
+		for ((file, handle), keys) in blocks {
+			tables.entry(file).or_default().push((handle, keys));
+		}
+
+		for (file, blocks) in tables {
+			let path = &self.files[file].path;
+			(|| -> Result<()> {
+				let mut file = self.input.open(path)?;
+				for (handle, keys) in blocks {
+					let block = Self::read_block(&mut *file, handle)?;
+
+					for (internal_key, value) in block_entries(&block)? {
+						let (user_key, seq, _) = parse_internal_key(&internal_key)?;
+						if let Some(&(key, _)) = keys
+							.iter()
+							.find(|&&(key, key_seq)| key == user_key && key_seq == seq)
+						{
+							values.push((key, value.to_vec()));
+						}
+					}
+				}
+				Ok(())
+			})()
+			.with_context(|| format!("Failed to read table {}", path.display()))?;
+		}
+
+		Ok(values)
+	}
+}
@@ -1,4 +1,9 @@
 //! Input/output functions
 
 pub mod fs;
+pub mod input;
+pub mod leveldb;
+#[cfg(feature = "sftp")]
+pub mod sftp;
 pub mod storage;
+pub mod target;
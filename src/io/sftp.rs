@@ -0,0 +1,320 @@
+//! [InputSource] reading save data from a remote host over SFTP
+
+use std::{
+	collections::{HashMap, HashSet, VecDeque},
+	io::{Cursor, Read},
+	net::TcpStream,
+	path::{Path, PathBuf},
+	sync::{Arc, Condvar, Mutex},
+	thread,
+	time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use super::input::{InputSource, ReadSeek};
+
+/// Connection parameters parsed from an `sftp://` URL
+#[derive(Debug, Clone)]
+struct Remote {
+	/// Remote user name
+	user: String,
+	/// Remote host name
+	host: String,
+	/// SSH port
+	port: u16,
+	/// Path of the save data root on the remote host
+	root: PathBuf,
+}
+
+impl Remote {
+	/// Parses a URL of the form `sftp://[user@]host[:port]/path`
+	fn parse(url: &str) -> Result<Self> {
+		let rest = url.strip_prefix("sftp://").context("Invalid SFTP URL")?;
+		let (authority, path) = rest
+			.split_once('/')
+			.context("SFTP URL must contain a path")?;
+		let (user, host_port) = match authority.rsplit_once('@') {
+			Some((user, host_port)) => (user.to_string(), host_port),
+			None => (
+				std::env::var("USER").context("No user given in SFTP URL")?,
+				authority,
+			),
+		};
+		let (host, port) = match host_port.rsplit_once(':') {
+			Some((host, port)) => (host, port.parse().context("Invalid port in SFTP URL")?),
+			None => (host_port, 22),
+		};
+
+		Ok(Remote {
+			user,
+			host: host.to_string(),
+			port,
+			root: Path::new("/").join(path),
+		})
+	}
+
+	/// Establishes a new SFTP connection
+	///
+	/// The host key is checked against the user's `known_hosts` file.
+	/// Authentication is attempted using the SSH agent first, followed by
+	/// the default private key files.
+	fn connect(&self) -> Result<ssh2::Sftp> {
+		(|| -> Result<ssh2::Sftp> {
+			let tcp = TcpStream::connect((self.host.as_str(), self.port))?;
+			let mut session = ssh2::Session::new()?;
+			session.set_tcp_stream(tcp);
+			session.handshake()?;
+
+			let ssh_dir = home_dir()?.join(".ssh");
+
+			let mut known_hosts = session.known_hosts()?;
+			known_hosts
+				.read_file(
+					&ssh_dir.join("known_hosts"),
+					ssh2::KnownHostFileKind::OpenSSH,
+				)
+				.context("Failed to read known_hosts")?;
+			let (key, _) = session.host_key().context("No host key")?;
+			match known_hosts.check_port(&self.host, self.port, key) {
+				ssh2::CheckResult::Match => {}
+				ssh2::CheckResult::Mismatch => bail!("Host key mismatch"),
+				_ => bail!("Host key not found in known_hosts"),
+			}
+
+			if session.userauth_agent(&self.user).is_err() {
+				for key in ["id_ed25519", "id_ecdsa", "id_rsa"] {
+					let path = ssh_dir.join(key);
+					if path.exists()
+						&& session
+							.userauth_pubkey_file(&self.user, None, &path, None)
+							.is_ok()
+					{
+						break;
+					}
+				}
+			}
+			if !session.authenticated() {
+				bail!("Authentication failed");
+			}
+
+			Ok(session.sftp()?)
+		})()
+		.with_context(|| format!("Failed to connect to {}:{}", self.host, self.port))
+	}
+}
+
+/// Returns the home directory of the current user
+fn home_dir() -> Result<PathBuf> {
+	std::env::var_os("HOME")
+		.or_else(|| std::env::var_os("USERPROFILE"))
+		.map(PathBuf::from)
+		.context("Failed to determine home directory")
+}
+
+/// Shared state of the prefetching threads
+#[derive(Debug, Default)]
+struct PrefetchState {
+	/// Files waiting to be prefetched
+	queue: VecDeque<PathBuf>,
+	/// Files currently being downloaded
+	in_flight: HashSet<PathBuf>,
+	/// Downloaded files that have not been opened yet
+	done: HashMap<PathBuf, Result<Vec<u8>, String>>,
+	/// Set when the [SftpSource] is dropped
+	shutdown: bool,
+}
+
+/// Inner data of an [SftpSource], shared with the prefetching threads
+struct Inner {
+	/// Connection parameters
+	remote: Remote,
+	/// Idle connections
+	connections: Mutex<Vec<ssh2::Sftp>>,
+	/// Modification times of files seen in directory listings
+	mtimes: Mutex<HashMap<PathBuf, SystemTime>>,
+	/// Prefetching state
+	prefetch: Mutex<PrefetchState>,
+	/// Signalled whenever *prefetch* has changed
+	prefetch_cond: Condvar,
+	/// Maximum number of downloaded files kept in memory
+	prefetch_limit: usize,
+}
+
+impl Inner {
+	/// Runs a closure with an idle connection, establishing a new connection
+	/// if none is available
+	fn with_connection<T, F>(&self, f: F) -> Result<T>
+	where
+		F: FnOnce(&ssh2::Sftp) -> Result<T>,
+	{
+		let connection = self.connections.lock().unwrap().pop();
+		let connection = match connection {
+			Some(connection) => connection,
+			None => self.remote.connect()?,
+		};
+		let ret = f(&connection);
+		if ret.is_ok() {
+			self.connections.lock().unwrap().push(connection);
+		}
+		ret
+	}
+
+	/// Downloads a whole file into memory
+	fn download(&self, path: &Path) -> Result<Vec<u8>> {
+		let remote_path = self.remote.root.join(path);
+		self.with_connection(|sftp| {
+			let mut data = Vec::new();
+			sftp.open(&remote_path)?.read_to_end(&mut data)?;
+			Ok(data)
+		})
+		.with_context(|| format!("Failed to download {}", remote_path.display()))
+	}
+
+	/// Main loop of a prefetching thread
+	fn prefetch_worker(&self) {
+		let mut state = self.prefetch.lock().unwrap();
+		loop {
+			if state.shutdown {
+				return;
+			}
+			if state.done.len() + state.in_flight.len() >= self.prefetch_limit {
+				state = self.prefetch_cond.wait(state).unwrap();
+				continue;
+			}
+			let Some(path) = state.queue.pop_front() else {
+				return;
+			};
+			state.in_flight.insert(path.clone());
+			drop(state);
+
+			let data = self.download(&path).map_err(|err| format!("{:?}", err));
+
+			state = self.prefetch.lock().unwrap();
+			state.in_flight.remove(&path);
+			state.done.insert(path, data);
+			self.prefetch_cond.notify_all();
+		}
+	}
+}
+
+/// [InputSource] for save data on a remote host, accessed over SFTP
+///
+/// Multiple connections are used to transfer files in parallel. Files are
+/// downloaded into memory completely when opened; region files that are
+/// known to be needed can be prefetched in the background.
+pub struct SftpSource(Arc<Inner>);
+
+impl SftpSource {
+	/// Connects to the host given by an URL of the form `sftp://[user@]host[:port]/path`
+	///
+	/// *connections* is the maximum number of connections used for prefetching.
+	pub fn open(url: &str, connections: usize) -> Result<Self> {
+		let remote = Remote::parse(url)?;
+		let connection = remote.connect()?;
+
+		Ok(SftpSource(Arc::new(Inner {
+			remote,
+			connections: Mutex::new(vec![connection]),
+			mtimes: Default::default(),
+			prefetch: Default::default(),
+			prefetch_cond: Condvar::new(),
+			prefetch_limit: 2 * connections.max(1),
+		})))
+	}
+}
+
+impl Drop for SftpSource {
+	fn drop(&mut self) {
+		self.0.prefetch.lock().unwrap().shutdown = true;
+		self.0.prefetch_cond.notify_all();
+	}
+}
+
+impl InputSource for SftpSource {
+	fn list_files(&self, dir: &Path) -> Result<Vec<String>> {
+		let remote_dir = self.0.remote.root.join(dir);
+		let entries = self
+			.0
+			.with_connection(|sftp| Ok(sftp.readdir(&remote_dir)?))
+			.with_context(|| format!("Failed to read directory {}", remote_dir.display()))?;
+
+		let mut mtimes = self.0.mtimes.lock().unwrap();
+		Ok(entries
+			.into_iter()
+			.filter(|(_, stat)| stat.is_file())
+			.filter_map(|(path, stat)| {
+				let name = path.file_name()?.to_str()?.to_string();
+				if let Some(mtime) = stat.mtime {
+					mtimes.insert(
+						dir.join(&name),
+						SystemTime::UNIX_EPOCH + Duration::from_secs(mtime),
+					);
+				}
+				Some(name)
+			})
+			.collect())
+	}
+
+	fn list_dirs(&self, dir: &Path) -> Result<Vec<String>> {
+		let remote_dir = self.0.remote.root.join(dir);
+		let entries = self
+			.0
+			.with_connection(|sftp| Ok(sftp.readdir(&remote_dir)?))
+			.with_context(|| format!("Failed to read directory {}", remote_dir.display()))?;
+
+		Ok(entries
+			.into_iter()
+			.filter(|(_, stat)| stat.is_dir())
+			.filter_map(|(path, _)| Some(path.file_name()?.to_str()?.to_string()))
+			.collect())
+	}
+
+	fn modified(&self, path: &Path) -> Result<SystemTime> {
+		if let Some(&mtime) = self.0.mtimes.lock().unwrap().get(path) {
+			return Ok(mtime);
+		}
+
+		let remote_path = self.0.remote.root.join(path);
+		let mtime = self
+			.0
+			.with_connection(|sftp| Ok(sftp.stat(&remote_path)?))
+			.and_then(|stat| stat.mtime.ok_or_else(|| anyhow!("No modification time")))
+			.with_context(|| {
+				format!(
+					"Failed to get modified timestamp of file {}",
+					remote_path.display()
+				)
+			})?;
+		Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(mtime))
+	}
+
+	fn open(&self, path: &Path) -> Result<Box<dyn ReadSeek>> {
+		let mut state = self.0.prefetch.lock().unwrap();
+		loop {
+			if let Some(data) = state.done.remove(path) {
+				self.0.prefetch_cond.notify_all();
+				return Ok(Box::new(Cursor::new(data.map_err(|err| anyhow!(err))?)));
+			}
+			if !state.in_flight.contains(path) {
+				break;
+			}
+			state = self.0.prefetch_cond.wait(state).unwrap();
+		}
+		// Not prefetched yet; download the file right away
+		state.queue.retain(|queued| queued != path);
+		drop(state);
+
+		Ok(Box::new(Cursor::new(self.0.download(path)?)))
+	}
+
+	fn prefetch(&self, paths: &mut dyn Iterator<Item = PathBuf>) {
+		let workers = self.0.prefetch_limit / 2;
+		self.0.prefetch.lock().unwrap().queue.extend(paths);
+
+		for _ in 0..workers {
+			let inner = Arc::clone(&self.0);
+			thread::spawn(move || inner.prefetch_worker());
+		}
+	}
+}
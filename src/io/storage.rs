@@ -21,19 +21,27 @@ pub fn write<T: Serialize>(
 	path: &Path,
 	value: &T,
 	version: fs::FileMetaVersion,
+	settings: fs::SettingsHash,
 	timestamp: SystemTime,
 ) -> Result<()> {
-	fs::create_with_timestamp(path, version, timestamp, |file| {
-		let data = bincode::serialize(value)?;
-		let len = u32::try_from(data.len())?;
-		let compressed = zstd::bulk::compress(&data, 1)?;
-		drop(data);
+	fs::create_with_timestamp(
+		path,
+		version,
+		settings,
+		timestamp,
+		|file| {
+			let data = bincode::serialize(value)?;
+			let len = u32::try_from(data.len())?;
+			let compressed = zstd::bulk::compress(&data, 1)?;
+			drop(data);
 
-		file.write_all(&len.to_be_bytes())?;
-		file.write_all(&compressed)?;
+			file.write_all(&len.to_be_bytes())?;
+			file.write_all(&compressed)?;
 
-		Ok(())
-	})
+			Ok(())
+		},
+		|_| Ok(()),
+	)
 }
 
 /// Reads data from a file and deserializes it
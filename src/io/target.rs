@@ -0,0 +1,125 @@
+//! Output targets receiving generated viewer files
+//!
+//! All files are first generated in the local output directory, which also
+//! stores the metadata required for incremental updates and the processed
+//! region data. Viewer files (tiles and metadata) are then passed on to an
+//! [OutputTarget] whenever they have been created or modified.
+
+use std::path::Path;
+
+use anyhow::Result;
+
+/// A destination for generated viewer files
+pub trait OutputTarget: Send + Sync {
+	/// Stores a generated file
+	///
+	/// *key* is the path of the file relative to the output directory,
+	/// using `/` as a separator; *path* is the location of the file in
+	/// the local output directory.
+	fn store(&self, key: &str, path: &Path) -> Result<()>;
+	/// Removes a file that is not part of the generated map anymore
+	fn remove(&self, key: &str) -> Result<()>;
+}
+
+/// Output target keeping the generated files in the local output directory only
+#[derive(Debug, Default)]
+pub struct LocalTarget;
+
+impl OutputTarget for LocalTarget {
+	fn store(&self, _key: &str, _path: &Path) -> Result<()> {
+		Ok(())
+	}
+
+	fn remove(&self, _key: &str) -> Result<()> {
+		Ok(())
+	}
+}
+
+/// Output target uploading generated files to an S3-compatible object storage
+#[cfg(feature = "s3")]
+pub struct S3Target {
+	/// Bucket to upload to
+	bucket: Box<s3::Bucket>,
+	/// Prefix prepended to all object keys
+	prefix: String,
+}
+
+#[cfg(feature = "s3")]
+impl S3Target {
+	/// Creates a new [S3Target]
+	///
+	/// When *endpoint* is given, it is used instead of the AWS endpoint of the
+	/// passed region, and path-style requests are used, as expected by most
+	/// self-hosted S3 implementations like MinIO. Credentials are taken from
+	/// the usual AWS environment variables or profile files.
+	pub fn new(bucket: &str, region: &str, endpoint: Option<&str>, prefix: &str) -> Result<Self> {
+		use anyhow::Context;
+
+		let region = match endpoint {
+			Some(endpoint) => s3::Region::Custom {
+				region: region.to_string(),
+				endpoint: endpoint.to_string(),
+			},
+			None => region.parse().context("Invalid S3 region")?,
+		};
+		let credentials =
+			s3::creds::Credentials::default().context("Failed to get S3 credentials")?;
+		let mut bucket = s3::Bucket::new(bucket, region, credentials)
+			.context("Failed to configure S3 bucket")?;
+		if endpoint.is_some() {
+			bucket = bucket.with_path_style();
+		}
+
+		let mut prefix = prefix.trim_matches('/').to_string();
+		if !prefix.is_empty() {
+			prefix.push('/');
+		}
+
+		Ok(S3Target { bucket, prefix })
+	}
+}
+
+#[cfg(feature = "s3")]
+impl OutputTarget for S3Target {
+	fn store(&self, key: &str, path: &Path) -> Result<()> {
+		use anyhow::{bail, Context};
+
+		let content_type = match path.extension().and_then(|ext| ext.to_str()) {
+			Some("png") => "image/png",
+			Some("json") => "application/json",
+			_ => "application/octet-stream",
+		};
+		let object = format!("{}{}", self.prefix, key);
+
+		(|| -> Result<()> {
+			let mut file = std::fs::File::open(path)?;
+			// Uses a multipart upload for files larger than a single chunk
+			let status = self.bucket.put_object_stream_with_content_type(
+				&mut file,
+				&object,
+				content_type,
+			)?;
+			if !(200..300).contains(&status) {
+				bail!("Unexpected HTTP status {}", status);
+			}
+			Ok(())
+		})()
+		.with_context(|| {
+			format!(
+				"Failed to upload {} to S3 object {}",
+				path.display(),
+				object
+			)
+		})
+	}
+
+	fn remove(&self, key: &str) -> Result<()> {
+		use anyhow::Context;
+
+		let object = format!("{}{}", self.prefix, key);
+		self.bucket
+			.delete_object(&object)
+			.with_context(|| format!("Failed to delete S3 object {}", object))?;
+		Ok(())
+	}
+}
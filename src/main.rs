@@ -4,6 +4,7 @@
 
 mod core;
 mod io;
+mod tools;
 mod util;
 mod world;
 
@@ -0,0 +1,111 @@
+//! The `chunk` subcommand
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+
+use crate::{
+	core::common::{Dimension, RegionFormat, TileCoords},
+	types::*,
+};
+
+/// Subcommands for inspecting chunks
+#[derive(Debug, Subcommand)]
+pub enum ChunkCommand {
+	/// Print the NBT data of a chunk
+	///
+	/// The chunk is located in the region files of the given world, without
+	/// reading any other chunks. When the input contains multiple worlds,
+	/// the main world is used.
+	Dump {
+		/// Print the data in SNBT syntax instead of JSON
+		#[arg(long)]
+		snbt: bool,
+		/// Dimension of the chunk
+		#[arg(long, value_enum, default_value_t = Dimension::Overworld)]
+		dimension: Dimension,
+		/// Minecraft save directory
+		world: PathBuf,
+		/// X coordinate of the chunk
+		#[arg(allow_negative_numbers = true)]
+		x: i32,
+		/// Z coordinate of the chunk
+		#[arg(allow_negative_numbers = true)]
+		z: i32,
+	},
+}
+
+/// Runs a `chunk` subcommand
+pub fn run(command: ChunkCommand) -> Result<()> {
+	match command {
+		ChunkCommand::Dump {
+			snbt,
+			dimension,
+			world,
+			x,
+			z,
+		} => {
+			let value = read_chunk(&world, dimension, x, z)?;
+			super::print_value(&value, snbt)
+		}
+	}
+}
+
+/// Reads the NBT data of a chunk given by its absolute coordinates
+fn read_chunk(world: &Path, dimension: Dimension, x: i32, z: i32) -> Result<fastnbt::Value> {
+	/// Width/height of a region in chunks
+	const CHUNKS: i32 = CHUNKS_PER_REGION as i32;
+
+	let source = super::open_dimension(world, dimension)?;
+
+	let region_coords = TileCoords {
+		x: x.div_euclid(CHUNKS),
+		z: z.div_euclid(CHUNKS),
+	};
+	let coords = ChunkCoords {
+		x: ChunkX::new(x.rem_euclid(CHUNKS)),
+		z: ChunkZ::new(z.rem_euclid(CHUNKS)),
+	};
+	let path = source.region_path(region_coords);
+
+	let value = match source.format {
+		RegionFormat::Linear => {
+			let reader = source
+				.input
+				.open(&path)
+				.with_context(|| format!("Region file {} not found", path.display()))?;
+			read_linear_chunk(reader, coords)?
+		}
+		_ => super::open_region(&source, region_coords)?.get_chunk(coords)?,
+	};
+
+	value.with_context(|| format!("Chunk ({}, {}) not found in {}", x, z, path.display()))
+}
+
+/// Reads the NBT data of a chunk from a linear region file
+#[cfg(feature = "zstd")]
+fn read_linear_chunk(
+	reader: Box<dyn crate::io::input::ReadSeek>,
+	coords: ChunkCoords,
+) -> Result<Option<fastnbt::Value>> {
+	let mut value = None;
+	crate::nbt::linear::from_reader(reader).foreach_chunk_data(
+		|chunk_coords, data| -> Result<()> {
+			if chunk_coords == coords {
+				value = Some(crate::nbt::data::from_bytes(data)?);
+			}
+			Ok(())
+		},
+	)?;
+	Ok(value)
+}
+
+/// Reads the NBT data of a chunk from a linear region file
+#[cfg(not(feature = "zstd"))]
+fn read_linear_chunk(
+	_reader: Box<dyn crate::io::input::ReadSeek>,
+	_coords: ChunkCoords,
+) -> Result<Option<fastnbt::Value>> {
+	anyhow::bail!("Reading linear region files requires the zstd feature")
+}
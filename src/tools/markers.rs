@@ -0,0 +1,631 @@
+//! The `export-markers` subcommand
+
+use std::{
+	collections::BTreeMap,
+	io::Write,
+	path::{Path, PathBuf},
+	time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use fastnbt::Value;
+use rayon::prelude::*;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+	core::{
+		common::{Dimension, RegionSource, TileCoords},
+		layout::WorldLayout,
+	},
+	io::{fs, input::InputSource},
+	nbt::data,
+	types::*,
+};
+
+/// Kind of a [Marker]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum MarkerKind {
+	/// A sign with text
+	Sign,
+	/// The last known position of a player
+	Player,
+	/// The start of a generated structure like a village
+	Structure,
+}
+
+impl MarkerKind {
+	/// Returns the name of the marker kind
+	fn name(self) -> &'static str {
+		match self {
+			MarkerKind::Sign => "signs",
+			MarkerKind::Player => "players",
+			MarkerKind::Structure => "structures",
+		}
+	}
+
+	/// Returns the label of the marker kind
+	fn label(self) -> &'static str {
+		match self {
+			MarkerKind::Sign => "Signs",
+			MarkerKind::Player => "Players",
+			MarkerKind::Structure => "Structures",
+		}
+	}
+
+	/// Returns the name of the built-in Dynmap icon for the marker kind
+	fn dynmap_icon(self) -> &'static str {
+		match self {
+			MarkerKind::Sign => "sign",
+			MarkerKind::Player => "offlineuser",
+			MarkerKind::Structure => "temple",
+		}
+	}
+}
+
+/// Output format of the markers
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum MarkerFormat {
+	/// The `tiles/_markers_/marker_<world>.json` file of the Dynmap web viewer
+	Dynmap,
+	/// The `live/markers.json` file of a BlueMap map
+	Bluemap,
+	/// The `markers.json` file of a world of the squaremap or Pl3xMap plugins
+	Squaremap,
+}
+
+/// Arguments of the `export-markers` subcommand
+#[derive(Debug, Args)]
+pub struct ExportMarkersArgs {
+	/// Output format
+	#[arg(long, value_enum, default_value_t = MarkerFormat::Dynmap)]
+	format: MarkerFormat,
+	/// Dimension to export the markers of
+	#[arg(long, value_enum, default_value_t = Dimension::Overworld)]
+	dimension: Dimension,
+	/// Kinds of markers to export, separated by commas
+	///
+	/// By default, all kinds of markers are exported.
+	#[arg(long, value_enum, value_delimiter = ',')]
+	kinds: Vec<MarkerKind>,
+	/// Minecraft save directory
+	///
+	/// Player names are looked up in the `usercache.json` of a server when
+	/// the server directory is passed.
+	world: PathBuf,
+	/// JSON file to write the markers to
+	output: PathBuf,
+}
+
+/// A point of interest found in the save data
+#[derive(Debug, Clone)]
+pub struct Marker {
+	/// Kind of the marker
+	pub kind: MarkerKind,
+	/// Identifier of the marker, unique for its kind
+	pub id: String,
+	/// Text shown for the marker
+	pub label: String,
+	/// X block coordinate
+	pub x: f64,
+	/// Y block coordinate
+	pub y: f64,
+	/// Z block coordinate
+	pub z: f64,
+}
+
+/// Entry of the `usercache.json` file of a server
+#[derive(Debug, Deserialize)]
+struct UserCacheEntry {
+	/// Player name
+	name: String,
+	/// Player UUID, in hyphenated format
+	uuid: String,
+}
+
+/// Returns a child of an NBT compound
+fn get<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+	match value {
+		Value::Compound(compound) => compound.get(key),
+		_ => None,
+	}
+}
+
+/// Returns the elements of an NBT list
+fn list(value: Option<&Value>) -> &[Value] {
+	match value {
+		Some(Value::List(list)) => list,
+		_ => &[],
+	}
+}
+
+/// Returns the plain text of a JSON text component
+fn component_text(component: &serde_json::Value) -> String {
+	use serde_json::Value as Json;
+
+	match component {
+		Json::String(text) => text.clone(),
+		Json::Array(parts) => parts.iter().map(component_text).collect(),
+		Json::Object(object) => {
+			let mut text = object.get("text").map(component_text).unwrap_or_default();
+			for part in object
+				.get("extra")
+				.and_then(Json::as_array)
+				.into_iter()
+				.flatten()
+			{
+				text.push_str(&component_text(part));
+			}
+			text
+		}
+		Json::Null => String::new(),
+		other => other.to_string(),
+	}
+}
+
+/// Returns the plain text of a line of a sign
+///
+/// Depending on the Minecraft version, lines are stored as plain strings,
+/// serialized JSON text components or NBT text components.
+fn line_text(line: &Value) -> String {
+	match line {
+		Value::String(line) => match serde_json::from_str(line) {
+			Ok(component) => component_text(&component),
+			Err(_) => line.clone(),
+		},
+		other => component_text(&super::to_json(other)),
+	}
+}
+
+/// Returns the non-empty lines of a sign block entity
+fn sign_lines(block_entity: &Value) -> Vec<String> {
+	let lines: Vec<&Value> = if get(block_entity, "front_text").is_some() {
+		["front_text", "back_text"]
+			.into_iter()
+			.flat_map(|side| list(get(block_entity, side).and_then(|text| get(text, "messages"))))
+			.collect()
+	} else {
+		["Text1", "Text2", "Text3", "Text4"]
+			.into_iter()
+			.filter_map(|key| get(block_entity, key))
+			.collect()
+	};
+
+	lines
+		.into_iter()
+		.map(line_text)
+		.map(|line| line.trim().to_string())
+		.filter(|line| !line.is_empty())
+		.collect()
+}
+
+/// Returns the integer value of an NBT number
+fn int(value: Option<&Value>) -> Option<i32> {
+	match value? {
+		Value::Byte(v) => Some((*v).into()),
+		Value::Short(v) => Some((*v).into()),
+		Value::Int(v) => Some(*v),
+		_ => None,
+	}
+}
+
+/// Collects the sign markers of a chunk
+fn add_signs(markers: &mut Vec<Marker>, block_entities: &[Value]) {
+	for block_entity in block_entities {
+		let Some(Value::String(id)) = get(block_entity, "id") else {
+			continue;
+		};
+		if !id.to_ascii_lowercase().ends_with("sign") {
+			continue;
+		}
+		let (Some(x), Some(y), Some(z)) = (
+			int(get(block_entity, "x")),
+			int(get(block_entity, "y")),
+			int(get(block_entity, "z")),
+		) else {
+			continue;
+		};
+		let lines = sign_lines(block_entity);
+		if lines.is_empty() {
+			continue;
+		}
+
+		markers.push(Marker {
+			kind: MarkerKind::Sign,
+			id: format!("sign_{}_{}_{}", x, y, z),
+			label: lines.join(" "),
+			x: f64::from(x) + 0.5,
+			y: f64::from(y),
+			z: f64::from(z) + 0.5,
+		});
+	}
+}
+
+/// Collects the structure start markers of a chunk
+///
+/// Structures are placed at the center of the bounding box of their pieces.
+fn add_structures(markers: &mut Vec<Marker>, chunk_coords: (i32, i32), starts: &Value) {
+	let Value::Compound(starts) = starts else {
+		return;
+	};
+
+	for (name, start) in starts {
+		let id = match get(start, "id") {
+			Some(Value::String(id)) => id,
+			_ => name,
+		};
+		if id == "INVALID" {
+			continue;
+		}
+
+		let mut bounds: Option<[i32; 6]> = None;
+		for child in list(get(start, "children").or_else(|| get(start, "Children"))) {
+			let Some(Value::IntArray(bb)) = get(child, "BB") else {
+				continue;
+			};
+			let &[x0, y0, z0, x1, y1, z1] = bb.as_ref() else {
+				continue;
+			};
+			bounds = Some(match bounds {
+				Some([bx0, by0, bz0, bx1, by1, bz1]) => [
+					bx0.min(x0),
+					by0.min(y0),
+					bz0.min(z0),
+					bx1.max(x1),
+					by1.max(y1),
+					bz1.max(z1),
+				],
+				None => [x0, y0, z0, x1, y1, z1],
+			});
+		}
+
+		let chunk_x = int(get(start, "ChunkX")).unwrap_or(chunk_coords.0);
+		let chunk_z = int(get(start, "ChunkZ")).unwrap_or(chunk_coords.1);
+		let [x0, y0, z0, x1, _, z1] = bounds.unwrap_or([
+			chunk_x * BLOCKS_PER_CHUNK as i32,
+			64,
+			chunk_z * BLOCKS_PER_CHUNK as i32,
+			chunk_x * BLOCKS_PER_CHUNK as i32 + BLOCKS_PER_CHUNK as i32 - 1,
+			64,
+			chunk_z * BLOCKS_PER_CHUNK as i32 + BLOCKS_PER_CHUNK as i32 - 1,
+		]);
+		let name = id.strip_prefix("minecraft:").unwrap_or(id);
+
+		markers.push(Marker {
+			kind: MarkerKind::Structure,
+			id: format!("{}_{}_{}", name, chunk_x, chunk_z),
+			label: name.replace('_', " "),
+			x: f64::from(x0 + x1 + 1) / 2.0,
+			y: f64::from(y0),
+			z: f64::from(z0 + z1 + 1) / 2.0,
+		});
+	}
+}
+
+/// Collects the sign and structure markers of a region
+fn add_region(
+	markers: &mut Vec<Marker>,
+	kinds: &[MarkerKind],
+	source: &RegionSource,
+	coords: TileCoords,
+) -> Result<()> {
+	super::foreach_chunk_value(source, coords, |chunk_coords, chunk| {
+		// Chunks before 1.18 store their contents in a Level compound
+		let level = get(&chunk, "Level").unwrap_or(&chunk);
+
+		if kinds.contains(&MarkerKind::Sign) {
+			let block_entities =
+				get(level, "block_entities").or_else(|| get(level, "TileEntities"));
+			add_signs(markers, list(block_entities));
+		}
+
+		if kinds.contains(&MarkerKind::Structure) {
+			let structures = get(level, "structures").or_else(|| get(level, "Structures"));
+			if let Some(starts) =
+				structures.and_then(|s| get(s, "starts").or_else(|| get(s, "Starts")))
+			{
+				let region_chunk =
+					|region: i32, chunk: u8| region * CHUNKS_PER_REGION as i32 + i32::from(chunk);
+				let chunk_coords = (
+					region_chunk(coords.x, chunk_coords.x.0),
+					region_chunk(coords.z, chunk_coords.z.0),
+				);
+				add_structures(markers, chunk_coords, starts);
+			}
+		}
+
+		Ok(())
+	})
+}
+
+/// Returns the dimension of a player, given as numeric or namespaced ID
+fn player_dimension(value: Option<&Value>) -> Option<Dimension> {
+	match value {
+		Some(Value::String(id)) => [Dimension::Overworld, Dimension::Nether, Dimension::End]
+			.into_iter()
+			.find(|dimension| dimension.id() == id),
+		None => Some(Dimension::Overworld),
+		value => match int(value)? {
+			0 => Some(Dimension::Overworld),
+			-1 => Some(Dimension::Nether),
+			1 => Some(Dimension::End),
+			_ => None,
+		},
+	}
+}
+
+/// Returns a player marker for the data of a player, if the player is in
+/// the given dimension
+fn player_marker(player: &Value, dimension: Dimension, id: &str, name: &str) -> Option<Marker> {
+	if player_dimension(get(player, "Dimension"))? != dimension {
+		return None;
+	}
+	let &[Value::Double(x), Value::Double(y), Value::Double(z)] = list(get(player, "Pos")) else {
+		return None;
+	};
+
+	Some(Marker {
+		kind: MarkerKind::Player,
+		id: id.to_string(),
+		label: name.to_string(),
+		x,
+		y,
+		z,
+	})
+}
+
+/// Reads the player names from the `usercache.json` of a server
+fn read_user_cache(input: &dyn InputSource, path: &Path) -> BTreeMap<String, String> {
+	(|| -> Option<Vec<UserCacheEntry>> { serde_json::from_reader(input.open(path).ok()?).ok() })()
+		.unwrap_or_default()
+		.into_iter()
+		.map(|entry| (entry.uuid, entry.name))
+		.collect()
+}
+
+/// Collects the player markers of a world
+///
+/// Player data is read from the `playerdata` directory. For single-player
+/// worlds without player data files, the player stored in `level.dat` is
+/// used instead.
+fn add_players(
+	markers: &mut Vec<Marker>,
+	input: &dyn InputSource,
+	world: &WorldLayout,
+	dimension: Dimension,
+) -> Result<()> {
+	let world_dir = world.level_dat_path.parent().unwrap_or(Path::new(""));
+	let player_dir = world_dir.join("playerdata");
+	let names = read_user_cache(
+		input,
+		&world_dir
+			.parent()
+			.unwrap_or(Path::new(""))
+			.join("usercache.json"),
+	);
+
+	let files = input.list_files(&player_dir).unwrap_or_default();
+	let players: Vec<_> = files
+		.iter()
+		.filter_map(|file| file.strip_suffix(".dat"))
+		.collect();
+
+	for uuid in &players {
+		let path = player_dir.join(format!("{}.dat", uuid));
+		let player: Value = match input
+			.open(&path)
+			.and_then(|reader| Ok(data::from_reader(reader)?))
+		{
+			Ok(player) => player,
+			Err(err) => {
+				eprintln!("Skipping player {}: {:?}", path.display(), err);
+				continue;
+			}
+		};
+		let name = names.get(*uuid).map(String::as_str).unwrap_or(uuid);
+		markers.extend(player_marker(&player, dimension, uuid, name));
+	}
+
+	if players.is_empty() {
+		let level_dat: Value = data::from_reader(input.open(&world.level_dat_path)?)
+			.context("Failed to read level.dat")?;
+		if let Some(player) = get(&level_dat, "Data").and_then(|data| get(data, "Player")) {
+			markers.extend(player_marker(player, dimension, "player", "Player"));
+		}
+	}
+
+	Ok(())
+}
+
+/// Writes markers in the format of the `tiles/_markers_/marker_<world>.json` file of Dynmap
+///
+/// Each kind of marker is written as a separate marker set, using the
+/// built-in Dynmap icons.
+fn write_dynmap<W: Write>(writer: &mut W, kinds: &[MarkerKind], markers: &[Marker]) -> Result<()> {
+	let timestamp = SystemTime::now()
+		.duration_since(SystemTime::UNIX_EPOCH)
+		.map_or(0, |duration| duration.as_millis());
+
+	let sets: serde_json::Map<_, _> = kinds
+		.iter()
+		.enumerate()
+		.map(|(prio, &kind)| {
+			let set_markers: serde_json::Map<_, _> = markers
+				.iter()
+				.filter(|marker| marker.kind == kind)
+				.map(|marker| {
+					let entry = json!({
+						"x": marker.x,
+						"y": marker.y,
+						"z": marker.z,
+						"icon": kind.dynmap_icon(),
+						"label": marker.label,
+						"markup": false,
+						"desc": null,
+						"dim": "16x16",
+						"minzoom": -1,
+						"maxzoom": -1,
+					});
+					(marker.id.clone(), entry)
+				})
+				.collect();
+			let set = json!({
+				"label": kind.label(),
+				"hide": false,
+				"layerprio": prio,
+				"minzoom": -1,
+				"maxzoom": -1,
+				"showlabels": null,
+				"markers": set_markers,
+				"areas": {},
+				"lines": {},
+				"circles": {},
+			});
+			(kind.name().to_string(), set)
+		})
+		.collect();
+
+	serde_json::to_writer(writer, &json!({ "timestamp": timestamp, "sets": sets }))?;
+	Ok(())
+}
+
+/// Writes markers in the format of the `live/markers.json` file of BlueMap
+///
+/// Each kind of marker is written as a separate marker set of POI markers,
+/// using the default BlueMap icon.
+fn write_bluemap<W: Write>(writer: &mut W, kinds: &[MarkerKind], markers: &[Marker]) -> Result<()> {
+	let sets: serde_json::Map<_, _> = kinds
+		.iter()
+		.enumerate()
+		.map(|(sorting, &kind)| {
+			let set_markers: serde_json::Map<_, _> = markers
+				.iter()
+				.filter(|marker| marker.kind == kind)
+				.map(|marker| {
+					let entry = json!({
+						"type": "poi",
+						"label": marker.label,
+						"position": {
+							"x": marker.x,
+							"y": marker.y,
+							"z": marker.z,
+						},
+						"sorting": 0,
+						"listed": true,
+					});
+					(marker.id.clone(), entry)
+				})
+				.collect();
+			let set = json!({
+				"label": kind.label(),
+				"toggleable": true,
+				"default-hidden": false,
+				"sorting": sorting,
+				"markers": set_markers,
+			});
+			(kind.name().to_string(), set)
+		})
+		.collect();
+
+	serde_json::to_writer(writer, &sets)?;
+	Ok(())
+}
+
+/// Writes markers in the format of the `markers.json` file of squaremap
+///
+/// Each kind of marker is written as a separate layer of icon markers. The
+/// icons are named `minedmap_<kind>` and must be provided by the web server.
+fn write_squaremap<W: Write>(
+	writer: &mut W,
+	kinds: &[MarkerKind],
+	markers: &[Marker],
+) -> Result<()> {
+	let timestamp = SystemTime::now()
+		.duration_since(SystemTime::UNIX_EPOCH)
+		.map_or(0, |duration| duration.as_millis());
+
+	let layers: Vec<_> = kinds
+		.iter()
+		.enumerate()
+		.map(|(order, &kind)| {
+			let layer_markers: Vec<_> = markers
+				.iter()
+				.filter(|marker| marker.kind == kind)
+				.map(|marker| {
+					json!({
+						"type": "icon",
+						"point": { "x": marker.x, "z": marker.z },
+						"size": { "x": 16, "z": 16 },
+						"icon": format!("minedmap_{}", kind.name()),
+						"tooltip": marker.label,
+					})
+				})
+				.collect();
+			json!({
+				"id": format!("minedmap_{}", kind.name()),
+				"name": kind.label(),
+				"order": order,
+				"z_index": order,
+				"hide": false,
+				"control": true,
+				"timestamp": timestamp,
+				"markers": layer_markers,
+			})
+		})
+		.collect();
+
+	serde_json::to_writer(writer, &layers)?;
+	Ok(())
+}
+
+/// Runs the `export-markers` subcommand
+pub fn run(args: ExportMarkersArgs) -> Result<()> {
+	let mut kinds = args.kinds;
+	if kinds.is_empty() {
+		kinds = MarkerKind::value_variants().to_vec();
+	}
+	kinds.sort();
+	kinds.dedup();
+
+	let (input, world) = super::open_world(&args.world)?;
+	let source = super::dimension_source(input.clone(), &world, args.dimension)?;
+
+	let mut markers = source
+		.list_regions()?
+		.into_par_iter()
+		.map(|coords| {
+			let mut markers = Vec::new();
+			if let Err(err) = add_region(&mut markers, &kinds, &source, coords) {
+				eprintln!(
+					"Skipping region {}: {:?}",
+					source.region_path(coords).display(),
+					err
+				);
+				return Vec::new();
+			}
+			markers
+		})
+		.reduce(Vec::new, |mut a, b| {
+			a.extend(b);
+			a
+		});
+
+	if kinds.contains(&MarkerKind::Player) {
+		add_players(&mut markers, input.as_ref(), &world, args.dimension)?;
+	}
+
+	markers.sort_by(|a, b| (a.kind, &a.id).cmp(&(b.kind, &b.id)));
+
+	match args.format {
+		MarkerFormat::Dynmap => {
+			fs::create(&args.output, |file| write_dynmap(file, &kinds, &markers))
+		}
+		MarkerFormat::Bluemap => {
+			fs::create(&args.output, |file| write_bluemap(file, &kinds, &markers))
+		}
+		MarkerFormat::Squaremap => {
+			fs::create(&args.output, |file| write_squaremap(file, &kinds, &markers))
+		}
+	}
+}
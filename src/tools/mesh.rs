@@ -0,0 +1,239 @@
+//! The `export-mesh` subcommand
+
+use std::{io::Write, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use glam::Vec3;
+
+use crate::{
+	core::common::{Dimension, ProcessedRegion, TileCoords},
+	io::{fs, storage},
+	resource::{base_block_color, BiomeTypes},
+	types::*,
+};
+
+/// Maximum number of vertices of an exported mesh
+const MAX_VERTICES: u64 = 1 << 24;
+
+/// Arguments of the `export-mesh` subcommand
+#[derive(Debug, Args)]
+pub struct ExportMeshArgs {
+	/// Dimension to export
+	#[arg(long, value_enum, default_value_t = Dimension::Overworld)]
+	dimension: Dimension,
+	/// Block coordinates of a corner of the exported area
+	#[arg(long, value_name = "X,Z", value_parser = crate::core::parse_coords, allow_hyphen_values = true)]
+	from: (i32, i32),
+	/// Block coordinates of the opposite corner of the exported area
+	#[arg(long, value_name = "X,Z", value_parser = crate::core::parse_coords, allow_hyphen_values = true)]
+	to: (i32, i32),
+	/// Distance between the sampled block columns
+	///
+	/// Larger steps reduce the size of the mesh for large areas.
+	#[arg(long, value_name = "BLOCKS", default_value_t = 1, value_parser = clap::value_parser!(i32).range(1..))]
+	step: i32,
+	/// Output directory of a previous MinedMap run
+	///
+	/// For server directories with multiple worlds, the subdirectory of the
+	/// world must be passed.
+	data_dir: PathBuf,
+	/// OBJ file to write the mesh to
+	output: PathBuf,
+}
+
+/// Height and color of a sampled block column
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+	/// Height of the top face of the column
+	height: f32,
+	/// Color of the column in the 0..1 range
+	color: Vec3,
+}
+
+/// Heightfield of the exported area
+struct Heightfield {
+	/// Minimum X block coordinate of the area
+	min_x: i32,
+	/// Minimum Z block coordinate of the area
+	min_z: i32,
+	/// Distance between samples in blocks
+	step: i32,
+	/// Number of samples in X direction
+	width: usize,
+	/// Number of samples in Z direction
+	depth: usize,
+	/// Samples in row-first order, None where no chunk data exists
+	samples: Vec<Option<Sample>>,
+}
+
+impl Heightfield {
+	/// Adds the sampled block columns of a processed region
+	fn add_region(
+		&mut self,
+		region: &ProcessedRegion,
+		coords: TileCoords,
+		biome_types: &BiomeTypes,
+	) {
+		let fallback_biome = biome_types
+			.get("minecraft:plains")
+			.expect("plains biome must exist");
+
+		for (chunk_coords, chunk) in region.chunks.iter() {
+			let Some(chunk) = chunk else {
+				continue;
+			};
+
+			for block_coords in
+				BlockZ::iter().flat_map(|z| BlockX::iter().map(move |x| LayerBlockCoords { x, z }))
+			{
+				let x = (coords.x * CHUNKS_PER_REGION as i32 + chunk_coords.x.0 as i32)
+					* BLOCKS_PER_CHUNK as i32
+					+ block_coords.x.0 as i32;
+				let z = (coords.z * CHUNKS_PER_REGION as i32 + chunk_coords.z.0 as i32)
+					* BLOCKS_PER_CHUNK as i32
+					+ block_coords.z.0 as i32;
+				let (dx, dz) = (x - self.min_x, z - self.min_z);
+				if dx < 0 || dz < 0 || dx % self.step != 0 || dz % self.step != 0 {
+					continue;
+				}
+				let (sx, sz) = ((dx / self.step) as usize, (dz / self.step) as usize);
+				if sx >= self.width || sz >= self.depth {
+					continue;
+				}
+
+				let (Some(block), Some(height)) = (
+					chunk.blocks[block_coords],
+					chunk.surface_height(block_coords),
+				) else {
+					continue;
+				};
+				let biome = chunk.biomes[block_coords]
+					.and_then(|index| region.biome_list.get_index(usize::from(index.get() - 1)))
+					.unwrap_or(fallback_biome);
+				let color = base_block_color(block, Some(biome), height as f32);
+
+				self.samples[sz * self.width + sx] = Some(Sample {
+					height: (height + 1) as f32,
+					color: (color / 255.0).clamp(Vec3::ZERO, Vec3::ONE),
+				});
+			}
+		}
+	}
+
+	/// Writes the heightfield as a Wavefront OBJ mesh with vertex colors
+	///
+	/// Each sample becomes a vertex at the top of its block column, with one
+	/// block corresponding to one unit. Squares of four neighboring samples
+	/// are split into two triangles; squares with missing samples are left
+	/// out.
+	fn write_obj(&self, mut writer: impl Write) -> Result<()> {
+		writeln!(writer, "# Exported by MinedMap")?;
+		writeln!(
+			writer,
+			"# Area {},{} to {},{}",
+			self.min_x,
+			self.min_z,
+			self.min_x + (self.width as i32 - 1) * self.step,
+			self.min_z + (self.depth as i32 - 1) * self.step,
+		)?;
+
+		// OBJ vertex indices are 1-based
+		let mut indices = vec![0usize; self.samples.len()];
+		let mut next_index = 1;
+		for (i, sample) in self.samples.iter().enumerate() {
+			let Some(sample) = sample else {
+				continue;
+			};
+			let x = (i % self.width) as i32 * self.step;
+			let z = (i / self.width) as i32 * self.step;
+			writeln!(
+				writer,
+				"v {} {} {} {:.4} {:.4} {:.4}",
+				x, sample.height, z, sample.color.x, sample.color.y, sample.color.z,
+			)?;
+			indices[i] = next_index;
+			next_index += 1;
+		}
+
+		for sz in 0..self.depth.saturating_sub(1) {
+			for sx in 0..self.width.saturating_sub(1) {
+				let a = indices[sz * self.width + sx];
+				let b = indices[sz * self.width + sx + 1];
+				let c = indices[(sz + 1) * self.width + sx];
+				let d = indices[(sz + 1) * self.width + sx + 1];
+
+				// Counter-clockwise when seen from above
+				if a != 0 && b != 0 && c != 0 {
+					writeln!(writer, "f {} {} {}", a, c, b)?;
+				}
+				if b != 0 && c != 0 && d != 0 {
+					writeln!(writer, "f {} {} {}", b, c, d)?;
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Runs the `export-mesh` subcommand
+pub fn run(args: ExportMeshArgs) -> Result<()> {
+	let (min_x, max_x) = (args.from.0.min(args.to.0), args.from.0.max(args.to.0));
+	let (min_z, max_z) = (args.from.1.min(args.to.1), args.from.1.max(args.to.1));
+
+	let width = ((i64::from(max_x) - i64::from(min_x)) / i64::from(args.step) + 1) as u64;
+	let depth = ((i64::from(max_z) - i64::from(min_z)) / i64::from(args.step) + 1) as u64;
+	if width * depth > MAX_VERTICES {
+		bail!("Area too large, select a smaller area or a larger step");
+	}
+
+	let processed_dir = match args.dimension {
+		Dimension::Overworld => args.data_dir.join("processed"),
+		other => args.data_dir.join(other.name()).join("processed"),
+	};
+
+	let mut heightfield = Heightfield {
+		min_x,
+		min_z,
+		step: args.step,
+		width: width as usize,
+		depth: depth as usize,
+		samples: vec![None; (width * depth) as usize],
+	};
+	let biome_types = BiomeTypes::default();
+
+	let region_range = |min: i32, max: i32| {
+		let blocks_per_region = (BLOCKS_PER_CHUNK * CHUNKS_PER_REGION) as i32;
+		min.div_euclid(blocks_per_region)..=max.div_euclid(blocks_per_region)
+	};
+
+	let mut found = false;
+	for region_z in region_range(min_z, max_z) {
+		for region_x in region_range(min_x, max_x) {
+			let coords = TileCoords {
+				x: region_x,
+				z: region_z,
+			};
+			let path = processed_dir.join(format!("r.{}.{}.bin", region_x, region_z));
+			// Skip regions that have not been generated
+			if !path.exists() {
+				continue;
+			}
+
+			let region: ProcessedRegion = storage::read(&path)
+				.with_context(|| format!("Failed to read {}", path.display()))?;
+			heightfield.add_region(&region, coords, &biome_types);
+			found = true;
+		}
+	}
+
+	if !found {
+		bail!(
+			"No processed region data found in {} for the selected area",
+			processed_dir.display()
+		);
+	}
+
+	fs::create(&args.output, |file| heightfield.write_obj(file))
+}
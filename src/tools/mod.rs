@@ -0,0 +1,262 @@
+//! Auxiliary subcommands for inspecting and maintaining save data
+
+mod chunk;
+mod markers;
+mod mesh;
+mod nbt;
+mod region;
+mod resources;
+mod slices;
+mod stats;
+
+use std::{
+	io::Read,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::Subcommand;
+use fastnbt::Value;
+
+use crate::{
+	core::{
+		common::{Dimension, RegionFormat, RegionSource, TileCoords},
+		layout,
+	},
+	io::input::{self, InputSource, ReadSeek},
+	nbt::{
+		data,
+		region::{external_chunk_filename, from_reader, Region},
+	},
+	types::*,
+	world::{de, mcregion},
+};
+
+/// Auxiliary subcommands of the minedmap CLI
+#[derive(Debug, Subcommand)]
+pub enum Command {
+	/// Inspect and maintain region files
+	#[command(subcommand)]
+	Region(region::RegionCommand),
+	/// Inspect chunks of a world
+	#[command(subcommand)]
+	Chunk(chunk::ChunkCommand),
+	/// Inspect NBT data files
+	#[command(subcommand)]
+	Nbt(nbt::NbtCommand),
+	/// Print statistics about the blocks, ores and biomes of a world
+	Stats(stats::StatsArgs),
+	/// Render images of single block layers of an area of a world
+	Slices(slices::SlicesArgs),
+	/// Generate the block type table of the resource crate from a Minecraft client JAR
+	ExtractResources(resources::ExtractResourcesArgs),
+	/// Export the terrain of an area as a colored 3D mesh
+	ExportMesh(mesh::ExportMeshArgs),
+	/// Export signs, players and structures as markers for other map viewers
+	ExportMarkers(markers::ExportMarkersArgs),
+}
+
+/// Runs an auxiliary subcommand
+pub fn run(command: Command) -> Result<()> {
+	match command {
+		Command::Region(command) => region::run(command),
+		Command::Chunk(command) => chunk::run(command),
+		Command::Nbt(command) => nbt::run(command),
+		Command::Stats(args) => stats::run(args),
+		Command::Slices(args) => slices::run(args),
+		Command::ExtractResources(args) => resources::run(args),
+		Command::ExportMesh(args) => mesh::run(args),
+		Command::ExportMarkers(args) => markers::run(args),
+	}
+}
+
+/// Converts an NBT value to JSON
+///
+/// Typed arrays are converted to plain JSON arrays of numbers, and
+/// compound keys are sorted to make the output deterministic.
+fn to_json(value: &Value) -> serde_json::Value {
+	use serde_json::Value as Json;
+
+	match value {
+		Value::Byte(v) => (*v).into(),
+		Value::Short(v) => (*v).into(),
+		Value::Int(v) => (*v).into(),
+		Value::Long(v) => (*v).into(),
+		Value::Float(v) => (*v).into(),
+		Value::Double(v) => (*v).into(),
+		Value::String(v) => v.as_str().into(),
+		Value::ByteArray(v) => v.iter().copied().collect(),
+		Value::IntArray(v) => v.iter().copied().collect(),
+		Value::LongArray(v) => v.iter().copied().collect(),
+		Value::List(v) => v.iter().map(to_json).collect(),
+		Value::Compound(v) => Json::Object(
+			v.iter()
+				.map(|(key, value)| (key.clone(), to_json(value)))
+				.collect(),
+		),
+	}
+}
+
+/// Prints an NBT value as pretty-printed JSON or SNBT
+fn print_value(value: &Value, snbt: bool) -> Result<()> {
+	if snbt {
+		println!("{}", crate::nbt::snbt::Snbt(value));
+	} else {
+		println!("{}", serde_json::to_string_pretty(&to_json(value))?);
+	}
+	Ok(())
+}
+
+/// Opens the main world of a Minecraft save directory
+///
+/// When the input contains multiple worlds, the first one is used.
+fn open_world(path: &Path) -> Result<(Arc<dyn InputSource>, layout::WorldLayout)> {
+	let input: Arc<dyn InputSource> = input::open(path, 1)?.into();
+	let world = layout::discover(&input)?
+		.into_iter()
+		.next()
+		.context("No world found")?;
+	Ok((input, world))
+}
+
+/// Opens the region files of a dimension of a Minecraft save directory
+///
+/// When the input contains multiple worlds, the main world is used.
+fn open_dimension(path: &Path, dimension: Dimension) -> Result<RegionSource> {
+	let (input, world) = open_world(path)?;
+	dimension_source(input, &world, dimension)
+}
+
+/// Returns the region files of a dimension of a world
+fn dimension_source(
+	input: Arc<dyn InputSource>,
+	world: &layout::WorldLayout,
+	dimension: Dimension,
+) -> Result<RegionSource> {
+	let layout = world
+		.dimensions
+		.iter()
+		.find(|layout| layout.dimension == dimension)
+		.with_context(|| format!("Dimension {} not found", dimension.name()))?;
+
+	let source = RegionSource::new(input, world, layout, TileCoords { x: 0, z: 0 });
+	match source.format {
+		RegionFormat::Bedrock(..) => bail!("Bedrock Edition worlds are not supported"),
+		RegionFormat::Cubic(_) => bail!("Cubic Chunks worlds are not supported"),
+		_ => Ok(source),
+	}
+}
+
+/// Opens an Anvil or McRegion file of a [RegionSource]
+///
+/// Oversized chunks are read from their external files.
+fn open_region(
+	source: &RegionSource,
+	coords: TileCoords,
+) -> Result<Region<'static, Box<dyn ReadSeek>>> {
+	let path = source.region_path(coords);
+	let reader = source
+		.input
+		.open(&path)
+		.with_context(|| format!("Region file {} not found", path.display()))?;
+
+	let input = source.input.clone();
+	let region_dir: PathBuf = source.region_dir.clone();
+	Ok(
+		from_reader(reader).with_external_chunks(move |chunk_coords| {
+			let filename = external_chunk_filename(coords.x, coords.z, chunk_coords);
+			let mut data = Vec::new();
+			input
+				.open(&region_dir.join(filename))
+				.map_err(std::io::Error::other)?
+				.read_to_end(&mut data)?;
+			Ok(data)
+		}),
+	)
+}
+
+/// Calls a function for each chunk of a region of a [RegionSource]
+///
+/// McRegion chunks are converted to the Anvil format.
+fn foreach_chunk<F>(source: &RegionSource, coords: TileCoords, mut f: F) -> Result<()>
+where
+	F: FnMut(ChunkCoords, &de::Chunk) -> Result<()>,
+{
+	match source.format {
+		RegionFormat::McRegion => open_region(source, coords)?.foreach_chunk(
+			|chunk_coords, chunk: mcregion::Chunk| -> Result<()> {
+				let chunk = chunk.into_anvil()?;
+				f(chunk_coords, &chunk)
+			},
+		),
+		#[cfg(feature = "zstd")]
+		RegionFormat::Linear => {
+			let reader = source.input.open(&source.region_path(coords))?;
+			crate::nbt::linear::from_reader(reader)
+				.foreach_chunk_data(|chunk_coords, data| f(chunk_coords, &data::from_bytes(data)?))
+		}
+		#[cfg(not(feature = "zstd"))]
+		RegionFormat::Linear => {
+			anyhow::bail!("Reading linear region files requires the zstd feature")
+		}
+		_ => open_region(source, coords)?
+			.foreach_chunk_data(|chunk_coords, data| f(chunk_coords, &data::from_bytes(data)?)),
+	}
+}
+
+/// Calls a function for each chunk of a region of a [RegionSource], passing
+/// the chunk data as a generic NBT value
+///
+/// Unlike [foreach_chunk], McRegion chunks are passed unconverted.
+fn foreach_chunk_value<F>(source: &RegionSource, coords: TileCoords, mut f: F) -> Result<()>
+where
+	F: FnMut(ChunkCoords, Value) -> Result<()>,
+{
+	match source.format {
+		#[cfg(feature = "zstd")]
+		RegionFormat::Linear => {
+			let reader = source.input.open(&source.region_path(coords))?;
+			crate::nbt::linear::from_reader(reader)
+				.foreach_chunk_data(|chunk_coords, data| f(chunk_coords, data::from_bytes(data)?))
+		}
+		#[cfg(not(feature = "zstd"))]
+		RegionFormat::Linear => {
+			anyhow::bail!("Reading linear region files requires the zstd feature")
+		}
+		_ => open_region(source, coords)?
+			.foreach_chunk_data(|chunk_coords, data| f(chunk_coords, data::from_bytes(data)?)),
+	}
+}
+
+/// Returns the number of days of a month, or [None] for invalid months
+fn days_in_month(year: i64, month: i64) -> Option<i64> {
+	let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+	Some(match month {
+		1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+		4 | 6 | 9 | 11 => 30,
+		2 if leap => 29,
+		2 => 28,
+		_ => return None,
+	})
+}
+
+/// Parses a date in YYYY-MM-DD format as a timestamp in seconds since the
+/// Unix epoch, referring to the start of the day in UTC
+fn parse_date(date: &str) -> Result<u32> {
+	let (year, month, day) = (|| {
+		let mut parts = date.split('-');
+		let year: i64 = parts.next()?.parse().ok()?;
+		let month: i64 = parts.next()?.parse().ok()?;
+		let day: i64 = parts.next()?.parse().ok()?;
+		if parts.next().is_some() || !(1..=days_in_month(year, month)?).contains(&day) {
+			return None;
+		}
+		Some((year, month, day))
+	})()
+	.with_context(|| format!("Invalid date {}, expected YYYY-MM-DD", date))?;
+
+	(crate::core::days_from_civil(year, month, day) * 86400)
+		.try_into()
+		.with_context(|| format!("Date {} out of range", date))
+}
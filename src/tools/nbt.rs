@@ -0,0 +1,38 @@
+//! The `nbt` subcommand
+
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+
+use crate::nbt::data;
+
+/// Subcommands for inspecting NBT data files
+#[derive(Debug, Subcommand)]
+pub enum NbtCommand {
+	/// Print the contents of an NBT data file
+	///
+	/// GZip-compressed, Zlib-compressed and uncompressed files are supported,
+	/// like level.dat, player data and map item files.
+	Print {
+		/// Print the data in SNBT syntax instead of JSON
+		#[arg(long)]
+		snbt: bool,
+		/// Filename to print
+		file: PathBuf,
+	},
+}
+
+/// Runs an `nbt` subcommand
+pub fn run(command: NbtCommand) -> Result<()> {
+	match command {
+		NbtCommand::Print { snbt, file } => {
+			let value = (|| -> Result<fastnbt::Value> {
+				let data = data::decompress(&fs::read(&file)?)?;
+				Ok(data::from_bytes(&data)?)
+			})()
+			.with_context(|| format!("Failed to read NBT file {}", file.display()))?;
+			super::print_value(&value, snbt)
+		}
+	}
+}
@@ -0,0 +1,586 @@
+//! The `region` subcommand
+
+use std::{
+	fs,
+	io::{Cursor, Seek, SeekFrom, Write},
+	path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{Subcommand, ValueEnum};
+
+use serde::Deserialize;
+
+use crate::{
+	core::common::{Dimension, RegionFormat, RegionSource, TileCoords},
+	io::fs as io_fs,
+	nbt::region::{self, ChunkInfo, Compression, RawChunk, RegionError},
+	types::*,
+};
+
+/// Size of a data block of a region file
+const BLOCKSIZE: u64 = 4096;
+
+/// Size of the header of a region file
+const HEADER_SIZE: u64 = 2 * BLOCKSIZE;
+
+/// Chunk compression format selectable on the command line
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CompressionArg {
+	/// GZip compression
+	Gzip,
+	/// Zlib compression (used by Minecraft)
+	Zlib,
+	/// No compression
+	None,
+}
+
+impl From<CompressionArg> for Compression {
+	fn from(compression: CompressionArg) -> Self {
+		match compression {
+			CompressionArg::Gzip => Compression::Gzip,
+			CompressionArg::Zlib => Compression::Zlib,
+			CompressionArg::None => Compression::Uncompressed,
+		}
+	}
+}
+
+/// Subcommands for inspecting and maintaining region files
+#[derive(Debug, Subcommand)]
+pub enum RegionCommand {
+	/// Print the layout of the chunks stored in a region file
+	///
+	/// For each chunk, the offset and number of its data blocks, the length
+	/// of the stored data, the compression format and the modification
+	/// timestamp are printed, followed by invalid header entries and the
+	/// amount of slack space that could be reclaimed by rewriting the file.
+	Info {
+		/// Region file in Anvil (.mca) or McRegion (.mcr) format
+		file: PathBuf,
+	},
+	/// Rewrite a region file without unused space between chunks
+	///
+	/// Chunks are stored back to back in the order of their coordinates,
+	/// keeping their timestamps. Chunks with invalid header entries are
+	/// dropped, printing a warning for each dropped chunk. Oversized chunks
+	/// stored in external files are not supported.
+	///
+	/// When the input file is replaced, the original file is kept with an
+	/// additional .bak extension.
+	Compact {
+		/// Recompress all chunks using the given format
+		///
+		/// By default, the compressed chunk data is copied unchanged.
+		#[arg(long, value_enum)]
+		compression: Option<CompressionArg>,
+		/// Write the compacted region to the given file instead of
+		/// replacing the input file
+		///
+		/// No backup of the input file is created in this case.
+		#[arg(short, long)]
+		output: Option<PathBuf>,
+		/// Region file in Anvil (.mca) or McRegion (.mcr) format
+		file: PathBuf,
+	},
+	/// Convert a region file between the Anvil and linear formats
+	///
+	/// The format of each file is determined by its extension: files
+	/// ending in .linear use the linear format of some Paper forks, all
+	/// other files the Anvil format. Converting between two Anvil files
+	/// allows to change the compression of the chunks. Chunk timestamps are
+	/// preserved, while chunks with invalid header entries are dropped with
+	/// a warning.
+	Convert {
+		/// Compression format of the chunks of an Anvil output file
+		///
+		/// When converting between Anvil files, the compressed chunk data is
+		/// copied unchanged by default. Otherwise, Zlib is used by default.
+		#[arg(long, value_enum)]
+		compression: Option<CompressionArg>,
+		/// zstd compression level of a linear output file
+		///
+		/// 0 selects the default level of the zstd library.
+		#[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+		level: i8,
+		/// Input region file
+		input: PathBuf,
+		/// Output region file
+		output: PathBuf,
+	},
+	/// List regions of a world that are candidates for trimming
+	///
+	/// A region is listed when none of its chunks has been inhabited by
+	/// players for more than the given time, or when none of its chunks has
+	/// been modified since the cutoff date. For each listed region, the
+	/// region file, its size, the number of chunks, the highest inhabited
+	/// time of its chunks in ticks, the newest chunk timestamp and the reason
+	/// it was listed are printed, separated by tabs.
+	Unused {
+		/// Highest inhabited time in ticks for regions considered unvisited
+		#[arg(long, value_name = "TICKS", default_value_t = 0)]
+		max_inhabited: i64,
+		/// List regions that have not been modified since the given date
+		#[arg(long, value_name = "YYYY-MM-DD", value_parser = super::parse_date)]
+		before: Option<u32>,
+		/// Dimension to check
+		#[arg(long, value_enum, default_value_t = Dimension::Overworld)]
+		dimension: Dimension,
+		/// Minecraft save directory
+		world: PathBuf,
+	},
+}
+
+/// Runs a `region` subcommand
+pub fn run(command: RegionCommand) -> Result<()> {
+	match command {
+		RegionCommand::Info { file } => info(file),
+		RegionCommand::Compact {
+			compression,
+			output,
+			file,
+		} => {
+			if is_linear(&file) {
+				bail!("Linear region files are not supported");
+			}
+			let output = match output {
+				Some(output) => output,
+				None => {
+					backup(&file)?;
+					file.clone()
+				}
+			};
+			convert(file, output, compression.map(Compression::from), 0)
+		}
+		RegionCommand::Convert {
+			compression,
+			level,
+			input,
+			output,
+		} => convert(input, output, compression.map(Compression::from), level),
+		RegionCommand::Unused {
+			max_inhabited,
+			before,
+			dimension,
+			world,
+		} => unused(&world, dimension, max_inhabited, before),
+	}
+}
+
+/// Copies a file to a backup file with an additional .bak extension
+///
+/// Fails if the backup file exists already, so the backup of an earlier run
+/// is never overwritten.
+fn backup(path: &Path) -> Result<()> {
+	let mut backup_path = path.as_os_str().to_os_string();
+	backup_path.push(".bak");
+	let backup_path = PathBuf::from(backup_path);
+
+	if backup_path.try_exists()? {
+		bail!(
+			"Backup file {} exists already, remove it or pass --output",
+			backup_path.display()
+		);
+	}
+	fs::copy(path, &backup_path).with_context(|| {
+		format!(
+			"Failed to copy {} to {}",
+			path.display(),
+			backup_path.display()
+		)
+	})?;
+	println!(
+		"Saved backup of {} to {}",
+		path.display(),
+		backup_path.display()
+	);
+
+	Ok(())
+}
+
+/// Returns true if a path refers to a linear region file
+fn is_linear(path: &Path) -> bool {
+	path.extension().is_some_and(|ext| ext == "linear")
+}
+
+/// Returns a human-readable name for a chunk compression format byte
+fn format_name(info: &ChunkInfo) -> String {
+	let Some(format) = info.format else {
+		return "-".to_string();
+	};
+	let name = match format & 0x7f {
+		1 => "gzip".to_string(),
+		2 => "zlib".to_string(),
+		3 => "none".to_string(),
+		4 => "lz4".to_string(),
+		format => format!("unknown({})", format),
+	};
+	if info.is_external() {
+		format!("{} (external)", name)
+	} else {
+		name
+	}
+}
+
+/// Prints information about the chunks of a region file
+fn info(path: PathBuf) -> Result<()> {
+	if is_linear(&path) {
+		bail!("Linear region files are not supported");
+	}
+
+	let file_len = fs::metadata(&path)
+		.with_context(|| format!("Failed to read metadata of {}", path.display()))?
+		.len();
+	let mut region = region::from_file(&path)?;
+
+	let invalid = region.invalid_chunks()?;
+	let mut is_invalid = ChunkArray::<bool>::default();
+	for coords in invalid.iter().filter_map(|err| err.chunk_coords()) {
+		is_invalid[coords] = true;
+	}
+
+	let mut chunks = Vec::new();
+	for coords in ChunkArray::<()>::keys() {
+		if let Some(info) = region.chunk_info(coords)? {
+			chunks.push((coords, info));
+		}
+	}
+	chunks.sort_by_key(|(_, info)| info.offset);
+
+	println!("Region file {} ({} bytes)", path.display(), file_len);
+	println!();
+	println!(
+		"{:<10} {:>8} {:>6} {:>9}  {:<16} Modified",
+		"Chunk", "Offset", "Blocks", "Bytes", "Format"
+	);
+
+	let mut used_blocks = 0;
+	let mut padding = 0;
+	for (coords, info) in &chunks {
+		let byte_len = info
+			.byte_len
+			.map_or_else(|| "-".to_string(), |len| len.to_string());
+		let modified = match info.timestamp {
+			0 => "-".to_string(),
+			timestamp => crate::core::format_timestamp(timestamp),
+		};
+		println!(
+			"{:<10} {:>8} {:>6} {:>9}  {:<16} {}",
+			format!("{:?}", coords),
+			info.offset,
+			info.len,
+			byte_len,
+			format_name(info),
+			modified,
+		);
+
+		if is_invalid[*coords] {
+			continue;
+		}
+		let allocated = u64::from(info.len) * BLOCKSIZE;
+		used_blocks += u64::from(info.len);
+		if let Some(len) = info.byte_len {
+			padding += allocated.saturating_sub(u64::from(len) + 4);
+		}
+	}
+
+	let valid = chunks
+		.iter()
+		.filter(|(coords, _)| !is_invalid[*coords])
+		.count();
+	println!();
+	println!("{} chunks, {} valid", chunks.len(), valid);
+
+	if !invalid.is_empty() {
+		println!();
+		println!("Invalid header entries:");
+		for err in &invalid {
+			println!("  {}", err);
+		}
+	}
+
+	let unused = file_len.saturating_sub(HEADER_SIZE + used_blocks * BLOCKSIZE);
+	println!();
+	println!(
+		"Slack space: {} bytes ({} bytes in unused blocks, {} bytes of padding)",
+		unused + padding,
+		unused,
+		padding,
+	);
+
+	Ok(())
+}
+
+/// Data of a chunk read for conversion
+enum ChunkData {
+	/// Compressed data read from an Anvil region
+	Raw(RawChunk),
+	/// Uncompressed NBT data read from a linear region
+	#[cfg(feature = "zstd")]
+	Nbt(Vec<u8>),
+}
+
+impl ChunkData {
+	/// Returns the uncompressed NBT data of the chunk
+	fn into_nbt(self, coords: ChunkCoords) -> Result<Vec<u8>, RegionError> {
+		match self {
+			ChunkData::Raw(raw) => raw
+				.decompress()
+				.map_err(|source| RegionError::Chunk { coords, source }),
+			#[cfg(feature = "zstd")]
+			ChunkData::Nbt(data) => Ok(data),
+		}
+	}
+}
+
+/// Reads the coordinates, timestamps and data of all chunks of a region file
+fn read_chunks(path: &Path) -> Result<Vec<(ChunkCoords, u32, ChunkData)>> {
+	let mut chunks = Vec::new();
+
+	if is_linear(path) {
+		#[cfg(feature = "zstd")]
+		crate::nbt::linear::from_reader(fs::File::open(path)?).foreach_chunk_entry(
+			|coords, timestamp, data| -> Result<(), RegionError> {
+				chunks.push((coords, timestamp, ChunkData::Nbt(data.to_vec())));
+				Ok(())
+			},
+		)?;
+		#[cfg(not(feature = "zstd"))]
+		bail!("Reading linear region files requires the zstd feature");
+	} else {
+		let mut region = region::from_file(path)?;
+		let invalid = region.invalid_chunks()?;
+		for err in &invalid {
+			eprintln!("Dropping chunk of {}: {}", path.display(), err);
+		}
+		if !invalid.is_empty() {
+			eprintln!(
+				"Dropped {} chunks with invalid header entries of {}",
+				invalid.len(),
+				path.display()
+			);
+		}
+		let timestamps = region.timestamps()?;
+		for (coords, raw) in region.chunks()? {
+			let raw = raw.map_err(|source| RegionError::Chunk { coords, source })?;
+			chunks.push((coords, timestamps[coords], ChunkData::Raw(raw)));
+		}
+	}
+
+	chunks.sort_by_key(|(coords, _, _)| (coords.z.0, coords.x.0));
+	Ok(chunks)
+}
+
+/// Writes chunks to an Anvil region without slack space
+///
+/// Chunks are recompressed if *compression* is given and differs from
+/// their current format.
+fn write_anvil(
+	chunks: Vec<(ChunkCoords, u32, ChunkData)>,
+	compression: Option<Compression>,
+) -> Result<Vec<u8>> {
+	let mut writer = region::Writer::create(Cursor::new(Vec::new()))?
+		.with_compression(compression.unwrap_or_default());
+	for (coords, timestamp, chunk) in chunks {
+		match (chunk, compression) {
+			(ChunkData::Raw(raw), None) => writer.write_chunk_raw(coords, &raw)?,
+			(ChunkData::Raw(raw), Some(compression)) if raw.format == compression.format() => {
+				writer.write_chunk_raw(coords, &raw)?
+			}
+			(chunk, _) => writer.write_chunk_data(coords, &chunk.into_nbt(coords)?)?,
+		}
+		writer.set_timestamp(coords, timestamp)?;
+	}
+	Ok(writer.finish()?.into_inner())
+}
+
+/// Writes chunks to a linear region
+#[cfg(feature = "zstd")]
+fn write_linear(chunks: Vec<(ChunkCoords, u32, ChunkData)>, level: i8) -> Result<Vec<u8>> {
+	let mut writer = crate::nbt::linear::Writer::new().with_compression_level(level);
+	for (coords, timestamp, chunk) in chunks {
+		writer.write_chunk_data(coords, &chunk.into_nbt(coords)?, timestamp);
+	}
+	Ok(writer.finish(Vec::new())?)
+}
+
+/// Writes chunks to a linear region
+#[cfg(not(feature = "zstd"))]
+fn write_linear(_chunks: Vec<(ChunkCoords, u32, ChunkData)>, _level: i8) -> Result<Vec<u8>> {
+	bail!("Writing linear region files requires the zstd feature");
+}
+
+/// Rewrites a region file, converting it to the format given by the
+/// extension of *output*
+fn convert(
+	input: PathBuf,
+	output: PathBuf,
+	compression: Option<Compression>,
+	level: i8,
+) -> Result<()> {
+	let input_len = fs::metadata(&input)
+		.with_context(|| format!("Failed to read metadata of {}", input.display()))?
+		.len();
+
+	let chunks = read_chunks(&input)?;
+	let data = if is_linear(&output) {
+		write_linear(chunks, level)?
+	} else {
+		write_anvil(chunks, compression)?
+	};
+
+	io_fs::create_with_tmpfile(&output, |file| {
+		file.write_all(&data)?;
+		Ok(())
+	})?;
+
+	let change = data.len() as f64 / input_len.max(1) as f64 - 1.0;
+	println!(
+		"Wrote {}: {} -> {} bytes ({:+.1}%)",
+		output.display(),
+		input_len,
+		data.len(),
+		change * 100.0,
+	);
+
+	Ok(())
+}
+
+/// Chunk fields relevant to the activity of players
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ChunkActivity {
+	/// Total time players have spent in the chunk, in ticks
+	#[serde(default)]
+	inhabited_time: i64,
+	/// Level data of chunks saved before Minecraft 1.18
+	level: Option<Box<ChunkActivity>>,
+}
+
+impl ChunkActivity {
+	/// Returns the inhabited time of the chunk in ticks
+	fn inhabited_time(&self) -> i64 {
+		match &self.level {
+			Some(level) => level.inhabited_time(),
+			None => self.inhabited_time,
+		}
+	}
+}
+
+/// Activity summary of a region
+#[derive(Debug, Default)]
+struct RegionActivity {
+	/// Number of chunks in the region
+	chunks: usize,
+	/// Highest inhabited time of the chunks, in ticks
+	max_inhabited: i64,
+	/// Newest chunk timestamp in seconds since the Unix epoch
+	newest: u32,
+}
+
+impl RegionActivity {
+	/// Adds a chunk to the summary
+	fn add(&mut self, timestamp: u32, activity: &ChunkActivity) {
+		self.chunks += 1;
+		self.max_inhabited = self.max_inhabited.max(activity.inhabited_time());
+		self.newest = self.newest.max(timestamp);
+	}
+}
+
+/// Collects the activity summary and file size of a region
+fn region_activity(source: &RegionSource, coords: TileCoords) -> Result<(RegionActivity, u64)> {
+	let mut activity = RegionActivity::default();
+	let size;
+
+	match source.format {
+		#[cfg(feature = "zstd")]
+		RegionFormat::Linear => {
+			let mut reader = source.input.open(&source.region_path(coords))?;
+			size = reader.seek(SeekFrom::End(0))?;
+			reader.rewind()?;
+			crate::nbt::linear::from_reader(reader).foreach_chunk_entry(
+				|_, timestamp, data| -> Result<()> {
+					activity.add(timestamp, &crate::nbt::data::from_bytes(data)?);
+					Ok(())
+				},
+			)?;
+		}
+		#[cfg(not(feature = "zstd"))]
+		RegionFormat::Linear => {
+			bail!("Reading linear region files requires the zstd feature")
+		}
+		_ => {
+			size = source
+				.input
+				.open(&source.region_path(coords))?
+				.seek(SeekFrom::End(0))?;
+			let mut region = super::open_region(source, coords)?;
+			let timestamps = region.timestamps()?;
+			region.foreach_chunk(|chunk_coords, chunk: ChunkActivity| -> Result<()> {
+				activity.add(timestamps[chunk_coords], &chunk);
+				Ok(())
+			})?;
+		}
+	}
+
+	Ok((activity, size))
+}
+
+/// Prints a list of regions that are candidates for trimming
+fn unused(
+	world: &Path,
+	dimension: Dimension,
+	max_inhabited: i64,
+	before: Option<u32>,
+) -> Result<()> {
+	let source = super::open_dimension(world, dimension)?;
+	let mut regions = source.list_regions()?;
+	regions.sort_by_key(|coords| (coords.z, coords.x));
+
+	let mut total_size = 0;
+	let mut count = 0;
+	for &coords in &regions {
+		let path = source.region_path(coords);
+		let (activity, size) = match region_activity(&source, coords) {
+			Ok(result) => result,
+			Err(err) => {
+				eprintln!("Skipping region {}: {:?}", path.display(), err);
+				continue;
+			}
+		};
+
+		let reason = if activity.max_inhabited == 0 {
+			"never visited"
+		} else if activity.max_inhabited <= max_inhabited {
+			"rarely visited"
+		} else if before.is_some_and(|before| activity.newest < before) {
+			"not modified since cutoff"
+		} else {
+			continue;
+		};
+
+		let newest = match activity.newest {
+			0 => "-".to_string(),
+			timestamp => crate::core::format_timestamp(timestamp),
+		};
+		println!(
+			"{}\t{}\t{}\t{}\t{}\t{}",
+			path.display(),
+			size,
+			activity.chunks,
+			activity.max_inhabited,
+			newest,
+			reason,
+		);
+		total_size += size;
+		count += 1;
+	}
+
+	eprintln!(
+		"{} of {} regions unused ({} bytes)",
+		count,
+		regions.len(),
+		total_size,
+	);
+
+	Ok(())
+}
@@ -0,0 +1,572 @@
+//! The `extract-resources` subcommand
+
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap},
+	fs::File,
+	io::{Read, Write},
+	path::PathBuf,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::world::de;
+
+/// Arguments of the `extract-resources` subcommand
+#[derive(Debug, Args)]
+pub struct ExtractResourcesArgs {
+	/// Block hints in the format of `resource/blocks.json`
+	///
+	/// Hints select the texture and biome color flags of a block, or hide it
+	/// from the map when set to null. Blocks without a hint are derived from
+	/// their block models.
+	#[arg(long, value_name = "FILE")]
+	blocks: Option<PathBuf>,
+	/// File to write the generated `block_types.rs` to instead of stdout
+	#[arg(long, value_name = "FILE")]
+	output: Option<PathBuf>,
+	/// Update the biome table in the given `biomes.rs` from the worldgen
+	/// biome definitions of the JAR
+	#[arg(long, value_name = "FILE")]
+	biomes: Option<PathBuf>,
+	/// Minecraft client JAR
+	jar: PathBuf,
+}
+
+/// Block hint of `resource/blocks.json`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockHint {
+	/// Texture name without the `block/` prefix
+	texture: Option<String>,
+	/// Block is colored using biome grass colors
+	#[serde(default)]
+	grass: bool,
+	/// Block is colored using biome foliage colors
+	#[serde(default)]
+	foliage: bool,
+	/// Block is birch foliage
+	#[serde(default)]
+	birch: bool,
+	/// Block is spruce foliage
+	#[serde(default)]
+	spruce: bool,
+	/// Block is colored using biome water colors
+	#[serde(default)]
+	water: bool,
+}
+
+/// Extracted information of a block type
+#[derive(Debug, Default)]
+struct BlockInfo {
+	/// Mean color of the texture, if visible
+	color: Option<[u8; 3]>,
+	/// Biome color flags
+	hint: BlockHint,
+}
+
+/// Access to the assets of a Minecraft client JAR
+struct Assets {
+	/// Opened JAR archive
+	archive: zip::ZipArchive<File>,
+	/// Cache of parsed block models
+	models: HashMap<String, Option<Value>>,
+}
+
+/// Strips the `minecraft:` namespace from an asset name
+fn strip_namespace(name: &str) -> &str {
+	name.strip_prefix("minecraft:").unwrap_or(name)
+}
+
+impl Assets {
+	/// Reads a file from the JAR, returning [None] if it does not exist
+	fn read(&mut self, path: &str) -> Result<Option<Vec<u8>>> {
+		let mut file = match self.archive.by_name(path) {
+			Ok(file) => file,
+			Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+			Err(err) => return Err(err).with_context(|| format!("Failed to open {}", path)),
+		};
+		let mut data = Vec::new();
+		file.read_to_end(&mut data)
+			.with_context(|| format!("Failed to read {}", path))?;
+		Ok(Some(data))
+	}
+
+	/// Reads and parses a JSON file from the JAR
+	fn read_json(&mut self, path: &str) -> Result<Option<Value>> {
+		self.read(path)?
+			.map(|data| serde_json::from_slice(&data))
+			.transpose()
+			.with_context(|| format!("Failed to parse {}", path))
+	}
+
+	/// Returns the sorted names of all JSON files in a directory of the JAR
+	fn json_names(&self, dir: &str) -> Vec<String> {
+		let mut names: Vec<_> = self
+			.archive
+			.file_names()
+			.filter_map(|name| name.strip_prefix(dir)?.strip_suffix(".json"))
+			.filter(|name| !name.contains('/'))
+			.map(String::from)
+			.collect();
+		names.sort();
+		names
+	}
+
+	/// Returns the IDs of all block types with a blockstate definition
+	fn block_ids(&self) -> Vec<String> {
+		self.json_names("assets/minecraft/blockstates/")
+	}
+
+	/// Returns the worldgen definitions of all biomes
+	fn biomes(&mut self) -> Result<BTreeMap<String, de::WorldgenBiome>> {
+		/// Directory of worldgen biome definitions
+		const DIR: &str = "data/minecraft/worldgen/biome/";
+
+		let mut biomes = BTreeMap::new();
+		for id in self.json_names(DIR) {
+			let path = format!("{}{}.json", DIR, id);
+			let Some(data) = self.read(&path)? else {
+				continue;
+			};
+			let biome = serde_json::from_slice(&data)
+				.with_context(|| format!("Failed to parse {}", path))?;
+			biomes.insert(id, biome);
+		}
+		Ok(biomes)
+	}
+
+	/// Returns the biome IDs listed in a biome tag
+	fn biome_tag(&mut self, tag: &str) -> Result<BTreeSet<String>> {
+		let path = format!("data/minecraft/tags/worldgen/biome/{}.json", tag);
+		let Some(tag) = self.read_json(&path)? else {
+			return Ok(BTreeSet::new());
+		};
+		Ok(tag
+			.get("values")
+			.and_then(Value::as_array)
+			.into_iter()
+			.flatten()
+			.filter_map(Value::as_str)
+			.filter(|value| !value.starts_with('#'))
+			.map(|value| strip_namespace(value).to_string())
+			.collect())
+	}
+
+	/// Returns a block model by name, using the cache if possible
+	fn model(&mut self, name: &str) -> Result<Option<Value>> {
+		let name = strip_namespace(name).to_string();
+		if let Some(model) = self.models.get(&name) {
+			return Ok(model.clone());
+		}
+		let model = self.read_json(&format!("assets/minecraft/models/{}.json", name))?;
+		self.models.insert(name, model.clone());
+		Ok(model)
+	}
+
+	/// Returns the name of the first model used by a blockstate definition
+	fn blockstate_model(&mut self, id: &str) -> Result<Option<String>> {
+		let Some(blockstate) =
+			self.read_json(&format!("assets/minecraft/blockstates/{}.json", id))?
+		else {
+			return Ok(None);
+		};
+
+		// Variants and multipart entries may contain a single model or a
+		// list of weighted models
+		let first_model = |entry: &Value| {
+			let entry = entry
+				.as_array()
+				.and_then(|list| list.first())
+				.unwrap_or(entry);
+			entry.get("model")?.as_str().map(String::from)
+		};
+
+		if let Some(variants) = blockstate.get("variants").and_then(Value::as_object) {
+			return Ok(variants
+				.get("")
+				.or_else(|| variants.values().next())
+				.and_then(first_model));
+		}
+		Ok(blockstate
+			.get("multipart")
+			.and_then(Value::as_array)
+			.and_then(|parts| parts.first())
+			.and_then(|part| part.get("apply"))
+			.and_then(first_model))
+	}
+
+	/// Determines the texture shown on the map for a block model
+	///
+	/// Returns the texture name and whether the face is tinted by a biome
+	/// color. Returns [None] for models without elements.
+	fn top_texture(&mut self, model: &str) -> Result<Option<(String, bool)>> {
+		/// Texture variables used when the model has no upward face
+		const TEXTURE_KEYS: &[&str] = &["top", "end", "up", "all", "texture", "side", "cross"];
+
+		let mut textures = BTreeMap::<String, String>::new();
+		let mut elements = None;
+		let mut name = Some(model.to_string());
+
+		while let Some(model_name) = name.take() {
+			let Some(model) = self.model(&model_name)? else {
+				break;
+			};
+			if let Some(model_textures) = model.get("textures").and_then(Value::as_object) {
+				for (key, value) in model_textures {
+					if let Some(value) = value.as_str() {
+						textures
+							.entry(key.clone())
+							.or_insert_with(|| value.to_string());
+					}
+				}
+			}
+			if elements.is_none() {
+				elements = model.get("elements").and_then(Value::as_array).cloned();
+			}
+			name = model
+				.get("parent")
+				.and_then(Value::as_str)
+				.map(String::from);
+		}
+
+		let Some(elements) = elements else {
+			return Ok(None);
+		};
+
+		// Use the upward face of the highest element
+		let top = elements
+			.iter()
+			.filter_map(|element| {
+				let height = element.get("to")?.get(1)?.as_f64()?;
+				let face = element.get("faces")?.get("up")?;
+				Some((height, face))
+			})
+			.max_by(|(a, _), (b, _)| a.total_cmp(b));
+
+		let (mut texture, tinted) = match top {
+			Some((_, face)) => (
+				face.get("texture")
+					.and_then(Value::as_str)
+					.map(String::from),
+				face.get("tintindex").is_some(),
+			),
+			None => (
+				TEXTURE_KEYS
+					.iter()
+					.find_map(|key| textures.get(*key).cloned()),
+				elements.iter().any(|element| {
+					element
+						.get("faces")
+						.and_then(Value::as_object)
+						.is_some_and(|faces| {
+							faces.values().any(|face| face.get("tintindex").is_some())
+						})
+				}),
+			),
+		};
+
+		// Resolve texture variable references
+		for _ in 0..16 {
+			let Some(var) = texture.as_deref().and_then(|t| t.strip_prefix('#')) else {
+				break;
+			};
+			texture = textures.get(var).cloned();
+		}
+
+		Ok(texture
+			.filter(|texture| !texture.starts_with('#'))
+			.map(|texture| (texture, tinted)))
+	}
+
+	/// Computes the mean color of a texture, weighted by opacity
+	///
+	/// Returns [None] for missing or fully transparent textures.
+	fn mean_color(&mut self, texture: &str) -> Result<Option<[u8; 3]>> {
+		let path = format!("assets/minecraft/textures/{}.png", strip_namespace(texture));
+		let Some(data) = self.read(&path)? else {
+			return Ok(None);
+		};
+		let image = image::load_from_memory_with_format(&data, image::ImageFormat::Png)
+			.with_context(|| format!("Failed to decode {}", path))?
+			.into_rgba8();
+
+		let mut sum = [0.0f64; 3];
+		let mut alpha = 0.0f64;
+		for pixel in image.pixels() {
+			let a = f64::from(pixel[3]);
+			for (sum, value) in sum.iter_mut().zip(pixel.0) {
+				*sum += f64::from(value) * a;
+			}
+			alpha += a;
+		}
+		if alpha == 0.0 {
+			return Ok(None);
+		}
+
+		Ok(Some(sum.map(|sum| (sum / alpha) as u8)))
+	}
+
+	/// Extracts the information of a block type
+	///
+	/// Hints take precedence over the block models.
+	fn block_info(&mut self, id: &str, hint: Option<Option<BlockHint>>) -> Result<BlockInfo> {
+		match hint {
+			Some(None) => Ok(BlockInfo::default()),
+			Some(Some(hint)) => {
+				let texture = format!("block/{}", hint.texture.as_deref().unwrap_or(id));
+				Ok(BlockInfo {
+					color: self.mean_color(&texture)?,
+					hint,
+				})
+			}
+			None => {
+				let Some(model) = self.blockstate_model(id)? else {
+					return Ok(BlockInfo::default());
+				};
+				let Some((texture, tinted)) = self.top_texture(&model)? else {
+					return Ok(BlockInfo::default());
+				};
+				Ok(BlockInfo {
+					color: self.mean_color(&texture)?,
+					hint: tint_hint(id, tinted),
+				})
+			}
+		}
+	}
+}
+
+/// Guesses the biome color flags of a block from its ID
+///
+/// Block models only specify whether a face is tinted, but not by which
+/// biome color.
+fn tint_hint(id: &str, tinted: bool) -> BlockHint {
+	if !tinted {
+		return BlockHint::default();
+	}
+	match id {
+		"birch_leaves" => BlockHint {
+			birch: true,
+			..Default::default()
+		},
+		"spruce_leaves" => BlockHint {
+			spruce: true,
+			..Default::default()
+		},
+		_ if id.contains("water") => BlockHint {
+			water: true,
+			..Default::default()
+		},
+		_ if id.ends_with("_leaves") || id.contains("vine") => BlockHint {
+			foliage: true,
+			..Default::default()
+		},
+		_ => BlockHint {
+			grass: true,
+			..Default::default()
+		},
+	}
+}
+
+/// Formats a temperature or downfall value for the `Biome::new()` arguments
+///
+/// The value is multiplied by 100, with an underscore in place of the
+/// decimal point.
+fn climate_literal(value: f32) -> String {
+	let value = (value * 100.0).round() as i32;
+	let sign = if value < 0 { "-" } else { "" };
+	format!("{}{}_{:02}", sign, value.abs() / 100, value.abs() % 100)
+}
+
+/// Generates the `BIOMES` table definition of `biomes.rs`
+fn biome_table(
+	biomes: &BTreeMap<String, de::WorldgenBiome>,
+	nether: &BTreeSet<String>,
+	end: &BTreeSet<String>,
+) -> Result<String> {
+	/// Default water color, which is not stored in the table
+	const DEFAULT_WATER_COLOR: [u8; 3] = [63, 118, 228];
+
+	let mut out = String::new();
+	out += "pub const BIOMES: &[(&str, Biome)] = {\n";
+	out += "\tuse BiomeGrassColorModifier::*;\n\n";
+	out += "\t// Data extracted from the worldgen biome definitions of the Minecraft client JAR\n";
+	out += "\t// using `minedmap extract-resources`\n\n";
+	out += "\t// We can't use floats in const functions, to temperature and downfall values\n";
+	out += "\t// are specified multipled by 100. The underscore is used in place of the decimal point\n";
+	out += "\t// of the original values.\n\n";
+	out += "\t#[allow(clippy::zero_prefixed_literal)]\n";
+	out += "\t&[\n";
+
+	let dimension = |id: &str| {
+		if nether.contains(id) {
+			"Nether"
+		} else if end.contains(id) {
+			"End"
+		} else {
+			"Overworld"
+		}
+	};
+	for section in ["Overworld", "Nether", "End"] {
+		out += &format!("\t\t// {}\n", section);
+
+		for (id, biome) in biomes.iter().filter(|(id, _)| dimension(id) == section) {
+			let effects = &biome.effects;
+			let mut entry = format!(
+				"Biome::new({}, {})",
+				climate_literal(biome.temperature),
+				climate_literal(biome.downfall),
+			);
+			if let Some(water) = effects
+				.water_color
+				.as_ref()
+				.and_then(de::WorldgenColor::rgb)
+				.filter(|&water| water != DEFAULT_WATER_COLOR)
+			{
+				entry += &format!(".water({:?})", water);
+			}
+			if let Some(foliage) = effects
+				.foliage_color
+				.as_ref()
+				.and_then(de::WorldgenColor::rgb)
+			{
+				entry += &format!(".foliage({:?})", foliage);
+			}
+			if let Some(grass) = effects
+				.grass_color
+				.as_ref()
+				.and_then(de::WorldgenColor::rgb)
+			{
+				entry += &format!(".grass({:?})", grass);
+			}
+			match effects.grass_color_modifier.as_deref() {
+				None | Some("none") => {}
+				Some("dark_forest") => entry += ".modify(DarkForest)",
+				Some("swamp") => entry += ".modify(Swamp)",
+				Some(modifier) => {
+					bail!("Unknown grass color modifier {} of biome {}", modifier, id)
+				}
+			}
+			out += &format!("\t\t({:?}, {}),\n", id, entry);
+		}
+	}
+
+	out += "\t]\n";
+	out += "};\n";
+	Ok(out)
+}
+
+/// Replaces the `BIOMES` table definition in the source of `biomes.rs`
+fn replace_biome_table(source: &str, table: &str) -> Result<String> {
+	/// First line of the table definition
+	const START: &str = "pub const BIOMES: &[(&str, Biome)] = {\n";
+	/// Last line of the table definition
+	const END: &str = "\n};\n";
+
+	let start = source
+		.find(START)
+		.context("Biome table definition not found")?;
+	let end = source[start..]
+		.find(END)
+		.context("End of biome table definition not found")?
+		+ start
+		+ END.len();
+
+	Ok(format!("{}{}{}", &source[..start], table, &source[end..]))
+}
+
+/// Writes the block type table in the format of `block_types.rs`
+fn write_block_types(mut out: impl Write, blocks: &BTreeMap<String, BlockInfo>) -> Result<()> {
+	writeln!(out, "use enumflags2::make_bitflags;")?;
+	writeln!(out)?;
+	writeln!(out, "use super::*;")?;
+	writeln!(out)?;
+	writeln!(out, "pub const BLOCK_TYPES: &[(&str, BlockType)] = &[")?;
+
+	for (id, info) in blocks {
+		let hint = &info.hint;
+		let flags: Vec<_> = [
+			(info.color.is_some(), "Opaque"),
+			(hint.grass, "Grass"),
+			(hint.foliage, "Foliage"),
+			(hint.birch, "Birch"),
+			(hint.spruce, "Spruce"),
+			(hint.water, "Water"),
+		]
+		.into_iter()
+		.filter(|&(set, _)| set && info.color.is_some())
+		.map(|(_, flag)| flag)
+		.collect();
+		let [r, g, b] = info.color.unwrap_or_default();
+
+		writeln!(out, "\t(")?;
+		writeln!(out, "\t\t\"{}\",", id)?;
+		writeln!(out, "\t\tBlockType {{")?;
+		writeln!(
+			out,
+			"\t\t\tflags: make_bitflags!(BlockFlag::{{{}}}),",
+			flags.join("|")
+		)?;
+		writeln!(out, "\t\t\tcolor: Color([{}, {}, {}]),", r, g, b)?;
+		writeln!(out, "\t\t}},")?;
+		writeln!(out, "\t),")?;
+	}
+
+	writeln!(out, "];")?;
+	Ok(())
+}
+
+/// Runs the `extract-resources` subcommand
+pub fn run(args: ExtractResourcesArgs) -> Result<()> {
+	let mut hints: BTreeMap<String, Option<BlockHint>> = match &args.blocks {
+		Some(path) => {
+			let data = std::fs::read_to_string(path)
+				.with_context(|| format!("Failed to read {}", path.display()))?;
+			serde_json::from_str(&data)
+				.with_context(|| format!("Failed to parse {}", path.display()))?
+		}
+		None => BTreeMap::new(),
+	};
+
+	let archive = zip::ZipArchive::new(
+		File::open(&args.jar).with_context(|| format!("Failed to open {}", args.jar.display()))?,
+	)
+	.with_context(|| format!("Failed to read {}", args.jar.display()))?;
+	let mut assets = Assets {
+		archive,
+		models: HashMap::new(),
+	};
+
+	if let Some(path) = &args.biomes {
+		let biomes = assets.biomes()?;
+		let nether = assets.biome_tag("is_nether")?;
+		let end = assets.biome_tag("is_end")?;
+		let table = biome_table(&biomes, &nether, &end)?;
+
+		let source = std::fs::read_to_string(path)
+			.with_context(|| format!("Failed to read {}", path.display()))?;
+		let source = replace_biome_table(&source, &table)
+			.with_context(|| format!("Failed to update {}", path.display()))?;
+		std::fs::write(path, source)
+			.with_context(|| format!("Failed to write {}", path.display()))?;
+	}
+
+	let mut blocks = BTreeMap::new();
+	for id in assets.block_ids() {
+		let info = assets
+			.block_info(&id, hints.remove(&id))
+			.with_context(|| format!("Failed to extract block type {}", id))?;
+		blocks.insert(id, info);
+	}
+
+	match &args.output {
+		Some(path) => {
+			let file = File::create(path)
+				.with_context(|| format!("Failed to create {}", path.display()))?;
+			write_block_types(std::io::BufWriter::new(file), &blocks)
+		}
+		None => write_block_types(std::io::stdout().lock(), &blocks),
+	}
+}
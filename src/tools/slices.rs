@@ -0,0 +1,244 @@
+//! The `slices` subcommand
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+
+use crate::{
+	core::common::{Dimension, TileCoords},
+	io::fs,
+	resource::{block_color, Biome, BiomeTypes, BlockFlag, BlockTypes},
+	types::*,
+	world::{chunk::Chunk, layer::BlockHeight},
+};
+
+/// Maximum number of pixels of all images rendered by a single invocation
+const MAX_PIXELS: u64 = 1 << 28;
+
+/// Arguments of the `slices` subcommand
+#[derive(Debug, Args)]
+pub struct SlicesArgs {
+	/// Dimension to render
+	#[arg(long, value_enum, default_value_t = Dimension::Overworld)]
+	dimension: Dimension,
+	/// Block coordinates of a corner of the rendered area
+	#[arg(long, value_name = "X,Z", value_parser = crate::core::parse_coords, allow_hyphen_values = true)]
+	from: (i32, i32),
+	/// Block coordinates of the opposite corner of the rendered area
+	#[arg(long, value_name = "X,Z", value_parser = crate::core::parse_coords, allow_hyphen_values = true)]
+	to: (i32, i32),
+	/// Lowest Y coordinate to render
+	#[arg(long, value_name = "Y", default_value_t = -64, allow_hyphen_values = true)]
+	min_y: i32,
+	/// Highest Y coordinate to render
+	#[arg(
+		long,
+		value_name = "Y",
+		default_value_t = 319,
+		allow_hyphen_values = true
+	)]
+	max_y: i32,
+	/// Number of block layers combined into each image
+	///
+	/// Each image shows the topmost block of each column within its band of
+	/// layers.
+	#[arg(long, value_name = "LAYERS", default_value_t = 1, value_parser = clap::value_parser!(i32).range(1..))]
+	band: i32,
+	/// Minecraft save directory
+	world: PathBuf,
+	/// Directory to write the images to
+	output_dir: PathBuf,
+}
+
+/// Images of the bands of block layers of an area
+struct Slices {
+	/// Minimum X block coordinate of the area
+	min_x: i32,
+	/// Minimum Z block coordinate of the area
+	min_z: i32,
+	/// Lowest Y coordinate of the lowest band
+	min_y: i32,
+	/// Highest Y coordinate to render
+	max_y: i32,
+	/// Number of block layers of each band
+	band: i32,
+	/// Images of the bands, starting with the lowest band
+	images: Vec<image::RgbaImage>,
+}
+
+impl Slices {
+	/// Returns the index of the band containing a Y coordinate
+	fn band_index(&self, y: i32) -> Option<usize> {
+		if y < self.min_y || y > self.max_y {
+			return None;
+		}
+		Some(((y - self.min_y) / self.band) as usize)
+	}
+
+	/// Renders the blocks of a chunk into the images of the bands
+	///
+	/// Sections are iterated from top to bottom, so the first opaque block
+	/// found in a column of a band is the topmost one.
+	fn add_chunk(
+		&mut self,
+		chunk: &Chunk,
+		chunk_x: i32,
+		chunk_z: i32,
+		fallback_biome: &Biome,
+	) -> Result<()> {
+		let (width, height) = self.images[0].dimensions();
+
+		for section in chunk.sections().rev() {
+			for y in BlockY::iter().rev() {
+				let block_height = BlockHeight::new(section.y, y)?;
+				let Some(index) = self.band_index(block_height.0) else {
+					continue;
+				};
+
+				for z in BlockZ::iter() {
+					for x in BlockX::iter() {
+						let px = chunk_x * BLOCKS_PER_CHUNK as i32 + x.0 as i32 - self.min_x;
+						let pz = chunk_z * BLOCKS_PER_CHUNK as i32 + z.0 as i32 - self.min_z;
+						let (Ok(px), Ok(pz)) = (u32::try_from(px), u32::try_from(pz)) else {
+							continue;
+						};
+						if px >= width || pz >= height {
+							continue;
+						}
+						let image = &mut self.images[index];
+						if image.get_pixel(px, pz)[3] != 0 {
+							continue;
+						}
+
+						let coords = SectionBlockCoords {
+							xz: LayerBlockCoords { x, z },
+							y,
+						};
+						let Some(block) = section
+							.section
+							.block_at(coords)?
+							.filter(|block| block.is(BlockFlag::Opaque))
+						else {
+							continue;
+						};
+						let biome = section.biomes.biome_at(section.y, coords)?;
+						let color = block_color(
+							block,
+							Some(biome.unwrap_or(fallback_biome)),
+							block_height.0 as f32,
+						);
+						image.put_pixel(
+							px,
+							pz,
+							image::Rgba([color[0] as u8, color[1] as u8, color[2] as u8, 255]),
+						);
+					}
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Saves the images of all bands into an output directory
+	///
+	/// The images are named after the range of Y coordinates of their band.
+	fn save(&self, output_dir: &std::path::Path) -> Result<()> {
+		fs::create_dir_all(output_dir)?;
+
+		for (index, image) in self.images.iter().enumerate() {
+			let lo = self.min_y + index as i32 * self.band;
+			let hi = (lo + self.band - 1).min(self.max_y);
+			let name = if lo == hi {
+				format!("y{}.png", lo)
+			} else {
+				format!("y{}_{}.png", lo, hi)
+			};
+			let path = output_dir.join(name);
+			fs::create(&path, |file| {
+				image
+					.write_to(file, image::ImageFormat::Png)
+					.context("Failed to save image")
+			})?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Runs the `slices` subcommand
+pub fn run(args: SlicesArgs) -> Result<()> {
+	let (min_x, max_x) = (args.from.0.min(args.to.0), args.from.0.max(args.to.0));
+	let (min_z, max_z) = (args.from.1.min(args.to.1), args.from.1.max(args.to.1));
+	if args.min_y > args.max_y {
+		bail!("--min-y must not be greater than --max-y");
+	}
+
+	let width = u32::try_from(i64::from(max_x) - i64::from(min_x) + 1)?;
+	let height = u32::try_from(i64::from(max_z) - i64::from(min_z) + 1)?;
+	let bands =
+		((i64::from(args.max_y) - i64::from(args.min_y)) / i64::from(args.band) + 1) as usize;
+	if u64::from(width) * u64::from(height) * bands as u64 > MAX_PIXELS {
+		bail!("Area too large, select a smaller area or fewer layers");
+	}
+
+	let source = super::open_dimension(&args.world, args.dimension)?;
+	let block_types = BlockTypes::default();
+	let biome_types = BiomeTypes::default();
+	let fallback_biome = biome_types
+		.get("minecraft:plains")
+		.expect("plains biome must exist");
+
+	let mut slices = Slices {
+		min_x,
+		min_z,
+		min_y: args.min_y,
+		max_y: args.max_y,
+		band: args.band,
+		images: vec![image::RgbaImage::new(width, height); bands],
+	};
+
+	let chunk_range = |min: i32, max: i32| {
+		min.div_euclid(BLOCKS_PER_CHUNK as i32)..=max.div_euclid(BLOCKS_PER_CHUNK as i32)
+	};
+	let (chunks_x, chunks_z) = (chunk_range(min_x, max_x), chunk_range(min_z, max_z));
+	let region_range = |chunks: &std::ops::RangeInclusive<i32>| {
+		chunks.start().div_euclid(CHUNKS_PER_REGION as i32)
+			..=chunks.end().div_euclid(CHUNKS_PER_REGION as i32)
+	};
+
+	for region_z in region_range(&chunks_z) {
+		for region_x in region_range(&chunks_x) {
+			let coords = TileCoords {
+				x: region_x,
+				z: region_z,
+			};
+			// Skip regions that have not been generated
+			if source.input.modified(&source.region_path(coords)).is_err() {
+				continue;
+			}
+
+			super::foreach_chunk(&source, coords, |chunk_coords, data| {
+				let chunk_x = region_x * CHUNKS_PER_REGION as i32 + chunk_coords.x.0 as i32;
+				let chunk_z = region_z * CHUNKS_PER_REGION as i32 + chunk_coords.z.0 as i32;
+				if !chunks_x.contains(&chunk_x) || !chunks_z.contains(&chunk_z) {
+					return Ok(());
+				}
+
+				let chunk = Chunk::new(data, &block_types, &biome_types)?;
+				slices
+					.add_chunk(&chunk, chunk_x, chunk_z, fallback_biome)
+					.with_context(|| format!("Failed to render chunk {:?}", chunk_coords))
+			})
+			.with_context(|| {
+				format!(
+					"Failed to read region {}",
+					source.region_path(coords).display()
+				)
+			})?;
+		}
+	}
+
+	slices.save(&args.output_dir)
+}
@@ -0,0 +1,403 @@
+//! The `stats` subcommand
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::{
+	core::common::{Dimension, RegionSource, TileCoords},
+	resource::{self, BiomeTypes, BlockTypes},
+	types::*,
+	world::{
+		de,
+		section::{BiomesV1_18, SectionV0, SectionV1_13},
+	},
+};
+
+/// Output format of the statistics
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum StatsFormat {
+	/// A single JSON object
+	Json,
+	/// CSV with the columns kind, name, y and value
+	Csv,
+}
+
+/// Arguments of the `stats` subcommand
+#[derive(Debug, Args)]
+pub struct StatsArgs {
+	/// Output format
+	#[arg(long, value_enum, default_value_t = StatsFormat::Json)]
+	format: StatsFormat,
+	/// Dimension to analyze
+	#[arg(long, value_enum, default_value_t = Dimension::Overworld)]
+	dimension: Dimension,
+	/// Block type to include in the ore distribution, like minecraft:diamond_ore
+	///
+	/// Can be passed multiple times. By default, all block types ending in
+	/// `_ore` and ancient debris are included.
+	#[arg(long, value_name = "BLOCK")]
+	ore: Vec<String>,
+	/// Minecraft save directory
+	world: PathBuf,
+}
+
+/// Statistics collected from the chunks of a dimension
+#[derive(Debug, Default)]
+struct Stats {
+	/// Number of chunks
+	chunks: u64,
+	/// Number of blocks of each block type
+	blocks: BTreeMap<String, u64>,
+	/// Number of ore blocks at each Y coordinate
+	ores: BTreeMap<String, BTreeMap<i32, u64>>,
+	/// Number of 4x4x4 biome cells of each biome
+	///
+	/// For pre-1.15 chunks, each entry counts a single block column instead.
+	biomes: BTreeMap<String, u64>,
+}
+
+/// Statistics report as written in JSON format
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+	/// Number of chunks
+	chunks: u64,
+	/// Number of blocks of each block type
+	blocks: &'a BTreeMap<String, u64>,
+	/// Number of ore blocks at each Y coordinate
+	ores: &'a BTreeMap<String, BTreeMap<i32, u64>>,
+	/// Share of each biome in percent
+	biomes: BTreeMap<&'a str, f64>,
+}
+
+/// Context for collecting [Stats]
+struct Collector<'a> {
+	/// Registry of known block types
+	block_types: &'a BlockTypes,
+	/// Registry of known biome types
+	biome_types: &'a BiomeTypes,
+	/// Explicit list of ore block types
+	ores: &'a [String],
+}
+
+impl Collector<'_> {
+	/// Returns true if the ore distribution of a block type is collected
+	fn is_ore(&self, name: &str) -> bool {
+		if !self.ores.is_empty() {
+			return self.ores.iter().any(|ore| ore == name);
+		}
+		name.ends_with("_ore") || name == "minecraft:ancient_debris"
+	}
+
+	/// Adds the block counts of a section with palette to the statistics
+	///
+	/// *counts* contains the number of blocks at each Y coordinate of the
+	/// section for each palette entry.
+	fn add_palette_counts(
+		&self,
+		stats: &mut Stats,
+		section_y: i32,
+		names: impl Iterator<Item = String>,
+		counts: &[[u64; BLOCKS_PER_CHUNK]],
+	) {
+		for (name, counts) in names.zip(counts) {
+			let total: u64 = counts.iter().sum();
+			if total == 0 {
+				continue;
+			}
+			if self.is_ore(&name) {
+				let ore = stats.ores.entry(name.clone()).or_default();
+				for (y, &count) in counts.iter().enumerate() {
+					if count > 0 {
+						*ore.entry(section_y * BLOCKS_PER_CHUNK as i32 + y as i32)
+							.or_default() += count;
+					}
+				}
+			}
+			*stats.blocks.entry(name).or_default() += total;
+		}
+	}
+
+	/// Adds a v1.13+ section to the statistics
+	fn add_section_v1_13(
+		&self,
+		stats: &mut Stats,
+		data_version: u32,
+		section_y: i32,
+		block_states: Option<&de::LongArray>,
+		palette: &[de::BlockStatePaletteEntry],
+	) -> Result<()> {
+		let section = SectionV1_13::new(data_version, block_states, palette, self.block_types)
+			.with_context(|| format!("Failed to load section at Y={}", section_y))?;
+
+		let mut counts = vec![[0; BLOCKS_PER_CHUNK]; palette.len()];
+		for y in BlockY::iter() {
+			for z in BlockZ::iter() {
+				for x in BlockX::iter() {
+					let coords = SectionBlockCoords {
+						xz: LayerBlockCoords { x, z },
+						y,
+					};
+					let index = section.palette_index_at(coords);
+					counts
+						.get_mut(index)
+						.context("Palette index out of bounds")?[y.0 as usize] += 1;
+				}
+			}
+		}
+
+		let names = palette.iter().map(|entry| entry.name.to_string());
+		self.add_palette_counts(stats, section_y, names, &counts);
+		Ok(())
+	}
+
+	/// Adds a pre-1.13 section to the statistics
+	///
+	/// Numeric block IDs and subtypes are reported with the names of the
+	/// corresponding 1.13+ block types.
+	fn add_section_v0(
+		&self,
+		stats: &mut Stats,
+		section_y: i32,
+		blocks: &[i8],
+		data: &[i8],
+	) -> Result<()> {
+		let section = SectionV0::new(blocks, data, self.block_types)
+			.with_context(|| format!("Failed to load section at Y={}", section_y))?;
+
+		let mut counts = vec![[0; BLOCKS_PER_CHUNK]; 256 * 16];
+		for y in BlockY::iter() {
+			for z in BlockZ::iter() {
+				for x in BlockX::iter() {
+					let (block, data) = section.legacy_id_at(SectionBlockCoords {
+						xz: LayerBlockCoords { x, z },
+						y,
+					});
+					counts[usize::from(block) * 16 + usize::from(data)][y.0 as usize] += 1;
+				}
+			}
+		}
+
+		// Only the IDs found in the section are passed on, so names are
+		// not formatted for all 4096 possible combinations
+		let (names, counts): (Vec<_>, Vec<_>) = counts
+			.into_iter()
+			.enumerate()
+			.filter(|(_, counts)| counts.iter().any(|&count| count > 0))
+			.map(|(index, counts)| {
+				let id = resource::legacy_block_id((index / 16) as u8, (index % 16) as u8);
+				(format!("minecraft:{}", id), counts)
+			})
+			.unzip();
+		self.add_palette_counts(stats, section_y, names.into_iter(), &counts);
+		Ok(())
+	}
+
+	/// Adds v1.18+ section biome data to the statistics
+	fn add_biomes_v1_18(&self, stats: &mut Stats, biomes: &de::BiomesV1_18) -> Result<()> {
+		let section = BiomesV1_18::new(biomes.data.as_ref(), &biomes.palette, self.biome_types)?;
+
+		let mut counts = vec![0; biomes.palette.len()];
+		for y in BlockY::iter().step_by(4) {
+			for z in BlockZ::iter().step_by(4) {
+				for x in BlockX::iter().step_by(4) {
+					let coords = SectionBlockCoords {
+						xz: LayerBlockCoords { x, z },
+						y,
+					};
+					let index = section.palette_index_at(coords);
+					*counts
+						.get_mut(index)
+						.context("Palette index out of bounds")? += 1;
+				}
+			}
+		}
+
+		for (name, count) in biomes.palette.iter().zip(counts) {
+			if count > 0 {
+				*stats.biomes.entry(name.to_string()).or_default() += count;
+			}
+		}
+		Ok(())
+	}
+
+	/// Adds pre-1.18 chunk biome data to the statistics
+	///
+	/// Numeric biome IDs are reported as `legacy:<id>`.
+	fn add_biomes_v0(&self, stats: &mut Stats, biomes: &de::BiomesV0) {
+		let ids: Vec<u8> = match biomes {
+			de::BiomesV0::IntArray(data) => data.iter().map(|&id| id as u8).collect(),
+			de::BiomesV0::ByteArray(data) => data.iter().map(|&id| id as u8).collect(),
+		};
+		for id in ids {
+			*stats.biomes.entry(format!("legacy:{}", id)).or_default() += 1;
+		}
+	}
+
+	/// Adds a chunk to the statistics
+	fn add_chunk(&self, stats: &mut Stats, chunk: &de::Chunk) -> Result<()> {
+		let data_version = chunk.data_version.unwrap_or_default();
+		stats.chunks += 1;
+
+		match &chunk.chunk {
+			de::ChunkVariants::V1_18 { sections } => {
+				for section in sections {
+					let de::SectionV1_18Variants::V1_18 {
+						block_states,
+						biomes,
+						..
+					} = &section.section
+					else {
+						continue;
+					};
+					self.add_section_v1_13(
+						stats,
+						data_version,
+						section.y,
+						block_states.data.as_ref(),
+						&block_states.palette,
+					)?;
+					self.add_biomes_v1_18(stats, biomes)?;
+				}
+			}
+			de::ChunkVariants::V0 { level } => {
+				for section in &level.sections {
+					match &section.section {
+						de::SectionV0Variants::V1_13 {
+							block_states,
+							palette,
+						} => self.add_section_v1_13(
+							stats,
+							data_version,
+							section.y,
+							Some(block_states),
+							palette,
+						)?,
+						de::SectionV0Variants::V0 { blocks, data } => self.add_section_v0(
+							stats,
+							section.y,
+							blocks.as_slice(),
+							data.as_slice(),
+						)?,
+						de::SectionV0Variants::Empty {} => {}
+					}
+				}
+				if let Some(biomes) = &level.biomes {
+					self.add_biomes_v0(stats, biomes);
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Collects the statistics of a region
+	fn add_region(
+		&self,
+		stats: &mut Stats,
+		source: &RegionSource,
+		coords: TileCoords,
+	) -> Result<()> {
+		super::foreach_chunk(source, coords, |chunk_coords, chunk| {
+			self.add_chunk(stats, chunk)
+				.with_context(|| format!("Failed to process chunk {:?}", chunk_coords))
+		})
+	}
+}
+
+impl Stats {
+	/// Merges the statistics of two sets of chunks
+	fn merge(mut self, other: Stats) -> Stats {
+		self.chunks += other.chunks;
+		for (name, count) in other.blocks {
+			*self.blocks.entry(name).or_default() += count;
+		}
+		for (name, ore) in other.ores {
+			let entry = self.ores.entry(name).or_default();
+			for (y, count) in ore {
+				*entry.entry(y).or_default() += count;
+			}
+		}
+		for (name, count) in other.biomes {
+			*self.biomes.entry(name).or_default() += count;
+		}
+		self
+	}
+
+	/// Returns the share of each biome in percent
+	fn biome_percentages(&self) -> BTreeMap<&str, f64> {
+		let total: u64 = self.biomes.values().sum();
+		self.biomes
+			.iter()
+			.map(|(name, &count)| (name.as_str(), count as f64 * 100.0 / total as f64))
+			.collect()
+	}
+
+	/// Prints the statistics in JSON format
+	fn print_json(&self) -> Result<()> {
+		let report = Report {
+			chunks: self.chunks,
+			blocks: &self.blocks,
+			ores: &self.ores,
+			biomes: self.biome_percentages(),
+		};
+		println!("{}", serde_json::to_string_pretty(&report)?);
+		Ok(())
+	}
+
+	/// Prints the statistics in CSV format
+	fn print_csv(&self) {
+		println!("kind,name,y,value");
+		println!("chunks,,,{}", self.chunks);
+		for (name, count) in &self.blocks {
+			println!("block,{},,{}", name, count);
+		}
+		for (name, ore) in &self.ores {
+			for (y, count) in ore {
+				println!("ore,{},{},{}", name, y, count);
+			}
+		}
+		for (name, percent) in self.biome_percentages() {
+			println!("biome,{},,{:.4}", name, percent);
+		}
+	}
+}
+
+/// Runs the `stats` subcommand
+pub fn run(args: StatsArgs) -> Result<()> {
+	let source = super::open_dimension(&args.world, args.dimension)?;
+	let block_types = BlockTypes::default();
+	let biome_types = BiomeTypes::default();
+	let collector = Collector {
+		block_types: &block_types,
+		biome_types: &biome_types,
+		ores: &args.ore,
+	};
+
+	let stats = source
+		.list_regions()?
+		.into_par_iter()
+		.map(|coords| {
+			let mut stats = Stats::default();
+			if let Err(err) = collector.add_region(&mut stats, &source, coords) {
+				eprintln!(
+					"Skipping region {}: {:?}",
+					source.region_path(coords).display(),
+					err
+				);
+				return Stats::default();
+			}
+			stats
+		})
+		.reduce(Stats::default, Stats::merge);
+
+	match args.format {
+		StatsFormat::Json => stats.print_json()?,
+		StatsFormat::Csv => stats.print_csv(),
+	}
+
+	Ok(())
+}
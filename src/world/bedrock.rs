@@ -0,0 +1,794 @@
+//! Decoding of Minecraft Bedrock Edition world data
+//!
+//! Bedrock worlds store their chunks in a LevelDB database, with separate
+//! entries for each 16x16x16 sub-chunk and the biome data of each chunk.
+//! The decoded data is passed on to the same processing as Java Edition
+//! chunks using [Chunk::new_bedrock](super::chunk::Chunk::new_bedrock).
+
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap},
+	path::Path,
+	sync::{Arc, Mutex},
+	time::SystemTime,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::{
+	io::{input::InputSource, leveldb::Database},
+	resource::{Biome, BiomeTypes, BlockType, BlockTypes},
+	types::*,
+	world::section::{count_palette_blocks, filter_palette_blocks, BlockFilter},
+};
+
+/// Key type of sub-chunk block data
+const TAG_SUB_CHUNK: u8 = 0x2f;
+/// Key type of 1.18+ 3D biome data
+const TAG_DATA_3D: u8 = 0x2b;
+/// Key type of pre-1.18 2D biome data
+const TAG_DATA_2D: u8 = 0x2d;
+/// Key type of the chunk version
+const TAG_VERSION: u8 = 0x2c;
+/// Key type of the chunk version in old worlds
+const TAG_LEGACY_VERSION: u8 = 0x76;
+
+/// Number of blocks or biome entries in a sub-chunk
+const SUB_CHUNK_SIZE: usize = 4096;
+
+/// Coordinates of a chunk in a Bedrock world
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct ChunkKey {
+	/// Dimension ID (0: overworld, 1: Nether, 2: End)
+	dimension: i32,
+	/// Chunk X coordinate
+	x: i32,
+	/// Chunk Z coordinate
+	z: i32,
+}
+
+impl ChunkKey {
+	/// Parses a database key into the chunk coordinates and key type
+	fn parse(key: &[u8]) -> Option<(Self, u8)> {
+		let int = |offset: usize| -> i32 {
+			i32::from_le_bytes(key[offset..offset + 4].try_into().unwrap())
+		};
+
+		let (dimension, tag_offset) = match key.len() {
+			9 | 10 => (0, 8),
+			13 | 14 => (int(8), 12),
+			_ => return None,
+		};
+		let tag = key[tag_offset];
+		if !matches!(
+			tag,
+			TAG_SUB_CHUNK | TAG_DATA_3D | TAG_DATA_2D | TAG_VERSION | TAG_LEGACY_VERSION
+		) {
+			return None;
+		}
+		if (tag == TAG_SUB_CHUNK) != (key.len() == tag_offset + 2) {
+			return None;
+		}
+
+		Some((
+			ChunkKey {
+				dimension,
+				x: int(0),
+				z: int(4),
+			},
+			tag,
+		))
+	}
+
+	/// Returns the common prefix of all database keys of the chunk
+	fn prefix(&self) -> Vec<u8> {
+		let mut prefix = Vec::with_capacity(12);
+		prefix.extend(self.x.to_le_bytes());
+		prefix.extend(self.z.to_le_bytes());
+		if self.dimension != 0 {
+			prefix.extend(self.dimension.to_le_bytes());
+		}
+		prefix
+	}
+}
+
+/// Entry of a sub-chunk block palette
+#[derive(Debug, Deserialize)]
+pub struct PaletteEntry {
+	/// Block type ID
+	pub name: String,
+	/// Block state properties
+	#[serde(default)]
+	pub states: HashMap<String, fastnbt::Value>,
+}
+
+/// Packed indices into a palette, stored in XZY order
+#[derive(Debug)]
+pub struct PalettedData {
+	/// Number of bits per entry
+	bits: u8,
+	/// Packed data
+	words: Vec<u32>,
+}
+
+impl PalettedData {
+	/// Looks up the palette index of an entry
+	fn index(&self, offset: usize) -> usize {
+		if self.bits == 0 {
+			return 0;
+		}
+
+		let bits = self.bits as usize;
+		let per_word = 32 / bits;
+		let word = self.words[offset / per_word];
+		((word >> ((offset % per_word) * bits)) & ((1 << bits) - 1)) as usize
+	}
+}
+
+/// Helper for decoding binary sub-chunk and biome data
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+	/// Consumes a number of bytes
+	fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+		if self.0.len() < len {
+			bail!("Unexpected end of data");
+		}
+		let (head, tail) = self.0.split_at(len);
+		self.0 = tail;
+		Ok(head)
+	}
+
+	/// Reads a single byte
+	fn byte(&mut self) -> Result<u8> {
+		Ok(self.take(1)?[0])
+	}
+
+	/// Reads a little-endian 32-bit integer
+	fn int(&mut self) -> Result<i32> {
+		Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+	}
+
+	/// Reads a palette length
+	fn len(&mut self) -> Result<usize> {
+		usize::try_from(self.int()?).context("Invalid palette length")
+	}
+
+	/// Reads the packed data of a paletted storage
+	///
+	/// Returns [None] for the special header marking biome data that is
+	/// identical to the previous sub-chunk.
+	fn paletted_data(&mut self) -> Result<Option<PalettedData>> {
+		let header = self.byte()?;
+		if header == 0xff {
+			return Ok(None);
+		}
+		let bits = header >> 1;
+		if !matches!(bits, 0..=6 | 8 | 16) {
+			bail!("Unsupported palette bit width {}", bits);
+		}
+
+		let words = if bits == 0 {
+			Vec::new()
+		} else {
+			let per_word = 32 / bits as usize;
+			let len = SUB_CHUNK_SIZE.div_ceil(per_word);
+			self.take(4 * len)?
+				.chunks_exact(4)
+				.map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+				.collect()
+		};
+
+		Ok(Some(PalettedData { bits, words }))
+	}
+
+	/// Reads the palette length of a paletted storage
+	fn palette_len(&mut self, data: &PalettedData) -> Result<usize> {
+		if data.bits == 0 {
+			Ok(1)
+		} else {
+			self.len()
+		}
+	}
+}
+
+/// Block data of a sub-chunk
+#[derive(Debug)]
+pub enum SubChunk {
+	/// Paletted block data of Bedrock 1.2.13+
+	Paletted {
+		/// Packed palette indices
+		data: PalettedData,
+		/// Block types indexed by *data*
+		palette: Vec<PaletteEntry>,
+	},
+	/// Legacy numeric block IDs
+	///
+	/// Converted to the YZX order of Java Edition pre-1.13 sections.
+	Legacy {
+		/// Block IDs
+		blocks: Vec<i8>,
+		/// Block data values, 4 bits per block
+		data: Vec<i8>,
+	},
+}
+
+impl SubChunk {
+	/// Decodes the block data of a sub-chunk
+	///
+	/// Only the first block storage is decoded; further storages contain
+	/// liquids in waterlogged blocks.
+	fn decode(buf: &[u8]) -> Result<Self> {
+		let mut reader = Reader(buf);
+
+		let version = reader.byte()?;
+		match version {
+			1 | 8 | 9 => {
+				if version != 1 {
+					let num_storages = reader.byte()?;
+					if num_storages == 0 {
+						bail!("Sub-chunk without block storage");
+					}
+				}
+				if version == 9 {
+					// Y index of the sub-chunk
+					reader.byte()?;
+				}
+
+				let data = reader
+					.paletted_data()?
+					.context("Invalid sub-chunk block storage")?;
+				let len = reader.palette_len(&data)?;
+				let mut palette = Vec::with_capacity(len.min(SUB_CHUNK_SIZE));
+				for _ in 0..len {
+					let (entry, consumed) = crate::nbt::le::from_bytes(reader.0)?;
+					reader.take(consumed)?;
+					palette.push(entry);
+				}
+
+				Ok(SubChunk::Paletted { data, palette })
+			}
+			0 | 2..=7 => {
+				let ids = reader.take(SUB_CHUNK_SIZE)?;
+				let values = reader.take(SUB_CHUNK_SIZE / 2)?;
+
+				let mut blocks = vec![0; SUB_CHUNK_SIZE];
+				let mut data = vec![0; SUB_CHUNK_SIZE / 2];
+				for (offset, &id) in ids.iter().enumerate() {
+					// XZY to YZX
+					let (x, z, y) = (offset >> 8, (offset >> 4) & 0xf, offset & 0xf);
+					let target = (y << 8) | (z << 4) | x;
+					blocks[target] = id as i8;
+
+					let value = (values[offset >> 1] >> ((offset & 1) * 4)) & 0xf;
+					data[target >> 1] |= (value << ((target & 1) * 4)) as i8;
+				}
+
+				Ok(SubChunk::Legacy { blocks, data })
+			}
+			_ => bail!("Unsupported sub-chunk version {}", version),
+		}
+	}
+}
+
+/// Biome data of a chunk
+#[derive(Debug)]
+pub enum BiomeData {
+	/// 1.18+ biome data with a paletted storage for each sub-chunk, starting
+	/// at the bottom of the dimension
+	Data3D(Vec<(PalettedData, Vec<i32>)>),
+	/// Pre-1.18 biome data with one biome ID per block column in ZX order
+	Data2D(Vec<u8>),
+}
+
+impl BiomeData {
+	/// Decodes a 1.18+ Data3D entry
+	fn decode_3d(buf: &[u8]) -> Result<Self> {
+		let mut reader = Reader(buf);
+		// Skip height map
+		reader.take(512)?;
+
+		let mut storages: Vec<(PalettedData, Vec<i32>)> = Vec::new();
+		while !reader.0.is_empty() {
+			let Some(data) = reader.paletted_data()? else {
+				let (data, palette) = storages.last().context("Invalid biome data")?;
+				let copy = PalettedData {
+					bits: data.bits,
+					words: data.words.clone(),
+				};
+				storages.push((copy, palette.clone()));
+				continue;
+			};
+			let len = reader.palette_len(&data)?;
+			let palette = (0..len).map(|_| reader.int()).collect::<Result<_>>()?;
+			storages.push((data, palette));
+		}
+
+		Ok(BiomeData::Data3D(storages))
+	}
+
+	/// Decodes a pre-1.18 Data2D entry
+	fn decode_2d(buf: &[u8]) -> Result<Self> {
+		let biomes = buf.get(512..768).context("Invalid biome data")?;
+		Ok(BiomeData::Data2D(biomes.to_vec()))
+	}
+}
+
+/// Decoded data of a Bedrock chunk
+#[derive(Debug)]
+pub struct ChunkData {
+	/// Block data of all sub-chunks
+	pub sub_chunks: BTreeMap<SectionY, SubChunk>,
+	/// Biome data
+	pub biomes: Option<BiomeData>,
+	/// Section Y coordinate of the lowest sub-chunk of the dimension
+	///
+	/// Used to associate 3D biome data with the sub-chunks.
+	pub min_section: i32,
+}
+
+/// Region-level index of the chunks of a Bedrock world
+#[derive(Debug, Default)]
+struct RegionInfo {
+	/// Chunks in the region
+	chunks: Vec<ChunkKey>,
+	/// Most recent modification time of the chunks' data
+	modified: Option<SystemTime>,
+}
+
+/// Minecraft Bedrock Edition world database
+///
+/// Chunks are grouped into 32x32 regions, so Bedrock worlds can be processed
+/// in the same way as the region files of Java Edition worlds.
+pub struct World {
+	/// The LevelDB database
+	db: Database,
+	/// Chunks of each dimension and region
+	regions: BTreeMap<(i32, i32, i32), RegionInfo>,
+}
+
+impl World {
+	/// Opens the database of a Bedrock world
+	pub fn open(input: Arc<dyn InputSource>, dir: &Path) -> Result<Self> {
+		let db = Database::open(input, dir)?;
+
+		let mut regions: BTreeMap<_, RegionInfo> = BTreeMap::new();
+		for key in db.keys() {
+			let Some((chunk, _)) = ChunkKey::parse(key) else {
+				continue;
+			};
+			let region = regions
+				.entry((chunk.dimension, chunk.x >> 5, chunk.z >> 5))
+				.or_default();
+			if region.chunks.last() != Some(&chunk) {
+				region.chunks.push(chunk);
+			}
+			region.modified = region.modified.max(db.modified(key));
+		}
+		for region in regions.values_mut() {
+			region.chunks.sort_unstable();
+			region.chunks.dedup();
+		}
+
+		Ok(World { db, regions })
+	}
+
+	/// Returns the coordinates of all regions of a dimension
+	pub fn regions(&self, dimension: i32) -> impl Iterator<Item = (i32, i32)> + '_ {
+		self.regions
+			.range((dimension, i32::MIN, i32::MIN)..=(dimension, i32::MAX, i32::MAX))
+			.map(|(&(_, x, z), _)| (x, z))
+	}
+
+	/// Returns the most recent modification time of the chunks of a region
+	pub fn region_modified(&self, dimension: i32, x: i32, z: i32) -> Option<SystemTime> {
+		self.regions.get(&(dimension, x, z))?.modified
+	}
+
+	/// Decodes the data of a single chunk
+	fn read_chunk(&self, chunk: ChunkKey) -> Result<ChunkData> {
+		let prefix = chunk.prefix();
+		let keys: Vec<_> = self
+			.db
+			.keys_with_prefix(&prefix)
+			.filter(
+				|key| matches!(ChunkKey::parse(key), Some((key_chunk, _)) if key_chunk == chunk),
+			)
+			.collect();
+
+		let mut sub_chunks = BTreeMap::new();
+		let mut biomes_3d = None;
+		let mut biomes_2d = None;
+
+		for (key, value) in self.db.get_many(&keys)? {
+			match key[prefix.len()] {
+				TAG_SUB_CHUNK => {
+					let y = key[prefix.len() + 1] as i8;
+					let sub_chunk = SubChunk::decode(&value)
+						.with_context(|| format!("Failed to decode sub-chunk at Y={}", y))?;
+					sub_chunks.insert(SectionY(y.into()), sub_chunk);
+				}
+				TAG_DATA_3D => biomes_3d = Some(BiomeData::decode_3d(&value)?),
+				TAG_DATA_2D => biomes_2d = Some(BiomeData::decode_2d(&value)?),
+				_ => {}
+			}
+		}
+
+		Ok(ChunkData {
+			sub_chunks,
+			biomes: biomes_3d.or(biomes_2d),
+			min_section: if chunk.dimension == 0 { -4 } else { 0 },
+		})
+	}
+
+	/// Calls a function for each chunk of a region
+	pub fn foreach_chunk<F>(&self, dimension: i32, x: i32, z: i32, mut f: F) -> Result<()>
+	where
+		F: FnMut(ChunkCoords, ChunkData) -> Result<()>,
+	{
+		let Some(region) = self.regions.get(&(dimension, x, z)) else {
+			return Ok(());
+		};
+
+		for &chunk in &region.chunks {
+			let coords = ChunkCoords {
+				x: ChunkX::new(chunk.x & 31),
+				z: ChunkZ::new(chunk.z & 31),
+			};
+			let data = self
+				.read_chunk(chunk)
+				.with_context(|| format!("Failed to read chunk {:?}", coords))?;
+			f(coords, data)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Bedrock block IDs differing from the Java Edition IDs
+const BLOCK_RENAMES: &[(&str, &str)] = &[
+	("brick_block", "bricks"),
+	("deadbush", "dead_bush"),
+	("dirt_with_roots", "rooted_dirt"),
+	("end_bricks", "end_stone_bricks"),
+	("fence_gate", "oak_fence_gate"),
+	("flowing_lava", "lava"),
+	("flowing_water", "water"),
+	("frog_spawn", "frogspawn"),
+	("golden_rail", "powered_rail"),
+	("grass", "grass_block"),
+	("grass_path", "dirt_path"),
+	("hardened_clay", "terracotta"),
+	("lit_pumpkin", "jack_o_lantern"),
+	("lit_redstone_lamp", "redstone_lamp"),
+	("lit_redstone_ore", "redstone_ore"),
+	("lit_deepslate_redstone_ore", "deepslate_redstone_ore"),
+	("azalea_leaves_flowered", "flowering_azalea_leaves"),
+	("magma", "magma_block"),
+	("melon_block", "melon"),
+	("mob_spawner", "spawner"),
+	("nether_brick", "nether_bricks"),
+	("noteblock", "note_block"),
+	("portal", "nether_portal"),
+	("quartz_ore", "nether_quartz_ore"),
+	("red_nether_brick", "red_nether_bricks"),
+	("reeds", "sugar_cane"),
+	("seaLantern", "sea_lantern"),
+	("slime", "slime_block"),
+	("snow", "snow_block"),
+	("snow_layer", "snow"),
+	("standing_sign", "oak_sign"),
+	("stonebrick", "stone_bricks"),
+	("trapdoor", "oak_trapdoor"),
+	("trip_wire", "tripwire"),
+	("unlit_redstone_torch", "redstone_torch"),
+	("wall_sign", "oak_wall_sign"),
+	("waterlily", "lily_pad"),
+	("web", "cobweb"),
+	("wooden_button", "oak_button"),
+	("wooden_door", "oak_door"),
+	("wooden_pressure_plate", "oak_pressure_plate"),
+	("yellow_flower", "dandelion"),
+];
+
+/// Derives the Java Edition block ID for a Bedrock palette entry
+///
+/// Older Bedrock versions distinguish many block types by state properties
+/// rather than by ID; the most common of these are handled as well.
+fn java_block_id(entry: &PaletteEntry) -> String {
+	let name = entry.name.strip_prefix("minecraft:").unwrap_or(&entry.name);
+	let state = |key: &str| match entry.states.get(key) {
+		Some(fastnbt::Value::String(value)) => Some(value.as_str()),
+		_ => None,
+	};
+	let color = || match state("color") {
+		Some("silver") => "light_gray",
+		Some(color) => color,
+		None => "white",
+	};
+
+	let id = match name {
+		"wool" | "carpet" | "concrete" | "stained_glass" | "stained_glass_pane" | "shulker_box" => {
+			format!("{}_{}", color(), name)
+		}
+		"concretePowder" | "concrete_powder" => format!("{}_concrete_powder", color()),
+		"stained_hardened_clay" => format!("{}_terracotta", color()),
+		"planks" | "fence" | "wooden_slab" => {
+			let suffix = name.strip_prefix("wooden_").unwrap_or(name);
+			format!("{}_{}", state("wood_type").unwrap_or("oak"), suffix)
+		}
+		"wood" => format!("{}_wood", state("wood_type").unwrap_or("oak")),
+		"log" => format!("{}_log", state("old_log_type").unwrap_or("oak")),
+		"log2" => format!("{}_log", state("new_log_type").unwrap_or("acacia")),
+		"leaves" => format!("{}_leaves", state("old_leaf_type").unwrap_or("oak")),
+		"leaves2" => format!("{}_leaves", state("new_leaf_type").unwrap_or("acacia")),
+		"sapling" => format!("{}_sapling", state("sapling_type").unwrap_or("oak")),
+		"stone" => match state("stone_type") {
+			Some(kind @ ("granite" | "diorite" | "andesite")) => kind.to_string(),
+			Some(kind) if kind.ends_with("_smooth") => {
+				format!("polished_{}", kind.strip_suffix("_smooth").unwrap())
+			}
+			_ => "stone".to_string(),
+		},
+		"sand" if state("sand_type") == Some("red") => "red_sand".to_string(),
+		"dirt" if state("dirt_type") == Some("coarse") => "coarse_dirt".to_string(),
+		"tallgrass" if state("tall_grass_type") == Some("fern") => "fern".to_string(),
+		"tallgrass" => "grass".to_string(),
+		"red_flower" => match state("flower_type") {
+			Some("orchid") => "blue_orchid",
+			Some("allium") => "allium",
+			Some("houstonia") => "azure_bluet",
+			Some("tulip_red") => "red_tulip",
+			Some("tulip_orange") => "orange_tulip",
+			Some("tulip_white") => "white_tulip",
+			Some("tulip_pink") => "pink_tulip",
+			Some("oxeye") => "oxeye_daisy",
+			Some("cornflower") => "cornflower",
+			Some("lily_of_the_valley") => "lily_of_the_valley",
+			_ => "poppy",
+		}
+		.to_string(),
+		"double_plant" => match state("double_plant_type") {
+			Some("sunflower") => "sunflower",
+			Some("syringa") => "lilac",
+			Some("fern") => "large_fern",
+			Some("rose") => "rose_bush",
+			Some("paeonia") => "peony",
+			_ => "tall_grass",
+		}
+		.to_string(),
+		"prismarine" => match state("prismarine_block_type") {
+			Some("dark") => "dark_prismarine",
+			Some("bricks") => "prismarine_bricks",
+			_ => "prismarine",
+		}
+		.to_string(),
+		"coral_block" => {
+			let kind = match state("coral_color") {
+				Some("pink") => "brain",
+				Some("purple") => "bubble",
+				Some("red") => "fire",
+				Some("yellow") => "horn",
+				_ => "tube",
+			};
+			match entry.states.get("dead_bit") {
+				Some(fastnbt::Value::Byte(1)) => format!("dead_{}_coral_block", kind),
+				_ => format!("{}_coral_block", kind),
+			}
+		}
+		_ => BLOCK_RENAMES
+			.iter()
+			.find(|(bedrock, _)| *bedrock == name)
+			.map_or(name, |(_, java)| java)
+			.to_string(),
+	};
+
+	format!("minecraft:{}", id)
+}
+
+/// Bedrock biome IDs that differ from the numeric biome IDs of Java Edition
+const BIOME_IDS: &[(i32, &str)] = &[
+	(40, "warm_ocean"),
+	(41, "deep_warm_ocean"),
+	(42, "lukewarm_ocean"),
+	(43, "deep_lukewarm_ocean"),
+	(44, "cold_ocean"),
+	(45, "deep_cold_ocean"),
+	(46, "frozen_ocean"),
+	(47, "deep_frozen_ocean"),
+	(48, "bamboo_jungle"),
+	(49, "bamboo_jungle"),
+	(178, "soul_sand_valley"),
+	(179, "crimson_forest"),
+	(180, "warped_forest"),
+	(181, "basalt_deltas"),
+	(182, "jagged_peaks"),
+	(183, "frozen_peaks"),
+	(184, "snowy_slopes"),
+	(185, "grove"),
+	(186, "meadow"),
+	(187, "lush_caves"),
+	(188, "dripstone_caves"),
+	(189, "stony_peaks"),
+	(190, "deep_dark"),
+	(191, "mangrove_swamp"),
+	(192, "cherry_grove"),
+];
+
+/// Resolves a Bedrock numeric biome ID
+fn biome_type(biome_types: &BiomeTypes, id: i32) -> Option<&Biome> {
+	if let Some((_, name)) = BIOME_IDS.iter().find(|&&(bedrock, _)| bedrock == id) {
+		return biome_types.get(&format!("minecraft:{}", name));
+	}
+	match id {
+		0..=39 | 127..=167 => biome_types.get_legacy(id as u8),
+		_ => None,
+	}
+}
+
+/// Sub-chunk block data with resolved block types
+#[derive(Debug)]
+pub struct SectionBedrock<'a> {
+	/// Packed palette indices
+	data: &'a PalettedData,
+	/// Block types indexed by *data*
+	palette: Vec<Option<BlockType>>,
+	/// Java Edition block IDs corresponding to *palette*
+	ids: Vec<String>,
+	/// Indices of the palette entries with unknown block IDs
+	unknown: Vec<usize>,
+}
+
+impl<'a> SectionBedrock<'a> {
+	/// Resolves the block types of a paletted sub-chunk
+	pub fn new(
+		data: &'a PalettedData,
+		palette: &'a [PaletteEntry],
+		block_types: &BlockTypes,
+	) -> Self {
+		let ids: Vec<_> = palette.iter().map(java_block_id).collect();
+		let palette = palette
+			.iter()
+			.zip(&ids)
+			.map(|(entry, id)| {
+				let block_type = block_types.resolve(id);
+				if block_type.is_none() {
+					eprintln!("Unknown block type: {}", entry.name);
+				}
+				block_type
+			})
+			.collect();
+		let unknown = ids
+			.iter()
+			.enumerate()
+			.filter(|(_, id)| block_types.get(id).is_none())
+			.map(|(index, _)| index)
+			.collect();
+
+		SectionBedrock {
+			data,
+			palette,
+			ids,
+			unknown,
+		}
+	}
+}
+
+impl<'a> super::section::Section for SectionBedrock<'a> {
+	fn block_at(&self, coords: SectionBlockCoords) -> Result<Option<BlockType>> {
+		let offset = xzy_offset(coords);
+		Ok(*self
+			.palette
+			.get(self.data.index(offset))
+			.context("Palette index out of bounds")?)
+	}
+
+	fn block_id_at(&self, coords: SectionBlockCoords) -> Result<&str> {
+		let offset = xzy_offset(coords);
+		Ok(self
+			.ids
+			.get(self.data.index(offset))
+			.context("Palette index out of bounds")?)
+	}
+
+	fn count_blocks(&self, filter: &BlockFilter, counts: &mut LayerBlockArray<u16>) -> Result<()> {
+		let mask: Vec<_> = self.ids.iter().map(|id| filter.matches(id)).collect();
+		count_palette_blocks(&mask, counts, |coords| self.data.index(xzy_offset(coords)))
+	}
+
+	fn filter_blocks(&self, filter: &BlockFilter) -> Result<Vec<bool>> {
+		let mask: Vec<_> = self.ids.iter().map(|id| filter.matches(id)).collect();
+		filter_palette_blocks(&mask, |coords| self.data.index(xzy_offset(coords)))
+	}
+	fn unknown_block_ids(&self) -> Vec<&str> {
+		self.unknown
+			.iter()
+			.map(|&index| self.ids[index].as_str())
+			.collect()
+	}
+}
+
+/// Computes the offset of a block in XZY-ordered sub-chunk data
+fn xzy_offset(coords: SectionBlockCoords) -> usize {
+	let x = coords.xz.x.0 as usize;
+	let y = coords.y.0 as usize;
+	let z = coords.xz.z.0 as usize;
+	(x << 8) | (z << 4) | y
+}
+
+/// Unknown biome IDs that have already been warned about
+///
+/// Each chunk resolves its own biome palettes, so without this, the same
+/// warning would be printed for every chunk using an unknown biome.
+static UNKNOWN_BIOMES: Mutex<BTreeSet<i32>> = Mutex::new(BTreeSet::new());
+
+/// Chunk biome data with resolved biome types
+#[derive(Debug)]
+pub struct BiomesBedrock<'a> {
+	/// Biome data of the chunk
+	data: Option<&'a BiomeData>,
+	/// Resolved palettes of the 3D biome data, or the single palette used
+	/// for 2D biome data
+	palettes: Vec<Vec<Option<&'a Biome>>>,
+	/// Section Y coordinate of the first 3D biome storage
+	min_section: i32,
+}
+
+impl<'a> BiomesBedrock<'a> {
+	/// Resolves the biome types of a chunk's biome data
+	pub fn new(chunk: &'a ChunkData, biome_types: &'a BiomeTypes) -> Self {
+		let resolve = |id: i32| {
+			let biome = biome_type(biome_types, id);
+			if biome.is_none() && UNKNOWN_BIOMES.lock().unwrap().insert(id) {
+				eprintln!("Unknown biome ID: {}", id);
+			}
+			biome
+		};
+
+		let palettes = match &chunk.biomes {
+			Some(BiomeData::Data3D(storages)) => storages
+				.iter()
+				.map(|(_, palette)| palette.iter().map(|&id| resolve(id)).collect())
+				.collect(),
+			Some(BiomeData::Data2D(biomes)) => {
+				// Only the IDs used by the chunk are resolved
+				let mut used = [false; 256];
+				for &id in biomes {
+					used[usize::from(id)] = true;
+				}
+				vec![(0..=255)
+					.map(|id| if used[id as usize] { resolve(id) } else { None })
+					.collect()]
+			}
+			None => Vec::new(),
+		};
+
+		BiomesBedrock {
+			data: chunk.biomes.as_ref(),
+			palettes,
+			min_section: chunk.min_section,
+		}
+	}
+}
+
+impl<'a> super::section::Biomes for BiomesBedrock<'a> {
+	fn biome_at(&self, section: SectionY, coords: SectionBlockCoords) -> Result<Option<&Biome>> {
+		let (palette, index) = match self.data {
+			Some(BiomeData::Data3D(storages)) => {
+				let Ok(storage) = usize::try_from(section.0 - self.min_section) else {
+					return Ok(None);
+				};
+				let Some((data, _)) = storages.get(storage) else {
+					return Ok(None);
+				};
+				(&self.palettes[storage], data.index(xzy_offset(coords)))
+			}
+			Some(BiomeData::Data2D(biomes)) => {
+				(&self.palettes[0], biomes[coords.xz.offset()].into())
+			}
+			None => return Ok(None),
+		};
+
+		Ok(*palette.get(index).context("Palette index out of bounds")?)
+	}
+}
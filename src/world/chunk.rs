@@ -10,7 +10,11 @@ use std::{
 
 use anyhow::{bail, Context, Result};
 
-use super::{de, section::*};
+use super::{
+	bedrock::{self, BiomesBedrock, SectionBedrock},
+	de,
+	section::*,
+};
 use crate::{
 	resource::{BiomeTypes, BlockTypes},
 	types::*,
@@ -46,10 +50,46 @@ pub enum Chunk<'a> {
 		/// Biome data
 		biomes: BiomesV0<'a>,
 	},
+	/// Minecraft Bedrock Edition chunk
+	Bedrock {
+		/// Section data
+		section_map: BTreeMap<SectionY, Box<dyn Section + 'a>>,
+		/// Biome data
+		biomes: BiomesBedrock<'a>,
+	},
 	/// Unpopulated chunk without any block data
 	Empty,
 }
 
+/// Chunk data schema, determined by the data version of a chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Schema {
+	/// Pre-1.13 numeric block IDs
+	V0,
+	/// 1.13+ block palettes inside the `Level` compound
+	V1_13,
+	/// 1.18+ sections at the top level of the chunk
+	V1_18,
+}
+
+impl Schema {
+	/// First data version using block palettes (17w47a)
+	const V1_13_DATA_VERSION: u32 = 1451;
+	/// First data version without the `Level` compound (21w43a)
+	const V1_18_DATA_VERSION: u32 = 2844;
+
+	/// Determines the schema of a chunk from its data version
+	///
+	/// Chunks saved before Minecraft 1.9 don't have a data version.
+	fn new(data_version: Option<u32>) -> Self {
+		match data_version.unwrap_or_default() {
+			v if v >= Self::V1_18_DATA_VERSION => Schema::V1_18,
+			v if v >= Self::V1_13_DATA_VERSION => Schema::V1_13,
+			_ => Schema::V0,
+		}
+	}
+}
+
 /// Inner data structure of [SectionIter]
 #[derive(Debug, Clone)]
 enum SectionIterInner<'a> {
@@ -72,6 +112,13 @@ enum SectionIterInner<'a> {
 		/// Chunk biome data
 		biomes: &'a BiomesV0<'a>,
 	},
+	/// Iterator over sections of [Chunk::Bedrock]
+	Bedrock {
+		/// Inner iterator into section map
+		iter: btree_map::Iter<'a, SectionY, Box<dyn Section + 'a>>,
+		/// Chunk biome data
+		biomes: &'a BiomesBedrock<'a>,
+	},
 	/// Empty iterator over an unpopulated chunk ([Chunk::Empty])
 	Empty,
 }
@@ -86,26 +133,66 @@ pub struct SectionIter<'a> {
 impl<'a> Chunk<'a> {
 	/// Creates a new [Chunk] from a deserialized [de::Chunk]
 	pub fn new(
-		data: &'a de::Chunk,
+		data: &'a de::Chunk<'a>,
 		block_types: &'a BlockTypes,
 		biome_types: &'a BiomeTypes,
 	) -> Result<Self> {
 		let data_version = data.data_version.unwrap_or_default();
+		let schema = Schema::new(data.data_version);
 
-		match &data.chunk {
-			de::ChunkVariants::V1_18 { sections } => {
+		match (schema, &data.chunk) {
+			(Schema::V1_18, de::ChunkVariants::V1_18 { sections }) => {
 				Self::new_v1_18(data_version, sections, block_types, biome_types)
 			}
-			de::ChunkVariants::V0 { level } => {
-				Self::new_v0(data_version, level, block_types, biome_types)
+			(Schema::V0 | Schema::V1_13, de::ChunkVariants::V0 { level }) => {
+				Self::new_v0(schema, data_version, level, block_types, biome_types)
 			}
+			(Schema::V1_18, de::ChunkVariants::V0 { .. }) => bail!(
+				"Unsupported chunk data version {}: expected 1.18+ sections, found Level compound",
+				data_version
+			),
+			(_, de::ChunkVariants::V1_18 { .. }) => bail!(
+				"Unsupported chunk data version {}: expected Level compound, found 1.18+ sections",
+				data_version
+			),
+		}
+	}
+
+	/// Creates a new [Chunk] from decoded Bedrock Edition chunk data
+	pub fn new_bedrock(
+		data: &'a bedrock::ChunkData,
+		block_types: &'a BlockTypes,
+		biome_types: &'a BiomeTypes,
+	) -> Result<Self> {
+		let mut section_map = BTreeMap::new();
+
+		for (&y, sub_chunk) in &data.sub_chunks {
+			let section: Box<dyn Section + 'a> = match sub_chunk {
+				bedrock::SubChunk::Paletted { data, palette } => {
+					Box::new(SectionBedrock::new(data, palette, block_types))
+				}
+				bedrock::SubChunk::Legacy { blocks, data } => Box::new(
+					SectionV0::new(blocks, data, block_types)
+						.with_context(|| format!("Failed to load section at Y={}", y.0))?,
+				),
+			};
+			section_map.insert(y, section);
 		}
+
+		if section_map.is_empty() {
+			return Ok(Chunk::Empty);
+		}
+
+		Ok(Chunk::Bedrock {
+			section_map,
+			biomes: BiomesBedrock::new(data, biome_types),
+		})
 	}
 
 	/// [Chunk::new] implementation for Minecraft v1.18+ chunks
 	fn new_v1_18(
 		data_version: u32,
-		sections: &'a Vec<de::SectionV1_18>,
+		sections: &'a Vec<de::SectionV1_18<'a>>,
 		block_types: &'a BlockTypes,
 		biome_types: &'a BiomeTypes,
 	) -> Result<Self> {
@@ -123,20 +210,21 @@ impl<'a> Chunk<'a> {
 						(
 							SectionV1_13::new(
 								data_version,
-								block_states.data.as_deref(),
+								block_states.data.as_ref(),
 								&block_states.palette,
 								block_types,
 							)
 							.with_context(|| {
 								format!("Failed to load section at Y={}", section.y)
 							})?,
-							BiomesV1_18::new(biomes.data.as_deref(), &biomes.palette, biome_types)
+							BiomesV1_18::new(biomes.data.as_ref(), &biomes.palette, biome_types)
 								.with_context(|| {
 									format!("Failed to load section biomes at Y={}", section.y)
 								})?,
-							BlockLight::new(block_light.as_deref()).with_context(|| {
-								format!("Failed to load section block light at Y={}", section.y)
-							})?,
+							BlockLight::new(block_light.as_ref().map(de::ByteArray::as_slice))
+								.with_context(|| {
+									format!("Failed to load section block light at Y={}", section.y)
+								})?,
 						),
 					);
 				}
@@ -149,8 +237,9 @@ impl<'a> Chunk<'a> {
 
 	/// [Chunk::new] implementation for all pre-1.18 chunk variants
 	fn new_v0(
+		schema: Schema,
 		data_version: u32,
-		level: &'a de::LevelV0,
+		level: &'a de::LevelV0<'a>,
 		block_types: &'a BlockTypes,
 		biome_types: &'a BiomeTypes,
 	) -> Result<Self> {
@@ -159,16 +248,24 @@ impl<'a> Chunk<'a> {
 
 		for section in &level.sections {
 			let block_light =
-				BlockLight::new(section.block_light.as_deref()).with_context(|| {
-					format!("Failed to load section block light at Y={}", section.y)
-				})?;
+				BlockLight::new(section.block_light.as_ref().map(de::ByteArray::as_slice))
+					.with_context(|| {
+						format!("Failed to load section block light at Y={}", section.y)
+					})?;
 			match &section.section {
 				de::SectionV0Variants::V1_13 {
 					block_states,
 					palette,
 				} => {
+					if schema != Schema::V1_13 {
+						bail!(
+							"Unsupported chunk data version {}: unexpected 1.13+ section at Y={}",
+							data_version,
+							section.y,
+						);
+					}
 					section_map_v1_13.insert(
-						SectionY(section.y.into()),
+						SectionY(section.y),
 						(
 							SectionV1_13::new(
 								data_version,
@@ -184,12 +281,20 @@ impl<'a> Chunk<'a> {
 					);
 				}
 				de::SectionV0Variants::V0 { blocks, data } => {
+					if schema != Schema::V0 {
+						bail!(
+							"Unsupported chunk data version {}: unexpected pre-1.13 section at Y={}",
+							data_version,
+							section.y,
+						);
+					}
 					section_map_v0.insert(
-						SectionY(section.y.into()),
+						SectionY(section.y),
 						(
-							SectionV0::new(blocks, data, block_types).with_context(|| {
-								format!("Failed to load section at Y={}", section.y)
-							})?,
+							SectionV0::new(blocks.as_slice(), data.as_slice(), block_types)
+								.with_context(|| {
+									format!("Failed to load section at Y={}", section.y)
+								})?,
 							block_light,
 						),
 					);
@@ -200,22 +305,20 @@ impl<'a> Chunk<'a> {
 
 		let biomes = BiomesV0::new(level.biomes.as_ref(), biome_types);
 
-		Ok(
-			match (section_map_v1_13.is_empty(), section_map_v0.is_empty()) {
-				(true, true) => Chunk::Empty,
-				(false, true) => Chunk::V1_13 {
-					section_map: section_map_v1_13,
-					biomes: biomes?,
-				},
-				(true, false) => Chunk::V0 {
-					section_map: section_map_v0,
-					biomes: biomes?,
-				},
-				(false, false) => {
-					bail!("Mixed section versions");
-				}
+		if section_map_v1_13.is_empty() && section_map_v0.is_empty() {
+			return Ok(Chunk::Empty);
+		}
+
+		Ok(match schema {
+			Schema::V1_13 => Chunk::V1_13 {
+				section_map: section_map_v1_13,
+				biomes: biomes?,
 			},
-		)
+			_ => Chunk::V0 {
+				section_map: section_map_v0,
+				biomes: biomes?,
+			},
+		})
 	}
 
 	/// Returns true if the chunk does not contain any sections
@@ -224,12 +327,13 @@ impl<'a> Chunk<'a> {
 			Chunk::V1_18 { section_map } => section_map.is_empty(),
 			Chunk::V1_13 { section_map, .. } => section_map.is_empty(),
 			Chunk::V0 { section_map, .. } => section_map.is_empty(),
+			Chunk::Bedrock { section_map, .. } => section_map.is_empty(),
 			Chunk::Empty => true,
 		}
 	}
 
 	/// Returns an interator over the chunk's sections and their Y coordinates
-	pub fn sections(&self) -> SectionIter {
+	pub fn sections(&self) -> SectionIter<'_> {
 		use SectionIterInner::*;
 		SectionIter {
 			inner: match self {
@@ -250,6 +354,13 @@ impl<'a> Chunk<'a> {
 					iter: section_map.iter(),
 					biomes,
 				},
+				Chunk::Bedrock {
+					section_map,
+					biomes,
+				} => Bedrock {
+					iter: section_map.iter(),
+					biomes,
+				},
 				Chunk::Empty => Empty,
 			},
 		}
@@ -314,6 +425,14 @@ impl<'a> SectionIter<'a> {
 					block_light: *block_light,
 				},
 			)),
+			SectionIterInner::Bedrock { iter, biomes } => {
+				f(&mut iter.map(|(&y, section)| SectionIterItem {
+					y,
+					section: section.as_ref(),
+					biomes: *biomes,
+					block_light: BlockLight::default(),
+				}))
+			}
 			SectionIterInner::Empty => f(&mut iter::empty()),
 		}
 	}
@@ -331,6 +450,7 @@ impl<'a> Iterator for SectionIter<'a> {
 			SectionIterInner::V1_18 { iter } => iter.size_hint(),
 			SectionIterInner::V1_13 { iter, .. } => iter.size_hint(),
 			SectionIterInner::V0 { iter, .. } => iter.size_hint(),
+			SectionIterInner::Bedrock { iter, .. } => iter.size_hint(),
 			SectionIterInner::Empty => (0, Some(0)),
 		}
 	}
@@ -352,9 +472,76 @@ impl<'a> ExactSizeIterator for SectionIter<'a> {
 			SectionIterInner::V1_18 { iter } => iter.len(),
 			SectionIterInner::V1_13 { iter, .. } => iter.len(),
 			SectionIterInner::V0 { iter, .. } => iter.len(),
+			SectionIterInner::Bedrock { iter, .. } => iter.len(),
 			SectionIterInner::Empty => 0,
 		}
 	}
 }
 
 impl<'a> FusedIterator for SectionIter<'a> {}
+
+#[cfg(test)]
+mod test {
+	use std::collections::HashMap;
+
+	use fastnbt::{ByteArray, Value};
+
+	use super::*;
+
+	/// Builds a compound value from a list of fields
+	fn compound<const N: usize>(fields: [(&str, Value); N]) -> Value {
+		Value::Compound(
+			fields
+				.into_iter()
+				.map(|(key, value)| (key.to_string(), value))
+				.collect::<HashMap<_, _>>(),
+		)
+	}
+
+	#[test]
+	fn test_pre_1_9_chunk() {
+		// Chunks saved by Minecraft 1.2 to 1.8 have no data version
+		let mut blocks = vec![0; 4096];
+		blocks[0] = 1; // Stone
+		blocks[1] = 35; // Wool, with data value 14 (red)
+		let mut data = vec![0; 2048];
+		data[0] = 0xe0u8 as i8;
+
+		let value = compound([(
+			"Level",
+			compound([
+				(
+					"Sections",
+					Value::List(vec![compound([
+						("Y", Value::Byte(0)),
+						("Blocks", Value::ByteArray(ByteArray::new(blocks))),
+						("Data", Value::ByteArray(ByteArray::new(data))),
+						(
+							"BlockLight",
+							Value::ByteArray(ByteArray::new(vec![0; 2048])),
+						),
+					])]),
+				),
+				("Biomes", Value::ByteArray(ByteArray::new(vec![1; 256]))),
+			]),
+		)]);
+		let bytes = crate::nbt::data::to_bytes(&value).unwrap();
+		let data: de::Chunk = crate::nbt::data::from_bytes(&bytes).unwrap();
+
+		let block_types = BlockTypes::default();
+		let biome_types = BiomeTypes::default();
+		let chunk = Chunk::new(&data, &block_types, &biome_types).unwrap();
+		assert!(matches!(chunk, Chunk::V0 { .. }));
+
+		let section = chunk.sections().next().unwrap().section;
+		let coords = |x| SectionBlockCoords {
+			xz: LayerBlockCoords {
+				x: BlockX::new(x),
+				z: BlockZ::new(0),
+			},
+			y: BlockY::new(0),
+		};
+		assert_eq!(section.block_id_at(coords(0)).unwrap(), "stone");
+		assert_eq!(section.block_id_at(coords(1)).unwrap(), "red_wool");
+	}
+}
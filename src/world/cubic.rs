@@ -0,0 +1,337 @@
+//! Reading of worlds saved by the Cubic Chunks mod
+//!
+//! The Cubic Chunks mod for Minecraft 1.12 removes the height limit by storing
+//! 16x16x16 cubes in `region3d/<x>.<y>.<z>.3dr` files, each containing
+//! 16x16x16 cubes, and per-column data like biomes in `region2d/<x>.<z>.2dr`
+//! files covering 32x32 columns. Both use the sector-based file format of the
+//! RegionLib library.
+//!
+//! The cubes of each 32x32 column area are combined into pre-1.13 Anvil
+//! chunks, so they can be processed in the same way as regular worlds.
+
+use std::{
+	collections::{BTreeMap, HashMap},
+	io::Read,
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::SystemTime,
+};
+
+use anyhow::{bail, Context, Result};
+use fastnbt::ByteArray;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use super::de;
+use crate::{io::input::InputSource, types::*};
+
+/// Size of the sectors of RegionLib files
+const SECTOR_SIZE: usize = 512;
+
+/// Number of entries of `.3dr` files
+const CUBES_PER_REGION: usize = 16 * 16 * 16;
+
+/// Number of cube regions along the X and Z axes of a tile
+const CUBE_REGIONS_PER_TILE: i32 = (CHUNKS_PER_REGION / 16) as i32;
+
+/// Legacy biome ID used for columns without biome data (plains)
+const BIOME: i8 = 1;
+
+/// Section element found in the `Sections` list of a [CubeLevel]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CubeSection {
+	/// Block type data
+	blocks: ByteArray,
+	/// Block damage / subtype data
+	data: ByteArray,
+	/// Block light data
+	block_light: Option<ByteArray>,
+}
+
+/// `Level` compound element of a [Cube]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CubeLevel {
+	/// Cube X coordinate
+	#[serde(rename = "x")]
+	x: i32,
+	/// Cube Y coordinate
+	#[serde(rename = "y")]
+	y: i32,
+	/// Cube Z coordinate
+	#[serde(rename = "z")]
+	z: i32,
+	/// Block data of the cube, consisting of a single section
+	#[serde(default)]
+	sections: Vec<CubeSection>,
+}
+
+/// Toplevel compound element of a cube stored in a `.3dr` file
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Cube {
+	/// `Level` field of the cube
+	level: CubeLevel,
+}
+
+/// `Level` compound element of a [Column]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ColumnLevel {
+	/// Column X coordinate
+	#[serde(rename = "x")]
+	x: i32,
+	/// Column Z coordinate
+	#[serde(rename = "z")]
+	z: i32,
+	/// Biome data
+	biomes: Option<ByteArray>,
+}
+
+/// Toplevel compound element of a column stored in a `.2dr` file
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Column {
+	/// `Level` field of the column
+	level: ColumnLevel,
+}
+
+/// Decompresses and deserializes a RegionLib entry
+fn decode_entry<T>(data: &[u8]) -> Result<T>
+where
+	T: DeserializeOwned,
+{
+	let mut decode_buffer = Vec::new();
+	match data {
+		[0x1f, 0x8b, ..] => GzDecoder::new(data).read_to_end(&mut decode_buffer),
+		[0x78, ..] => ZlibDecoder::new(data).read_to_end(&mut decode_buffer),
+		_ => {
+			decode_buffer.extend_from_slice(data);
+			Ok(data.len())
+		}
+	}
+	.context("Failed to decompress entry")?;
+
+	Ok(crate::nbt::data::from_bytes(&decode_buffer)?)
+}
+
+/// Calls a function for each entry of a RegionLib file
+///
+/// The header of the file consists of one 32-bit value for each possible
+/// entry, with the offset of the entry in sectors in the upper 24 bits and
+/// its size in sectors in the lower 8 bits. Each entry starts with its
+/// length in bytes.
+fn foreach_entry<F>(data: &[u8], entries: usize, mut f: F) -> Result<()>
+where
+	F: FnMut(&[u8]) -> Result<()>,
+{
+	let header = data
+		.get(..4 * entries)
+		.context("Failed to read region header")?;
+
+	for entry in header.chunks_exact(4) {
+		let offset_len = u32::from_be_bytes(entry.try_into().unwrap());
+		let offset = (offset_len >> 8) as usize * SECTOR_SIZE;
+		let sectors = (offset_len & 0xff) as usize;
+		if sectors == 0 {
+			continue;
+		}
+
+		let entry = data
+			.get(offset..offset + sectors * SECTOR_SIZE)
+			.or_else(|| data.get(offset..))
+			.context("Invalid entry offset")?;
+		let len = entry
+			.get(..4)
+			.map(|len| u32::from_be_bytes(len.try_into().unwrap()) as usize)
+			.context("Invalid entry length")?;
+		let Some(entry_data) = entry.get(4..4 + len) else {
+			bail!("Invalid entry length");
+		};
+		f(entry_data)?;
+	}
+
+	Ok(())
+}
+
+/// Cubic Chunks world
+///
+/// Indexes the cube region files of a dimension by the tile they belong to.
+pub struct World {
+	/// Source of the save data
+	input: Arc<dyn InputSource>,
+	/// Dimension directory containing the `region2d` and `region3d` directories
+	dir: PathBuf,
+	/// Names of the `.3dr` files of each tile
+	regions: BTreeMap<(i32, i32), Vec<String>>,
+}
+
+impl std::fmt::Debug for World {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("World")
+			.field("dir", &self.dir)
+			.field("regions", &self.regions)
+			.finish_non_exhaustive()
+	}
+}
+
+impl World {
+	/// Indexes the cube regions of a dimension directory
+	pub fn open(input: Arc<dyn InputSource>, dir: &Path) -> Result<Self> {
+		let mut regions: BTreeMap<_, Vec<_>> = BTreeMap::new();
+
+		for name in input.list_files(&dir.join("region3d"))? {
+			let Some(coords) = name.strip_suffix(".3dr") else {
+				continue;
+			};
+			let coords: Vec<i32> = match coords.split('.').map(str::parse).collect() {
+				Ok(coords) => coords,
+				Err(_) => continue,
+			};
+			let &[x, _, z] = coords.as_slice() else {
+				continue;
+			};
+			let tile = (
+				x.div_euclid(CUBE_REGIONS_PER_TILE),
+				z.div_euclid(CUBE_REGIONS_PER_TILE),
+			);
+			regions.entry(tile).or_default().push(name);
+		}
+
+		Ok(World {
+			input,
+			dir: dir.to_path_buf(),
+			regions,
+		})
+	}
+
+	/// Returns the coordinates of all tiles containing cubes
+	pub fn regions(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+		self.regions.keys().copied()
+	}
+
+	/// Returns the most recent modification time of the cube regions of a tile
+	pub fn region_modified(&self, x: i32, z: i32) -> Result<SystemTime> {
+		let names = self
+			.regions
+			.get(&(x, z))
+			.with_context(|| format!("Region ({}, {}) not found", x, z))?;
+
+		let mut paths: Vec<_> = names
+			.iter()
+			.map(|name| self.dir.join("region3d").join(name))
+			.collect();
+		paths.push(self.column_region_path(x, z));
+
+		paths
+			.iter()
+			.filter_map(|path| self.input.modified(path).ok())
+			.max()
+			.context("Failed to get modification time")
+	}
+
+	/// Returns the path of the `.2dr` file of a tile
+	fn column_region_path(&self, x: i32, z: i32) -> PathBuf {
+		self.dir.join("region2d").join(format!("{}.{}.2dr", x, z))
+	}
+
+	/// Reads a whole file of the input
+	fn read_file(&self, path: &Path) -> Result<Vec<u8>> {
+		let mut data = Vec::new();
+		self.input
+			.open(path)?
+			.read_to_end(&mut data)
+			.with_context(|| format!("Failed to read {}", path.display()))?;
+		Ok(data)
+	}
+
+	/// Reads the biome data of the columns of a tile
+	fn read_biomes(&self, x: i32, z: i32) -> Result<HashMap<(i32, i32), ByteArray>> {
+		let mut biomes = HashMap::new();
+
+		let path = self.column_region_path(x, z);
+		let Ok(data) = self.read_file(&path) else {
+			return Ok(biomes);
+		};
+		foreach_entry(&data, CHUNKS_PER_REGION * CHUNKS_PER_REGION, |entry| {
+			let Column { level } = decode_entry(entry)?;
+			if let Some(column_biomes) = level.biomes {
+				biomes.insert((level.x, level.z), column_biomes);
+			}
+			Ok(())
+		})
+		.with_context(|| format!("Failed to read {}", path.display()))?;
+
+		Ok(biomes)
+	}
+
+	/// Calls a function for each column of a tile
+	///
+	/// The cubes of each column are passed as a pre-1.13 Anvil [de::Chunk].
+	pub fn foreach_chunk<F>(&self, x: i32, z: i32, mut f: F) -> Result<()>
+	where
+		F: FnMut(ChunkCoords, de::Chunk<'_>) -> Result<()>,
+	{
+		let Some(names) = self.regions.get(&(x, z)) else {
+			return Ok(());
+		};
+
+		let mut columns: BTreeMap<(i32, i32), Vec<de::SectionV0<'static>>> = BTreeMap::new();
+
+		for name in names {
+			let path = self.dir.join("region3d").join(name);
+			let data = self.read_file(&path)?;
+			foreach_entry(&data, CUBES_PER_REGION, |entry| {
+				let Cube { level } = decode_entry(entry)?;
+				let Some(section) = level.sections.into_iter().next() else {
+					return Ok(());
+				};
+				if section.blocks.iter().all(|&block| block == 0) {
+					return Ok(());
+				}
+
+				columns
+					.entry((level.x, level.z))
+					.or_default()
+					.push(de::SectionV0 {
+						y: level.y,
+						block_light: section.block_light.map(|data| data.into_inner().into()),
+						section: de::SectionV0Variants::V0 {
+							blocks: section.blocks.into_inner().into(),
+							data: section.data.into_inner().into(),
+						},
+					});
+				Ok(())
+			})
+			.with_context(|| format!("Failed to read {}", path.display()))?;
+		}
+
+		let mut biomes = self.read_biomes(x, z)?;
+
+		for ((chunk_x, chunk_z), sections) in columns {
+			let chunk_coords = ChunkCoords {
+				x: ChunkX::new(chunk_x.rem_euclid(CHUNKS_PER_REGION as i32)),
+				z: ChunkZ::new(chunk_z.rem_euclid(CHUNKS_PER_REGION as i32)),
+			};
+			let column_biomes = biomes.remove(&(chunk_x, chunk_z)).unwrap_or_else(|| {
+				ByteArray::new(vec![BIOME; BLOCKS_PER_CHUNK * BLOCKS_PER_CHUNK])
+			});
+
+			let chunk = de::Chunk {
+				data_version: None,
+				chunk: de::ChunkVariants::V0 {
+					level: de::LevelV0 {
+						sections,
+						biomes: Some(de::BiomesV0::ByteArray(column_biomes)),
+						inhabited_time: None,
+					},
+				},
+				inhabited_time: None,
+			};
+			f(chunk_coords, chunk)?;
+		}
+
+		Ok(())
+	}
+}
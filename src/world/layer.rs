@@ -6,9 +6,13 @@ use anyhow::{Context, Result};
 use indexmap::IndexSet;
 use serde::{Deserialize, Serialize};
 
-use super::chunk::{Chunk, SectionIterItem};
+use super::{
+	chunk::{Chunk, SectionIterItem},
+	de::DimensionType,
+	section::BlockFilter,
+};
 use crate::{
-	resource::{Biome, BlockFlag, BlockType},
+	resource::{Biome, BiomeTypes, BlockFlag, BlockType},
 	types::*,
 };
 
@@ -40,20 +44,84 @@ pub type BlockArray = LayerBlockArray<Option<BlockType>>;
 /// Indices are stored incremented by 1 to allow using a [NonZeroU16].
 pub type BiomeArray = LayerBlockArray<Option<NonZeroU16>>;
 
+/// Array optionally storing a biome ID for each coordinate of a chunk
+///
+/// The IDs do not include the `minecraft:` namespace.
+pub type BiomeIdArray = LayerBlockArray<Option<&'static str>>;
+
+/// Array optionally storing a block ID index for each coordinate of a chunk
+///
+/// The entries refer to the block ID list of the [LayerData]. Indices are
+/// stored incremented by 1 to allow using a [NonZeroU16].
+pub type BlockIdArray = LayerBlockArray<Option<NonZeroU16>>;
+
 /// Array storing a block light value for each coordinate for a chunk
 pub type BlockLightArray = LayerBlockArray<u8>;
 
+/// Array storing for each coordinate of a chunk whether the top block is
+/// covered by skipped blocks
+///
+/// Columns are covered when opaque blocks above the top block have been
+/// skipped, either as part of the ceiling of the dimension or as hidden blocks.
+pub type CoveredArray = LayerBlockArray<bool>;
+
+/// Array storing the number of water blocks above the ground for each
+/// coordinate of a chunk
+pub type WaterDepthArray = LayerBlockArray<u16>;
+
 /// Array optionally storing a depth value for each coordinate for a chunk
 pub type DepthArray = LayerBlockArray<Option<BlockHeight>>;
 
+/// Range of block heights considered when searching a chunk
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeightLimits {
+	/// Height range of the dimension, if known
+	pub dimension: Option<DimensionType>,
+	/// Highest Y coordinate to consider, if limited
+	pub max_y: Option<i32>,
+	/// Skip the solid ceiling of roofed dimensions like the Nether
+	///
+	/// Only considered by [top_layer].
+	pub skip_ceiling: bool,
+}
+
+impl HeightLimits {
+	/// Returns true if a block height lies within the limits
+	pub fn contains(&self, height: BlockHeight) -> bool {
+		if let Some(DimensionType {
+			min_y, height: h, ..
+		}) = self.dimension
+		{
+			if height.0 < min_y || height.0 >= min_y.saturating_add(h) {
+				return false;
+			}
+		}
+		self.max_y.is_none_or(|max_y| height.0 <= max_y)
+	}
+}
+
 /// References to LayerData entries for a single coordinate pair
 struct LayerEntry<'a> {
 	/// The block type of the referenced entry
 	block: &'a mut Option<BlockType>,
+	/// The block ID index of the referenced entry
+	block_id: &'a mut Option<NonZeroU16>,
+	/// The block ID list of the LayerData
+	block_id_list: &'a mut IndexSet<String>,
 	/// The biome type of the referenced entry
 	biome: &'a mut Option<NonZeroU16>,
+	/// The biome ID of the referenced entry
+	biome_id: &'a mut Option<&'static str>,
 	/// The block light of the referenced entry
 	block_light: &'a mut u8,
+	/// The covered flag of the referenced entry
+	covered: &'a mut bool,
+	/// The water depth of the referenced entry
+	water_depth: &'a mut u16,
+	/// The block type below the water or see-through blocks of the referenced entry
+	floor: &'a mut Option<BlockType>,
+	/// The translucent block type above the referenced entry
+	tint: &'a mut Option<BlockType>,
 	/// The depth value of the referenced entry
 	depth: &'a mut Option<BlockHeight>,
 }
@@ -76,18 +144,27 @@ impl<'a> LayerEntry<'a> {
 	/// Fills in the LayerEntry
 	///
 	/// Checks whether the passed coordinates point at an opaque or non-water block and
-	/// fills in the entry accordingly. Returns true when the block has been filled including its depth.
+	/// fills in the entry accordingly. Hidden blocks are treated like air, but mark
+	/// the entry as covered. See-through blocks are handled like water, filling in
+	/// the block below them as the floor. Returns true when the block has been filled
+	/// including its depth.
 	fn fill(
 		&mut self,
 		biome_list: &mut IndexSet<Biome>,
+		biome_types: &BiomeTypes,
 		section: SectionIterItem,
 		coords: SectionBlockCoords,
+		hidden: bool,
+		see_through: bool,
 	) -> Result<bool> {
-		let Some(block_type) = section
+		let block_type = section
 			.section
 			.block_at(coords)?
-			.filter(|block_type| block_type.is(BlockFlag::Opaque))
-		else {
+			.filter(|block_type| block_type.is(BlockFlag::Opaque));
+		if hidden && block_type.is_some() && self.is_empty() {
+			*self.covered = true;
+		}
+		let Some(block_type) = block_type.filter(|_| !hidden) else {
 			if self.is_empty() {
 				*self.block_light = section.block_light.block_light_at(coords);
 			}
@@ -95,8 +172,22 @@ impl<'a> LayerEntry<'a> {
 			return Ok(false);
 		};
 
-		if self.is_empty() {
+		let top = self.is_empty();
+		if top {
 			*self.block = Some(block_type);
+
+			let block_id = section.section.block_id_at(coords)?;
+			let block_id = block_id.strip_prefix("minecraft:").unwrap_or(block_id);
+			let block_index = match self.block_id_list.get_index_of(block_id) {
+				Some(index) => index,
+				None => self.block_id_list.insert_full(block_id.to_string()).0,
+			};
+			*self.block_id = NonZeroU16::new(
+				(block_index + 1)
+					.try_into()
+					.expect("block ID index not in range"),
+			);
+
 			if let Some(biome) = section.biomes.biome_at(section.y, coords)? {
 				let (biome_index, _) = biome_list.insert_full(*biome);
 				*self.biome = NonZeroU16::new(
@@ -104,13 +195,22 @@ impl<'a> LayerEntry<'a> {
 						.try_into()
 						.expect("biome index not in range"),
 				);
+				*self.biome_id = biome_types.id(biome);
 			}
 		}
 
 		if block_type.is(BlockFlag::Water) {
+			*self.water_depth = self.water_depth.saturating_add(1);
+			return Ok(false);
+		}
+		if see_through {
 			return Ok(false);
 		}
 
+		if !top {
+			*self.floor = Some(block_type);
+		}
+
 		let height = BlockHeight::new(section.y, coords.y)?;
 		*self.depth = Some(height);
 
@@ -118,29 +218,64 @@ impl<'a> LayerEntry<'a> {
 	}
 }
 
+/// Search state of a block column in [top_layer] when skipping the ceiling
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum CeilingSearch {
+	/// Searching for the ceiling
+	#[default]
+	Above,
+	/// Searching for the first non-opaque block below the ceiling
+	Ceiling,
+	/// The ceiling has been passed
+	Below,
+}
+
 /// Top layer data
 ///
-/// A LayerData stores block type, biome, block light and depth data for
-/// each coordinate of a chunk.
+/// A LayerData stores block type, biome, block light, covered flag, depth and
+/// water data for each coordinate of a chunk.
 #[derive(Debug, Default)]
 pub struct LayerData {
 	/// Block type data
 	pub blocks: Box<BlockArray>,
+	/// Block ID data
+	pub block_ids: Box<BlockIdArray>,
+	/// List of the block IDs without the `minecraft:` namespace
+	///
+	/// Indexed by the block ID data
+	pub block_id_list: IndexSet<String>,
 	/// Biome data
 	pub biomes: Box<BiomeArray>,
+	/// Biome ID data
+	pub biome_ids: Box<BiomeIdArray>,
 	/// Block light data
 	pub block_light: Box<BlockLightArray>,
+	/// Covered column data
+	pub covered: Box<CoveredArray>,
+	/// Water depth data
+	pub water_depths: Box<WaterDepthArray>,
+	/// Block type data of the ground below water or see-through blocks
+	pub floors: Box<BlockArray>,
+	/// Block type data of translucent blocks above the top block
+	pub tints: Box<BlockArray>,
 	/// Depth data
 	pub depths: Box<DepthArray>,
 }
 
 impl LayerData {
 	/// Builds a [LayerEntry] referencing the LayerData at a given coordinate pair
-	fn entry(&mut self, coords: LayerBlockCoords) -> LayerEntry {
+	fn entry(&mut self, coords: LayerBlockCoords) -> LayerEntry<'_> {
 		LayerEntry {
 			block: &mut self.blocks[coords],
+			block_id: &mut self.block_ids[coords],
+			block_id_list: &mut self.block_id_list,
 			biome: &mut self.biomes[coords],
+			biome_id: &mut self.biome_ids[coords],
 			block_light: &mut self.block_light[coords],
+			covered: &mut self.covered[coords],
+			water_depth: &mut self.water_depths[coords],
+			floor: &mut self.floors[coords],
+			tint: &mut self.tints[coords],
 			depth: &mut self.depths[coords],
 		}
 	}
@@ -154,7 +289,35 @@ impl LayerData {
 /// map. For water blocks, the height of the first non-water block
 /// is additionally filled in as the water depth (the block height is
 /// used as depth otherwise).
-pub fn top_layer(biome_list: &mut IndexSet<Biome>, chunk: &Chunk) -> Result<Option<LayerData>> {
+///
+/// Biome IDs are resolved using the passed [BiomeTypes], which must be the
+/// instance used to load the chunk.
+///
+/// When the height range of the dimension is known, blocks outside of the
+/// range are ignored, like they are by the game. Blocks above the maximum
+/// height of the [HeightLimits] are ignored as well, rendering the world as
+/// if it was cut off at this height.
+///
+/// Blocks selected by the *hide* filter are treated like air, revealing the
+/// blocks below them. Blocks selected by the *see_through* filter are handled
+/// like water: they are returned as the top block, but the depth and floor
+/// are taken from the first block below them. Blocks selected by the
+/// *translucent* filter are skipped like hidden blocks, but the topmost of them
+/// is stored as the tint of the column.
+///
+/// When *skip_ceiling* is set in the [HeightLimits], the first continuous run
+/// of opaque blocks of each column (the bedrock and netherrack ceiling of the
+/// Nether) is skipped, and the search starts at the first non-opaque block
+/// below it. Columns without any space below the ceiling are left empty.
+pub fn top_layer(
+	biome_list: &mut IndexSet<Biome>,
+	biome_types: &BiomeTypes,
+	chunk: &Chunk,
+	limits: HeightLimits,
+	hide: Option<&BlockFilter>,
+	see_through: Option<&BlockFilter>,
+	translucent: Option<&BlockFilter>,
+) -> Result<Option<LayerData>> {
 	use BLOCKS_PER_CHUNK as N;
 
 	if chunk.is_empty() {
@@ -163,9 +326,26 @@ pub fn top_layer(biome_list: &mut IndexSet<Biome>, chunk: &Chunk) -> Result<Opti
 
 	let mut done = 0;
 	let mut ret = LayerData::default();
+	let mut ceiling = limits
+		.skip_ceiling
+		.then(LayerBlockArray::<CeilingSearch>::default);
 
 	for section in chunk.sections().rev() {
+		let hidden = hide
+			.map(|filter| section.section.filter_blocks(filter))
+			.transpose()?;
+		let see_through = see_through
+			.map(|filter| section.section.filter_blocks(filter))
+			.transpose()?;
+		let translucent = translucent
+			.map(|filter| section.section.filter_blocks(filter))
+			.transpose()?;
+
 		for y in BlockY::iter().rev() {
+			if !limits.contains(BlockHeight::new(section.y, y)?) {
+				continue;
+			}
+
 			for z in BlockZ::iter() {
 				for x in BlockX::iter() {
 					let xz = LayerBlockCoords { x, z };
@@ -176,7 +356,51 @@ pub fn top_layer(biome_list: &mut IndexSet<Biome>, chunk: &Chunk) -> Result<Opti
 					}
 
 					let coords = SectionBlockCoords { xz, y };
-					if !entry.fill(biome_list, section, coords)? {
+					let is_hidden = hidden
+						.as_ref()
+						.is_some_and(|hidden| hidden[coords.offset()]);
+					let is_see_through = see_through
+						.as_ref()
+						.is_some_and(|see_through| see_through[coords.offset()]);
+
+					if let Some(ceiling) = &mut ceiling {
+						let column = &mut ceiling[xz];
+						if *column != CeilingSearch::Below {
+							let opaque = !is_hidden
+								&& section
+									.section
+									.block_at(coords)?
+									.is_some_and(|block_type| block_type.is(BlockFlag::Opaque));
+							*column = match *column {
+								CeilingSearch::Above if opaque => {
+									*entry.covered = true;
+									CeilingSearch::Ceiling
+								}
+								CeilingSearch::Ceiling if !opaque => CeilingSearch::Below,
+								other => other,
+							};
+							if *column != CeilingSearch::Below {
+								continue;
+							}
+						}
+					}
+					if translucent
+						.as_ref()
+						.is_some_and(|translucent| translucent[coords.offset()])
+					{
+						if entry.is_empty() && entry.tint.is_none() {
+							*entry.tint = section.section.block_at(coords)?;
+						}
+						continue;
+					}
+					if !entry.fill(
+						biome_list,
+						biome_types,
+						section,
+						coords,
+						is_hidden,
+						is_see_through,
+					)? {
 						continue;
 					}
 
@@ -192,3 +416,102 @@ pub fn top_layer(biome_list: &mut IndexSet<Biome>, chunk: &Chunk) -> Result<Opti
 
 	Ok(Some(ret))
 }
+
+/// Search state of a block column in [cave_layer]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum CaveSearch {
+	/// Searching for the surface
+	#[default]
+	Surface,
+	/// Searching for an air pocket below the surface
+	Air,
+	/// Searching for the floor of the air pocket
+	Floor,
+	/// The cave floor has been found
+	Done,
+}
+
+/// Finds the floors of the topmost caves of a chunk
+///
+/// For each (X, Z) coordinate pair, the first air pocket below the surface
+/// is searched; the height of the first opaque non-water block below it is
+/// returned as the cave floor. Water and leaves are not considered part of
+/// the surface, so the ground below oceans and trees is searched for caves.
+/// Returns [None] for empty chunks.
+pub fn cave_layer(chunk: &Chunk, limits: HeightLimits) -> Result<Option<Box<DepthArray>>> {
+	use BLOCKS_PER_CHUNK as N;
+
+	if chunk.is_empty() {
+		return Ok(None);
+	}
+
+	let mut done = 0;
+	let mut state = LayerBlockArray::<CaveSearch>::default();
+	let mut ret = Box::<DepthArray>::default();
+
+	for section in chunk.sections().rev() {
+		for y in BlockY::iter().rev() {
+			let block_height = BlockHeight::new(section.y, y)?;
+			if !limits.contains(block_height) {
+				continue;
+			}
+
+			for z in BlockZ::iter() {
+				for x in BlockX::iter() {
+					let xz = LayerBlockCoords { x, z };
+					let column = &mut state[xz];
+					if *column == CaveSearch::Done {
+						continue;
+					}
+
+					let block_type = section.section.block_at(SectionBlockCoords { xz, y })?;
+					let opaque =
+						block_type.is_some_and(|block_type| block_type.is(BlockFlag::Opaque));
+					let water =
+						block_type.is_some_and(|block_type| block_type.is(BlockFlag::Water));
+					let foliage = block_type.is_some_and(|block_type| {
+						block_type.is(BlockFlag::Foliage)
+							|| block_type.is(BlockFlag::Birch)
+							|| block_type.is(BlockFlag::Spruce)
+					});
+
+					*column = match *column {
+						CaveSearch::Surface if opaque && !water && !foliage => CaveSearch::Air,
+						CaveSearch::Air if !opaque => CaveSearch::Floor,
+						CaveSearch::Floor if opaque && !water => {
+							ret[xz] = Some(block_height);
+							done += 1;
+							CaveSearch::Done
+						}
+						other => other,
+					};
+
+					if done == N * N {
+						return Ok(Some(ret));
+					}
+				}
+			}
+		}
+	}
+
+	Ok(Some(ret))
+}
+
+/// Array storing a block count for each coordinate of a chunk
+pub type BlockCountArray = LayerBlockArray<u16>;
+
+/// Counts the blocks selected by a [BlockFilter] in each block column of a chunk
+///
+/// Returns [None] for empty chunks.
+pub fn count_blocks(chunk: &Chunk, filter: &BlockFilter) -> Result<Option<Box<BlockCountArray>>> {
+	if chunk.is_empty() {
+		return Ok(None);
+	}
+
+	let mut counts = Box::<BlockCountArray>::default();
+	for section in chunk.sections() {
+		section.section.count_blocks(filter, &mut counts)?;
+	}
+
+	Ok(Some(counts))
+}
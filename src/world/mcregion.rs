@@ -0,0 +1,131 @@
+//! Conversion of pre-Anvil (MCRegion) chunk data
+//!
+//! Before Minecraft 1.2, the blocks of a chunk were stored as a single
+//! 16x128x16 array in XZY order, without any biome data. The chunks are
+//! converted to the pre-1.13 section format, so they can be processed
+//! like Anvil chunks.
+
+use anyhow::{bail, Result};
+use fastnbt::ByteArray;
+use serde::Deserialize;
+
+use super::de;
+use crate::types::*;
+
+/// Height of MCRegion chunks
+const HEIGHT: usize = 128;
+
+/// Number of sections of MCRegion chunks
+const SECTIONS: usize = HEIGHT / BLOCKS_PER_CHUNK;
+
+/// Legacy biome ID used for all MCRegion chunks (plains)
+const BIOME: i8 = 1;
+
+/// `Level` compound element found in MCRegion [chunks](Chunk)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Level {
+	/// Block type data
+	pub blocks: ByteArray,
+	/// Block damage / subtype data
+	pub data: ByteArray,
+	/// Block light data
+	pub block_light: Option<ByteArray>,
+}
+
+/// Toplevel compound element of an MCRegion chunk
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Chunk {
+	/// `Level` field of the chunk
+	pub level: Level,
+}
+
+/// Returns a 4-bit value from an array of nibbles
+fn nibble(data: &[i8], offset: usize) -> u8 {
+	(data[offset / 2] as u8 >> ((offset % 2) * 4)) & 0xf
+}
+
+/// Sets a 4-bit value in an array of nibbles
+fn set_nibble(data: &mut [i8], offset: usize, value: u8) {
+	data[offset / 2] |= (value << ((offset % 2) * 4)) as i8;
+}
+
+impl Chunk {
+	/// Converts the chunk to a pre-1.13 Anvil [de::Chunk]
+	///
+	/// Sections without any non-air blocks are omitted.
+	pub fn into_anvil(self) -> Result<de::Chunk<'static>> {
+		/// Number of blocks in a section
+		const N: usize = BLOCKS_PER_CHUNK * BLOCKS_PER_CHUNK * BLOCKS_PER_CHUNK;
+
+		let Level {
+			blocks,
+			data,
+			block_light,
+		} = self.level;
+
+		if blocks.len() != SECTIONS * N {
+			bail!("Invalid chunk block data");
+		}
+		if data.len() != SECTIONS * N / 2 {
+			bail!("Invalid chunk extra data");
+		}
+		if block_light
+			.as_ref()
+			.is_some_and(|block_light| block_light.len() != SECTIONS * N / 2)
+		{
+			bail!("Invalid chunk block light data");
+		}
+
+		let mut sections = Vec::new();
+
+		for section_y in 0..SECTIONS {
+			let mut section_blocks = vec![0; N];
+			let mut section_data = vec![0; N / 2];
+			let mut section_block_light = block_light.as_ref().map(|_| vec![0; N / 2]);
+
+			for (target, block) in section_blocks.iter_mut().enumerate() {
+				// YZX to XZY
+				let (y, z, x) = (target >> 8, (target >> 4) & 0xf, target & 0xf);
+				let offset = (x * BLOCKS_PER_CHUNK + z) * HEIGHT + section_y * BLOCKS_PER_CHUNK + y;
+
+				*block = blocks[offset];
+				set_nibble(&mut section_data, target, nibble(&data, offset));
+				if let (Some(target_light), Some(light)) = (&mut section_block_light, &block_light)
+				{
+					set_nibble(target_light, target, nibble(light, offset));
+				}
+			}
+
+			if section_blocks.iter().all(|&block| block == 0) {
+				continue;
+			}
+
+			sections.push(de::SectionV0 {
+				y: section_y as i32,
+				block_light: section_block_light.map(de::ByteArray::from),
+				section: de::SectionV0Variants::V0 {
+					blocks: section_blocks.into(),
+					data: section_data.into(),
+				},
+			});
+		}
+
+		Ok(de::Chunk {
+			data_version: None,
+			chunk: de::ChunkVariants::V0 {
+				level: de::LevelV0 {
+					sections,
+					biomes: Some(de::BiomesV0::ByteArray(ByteArray::new(vec![
+						BIOME;
+						BLOCKS_PER_CHUNK
+							* BLOCKS_PER_CHUNK
+					]))),
+					inhabited_time: None,
+				},
+			},
+			inhabited_time: None,
+		})
+	}
+}
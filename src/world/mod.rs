@@ -1,6 +1,9 @@
 //! Data structures describing Minecraft save data
 
+pub mod bedrock;
 pub mod chunk;
-pub mod de;
+pub mod cubic;
+pub use crate::nbt::de;
 pub mod layer;
+pub mod mcregion;
 pub mod section;
@@ -3,14 +3,14 @@
 //! The data types in this module attempt to provide interfaces abstracting
 //! over different data versions as much as possible.
 
-use std::fmt::Debug;
+use std::{borrow::Cow, collections::HashSet, fmt::Debug};
 
 use anyhow::{bail, Context, Result};
 use num_integer::div_rem;
 
 use super::de;
 use crate::{
-	resource::{Biome, BiomeTypes, BlockType, BlockTypes},
+	resource::{self, Biome, BiomeTypes, BlockType, BlockTypes},
 	types::*,
 };
 
@@ -40,19 +40,153 @@ fn palette_bits(len: usize, min: u8, max: u8) -> Option<u8> {
 	Some(bits)
 }
 
+/// Set of block types selected by their string IDs
+///
+/// Allows matching blocks of all supported data formats, including
+/// pre-1.13 numeric block type IDs.
+#[derive(Debug, Clone)]
+pub struct BlockFilter {
+	/// Selected block IDs without the `minecraft:` namespace
+	ids: HashSet<String>,
+	/// Selection state of each pre-1.13 numeric block type and subtype
+	legacy: Box<[[bool; 16]; 256]>,
+}
+
+impl BlockFilter {
+	/// Constructs a new [BlockFilter] from a list of block IDs
+	///
+	/// IDs may be passed with or without the `minecraft:` namespace.
+	pub fn new<I, S>(ids: I) -> Self
+	where
+		I: IntoIterator<Item = S>,
+		S: AsRef<str>,
+	{
+		let ids: HashSet<String> = ids
+			.into_iter()
+			.map(|id| {
+				let id = id.as_ref();
+				id.strip_prefix("minecraft:").unwrap_or(id).to_string()
+			})
+			.collect();
+		let legacy = Box::new(std::array::from_fn(|id| {
+			std::array::from_fn(|data| {
+				ids.contains(resource::legacy_block_id(id as u8, data as u8))
+			})
+		}));
+
+		BlockFilter { ids, legacy }
+	}
+
+	/// Returns true if a 1.13+ string block ID is selected
+	pub fn matches(&self, id: &str) -> bool {
+		self.ids
+			.contains(id.strip_prefix("minecraft:").unwrap_or(id))
+	}
+
+	/// Returns true if a pre-1.13 numeric block type ID is selected
+	pub fn matches_legacy(&self, id: u8, data: u8) -> bool {
+		self.legacy[id as usize][data as usize]
+	}
+}
+
 /// Trait for common functions of [SectionV1_13] and [SectionV0]
 pub trait Section: Debug {
 	/// Returns the [BlockType] at a coordinate tuple inside the section
 	fn block_at(&self, coords: SectionBlockCoords) -> Result<Option<BlockType>>;
+
+	/// Returns the string block ID at a coordinate tuple inside the section
+	///
+	/// The ID may or may not include the `minecraft:` namespace.
+	fn block_id_at(&self, coords: SectionBlockCoords) -> Result<&str>;
+
+	/// Adds the number of blocks selected by *filter* in each column of the
+	/// section to *counts*
+	fn count_blocks(&self, filter: &BlockFilter, counts: &mut LayerBlockArray<u16>) -> Result<()>;
+
+	/// Returns for each block of the section whether it is selected by
+	/// *filter*
+	///
+	/// The returned mask is indexed by [SectionBlockCoords::offset].
+	fn filter_blocks(&self, filter: &BlockFilter) -> Result<Vec<bool>>;
+
+	/// Returns the IDs of the section's palette entries that are neither
+	/// standard nor configured block types
+	///
+	/// The block types of these entries are guessed from their names.
+	fn unknown_block_ids(&self) -> Vec<&str>;
+}
+
+/// Adds the number of blocks of each column to *counts* for which the palette
+/// entry looked up by *index_at* is set in *mask*
+///
+/// Helper for palette-based [Section] implementations.
+pub fn count_palette_blocks<F>(
+	mask: &[bool],
+	counts: &mut LayerBlockArray<u16>,
+	index_at: F,
+) -> Result<()>
+where
+	F: Fn(SectionBlockCoords) -> usize,
+{
+	if !mask.contains(&true) {
+		return Ok(());
+	}
+
+	for y in BlockY::iter() {
+		for z in BlockZ::iter() {
+			for x in BlockX::iter() {
+				let xz = LayerBlockCoords { x, z };
+				let index = index_at(SectionBlockCoords { xz, y });
+				if *mask.get(index).context("Palette index out of bounds")? {
+					counts[xz] += 1;
+				}
+			}
+		}
+	}
+
+	Ok(())
+}
+
+/// Returns for each block whether the palette entry looked up by *index_at*
+/// is set in *mask*
+///
+/// Helper for palette-based [Section] implementations.
+pub fn filter_palette_blocks<F>(mask: &[bool], index_at: F) -> Result<Vec<bool>>
+where
+	F: Fn(SectionBlockCoords) -> usize,
+{
+	let mut ret = vec![false; N * N * N];
+	if !mask.contains(&true) {
+		return Ok(ret);
+	}
+
+	for y in BlockY::iter() {
+		for z in BlockZ::iter() {
+			for x in BlockX::iter() {
+				let coords = SectionBlockCoords {
+					xz: LayerBlockCoords { x, z },
+					y,
+				};
+				let index = index_at(coords);
+				ret[coords.offset()] = *mask.get(index).context("Palette index out of bounds")?;
+			}
+		}
+	}
+
+	Ok(ret)
 }
 
 /// Minecraft v1.13+ section block data
 #[derive(Debug)]
 pub struct SectionV1_13<'a> {
 	/// Packed block type data
-	block_states: Option<&'a [i64]>,
+	block_states: Option<&'a de::LongArray<'a>>,
 	/// List of block types indexed by entries encoded in *block_states*
 	palette: Vec<Option<BlockType>>,
+	/// Palette entries with the block IDs corresponding to *palette*
+	palette_entries: &'a [de::BlockStatePaletteEntry<'a>],
+	/// Indices of the palette entries with unknown block IDs
+	unknown: Vec<usize>,
 	/// Number of bits per block in *block_states*
 	bits: u8,
 	/// Set to true if packed block entries in *block_states* are aligned to i64
@@ -69,8 +203,8 @@ impl<'a> SectionV1_13<'a> {
 	/// to allow for faster lookup later.
 	pub fn new(
 		data_version: u32,
-		block_states: Option<&'a [i64]>,
-		palette: &'a [de::BlockStatePaletteEntry],
+		block_states: Option<&'a de::LongArray<'a>>,
+		palette: &'a [de::BlockStatePaletteEntry<'a>],
 		block_types: &'a BlockTypes,
 	) -> Result<Self> {
 		let aligned_blocks = data_version >= 2529;
@@ -80,7 +214,7 @@ impl<'a> SectionV1_13<'a> {
 		if let Some(block_states) = block_states {
 			let expected_length = if aligned_blocks {
 				let blocks_per_word = 64 / bits as usize;
-				(4096 + blocks_per_word - 1) / blocks_per_word
+				4096usize.div_ceil(blocks_per_word)
 			} else {
 				64 * bits as usize
 			};
@@ -92,24 +226,32 @@ impl<'a> SectionV1_13<'a> {
 		let palette_types = palette
 			.iter()
 			.map(|entry| {
-				let block_type = block_types.get(&entry.name);
+				let block_type = block_types.resolve(&entry.name);
 				if block_type.is_none() {
 					eprintln!("Unknown block type: {}", entry.name);
 				}
 				block_type
 			})
 			.collect();
+		let unknown = palette
+			.iter()
+			.enumerate()
+			.filter(|(_, entry)| block_types.get(&entry.name).is_none())
+			.map(|(index, _)| index)
+			.collect();
 
 		Ok(Self {
 			block_states,
 			palette: palette_types,
+			palette_entries: palette,
+			unknown,
 			bits,
 			aligned_blocks,
 		})
 	}
 
 	/// Looks up the block type palette index at the given coordinates
-	fn palette_index_at(&self, coords: SectionBlockCoords) -> usize {
+	pub fn palette_index_at(&self, coords: SectionBlockCoords) -> usize {
 		let Some(block_states) = self.block_states else {
 			return 0;
 		};
@@ -122,14 +264,14 @@ impl<'a> SectionV1_13<'a> {
 		let shifted = if self.aligned_blocks {
 			let blocks_per_word = 64 / bits;
 			let (word, shift) = div_rem(offset, blocks_per_word);
-			block_states[word] as u64 >> (shift * bits)
+			block_states.value(word) as u64 >> (shift * bits)
 		} else {
 			let bit_offset = offset * bits;
 			let (word, bit_shift) = div_rem(bit_offset, 64);
 
-			let mut tmp = (block_states[word] as u64) >> bit_shift;
+			let mut tmp = (block_states.value(word) as u64) >> bit_shift;
 			if bit_shift + bits > 64 {
-				tmp |= (block_states[word + 1] as u64) << (64 - bit_shift);
+				tmp |= (block_states.value(word + 1) as u64) << (64 - bit_shift);
 			}
 			tmp
 		};
@@ -146,6 +288,40 @@ impl<'a> Section for SectionV1_13<'a> {
 			.get(index)
 			.context("Palette index out of bounds")?)
 	}
+
+	fn block_id_at(&self, coords: SectionBlockCoords) -> Result<&str> {
+		let index = self.palette_index_at(coords);
+		Ok(&self
+			.palette_entries
+			.get(index)
+			.context("Palette index out of bounds")?
+			.name)
+	}
+
+	fn count_blocks(&self, filter: &BlockFilter, counts: &mut LayerBlockArray<u16>) -> Result<()> {
+		let mask: Vec<_> = self
+			.palette_entries
+			.iter()
+			.map(|entry| filter.matches(&entry.name))
+			.collect();
+		count_palette_blocks(&mask, counts, |coords| self.palette_index_at(coords))
+	}
+
+	fn filter_blocks(&self, filter: &BlockFilter) -> Result<Vec<bool>> {
+		let mask: Vec<_> = self
+			.palette_entries
+			.iter()
+			.map(|entry| filter.matches(&entry.name))
+			.collect();
+		filter_palette_blocks(&mask, |coords| self.palette_index_at(coords))
+	}
+
+	fn unknown_block_ids(&self) -> Vec<&str> {
+		self.unknown
+			.iter()
+			.map(|&index| &*self.palette_entries[index].name)
+			.collect()
+	}
 }
 
 /// Pre-1.13 section block data
@@ -179,10 +355,9 @@ impl<'a> SectionV0<'a> {
 			block_types,
 		})
 	}
-}
 
-impl<'a> Section for SectionV0<'a> {
-	fn block_at(&self, coords: SectionBlockCoords) -> Result<Option<BlockType>> {
+	/// Looks up the numeric block type and subtype IDs at the given coordinates
+	pub fn legacy_id_at(&self, coords: SectionBlockCoords) -> (u8, u8) {
 		let offset = coords.offset();
 		let block = self.blocks[offset] as u8;
 
@@ -194,8 +369,59 @@ impl<'a> Section for SectionV0<'a> {
 			data_byte & 0xf
 		};
 
+		(block, data)
+	}
+}
+
+impl<'a> Section for SectionV0<'a> {
+	fn block_at(&self, coords: SectionBlockCoords) -> Result<Option<BlockType>> {
+		let (block, data) = self.legacy_id_at(coords);
 		Ok(self.block_types.get_legacy(block, data))
 	}
+
+	fn block_id_at(&self, coords: SectionBlockCoords) -> Result<&str> {
+		let (block, data) = self.legacy_id_at(coords);
+		Ok(resource::legacy_block_id(block, data))
+	}
+
+	fn count_blocks(&self, filter: &BlockFilter, counts: &mut LayerBlockArray<u16>) -> Result<()> {
+		for y in BlockY::iter() {
+			for z in BlockZ::iter() {
+				for x in BlockX::iter() {
+					let xz = LayerBlockCoords { x, z };
+					let (block, data) = self.legacy_id_at(SectionBlockCoords { xz, y });
+					if filter.matches_legacy(block, data) {
+						counts[xz] += 1;
+					}
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	fn filter_blocks(&self, filter: &BlockFilter) -> Result<Vec<bool>> {
+		let mut ret = vec![false; N * N * N];
+
+		for y in BlockY::iter() {
+			for z in BlockZ::iter() {
+				for x in BlockX::iter() {
+					let coords = SectionBlockCoords {
+						xz: LayerBlockCoords { x, z },
+						y,
+					};
+					let (block, data) = self.legacy_id_at(coords);
+					ret[coords.offset()] = filter.matches_legacy(block, data);
+				}
+			}
+		}
+
+		Ok(ret)
+	}
+	fn unknown_block_ids(&self) -> Vec<&str> {
+		// All numeric block IDs have a standard block type
+		Vec::new()
+	}
 }
 
 /// Trait for common functions of [BiomesV1_18] and [BiomesV0]
@@ -217,7 +443,7 @@ pub struct BiomesV1_18<'a> {
 	///
 	/// Unlike block type data in [SectionV1_13], biome data is always aligned
 	/// to whole i64 values.
-	biomes: Option<&'a [i64]>,
+	biomes: Option<&'a de::LongArray<'a>>,
 	/// Biome palette indexed by entries encoded in *biomes*
 	palette: Vec<Option<&'a Biome>>,
 	/// Number of bits used for each entry in *biomes*
@@ -227,15 +453,15 @@ pub struct BiomesV1_18<'a> {
 impl<'a> BiomesV1_18<'a> {
 	/// Constructs a new [BiomesV1_18] from deserialized data structures
 	pub fn new(
-		biomes: Option<&'a [i64]>,
-		palette: &'a [String],
+		biomes: Option<&'a de::LongArray<'a>>,
+		palette: &'a [Cow<'a, str>],
 		biome_types: &'a BiomeTypes,
 	) -> Result<Self> {
 		let bits = palette_bits(palette.len(), 1, 6).context("Unsupported block palette size")?;
 
 		if let Some(biomes) = biomes {
 			let biomes_per_word = 64 / bits as usize;
-			let expected_length = (64 + biomes_per_word - 1) / biomes_per_word;
+			let expected_length = 64usize.div_ceil(biomes_per_word);
 			if biomes.len() != expected_length {
 				bail!("Invalid section biome data");
 			}
@@ -260,7 +486,7 @@ impl<'a> BiomesV1_18<'a> {
 	}
 
 	/// Looks up the block type palette index at the given coordinates
-	fn palette_index_at(&self, coords: SectionBlockCoords) -> usize {
+	pub fn palette_index_at(&self, coords: SectionBlockCoords) -> usize {
 		let Some(biomes) = self.biomes else {
 			return 0;
 		};
@@ -275,7 +501,7 @@ impl<'a> BiomesV1_18<'a> {
 
 		let blocks_per_word = 64 / bits;
 		let (word, shift) = div_rem(offset, blocks_per_word);
-		let shifted = biomes[word] as u64 >> (shift * bits);
+		let shifted = biomes.value(word) as u64 >> (shift * bits);
 
 		(shifted & mask) as usize
 	}
@@ -362,7 +588,7 @@ impl<'a> Biomes for BiomesV0<'a> {
 }
 
 /// Wrapper around chunk block light data array
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct BlockLight<'a>(Option<&'a [i8]>);
 
 impl<'a> BlockLight<'a> {
@@ -1,5 +1,5 @@
-// bsearch-based array element check
-function contains(array, elem) {
+// bsearch-based array element lookup, returning the index or -1
+function find(array, elem) {
 	var min = 0, max = array.length, i, cur;
 
 	while (min < max) {
@@ -7,20 +7,41 @@ function contains(array, elem) {
 		cur = array[i];
 
 		if (cur === elem)
-			return true;
+			return i;
 		else if (cur < elem)
 			min = i + 1;
 		else
 			max = i;
 	}
 
-	return false;
+	return -1;
+}
+
+// bsearch-based array element check
+function contains(array, elem) {
+	return find(array, elem) >= 0;
+}
+
+// Returns the URL of a tile, with its timestamp and the version of its
+// tile set appended to bypass outdated cached versions
+function tileURL(base, mipmap, x, z, version) {
+	var url = base+'r.'+x+'.'+z+'.png';
+	var i = find(mipmap.regions[z] || [], x);
+
+	if (i >= 0 && mipmap.timestamps && mipmap.timestamps[z])
+		url += '?t='+mipmap.timestamps[z][i];
+	if (version)
+		url += (url.indexOf('?') >= 0 ? '&' : '?')+'v='+version;
+
+	return url;
 }
 
 var MinedMapLayer = L.GridLayer.extend({
-	initialize: function (mipmaps, layer) {
+	initialize: function (mipmaps, dataPath, layer, version) {
 		this.mipmaps = mipmaps;
+		this.dataPath = dataPath;
 		this.layer = layer;
+		this.version = version;
 
 		this.zoomOffset = L.Browser.retina ? 1 : 0;
 
@@ -63,7 +84,7 @@ var MinedMapLayer = L.GridLayer.extend({
 		if (coords.x >= mipmap.bounds.minX && coords.x <= mipmap.bounds.maxX &&
 		    coords.y >= mipmap.bounds.minZ && coords.y <= mipmap.bounds.maxZ &&
 		    contains(mipmap.regions[coords.y] || [], coords.x))
-			tile.src = 'data/'+this.layer+'/'+z+'/r.'+coords.x+'.'+coords.y+'.png';
+			tile.src = tileURL(this.dataPath+this.layer+'/'+z+'/', mipmap, coords.x, coords.y, this.version);
 
 		if (z === 0)
 			L.DomUtil.addClass(tile, 'overzoomed');
@@ -119,6 +140,145 @@ var MinedMapLayer = L.GridLayer.extend({
 });
 
 
+// Reverses the PNG row filters of decompressed image data
+function unfilterPNG(data, width, height, bpp) {
+	var stride = width*bpp, out = new Uint8Array(stride*height);
+	var pos = 0, x, y, a, b, c, p, pa, pb, pc, filter, row, prev;
+
+	for (y = 0; y < height; y++) {
+		filter = data[pos++];
+		row = y*stride;
+		prev = row - stride;
+
+		for (x = 0; x < stride; x++) {
+			a = x >= bpp ? out[row+x-bpp] : 0;
+			b = y > 0 ? out[prev+x] : 0;
+			c = (x >= bpp && y > 0) ? out[prev+x-bpp] : 0;
+
+			switch (filter) {
+			case 1: p = a; break;
+			case 2: p = b; break;
+			case 3: p = (a+b) >> 1; break;
+			case 4:
+				pa = Math.abs(b-c);
+				pb = Math.abs(a-c);
+				pc = Math.abs(a+b-2*c);
+				p = (pa <= pb && pa <= pc) ? a : (pb <= pc ? b : c);
+				break;
+			default: p = 0;
+			}
+
+			out[row+x] = (data[pos++] + p) & 0xff;
+		}
+	}
+
+	return out;
+}
+
+// Number of channels of the PNG color types used for data tiles
+var pngChannels = {0: 1, 2: 3, 4: 2, 6: 4};
+
+// Decodes a data tile (a 16-bit grayscale or RGB PNG with alpha channel)
+// into the channel values of its pixels
+function decodeDataTile(png, callback) {
+	var view = new DataView(png.buffer), pos = 8, width = 0, height = 0, channels = 0, idat = [];
+
+	while (pos + 8 <= png.length) {
+		var length = view.getUint32(pos);
+		var type = String.fromCharCode.apply(null, png.subarray(pos+4, pos+8));
+
+		if (type === 'IHDR') {
+			width = view.getUint32(pos+8);
+			height = view.getUint32(pos+12);
+			channels = pngChannels[png[pos+17]];
+		} else if (type === 'IDAT') {
+			idat.push(png.subarray(pos+8, pos+8+length));
+		}
+
+		pos += 12 + length;
+	}
+
+	var stream = new Blob(idat).stream().pipeThrough(new DecompressionStream('deflate'));
+	new Response(stream).arrayBuffer().then(function (buffer) {
+		var pixels = unfilterPNG(new Uint8Array(buffer), width, height, 2*channels);
+		var values = new Uint16Array(width*height*channels);
+
+		for (var i = 0; i < values.length; i++)
+			values[i] = (pixels[2*i] << 8) | pixels[2*i+1];
+
+		callback({channels: channels, values: values});
+	}, function () {
+		callback(null);
+	});
+}
+
+// Loads the data tiles of a data tile set on demand
+var DataTiles = L.Class.extend({
+	initialize: function (mipmaps, dataPath, name, version) {
+		this.mipmap = mipmaps[0];
+		this.url = dataPath+name+'/0/';
+		this.version = version;
+		this.tiles = {};
+	},
+
+	// Calls back with the channel values for the block column at x, z, with
+	// the alpha channel removed, or with null if there is no data
+	get: function (x, z, callback) {
+		var tileX = Math.floor(x/512), tileZ = Math.floor(z/512);
+		var index = (z - 512*tileZ)*512 + (x - 512*tileX);
+
+		if (!contains(this.mipmap.regions[tileZ] || [], tileX)) {
+			callback(null);
+			return;
+		}
+
+		var key = tileX+','+tileZ, tile = this.tiles[key];
+		if (!tile) {
+			tile = this.tiles[key] = {data: null, callbacks: []};
+
+			var xhr = new XMLHttpRequest();
+			xhr.responseType = 'arraybuffer';
+			xhr.onload = function () {
+				var done = function (data) {
+					tile.data = data || {channels: 1, values: new Uint16Array(0)};
+					tile.callbacks.forEach(function (cb) { cb(); });
+					tile.callbacks = null;
+				};
+
+				if (this.status === 200)
+					decodeDataTile(new Uint8Array(this.response), done);
+				else
+					done(null);
+			};
+			xhr.open('GET', tileURL(this.url, this.mipmap, tileX, tileZ, this.version), true);
+			xhr.send();
+		}
+
+		var lookup = function () {
+			var n = tile.data.channels, pixel = tile.data.values.subarray(n*index, n*(index+1));
+
+			if (pixel.length < n || !pixel[n-1])
+				callback(null);
+			else
+				callback(Array.prototype.slice.call(pixel, 0, n-1));
+		};
+
+		if (tile.data)
+			lookup();
+		else
+			tile.callbacks.push(lookup);
+	}
+});
+
+
+// Formats a namespaced ID like "minecraft:cherry_grove" as "Cherry Grove"
+var formatID = function (id) {
+	return id.replace(/^.*:/, '').split('_').map(function (word) {
+		return word.charAt(0).toUpperCase() + word.substr(1);
+	}).join(' ');
+};
+
+
 var CoordControl = L.Control.extend({
 	initialize: function () {
 		this.options.position = 'bottomleft';
@@ -130,10 +290,185 @@ var CoordControl = L.Control.extend({
 		return this._container;
 	},
 
-	update: function (x, z) {
+	// Shows the coordinates under the cursor, followed by additional
+	// information texts about the block column
+	update: function (x, z, info) {
 		if (!this._map) { return; }
 
-		this._container.innerHTML = 'X: ' + x + '&nbsp;&nbsp;&nbsp;Z: ' + z;
+		var text = 'X: ' + x + '&nbsp;&nbsp;&nbsp;Z: ' + z;
+		(info || []).forEach(function (line) {
+			text += '&nbsp;&nbsp;&nbsp;' + line;
+		});
+		this._container.innerHTML = text;
+	}
+});
+
+
+// Formats the time since a Unix timestamp given in seconds
+//
+// With a locale, the time is formatted by the browser in its language
+var formatAge = function (timestamp, locale) {
+	var minutes = (Date.now()/1000 - timestamp) / 60;
+
+	if (locale && window.Intl && Intl.RelativeTimeFormat) {
+		var format = new Intl.RelativeTimeFormat(locale, {numeric: 'auto'});
+
+		if (minutes < 60)
+			return format.format(-Math.round(minutes), 'minute');
+		else if (minutes < 48*60)
+			return format.format(-Math.round(minutes/60), 'hour');
+		else
+			return format.format(-Math.round(minutes/(24*60)), 'day');
+	}
+
+	if (minutes < 1)
+		return 'just now';
+	else if (minutes < 60)
+		return Math.round(minutes) + ' min ago';
+	else if (minutes < 48*60)
+		return Math.round(minutes/60) + ' h ago';
+	else
+		return Math.round(minutes/(24*60)) + ' days ago';
+};
+
+
+// Selects the translated labels for the preferred language of the browser
+// from the translations in the metadata
+//
+// Labels without a translation fall back to the built-in English ones.
+var selectLabels = function (translations) {
+	var locales = navigator.languages || [navigator.language || ''];
+
+	for (var i = 0; i < locales.length; i++) {
+		var locale = locales[i], base = locale.split('-')[0];
+		var labels = translations[locale] ? translations[locale] : translations[base];
+		if (!labels)
+			continue;
+
+		return {
+			locale: translations[locale] ? locale : base,
+			layers: labels.layers || {},
+			dimensions: labels.dimensions || {},
+			ui: labels.ui || {},
+		};
+	}
+
+	return {locale: null, layers: {}, dimensions: {}, ui: {}};
+};
+
+
+// Converts a point or (nested) list of points of a squaremap marker to
+// Leaflet coordinates
+var markerLatLngs = function (points) {
+	if (points instanceof Array)
+		return points.map(markerLatLngs);
+	return [-points.z, points.x];
+};
+
+// Creates a Leaflet layer for a marker in the format of the squaremap and
+// Pl3xMap plugins, placed in the given map pane
+//
+// Icons referenced by icon markers are not available, so the default
+// marker icon is used for all of them.
+var createMarker = function (marker, pane) {
+	var options = {pane: pane}, layer;
+
+	['stroke', 'color', 'weight', 'opacity', 'fill', 'fillColor', 'fillOpacity', 'fillRule'].forEach(function (key) {
+		if (marker[key] !== undefined)
+			options[key] = marker[key];
+	});
+
+	switch (marker.type) {
+	case 'icon':
+		layer = L.marker(markerLatLngs(marker.point), {pane: pane});
+		break;
+	case 'polyline':
+		layer = L.polyline(markerLatLngs(marker.points), options);
+		break;
+	case 'polygon':
+		layer = L.polygon(markerLatLngs(marker.points), options);
+		break;
+	case 'rectangle':
+		layer = L.rectangle(markerLatLngs(marker.points), options);
+		break;
+	case 'circle':
+		options.radius = marker.radius;
+		layer = L.circle(markerLatLngs(marker.center), options);
+		break;
+	case 'ellipse':
+		var center = marker.center, points = [];
+		var radiusX = marker.radiusX || marker.radius_x, radiusZ = marker.radiusZ || marker.radius_z;
+		for (var i = 0; i < 64; i++) {
+			var angle = 2 * Math.PI * i / 64;
+			points.push({
+				x: center.x + radiusX * Math.cos(angle),
+				z: center.z + radiusZ * Math.sin(angle),
+			});
+		}
+		layer = L.polygon(markerLatLngs(points), options);
+		break;
+	default:
+		return null;
+	}
+
+	if (marker.tooltip)
+		layer.bindTooltip(marker.tooltip);
+	if (marker.popup)
+		layer.bindPopup(marker.popup);
+
+	return layer;
+};
+
+
+// Formats a duration given in ticks for the activity legend
+var formatTicks = function (ticks) {
+	var minutes = ticks / (20*60);
+
+	if (minutes < 60)
+		return Math.round(minutes) + ' min';
+	else
+		return Math.round(minutes/60) + ' h';
+};
+
+
+var ActivityLegend = L.Control.extend({
+	initialize: function (scale) {
+		this.options.position = 'bottomright';
+		this.scale = scale;
+	},
+
+	onAdd: function (map) {
+		var container = L.DomUtil.create('div', 'leaflet-control-attribution activity-legend');
+		var bar = L.DomUtil.create('div', 'activity-legend-bar', container);
+		var labels = L.DomUtil.create('div', 'activity-legend-labels', container);
+
+		L.DomUtil.create('span', '', labels).textContent = formatTicks(this.scale.minTicks);
+		L.DomUtil.create('span', '', labels).textContent = formatTicks(this.scale.maxTicks);
+
+		return container;
+	}
+});
+
+
+var TitleControl = L.Control.extend({
+	initialize: function (title, description, seedText) {
+		this.options.position = 'topright';
+		this.title = title;
+		this.description = description;
+		this.seedText = seedText;
+	},
+
+	onAdd: function (map) {
+		var container = L.DomUtil.create('div', 'leaflet-bar map-title');
+
+		if (this.title)
+			L.DomUtil.create('h1', '', container).textContent = this.title;
+		if (this.description)
+			L.DomUtil.create('p', '', container).textContent = this.description;
+		if (this.seedText)
+			L.DomUtil.create('p', '', container).textContent = this.seedText;
+
+		return container;
 	}
 });
 
@@ -156,14 +491,159 @@ var parseHash = function () {
 }
 
 
-window.createMap = function () {
+var WorldControl = L.Control.extend({
+	initialize: function (worlds, world) {
+		this.options.position = 'topright';
+		this.worlds = worlds;
+		this.world = world;
+	},
+
+	onAdd: function (map) {
+		var container = L.DomUtil.create('div', 'leaflet-bar');
+		var select = L.DomUtil.create('select', '', container);
+
+		for (var i = 0; i < this.worlds.length; i++) {
+			var option = L.DomUtil.create('option', '', select);
+			option.value = this.worlds[i];
+			option.textContent = this.worlds[i];
+			option.selected = (this.worlds[i] === this.world);
+		}
+
+		L.DomEvent.disableClickPropagation(container);
+		L.DomEvent.on(select, 'change', function () {
+			window.location.hash = '#world='+encodeURIComponent(select.value);
+		});
+
+		return container;
+	}
+});
+
+
+// Titles of the dimensions listed in the metadata
+var dimensionTitles = {
+	overworld: "Overworld",
+	nether: "Nether",
+	end: "End",
+};
+
+var DimensionControl = L.Control.extend({
+	initialize: function (dimensions, dimension, world, titles) {
+		this.options.position = 'topright';
+		this.dimensions = dimensions;
+		this.dimension = dimension;
+		this.world = world;
+		this.titles = titles;
+	},
+
+	onAdd: function (map) {
+		var container = L.DomUtil.create('div', 'leaflet-bar');
+		var select = L.DomUtil.create('select', '', container);
+		var world = this.world;
+
+		for (var i = 0; i < this.dimensions.length; i++) {
+			var option = L.DomUtil.create('option', '', select);
+			option.value = this.dimensions[i];
+			option.textContent = this.titles[this.dimensions[i]] ||
+				dimensionTitles[this.dimensions[i]] || this.dimensions[i];
+			option.selected = (this.dimensions[i] === this.dimension);
+		}
+
+		L.DomEvent.disableClickPropagation(container);
+		L.DomEvent.on(select, 'change', function () {
+			// The overworld is selected by leaving out the dimension
+			var params = [];
+			if (world)
+				params.push('world='+encodeURIComponent(world));
+			if (select.value !== 'overworld')
+				params.push('dim='+select.value);
+			window.location.hash = '#'+params.join('&');
+		});
+
+		return container;
+	}
+});
+
+
+// Layer control titles of the alternative base layers listed in the metadata
+var baseLayerTitles = {
+	biomes: "Biomes",
+	height: "Elevation",
+	terrain: "Terrain",
+	caves: "Caves",
+	night: "Night",
+	floor: "Ocean floor",
+	iso: "Isometric",
+};
+
+// Layer control titles of the optional overlays listed in the metadata
+var overlayTitles = {
+	ores: "Ore density",
+	activity: "Player activity",
+	spawnable: "Mob spawning",
+	chunks: "Chunk grid",
+	regions: "Region grid",
+	slime: "Slime chunks",
+	spawn: "Spawn chunks",
+	border: "World border",
+	contours: "Contour lines",
+};
+
+
+var loadJSON = function (url, callback) {
 	var xhr = new XMLHttpRequest();
 	xhr.onload = function () {
-		var res = JSON.parse(this.responseText),
-		    mipmaps = res.mipmaps,
-		    spawn = res.spawn;
+		callback(JSON.parse(this.responseText));
+	};
+	xhr.open('GET', url, true);
+	xhr.send();
+}
+
+
+window.createMap = function () {
+	var args = parseHash();
+	var world = args['world'] ? decodeURIComponent(args['world']) : '';
+	var dim = args['dim'] || '';
+
+	loadJSON('data/info.json', function (res) {
+		// Data directories with multiple worlds have a top-level index
+		// listing the world subdirectories
+		var worlds = res.worlds || [];
+		if (worlds.length && worlds.indexOf(world) < 0)
+			world = worlds[0];
+
+		if (!worlds.length && !dim) {
+			showMap(res, 'data/', worlds);
+			return;
+		}
+
+		// Dimensions other than the overworld are stored in subdirectories
+		var dataPath = 'data/';
+		if (world)
+			dataPath += encodeURIComponent(world)+'/';
+		if (dim)
+			dataPath += dim+'/';
 
-		var x, z, zoom, light;
+		loadJSON(dataPath+'info.json', function (res) {
+			showMap(res, dataPath, worlds);
+		});
+	});
+
+	var showMap = function (res, dataPath, worlds) {
+		var mipmaps = res.mipmaps,
+		    spawn = res.spawn || {x: 0, z: 0},
+		    view = res.view || {},
+		    labels = selectLabels(res.labels || {}),
+		    overlays = res.overlays || [],
+		    tileVersions = res.tileVersions || {},
+		    activityScale = res.activityScale,
+		    worldBorder = res.worldBorder;
+
+		// Without the world border overlay tiles, the border is drawn from the metadata
+		var borderOutline = worldBorder && overlays.indexOf('border') < 0;
+		if (borderOutline)
+			overlays = overlays.concat(['border']);
+
+		var x, z, zoom, light, overlayParams = {};
 
 		var updateParams = function () {
 			var args = parseHash();
@@ -173,21 +653,32 @@ window.createMap = function () {
 			z = parseFloat(args['z']);
 			light = parseInt(args['light']);
 
+			overlays.forEach(function (name) {
+				overlayParams[name] = parseInt(args[name]);
+			});
+
 			if (isNaN(zoom))
-				zoom = 0;
+				zoom = view.zoom || 0;
 			if (isNaN(x))
-				x = spawn.x;
+				x = view.x !== undefined ? view.x : spawn.x;
 			if (isNaN(z))
-				z = spawn.z;
+				z = view.z !== undefined ? view.z : spawn.z;
 		};
 
 		updateParams();
 
+		// The map can't be zoomed out beyond the highest mipmap level
+		var minZoom = -(mipmaps.length-1), maxZoom = 3;
+		if (view.minZoom !== undefined)
+			minZoom = Math.min(Math.max(view.minZoom, minZoom), maxZoom);
+		if (view.maxZoom !== undefined)
+			maxZoom = Math.max(view.maxZoom, minZoom);
+
 		var map = L.map('map', {
 			center: [-z, x],
 			zoom: zoom,
-			minZoom: -(mipmaps.length-1),
-			maxZoom: 3,
+			minZoom: minZoom,
+			maxZoom: maxZoom,
 			crs: L.CRS.Simple,
 			maxBounds: [
 				[-512*(mipmaps[0].bounds.maxZ+1), 512*mipmaps[0].bounds.minX],
@@ -195,36 +686,211 @@ window.createMap = function () {
 			],
 		});
 
-		var mapLayer = new MinedMapLayer(mipmaps, 'map');
-		var lightLayer = new MinedMapLayer(mipmaps, 'light');
+		var mapLayer = new MinedMapLayer(mipmaps, dataPath, 'map', tileVersions.map);
+		var lightLayer = new MinedMapLayer(mipmaps, dataPath, 'light', tileVersions.light);
+
+		// Keep base layers below all overlays when switching between them
+		L.setOptions(mapLayer, {zIndex: 0});
 
-		mapLayer.addTo(map);
+		var baseMaps = {};
+		baseMaps[labels.layers.map || "Blocks"] = mapLayer;
 
 		if (light)
 			map.addLayer(lightLayer);
 
-		var overlayMaps = {
-			"Illumination": lightLayer,
+		var overlayMaps = {};
+		overlayMaps[labels.layers.light || "Illumination"] = lightLayer;
+
+		var overlayLayers = {};
+		overlays.forEach(function (name) {
+			var layer;
+			if (name === 'border' && borderOutline) {
+				var radius = worldBorder.size / 2;
+				layer = L.rectangle([
+					[-(worldBorder.centerZ + radius), worldBorder.centerX - radius],
+					[-(worldBorder.centerZ - radius), worldBorder.centerX + radius],
+				], {
+					color: '#f00',
+					weight: 2,
+					fill: false,
+					interactive: false,
+				});
+			} else {
+				layer = new MinedMapLayer(mipmaps, dataPath, name, tileVersions[name]);
+			}
+			overlayLayers[name] = layer;
+
+			if (baseLayerTitles[name]) {
+				L.setOptions(layer, {zIndex: 0});
+				baseMaps[labels.layers[name] || baseLayerTitles[name]] = layer;
+			} else {
+				overlayMaps[labels.layers[name] || overlayTitles[name] || name] = layer;
+			}
+		});
+
+		// Marker layers are ordered in the layer control by their order value
+		var markerLayers = (res.markerLayers || []).slice().sort(function (a, b) {
+			return (a.order || 0) - (b.order || 0);
+		});
+
+		markerLayers.forEach(function (markerLayer, i) {
+			// Each layer gets its own pane to stack the layers by their z_index
+			var pane = 'markers'+i;
+			map.createPane(pane).style.zIndex = 600 + (markerLayer.z_index || 0);
+
+			var layer = L.layerGroup();
+			markerLayer.markers.forEach(function (marker) {
+				var markerObject = createMarker(marker, pane);
+				if (markerObject)
+					layer.addLayer(markerObject);
+			});
+
+			if (!markerLayer.hide)
+				map.addLayer(layer);
+			overlayMaps[labels.layers[markerLayer.id] || markerLayer.name] = layer;
+		});
+
+		var updateLayers = function () {
+			var baseLayer = mapLayer;
+
+			overlays.forEach(function (name) {
+				if (baseLayerTitles[name] && overlayParams[name])
+					baseLayer = overlayLayers[name];
+			});
+
+			for (var title in baseMaps) {
+				if (baseMaps[title] !== baseLayer)
+					map.removeLayer(baseMaps[title]);
+			}
+			map.addLayer(baseLayer);
+
+			overlays.forEach(function (name) {
+				if (baseLayerTitles[name])
+					return;
+
+				if (overlayParams[name])
+					map.addLayer(overlayLayers[name]);
+				else
+					map.removeLayer(overlayLayers[name]);
+			});
 		};
 
-		L.control.layers({}, overlayMaps).addTo(map);
+		updateLayers();
+
+		if (res.title)
+			document.title = res.title;
+		if (res.title || res.description || res.seed)
+			new TitleControl(
+				res.title, res.description,
+				res.seed && (labels.ui.seed || 'Seed') + ': ' + res.seed
+			).addTo(map);
+		if (res.attribution)
+			map.attributionControl.addAttribution(res.attribution);
+		if (res.updated) {
+			var updated = new Date(res.updated*1000);
+			map.attributionControl.addAttribution(
+				'<span title="'+updated.toLocaleString(labels.locale || undefined)+'">'+
+				(labels.ui.updated || 'Updated')+' '+formatAge(res.updated, labels.locale)+'</span>'
+			);
+		}
+
+		L.control.layers(Object.keys(baseMaps).length > 1 ? baseMaps : {}, overlayMaps).addTo(map);
+
+		// The legend of the activity overlay is only shown while the overlay is enabled
+		if (activityScale) {
+			var activityLegend = new ActivityLegend(activityScale);
+			var activityLayer = overlayLayers['activity'];
+
+			if (map.hasLayer(activityLayer))
+				activityLegend.addTo(map);
+
+			activityLayer.on('add', function () {
+				activityLegend.addTo(map);
+			});
+			activityLayer.on('remove', function () {
+				activityLegend.remove();
+			});
+		}
 
 		var coordControl = new CoordControl();
 		coordControl.addTo(map);
 
+		// Data tiles shown on hover, with functions formatting their values;
+		// they can only be decoded by browsers supporting DecompressionStream
+		var dataTiles = res.dataTiles || [];
+		var hoverInfo = [];
+
+		var addHoverInfo = function (name, format) {
+			if (window.DecompressionStream && dataTiles.indexOf(name) >= 0)
+				hoverInfo.push({tiles: new DataTiles(mipmaps, dataPath, name, tileVersions[name]), format: format});
+		};
+
+		if (res.blockPalette) {
+			addHoverInfo('blockdata', function (values) {
+				var block = res.blockPalette[values[0]];
+				if (block === undefined)
+					return [];
+				return [
+					(labels.ui.block || 'Block') + ': ' + formatID(block),
+					'Y: ' + (values[1] - 32768),
+				];
+			});
+		}
+
+		if (res.biomePalette) {
+			addHoverInfo('biomedata', function (values) {
+				var biome = res.biomePalette[values[0]];
+				if (biome === undefined)
+					return [];
+				return [(labels.ui.biome || 'Biome') + ': ' + formatID(biome)];
+			});
+		}
+
+		var hover = 0;
+
 		map.on('mousemove', function(e) {
-			coordControl.update(Math.round(e.latlng.lng), Math.round(-e.latlng.lat));
+			var x = Math.round(e.latlng.lng), z = Math.round(-e.latlng.lat);
+			coordControl.update(x, z);
+
+			var current = ++hover, pending = hoverInfo.length, results = [];
+			hoverInfo.forEach(function (entry, i) {
+				entry.tiles.get(Math.floor(e.latlng.lng), Math.floor(-e.latlng.lat), function (values) {
+					results[i] = values ? entry.format(values) : [];
+
+					if (--pending || current !== hover)
+						return;
+
+					coordControl.update(x, z, [].concat.apply([], results));
+				});
+			});
 		});
 
+		if (worlds.length > 1)
+			new WorldControl(worlds, world).addTo(map);
+
+		if (res.dimensions && res.dimensions.length > 1)
+			new DimensionControl(res.dimensions, res.dimension, world, labels.dimensions).addTo(map);
+
 		var makeHash = function () {
 			var ret = '#x='+x+'&z='+z;
 
-			if (zoom != 0)
+			if (world)
+				ret += '&world='+encodeURIComponent(world);
+
+			if (dim)
+				ret += '&dim='+dim;
+
+			if (zoom != (view.zoom || 0))
 				ret += '&zoom='+zoom;
 
 			if (map.hasLayer(lightLayer))
 				ret += '&light=1';
 
+			overlays.forEach(function (name) {
+				if (map.hasLayer(overlayLayers[name]))
+					ret += '&'+name+'=1';
+			});
+
 			return ret;
 		};
 
@@ -252,6 +918,13 @@ window.createMap = function () {
 			if (window.location.hash === makeHash())
 				return;
 
+			var args = parseHash();
+			if ((args['world'] ? decodeURIComponent(args['world']) : world) !== world ||
+			    (args['dim'] || '') !== dim) {
+				window.location.reload();
+				return;
+			}
+
 			updateParams();
 
 			map.setView([-z, x], zoom);
@@ -261,11 +934,10 @@ window.createMap = function () {
 			else
 				map.removeLayer(lightLayer);
 
+			updateLayers();
+
 			updateHash();
 		};
 
 	};
-
-	xhr.open('GET', 'data/info.json', true);
-	xhr.send();
 }